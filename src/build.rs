@@ -0,0 +1,322 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, bail};
+use log::{debug, error, info, warn};
+
+use crate::{
+    codegen::{
+        c::{CProgram, EmitOptions, Overflow, emit_module},
+        runtime,
+    },
+    diagnostics::{Diagnostic, print_all},
+    ir::{Module, lower::lower, simplify},
+    parsing::{
+        ast::nodes::{Location, NodeKind},
+        modules::{Program, load_program_with},
+    },
+    project::{Kind, Project},
+    semantics::check_program,
+    sources::SourceMap,
+};
+
+/// Loads, checks and lowers the program rooted at `entry` to the IR, ready
+/// for code generation. Problems with the program are printed as they are
+/// found.
+pub fn lower_program(entry: &Path, options: &EmitOptions) -> anyhow::Result<(Program, Module)> {
+    lower_linked(load(entry, &[])?, options)
+}
+
+/// Loads the program rooted at `entry`, along with the files of `sources`
+/// it does not import.
+fn load(entry: &Path, sources: &[PathBuf]) -> anyhow::Result<Program> {
+    debug!("Loading {:?} and its imports", entry);
+    match load_program_with(entry, sources) {
+        Ok(program) => Ok(program),
+        Err(e) => bail!(e),
+    }
+}
+
+/// Checks and lowers a loaded program, as [`lower_program`] does.
+fn lower_linked(program: Program, options: &EmitOptions) -> anyhow::Result<(Program, Module)> {
+    debug!("Checking program");
+    let analysis = check_program(&program.ast);
+    if print_all(&analysis.diagnostics, &program.sources) {
+        bail!("Semantic checks failed");
+    }
+    debug!("Lowering program");
+    let mut module = match lower(&program.ast, &analysis.types) {
+        Ok(module) => module,
+        Err(diagnostics) => {
+            print_all(&diagnostics, &program.sources);
+            bail!("Lowering failed")
+        }
+    };
+    debug!("Simplifying program");
+    let wrapping = options.overflow == Overflow::Wrapping;
+    if let Err(diagnostics) = simplify(&mut module, wrapping, options.inlining) {
+        print_all(&diagnostics, &program.sources);
+        bail!("Constant evaluation failed");
+    }
+    Ok((program, module))
+}
+
+/// Loads, checks and compiles the program rooted at `entry` to C. Problems
+/// with the program are printed as they are found.
+pub fn transpile(entry: &Path, options: &EmitOptions) -> anyhow::Result<(Program, CProgram)> {
+    transpile_linked(load(entry, &[])?, options)
+}
+
+/// Checks and compiles a loaded program to C, as [`transpile`] does.
+fn transpile_linked(
+    program: Program,
+    options: &EmitOptions,
+) -> anyhow::Result<(Program, CProgram)> {
+    let (program, module) = lower_linked(program, options)?;
+    debug!("Generating C");
+    // runtime errors name the files they happen in
+    let options = EmitOptions {
+        file_names: program
+            .sources
+            .files()
+            .map(|file| (file.id(), file.name().to_string()))
+            .collect(),
+        ..options.clone()
+    };
+    match emit_module(&module, &options) {
+        Ok(c) if options.line_directives => {
+            let c = c.with_line_directives(&program.sources);
+            Ok((program, c))
+        }
+        Ok(c) => Ok((program, c)),
+        Err(diagnostics) => {
+            print_all(&diagnostics, &program.sources);
+            bail!("C generation failed")
+        }
+    }
+}
+
+/// Fails unless the program defines the `main` an executable starts from.
+pub fn check_entry(program: &Program) -> anyhow::Result<()> {
+    let NodeKind::Block { expressions } = &program.ast.kind else {
+        bail!("The program is not a list of definitions");
+    };
+    let defined = expressions.iter().any(
+        |node| matches!(&node.kind, NodeKind::Fn { name: Some(name), .. } if name.name() == "main"),
+    );
+    if !defined {
+        bail!(
+            "An executable needs a `main` function to start from, such as `(fn:i32 main () ...)`"
+        );
+    }
+    Ok(())
+}
+
+/// A C compiler with gcc-style arguments, such as `cc`, `gcc` or `clang`.
+#[derive(Debug, Clone)]
+pub struct CCompiler {
+    pub program: String,
+    pub flags: Vec<String>,
+}
+impl CCompiler {
+    /// The compiler in `$CC`, or `cc` when it is not set. Like make, this
+    /// allows `$CC` to carry arguments of its own, as in `ccache gcc`.
+    pub fn from_env() -> CCompiler {
+        let cc = std::env::var("CC").unwrap_or_default();
+        let mut words = cc.split_whitespace().map(str::to_string);
+        match words.next() {
+            Some(program) => CCompiler {
+                program,
+                flags: words.collect(),
+            },
+            None => CCompiler {
+                program: "cc".to_string(),
+                flags: vec![],
+            },
+        }
+    }
+
+    /// Compiles `c_file`, which holds the C generated for `c`, into an
+    /// executable at `output`, or just an object file with `object_only`.
+    /// An executable is linked with the runtime, which has to be written
+    /// next to `c_file`. What the compiler has to say about the generated
+    /// code is reported against the Crisp code it came from where possible.
+    pub fn compile(
+        &self,
+        c_file: &Path,
+        output: &Path,
+        object_only: bool,
+        c: &CProgram,
+        sources: &SourceMap,
+    ) -> anyhow::Result<()> {
+        let mut command = Command::new(&self.program);
+        command.args(&self.flags);
+        if object_only {
+            command.arg("-c");
+        }
+        command.arg(c_file);
+        if !object_only {
+            command.arg(c_file.with_file_name(runtime::SOURCE_NAME));
+        }
+        command.arg("-o").arg(output);
+        debug!("Running {:?}", command);
+        let result = command
+            .output()
+            .with_context(|| format!("Could not run the C compiler `{}`", self.program))?;
+
+        let c_file = c_file.to_string_lossy();
+        let mut mapped = vec![];
+        for line in String::from_utf8_lossy(&result.stderr).lines() {
+            // anything else, such as linker errors, is passed on as it is
+            match c_diagnostic(line, &c_file, c, sources) {
+                Some(diagnostic) => mapped.push(diagnostic),
+                None if line.contains("warning") => warn!("{}", line),
+                None if !result.status.success() => error!("{}", line),
+                None => debug!("{}", line),
+            }
+        }
+        print_all(&mapped, sources);
+        if !result.status.success() {
+            bail!(
+                "The C compiler `{}` failed with {}",
+                self.program,
+                result.status
+            );
+        }
+        Ok(())
+    }
+
+    /// Compiles the runtime written into `dir` into an object file at
+    /// `output`, for libraries that carry it along.
+    pub fn compile_runtime(&self, dir: &Path, output: &Path) -> anyhow::Result<()> {
+        run(Command::new(&self.program)
+            .args(&self.flags)
+            .arg("-c")
+            .arg(dir.join(runtime::SOURCE_NAME))
+            .arg("-o")
+            .arg(output))
+    }
+}
+
+/// Reads an error or warning from a line of gcc or clang output, of the
+/// shape `file:line:col: error: message`. A message about `c_file` is placed
+/// at the Crisp code that line of C was generated for, while one about a
+/// Crisp file, as `#line` directives produce, is placed as it is.
+pub fn c_diagnostic(
+    line: &str,
+    c_file: &str,
+    c: &CProgram,
+    sources: &SourceMap,
+) -> Option<Diagnostic> {
+    // file names may contain colons themselves, so find the line number
+    // from the back of the position
+    let (position, message) = line.split_once(": ")?;
+    let (severity, message) = message.split_once(": ")?;
+    let mut parts = position.rsplitn(3, ':');
+    let (first, second, file) = (parts.next()?, parts.next()?, parts.next());
+    let (file, line_number, col) = match file {
+        Some(file) if second.parse::<usize>().is_ok() => (file, second, first),
+        _ => (second, first, "1"),
+    };
+    let (line_number, col): (usize, usize) = (line_number.parse().ok()?, col.parse().ok()?);
+    let (loc, message) = if file == c_file {
+        let message = format!("{} (in the generated C, line {})", message, line_number);
+        (c.location_of(line_number)?, message)
+    } else {
+        let source = &sources[sources.find(file)?];
+        let (line_start, line_end) = source.line_span(line_number);
+        let start = (line_start + col.saturating_sub(1)).min(line_end);
+        let loc = Location {
+            file: source.id(),
+            line: line_number,
+            col,
+            start,
+            end: line_end,
+        };
+        (loc, message.to_string())
+    };
+    match severity {
+        "error" | "fatal error" => Some(Diagnostic::error(message, loc)),
+        "warning" => Some(Diagnostic::warning(message, loc)),
+        _ => None,
+    }
+}
+
+/// Transpiles a project and compiles the result into an executable or a
+/// static library in its output directory, returning the path of what was
+/// built.
+pub fn build(project: &Project, options: &EmitOptions) -> anyhow::Result<PathBuf> {
+    // every source is built, whether the entry point imports it or not
+    let program = load(&project.entry(), &project.sources()?)?;
+    let (program, c) = transpile_linked(program, options)?;
+    if project.manifest.package.kind == Kind::Bin {
+        check_entry(&program)?;
+    }
+
+    let output_dir = project.output_dir();
+    fs::create_dir_all(&output_dir)
+        .with_context(|| format!("Could not create {:?}", output_dir))?;
+    let c_file = output_dir.join(format!("{}.c", project.name()));
+    fs::write(&c_file, &c.code).with_context(|| format!("Could not write {:?}", c_file))?;
+    info!("Wrote {:?}", c_file);
+    runtime::write_runtime(&output_dir)
+        .with_context(|| format!("Could not write the runtime to {:?}", output_dir))?;
+    if let Some(header) = c.header(project.name()) {
+        let header_file = c_file.with_extension("h");
+        fs::write(&header_file, header)
+            .with_context(|| format!("Could not write {:?}", header_file))?;
+        info!("Wrote {:?}", header_file);
+    }
+
+    let config = &project.manifest.c;
+    let mut compiler = match &config.compiler {
+        Some(program) => CCompiler {
+            program: program.clone(),
+            flags: vec![],
+        },
+        None => CCompiler::from_env(),
+    };
+    compiler.flags.push(format!("-std={}", config.standard));
+    compiler.flags.extend(config.flags.iter().cloned());
+    let artifact = match project.manifest.package.kind {
+        Kind::Bin => {
+            let executable = output_dir.join(project.name());
+            compiler.compile(&c_file, &executable, false, &c, &program.sources)?;
+            executable
+        }
+        Kind::Lib => {
+            let object = output_dir.join(format!("{}.o", project.name()));
+            compiler.compile(&c_file, &object, true, &c, &program.sources)?;
+            let runtime_object = output_dir.join("crisp_runtime.o");
+            compiler.compile_runtime(&output_dir, &runtime_object)?;
+            let library = output_dir.join(format!("lib{}.a", project.name()));
+            // ar adds to an existing archive, so start from scratch
+            if library.exists() {
+                fs::remove_file(&library)?;
+            }
+            run(Command::new("ar")
+                .arg("rcs")
+                .arg(&library)
+                .arg(&object)
+                .arg(&runtime_object))?;
+            library
+        }
+    };
+    info!("Built {:?}", artifact);
+    Ok(artifact)
+}
+
+/// Runs a build tool, failing if it cannot be started or does not succeed.
+fn run(command: &mut Command) -> anyhow::Result<()> {
+    debug!("Running {:?}", command);
+    let status = command
+        .status()
+        .with_context(|| format!("Could not run {:?}", command.get_program()))?;
+    if !status.success() {
+        bail!("{:?} failed with {}", command.get_program(), status);
+    }
+    Ok(())
+}
@@ -23,5 +23,82 @@ pub enum Command {
     T {
         #[arg(value_name = "FILE", value_parser = clap::value_parser!(std::path::PathBuf))]
         input: PathBuf,
+        /// Where to write the generated C, defaults to the input with a .c extension.
+        /// With --compile this is where the executable goes instead, and the C is
+        /// written next to it.
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// What to generate: C, or the lowered IR in its text form, written with a .ir
+        /// extension by default.
+        #[arg(long, value_name = "KIND", default_value = "c", value_parser = ["c", "ir"],
+              conflicts_with = "compile")]
+        emit: String,
+        /// Leave out the runtime bounds checks on array indexing.
+        #[arg(long)]
+        no_bounds_checks: bool,
+        /// What integer arithmetic does on overflow: panic, wrap around or whatever C does.
+        #[arg(long, value_name = "MODE", default_value = "checked",
+              value_parser = ["checked", "wrapping", "unchecked"])]
+        overflow: String,
+        /// Emit #line directives so C compiler messages and debuggers point at the Crisp source.
+        #[arg(long)]
+        line_directives: bool,
+        /// Compile the generated C into an executable with $CC, or cc if it is not set.
+        #[arg(short, long)]
+        compile: bool,
+        /// How much to optimize: 0 inlines nothing, 1 and s what is marked (inline), and 2
+        /// and 3 small functions as well. With --compile it is also passed to the C
+        /// compiler as -O<LEVEL>.
+        #[arg(long, value_name = "LEVEL", value_parser = ["0", "1", "2", "3", "s"])]
+        opt_level: Option<String>,
+        /// Have the C compiler include debug information.
+        #[arg(short, long, requires = "compile")]
+        debug: bool,
+        /// An extra flag for the C compiler, such as --cflag=-Wall. Can be repeated.
+        #[arg(
+            long = "cflag",
+            value_name = "FLAG",
+            requires = "compile",
+            allow_hyphen_values = true
+        )]
+        cflags: Vec<String>,
+    },
+    /// Builds the project described by the closest Crisp.toml.
+    Build {
+        /// The manifest to build, instead of searching up from the current directory.
+        #[arg(long, value_name = "PATH")]
+        manifest_path: Option<PathBuf>,
+        /// Leave out the runtime bounds checks on array indexing.
+        #[arg(long)]
+        no_bounds_checks: bool,
+        /// What integer arithmetic does on overflow: panic, wrap around or whatever C does.
+        #[arg(long, value_name = "MODE", default_value = "checked",
+              value_parser = ["checked", "wrapping", "unchecked"])]
+        overflow: String,
+        /// Emit #line directives so C compiler messages and debuggers point at the Crisp source.
+        #[arg(long)]
+        line_directives: bool,
+    },
+    /// Generates documentation from the ;;; comments above definitions, with a page
+    /// for the entry file and one for each module it imports.
+    Doc {
+        /// The entry file, instead of that of the closest Crisp.toml.
+        #[arg(value_name = "FILE", value_parser = clap::value_parser!(std::path::PathBuf))]
+        input: Option<PathBuf>,
+        /// The directory to write the pages to, defaults to doc/ next to the entry file,
+        /// or in the output directory of the project.
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+        /// What to write the pages in.
+        #[arg(long, value_name = "FORMAT", default_value = "html",
+              value_parser = ["html", "markdown"])]
+        format: String,
+    },
+    /// Runs a language server for editors over stdin and stdout.
+    Lsp,
+    /// Creates a new project in a directory of the same name.
+    New {
+        #[arg(value_name = "NAME")]
+        name: String,
     },
 }
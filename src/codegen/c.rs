@@ -0,0 +1,1614 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use crate::{
+    codegen::runtime,
+    diagnostics::Diagnostic,
+    ir::{
+        Aggregate, Base, BlockId, Builtin, Callee, Constant, Function, LocalId, Module, Operand,
+        Place, Projection, Rvalue, Statement, StatementKind, Terminator, TerminatorKind,
+        inline::Inlining, lower::lower, simplify,
+    },
+    parsing::ast::nodes::{Location, Node},
+    semantics::{
+        operators::{Operator, OperatorKind},
+        typecheck::{EnumDef, StructDef, TypeInfo},
+        types::Type,
+    },
+    sources::{FileId, SourceMap},
+};
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while", "bool", "true", "false",
+];
+
+/// Quotes a string for C. Crisp strings have no escapes, every character
+/// stands for itself, so backslashes are escaped and control characters
+/// spelled out. Octal escapes are used as they, unlike hex ones, cannot run
+/// into the digits after them.
+fn string_literal(value: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => literal.push_str("\\\\"),
+            '"' => literal.push_str("\\\""),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            c if c.is_ascii_control() => {
+                let _ = write!(literal, "\\{:03o}", c as u32);
+            }
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// The name of the runtime helper an integer operator needs to overflow
+/// or divide safely, if it needs one.
+fn runtime_operation(operator: Operator, operands: usize) -> Option<&'static str> {
+    let op = match operator {
+        Operator::Add => "add",
+        Operator::Sub if operands == 1 => "neg",
+        Operator::Sub => "sub",
+        Operator::Mul => "mul",
+        Operator::Div => "div",
+        Operator::Rem => "rem",
+        Operator::Shl => "shl",
+        Operator::Shr => "shr",
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Drops the parentheses around a whole expression, which C compilers warn
+/// about in conditions such as `if ((a == b))`.
+fn without_parens(expr: &str) -> &str {
+    let Some(inner) = expr.strip_prefix('(').and_then(|e| e.strip_suffix(')')) else {
+        return expr;
+    };
+    let (mut depth, mut in_string, mut escaped) = (0, false, false);
+    for c in inner.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            '(' => depth += 1,
+            ')' if depth == 0 => return expr,
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    inner
+}
+
+/// Turns a Crisp symbol into a valid C identifier. Dashes become
+/// underscores, every other character C does not allow is hex-escaped.
+/// Different symbols can come out the same, which the emitter reports.
+pub fn c_ident(name: &str) -> String {
+    let mut ident = String::new();
+    for c in name.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => ident.push(c),
+            '-' => ident.push('_'),
+            // math/add is add from module math
+            '/' => ident.push_str("__"),
+            other => {
+                let _ = write!(ident, "_{:02x}", other as u32);
+            }
+        }
+    }
+    if C_KEYWORDS.contains(&ident.as_str()) || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.push('_');
+    }
+    ident
+}
+
+/// The C spelling of a Crisp type.
+pub fn c_type(ty: &Type) -> Option<String> {
+    let name = match ty {
+        Type::I8 => "int8_t",
+        Type::I16 => "int16_t",
+        Type::I32 => "int32_t",
+        Type::I64 => "int64_t",
+        Type::U8 => "uint8_t",
+        Type::U16 => "uint16_t",
+        Type::U32 => "uint32_t",
+        Type::U64 => "uint64_t",
+        Type::F32 => "float",
+        Type::F64 => "double",
+        Type::Bool => "bool",
+        Type::String => "crisp_string",
+        Type::Void => "void",
+        Type::Named(name) => return Some(c_ident(name)),
+        Type::Array(..) | Type::Slice(_) => {
+            return type_key(ty).map(|key| format!("crisp_{}", key));
+        }
+        Type::Param(_) | Type::Unknown => return None,
+    };
+    Some(name.to_string())
+}
+
+/// A spelling of a type that can be part of a C identifier, used to name
+/// the structs arrays and slices are wrapped in.
+fn type_key(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Array(element, len) => Some(format!("array_{}_{}", type_key(element)?, len)),
+        Type::Slice(element) => Some(format!("slice_{}", type_key(element)?)),
+        Type::Named(name) => Some(c_ident(name)),
+        Type::Param(_) | Type::Unknown => None,
+        primitive => Some(primitive.to_string()),
+    }
+}
+
+/// The C name of one instantiation of a generic function, e.g. `max__i32`
+/// for `max` called with `i32`s.
+pub fn instance_name(name: &str, type_args: &[Type]) -> String {
+    let mut name = c_ident(name);
+    for ty in type_args {
+        name.push_str("__");
+        name.push_str(&type_key(ty).unwrap_or_default());
+    }
+    name
+}
+
+/// The C name an exported function is declared under in the header: its
+/// own name, without the module it comes from.
+pub fn export_name(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+/// What integer arithmetic does when the result does not fit its type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Panic at the Crisp code that overflowed.
+    #[default]
+    Checked,
+    /// Wrap around, as two's complement does.
+    Wrapping,
+    /// Leave it to C, where signed overflow is undefined behavior.
+    Unchecked,
+}
+impl Overflow {
+    pub fn from_name(name: &str) -> Option<Overflow> {
+        match name {
+            "checked" => Some(Overflow::Checked),
+            "wrapping" => Some(Overflow::Wrapping),
+            "unchecked" => Some(Overflow::Unchecked),
+            _ => None,
+        }
+    }
+}
+
+/// Knobs for the generated C.
+#[derive(Debug, Clone)]
+pub struct EmitOptions {
+    /// Whether indexing checks the index against the length at runtime.
+    pub bounds_checks: bool,
+    /// What integer arithmetic does on overflow. Dividing by zero panics
+    /// unless this is [`Overflow::Unchecked`].
+    pub overflow: Overflow,
+    /// Whether to point the C compiler and debuggers at the Crisp source
+    /// with `#line` directives.
+    pub line_directives: bool,
+    /// Which calls are replaced by the body of the function they call.
+    pub inlining: Inlining,
+    /// The names runtime errors refer to the source files by.
+    pub file_names: HashMap<FileId, String>,
+}
+impl Default for EmitOptions {
+    fn default() -> Self {
+        EmitOptions {
+            bounds_checks: true,
+            overflow: Overflow::Checked,
+            line_directives: false,
+            inlining: Inlining::default(),
+            file_names: HashMap::new(),
+        }
+    }
+}
+
+/// Generated C together with the Crisp code each of its lines came from.
+#[derive(Debug)]
+pub struct CProgram {
+    pub code: String,
+    /// The location of the node each line was emitted for, by 0-based line.
+    lines: Vec<Option<Location>>,
+    /// The declarations of the exported functions, if there are any.
+    exports: Option<String>,
+}
+impl CProgram {
+    /// The Crisp code a 1-based line of the C came from. A line without a
+    /// node of its own belongs to the closest one above it.
+    pub fn location_of(&self, line: usize) -> Option<Location> {
+        let last = line.checked_sub(1)?.min(self.lines.len().checked_sub(1)?);
+        self.lines[..=last].iter().rev().find_map(|loc| *loc)
+    }
+
+    /// Adds a `#line` directive wherever the Crisp line a line of C comes
+    /// from is not the one the C compiler would count to, so that compiler
+    /// messages and debuggers refer to the Crisp source instead.
+    pub fn with_line_directives(self, sources: &SourceMap) -> CProgram {
+        let mut code = String::new();
+        let mut lines = vec![];
+        // the file and line the compiler takes the next line of C to be
+        let mut counted: Option<(FileId, usize)> = None;
+        for (text, loc) in self.code.lines().zip(self.lines) {
+            if let Some(loc) = loc {
+                if counted != Some((loc.file, loc.line)) {
+                    let name = sources
+                        .get(loc.file)
+                        .map_or("<unknown>", |file| file.name());
+                    let _ = writeln!(code, "#line {} {}", loc.line, c_string(name));
+                    lines.push(None);
+                }
+                counted = Some((loc.file, loc.line));
+            }
+            code.push_str(text);
+            code.push('\n');
+            lines.push(loc);
+            counted = counted.map(|(file, line)| (file, line + 1));
+        }
+        CProgram {
+            code,
+            lines,
+            exports: self.exports,
+        }
+    }
+
+    /// A header declaring the exported functions for C code to call, or
+    /// `None` if nothing is exported. `name` names the include guard, and is
+    /// usually that of the header itself.
+    pub fn header(&self, name: &str) -> Option<String> {
+        let guard = format!("{}_H", c_ident(name).to_uppercase());
+        let exports = self.exports.as_ref()?;
+        Some(format!(
+            "#ifndef {guard}\n#define {guard}\n\n{exports}\n#endif\n"
+        ))
+    }
+}
+
+struct Emitter<'m> {
+    module: &'m Module,
+    options: &'m EmitOptions,
+    out: String,
+    sequences: HashSet<Type>,
+    /// Array and slice types spelled out since the type definitions were
+    /// emitted, which still need a definition of their own.
+    pending_sequences: Vec<Type>,
+    diagnostics: Vec<Diagnostic>,
+    /// Where in `out` the code for each statement starts.
+    marks: Vec<(usize, Location)>,
+    /// The C names of the locals of the function being emitted.
+    locals: Vec<String>,
+    /// How each local of that function is declared.
+    declarations: Vec<Declaration>,
+    /// The C expressions of folded temporaries that are yet to be used.
+    folded: HashMap<LocalId, String>,
+    /// The C names of everything the program defines outside functions,
+    /// which locals are kept from hiding.
+    file_scope: HashSet<String>,
+}
+
+/// Where a local is declared in C.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Declaration {
+    /// A parameter, declared by the signature.
+    Param,
+    /// A temporary assigned and used once, right after, whose value is
+    /// written straight into the code that uses it.
+    Folded,
+    /// At the assignment that first sets it, as `const` if nothing assigns
+    /// to it again.
+    At {
+        block: BlockId,
+        statement: usize,
+        constant: bool,
+    },
+    /// At the top of the function, because something reads it before the
+    /// first assignment in the order of the C.
+    Top,
+}
+
+/// Translates a checked program into a single C translation unit.
+pub fn emit_c(
+    ast: &Node,
+    types: &TypeInfo,
+    options: &EmitOptions,
+) -> Result<String, Vec<Diagnostic>> {
+    emit_c_program(ast, types, options).map(|program| program.code)
+}
+
+/// Like [`emit_c`], but keeps track of the Crisp code behind each line so
+/// errors from the C compiler can be reported against it.
+pub fn emit_c_program(
+    ast: &Node,
+    types: &TypeInfo,
+    options: &EmitOptions,
+) -> Result<CProgram, Vec<Diagnostic>> {
+    let mut module = lower(ast, types)?;
+    simplify(
+        &mut module,
+        options.overflow == Overflow::Wrapping,
+        options.inlining,
+    )?;
+    emit_module(&module, options)
+}
+
+/// Generates C from the IR of a program.
+pub fn emit_module(module: &Module, options: &EmitOptions) -> Result<CProgram, Vec<Diagnostic>> {
+    let mut emitter = Emitter {
+        module,
+        options,
+        out: String::new(),
+        sequences: HashSet::new(),
+        pending_sequences: vec![],
+        diagnostics: vec![],
+        marks: vec![],
+        locals: vec![],
+        declarations: vec![],
+        folded: HashMap::new(),
+        file_scope: HashSet::new(),
+    };
+
+    for name in type_order(module) {
+        if let Some(def) = module.struct_def(name) {
+            emitter.emit_struct(def);
+        } else if let Some(def) = module.enum_def(name) {
+            emitter.emit_enum(def);
+        }
+    }
+    let type_definitions = std::mem::take(&mut emitter.out);
+
+    for function in &module.externs {
+        emitter.emit_signature(
+            &function.name,
+            &function.returns,
+            &function.params,
+            true,
+            function.loc,
+        );
+        emitter.out.push_str(";\n");
+    }
+    let functions: Vec<(&Function, String)> = module
+        .functions
+        .iter()
+        .map(|function| {
+            let name = emitter.function_name(&function.name, &function.type_args);
+            (function, name)
+        })
+        .collect();
+    let exported: Vec<(&Function, &str)> = functions
+        .iter()
+        .filter(|(function, _)| function.exported)
+        .map(|(function, name)| (*function, name.as_str()))
+        .collect();
+    let file_scope = file_scope_names(module, &functions);
+    for diagnostic in name_clashes(&file_scope) {
+        emitter.error(diagnostic);
+    }
+    emitter.file_scope = file_scope.into_iter().map(|name| name.c_name).collect();
+    for (function, name) in &functions {
+        emitter.emit_fn_signature(function, name);
+        emitter.out.push_str(";\n");
+    }
+    for global in &module.globals {
+        let mut ty = emitter.c_type(&global.ty, global.loc);
+        if !global.mutable {
+            ty = const_type(&ty);
+        }
+        // a plain initializer, since C does not accept compound literals
+        // for globals
+        let value = emitter.constant_initializer(&global.value);
+        emitter.out.push('\n');
+        emitter.marks.push((emitter.out.len(), global.loc));
+        let _ = writeln!(
+            emitter.out,
+            "static {} {} = {};",
+            ty,
+            c_ident(&global.name),
+            value
+        );
+    }
+    for (function, name) in &functions {
+        emitter.out.push('\n');
+        emitter.marks.push((emitter.out.len(), function.loc));
+        emitter.emit_fn_signature(function, name);
+        emitter.out.push_str(" {\n");
+        emitter.emit_body(function);
+        emitter.out.push_str("}\n");
+    }
+    if let Some((function, name)) = functions
+        .iter()
+        .find(|(function, name)| function.name == "main" && name != "main")
+    {
+        emitter.emit_entry(function, name);
+    }
+    let code = std::mem::take(&mut emitter.out);
+    let marks = std::mem::take(&mut emitter.marks);
+    for ty in std::mem::take(&mut emitter.pending_sequences) {
+        emitter.emit_sequence_type(&ty);
+    }
+    let exports = (!exported.is_empty()).then(|| emitter.emit_header(&exported));
+
+    if !emitter.diagnostics.is_empty() {
+        return Err(emitter.diagnostics);
+    }
+    let mut header = format!("#include {}\n", c_string(runtime::HEADER_NAME));
+    for name in &module.includes {
+        // a local header is quoted, a system one already carries its <>
+        if name.starts_with('<') {
+            let _ = writeln!(header, "#include {}", name);
+        } else {
+            let _ = writeln!(header, "#include {}", c_string(name));
+        }
+    }
+    let definitions = format!("{}{}", type_definitions, emitter.out);
+    let body = format!("{}{}", definitions, code);
+    let c = format!("{}\n{}", header, body.trim_start());
+    // the marks are offsets into the code, which ends the output
+    let trimmed = body.len() - body.trim_start().len();
+    let mut lines = vec![None; c.lines().count()];
+    for (offset, loc) in marks {
+        let offset = header.len() + 1 + (definitions.len() + offset).saturating_sub(trimmed);
+        let line = c[..offset.min(c.len())].matches('\n').count();
+        if let Some(slot) = lines.get_mut(line) {
+            slot.get_or_insert(loc);
+        }
+    }
+    Ok(CProgram {
+        code: c,
+        lines,
+        exports,
+    })
+}
+
+/// Adds a user-defined type a signature mentions to `needed`, along with
+/// the types it holds.
+fn needed_types(ty: &Type, module: &Module, needed: &mut HashSet<String>) {
+    match ty {
+        Type::Named(name) if needed.insert(name.clone()) => {
+            for contained in module.contained_types(name) {
+                needed_types(&Type::Named(contained.to_string()), module, needed);
+            }
+        }
+        Type::Array(element, _) | Type::Slice(element) => needed_types(element, module, needed),
+        _ => {}
+    }
+}
+
+/// Orders the user-defined types so every type comes after the types it
+/// holds by value, keeping source order where there is a choice.
+fn type_order(module: &Module) -> Vec<&str> {
+    fn visit<'m>(name: &'m str, module: &'m Module, order: &mut Vec<&'m str>) {
+        if order.contains(&name) {
+            return;
+        }
+        for contained in module.contained_types(name) {
+            visit(contained, module, order);
+        }
+        order.push(name);
+    }
+    let mut defs: Vec<(&str, Location)> = module
+        .structs
+        .iter()
+        .map(|def| (def.name.as_str(), def.loc))
+        .chain(module.enums.iter().map(|def| (def.name.as_str(), def.loc)))
+        .collect();
+    defs.sort_by_key(|(_, loc)| loc.start);
+    let mut order = vec![];
+    for (name, _) in defs {
+        visit(name, module, &mut order);
+    }
+    order
+}
+
+/// A name a program defines outside its functions, as C spells it.
+struct CName {
+    c_name: String,
+    /// What it is in Crisp, such as ``Function `my-f` ``.
+    definition: String,
+    /// The Crisp name itself.
+    name: String,
+    loc: Location,
+    exported: bool,
+}
+
+/// The names a program defines outside its functions, in C.
+fn file_scope_names(module: &Module, functions: &[(&Function, String)]) -> Vec<CName> {
+    let name = |c_name: String, kind: &str, name: &str, loc: Location| CName {
+        c_name,
+        definition: format!("{} `{}`", kind, name),
+        name: name.to_string(),
+        loc,
+        exported: false,
+    };
+    let mut names = vec![];
+    for def in &module.structs {
+        names.push(name(c_ident(&def.name), "Struct", &def.name, def.loc));
+    }
+    for def in &module.enums {
+        names.push(name(c_ident(&def.name), "Enum", &def.name, def.loc));
+        let tag = format!("{}_Tag", c_ident(&def.name));
+        names.push(name(tag, "Enum", &def.name, def.loc));
+        for variant in &def.variants {
+            let c_name = tag_name(&def.name, &variant.name);
+            names.push(name(c_name, "Variant", &variant.name, def.loc));
+        }
+    }
+    for function in &module.externs {
+        names.push(name(
+            function.name.clone(),
+            "C function",
+            &function.name,
+            function.loc,
+        ));
+    }
+    for global in &module.globals {
+        names.push(name(
+            c_ident(&global.name),
+            "Global",
+            &global.name,
+            global.loc,
+        ));
+    }
+    for (function, c_name) in functions {
+        let kind = if function.exported {
+            "Exported function"
+        } else {
+            "Function"
+        };
+        names.push(CName {
+            exported: function.exported,
+            ..name(c_name.clone(), kind, &function.name, function.loc)
+        });
+    }
+    names
+}
+
+/// Reports the names that differ in Crisp but not in C, as `my-f` and
+/// `my_f` do, or as an exported function that loses its module does.
+/// Names C code of the runtime uses are reported as well.
+fn name_clashes(names: &[CName]) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut seen: HashMap<&str, &CName> = HashMap::new();
+    for name in names {
+        let reserved = |c_name: &str| c_name.starts_with("crisp_") || c_name.starts_with("_crisp");
+        // unless the compiler chose the name, as it does for `main`
+        if reserved(&name.c_name) && reserved(&c_ident(&name.name)) {
+            diagnostics.push(Diagnostic::error(
+                format!(
+                    "{} is called `{}` in C, which is kept for the runtime",
+                    name.definition, name.c_name
+                ),
+                name.loc,
+            ));
+            continue;
+        }
+        match seen.get(name.c_name.as_str()) {
+            // an enum and its tag type are the same definition
+            Some(first) if first.loc == name.loc && first.name == name.name => {}
+            Some(first) => {
+                // the exported one is the one whose name was changed
+                let (clashing, other) = if first.exported {
+                    (*first, name)
+                } else {
+                    (name, *first)
+                };
+                diagnostics.push(
+                    Diagnostic::error(
+                        format!(
+                            "{} is called `{}` in C, which clashes with `{}`",
+                            clashing.definition, clashing.c_name, other.name
+                        ),
+                        clashing.loc,
+                    )
+                    .with_note(format!("`{}` is defined here", other.name), other.loc),
+                );
+            }
+            None => {
+                seen.insert(&name.c_name, name);
+            }
+        }
+    }
+    diagnostics
+}
+
+/// The C enum constant tagging a variant.
+fn tag_name(enum_name: &str, variant: &str) -> String {
+    format!("{}_{}", c_ident(enum_name), c_ident(variant))
+}
+
+/// The operands an rvalue reads, in order.
+fn rvalue_operands(rvalue: &Rvalue) -> Vec<&Operand> {
+    match rvalue {
+        Rvalue::Use(operand)
+        | Rvalue::Unary { operand, .. }
+        | Rvalue::Increment(operand)
+        | Rvalue::Cast { operand, .. }
+        | Rvalue::Len(operand)
+        | Rvalue::IsVariant { operand, .. } => vec![operand],
+        Rvalue::Binary { left, right, .. } => vec![left, right],
+        Rvalue::CheckIndex { index, len, .. } => vec![index, len],
+        Rvalue::Aggregate { operands, .. } => operands.iter().collect(),
+        Rvalue::Call { args, .. } => args.iter().collect(),
+    }
+}
+
+/// The operands a place reads to find what it refers to, which are the
+/// indices it goes through.
+fn place_operands(place: &Place) -> Vec<&Operand> {
+    place
+        .projections
+        .iter()
+        .filter_map(|projection| match projection {
+            Projection::Index(index) => Some(index),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The operands a statement or terminator reads, in order, together with
+/// the local it assigns as a whole, if it does.
+fn reads<'f>(
+    statement: Option<&'f StatementKind>,
+    terminator: &'f TerminatorKind,
+) -> (Vec<&'f Operand>, Option<LocalId>) {
+    match statement {
+        Some(StatementKind::Assign(place, rvalue)) => {
+            let mut operands = place_operands(place);
+            operands.extend(rvalue_operands(rvalue));
+            let whole = match (&place.base, place.projections.is_empty()) {
+                (Base::Local(local), true) => Some(*local),
+                _ => None,
+            };
+            (operands, whole)
+        }
+        Some(StatementKind::Eval(rvalue)) => (rvalue_operands(rvalue), None),
+        None => match terminator {
+            TerminatorKind::Branch { condition, .. } => (vec![condition], None),
+            TerminatorKind::Return(Some(value)) => (vec![value], None),
+            _ => (vec![], None),
+        },
+    }
+}
+
+/// Whether an operand reads a global that code could assign to while it is
+/// being evaluated.
+fn reads_mutable_global(operand: &Operand, module: &Module) -> bool {
+    let Operand::Copy(place) = operand else {
+        return false;
+    };
+    let mutable = match &place.base {
+        Base::Global(name) => module
+            .globals
+            .iter()
+            .any(|global| global.name == *name && global.mutable),
+        Base::Local(_) => false,
+    };
+    mutable
+        || place_operands(place)
+            .into_iter()
+            .any(|index| reads_mutable_global(index, module))
+}
+
+/// Calls `visit` with every local an operand reads, and whether it reads it
+/// whole rather than a part of it.
+fn visit_locals(operand: &Operand, visit: &mut impl FnMut(LocalId, bool)) {
+    let Operand::Copy(place) = operand else {
+        return;
+    };
+    if let Base::Local(local) = place.base {
+        visit(local, place.projections.is_empty());
+    }
+    for index in place_operands(place) {
+        visit_locals(index, visit);
+    }
+}
+
+/// Decides where each local of a function is declared. A temporary that is
+/// assigned once and used once by the very next statement is folded into
+/// it, unless that statement reads something the folded code could change,
+/// so that `f(g(x))` stays a nested call in C.
+fn declarations(function: &Function, module: &Module) -> Vec<Declaration> {
+    let count = function.locals.len();
+    let mut reads_of = vec![0; count];
+    let mut whole_reads = vec![0; count];
+    let mut assignments = vec![0; count];
+    let mut first_seen: Vec<Option<(BlockId, usize)>> = vec![None; count];
+    let mut first_assigned: Vec<Option<(BlockId, usize)>> = vec![None; count];
+    let mut read_at: Vec<Option<(BlockId, usize)>> = vec![None; count];
+    for (block_id, block) in function.blocks.iter().enumerate() {
+        let statements = block
+            .statements
+            .iter()
+            .map(|statement| Some(&statement.kind));
+        for (index, statement) in statements.chain([None]).enumerate() {
+            let at = (block_id, index);
+            let (operands, whole) = reads(statement, &block.terminator.kind);
+            for operand in operands {
+                visit_locals(operand, &mut |local, whole| {
+                    reads_of[local] += 1;
+                    whole_reads[local] += whole as usize;
+                    read_at[local] = Some(at);
+                    first_seen[local].get_or_insert(at);
+                });
+            }
+            if let Some(StatementKind::Assign(place, _)) = statement
+                && let Base::Local(local) = place.base
+            {
+                assignments[local] += 1;
+                first_seen[local].get_or_insert(at);
+            }
+            if let Some(local) = whole {
+                first_assigned[local].get_or_insert(at);
+            }
+        }
+    }
+    let mut declarations: Vec<Option<Declaration>> = vec![None; count];
+    // where each folded temporary is used, to fold temporaries assigned
+    // further up into the same statement as those assigned in between
+    let mut folded_into: HashMap<LocalId, (BlockId, usize)> = HashMap::new();
+    let mut candidates: Vec<LocalId> = (0..count)
+        .filter(|local| {
+            function.locals[*local].name.is_none()
+                && assignments[*local] == 1
+                && reads_of[*local] == 1
+                && whole_reads[*local] == 1
+        })
+        .collect();
+    // the later ones first, as each one depends on those assigned after it
+    candidates.sort_by_key(|local| std::cmp::Reverse(first_assigned[*local]));
+    for local in candidates {
+        let (Some((block, index)), Some((read_block, read))) =
+            (first_assigned[local], read_at[local])
+        else {
+            continue;
+        };
+        if read_block != block || read <= index {
+            continue;
+        }
+        let statements = &function.blocks[block].statements;
+        let between = (index + 1..read).all(|between| {
+            matches!(&statements[between].kind,
+                StatementKind::Assign(Place { base: Base::Local(other), .. }, _)
+                    if folded_into.get(other) == Some(&(block, read)))
+        });
+        // the folded code runs in between the other operands, in an order C
+        // leaves open
+        let first = if read == index + 1 { read } else { index };
+        let stable = (first..=read).all(|at| {
+            let (operands, _) = reads(
+                statements.get(at).map(|statement| &statement.kind),
+                &function.blocks[block].terminator.kind,
+            );
+            !operands
+                .into_iter()
+                .any(|operand| reads_mutable_global(operand, module))
+        });
+        if between && stable {
+            declarations[local] = Some(Declaration::Folded);
+            folded_into.insert(local, (block, read));
+        }
+    }
+    (0..count)
+        .map(|local| {
+            if function.params.contains(&local) {
+                return Declaration::Param;
+            }
+            if let Some(declaration) = declarations[local] {
+                return declaration;
+            }
+            let assigned = first_assigned[local];
+            match assigned {
+                Some((block, statement)) if first_seen[local] == assigned => Declaration::At {
+                    block,
+                    statement,
+                    constant: assignments[local] == 1 && !function.locals[local].mutable,
+                },
+                _ => Declaration::Top,
+            }
+        })
+        .collect()
+}
+
+/// The blocks that need a label, because something other than the block
+/// right before them jumps to them.
+fn labels(function: &Function) -> HashSet<BlockId> {
+    let mut labels = HashSet::new();
+    for (id, block) in function.blocks.iter().enumerate() {
+        match &block.terminator.kind {
+            TerminatorKind::Goto(target) if *target != id + 1 => {
+                labels.insert(*target);
+            }
+            TerminatorKind::Branch { yes, no, .. } => {
+                for target in [yes, no] {
+                    if *target != id + 1 {
+                        labels.insert(*target);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    labels
+}
+
+impl Emitter<'_> {
+    /// The C name of a function, or of an instance of a generic one, e.g.
+    /// `max__i32` for `max` called with `i32`s.
+    fn function_name(&self, name: &str, type_args: &[Type]) -> String {
+        if !type_args.is_empty() {
+            return instance_name(name, type_args);
+        }
+        match self.module.function(name, type_args) {
+            Some(function) if function.exported => export_name(name).to_string(),
+            // C starts from a `main` of its own, which calls this one
+            Some(function) if name == "main" && needs_entry(function) => "crisp_main".to_string(),
+            _ => c_ident(name),
+        }
+    }
+
+    /// The declarations C code needs to call the exported functions: the
+    /// types their signatures mention, and their prototypes.
+    fn emit_header(&mut self, exported: &[(&Function, &str)]) -> String {
+        let out = std::mem::take(&mut self.out);
+        let sequences = std::mem::take(&mut self.sequences);
+        let signatures: Vec<&Type> = exported
+            .iter()
+            .flat_map(|(function, _)| {
+                std::iter::once(&function.returns).chain(
+                    function
+                        .params
+                        .iter()
+                        .map(|param| &function.locals[*param].ty),
+                )
+            })
+            .collect();
+        let mut needed = HashSet::new();
+        for ty in &signatures {
+            needed_types(ty, self.module, &mut needed);
+        }
+        for name in type_order(self.module) {
+            if !needed.contains(name) {
+                continue;
+            }
+            if let Some(def) = self.module.struct_def(name) {
+                self.emit_struct(def);
+            } else if let Some(def) = self.module.enum_def(name) {
+                self.emit_enum(def);
+            }
+        }
+        for ty in signatures {
+            self.emit_sequence_type(ty);
+        }
+        for (function, name) in exported {
+            self.emit_fn_signature(function, name);
+            self.out.push_str(";\n");
+        }
+        self.pending_sequences.clear();
+        self.sequences = sequences;
+        let declarations = std::mem::replace(&mut self.out, out);
+        format!(
+            "#include {}\n\n{}",
+            c_string(runtime::HEADER_NAME),
+            declarations
+        )
+    }
+
+    /// The C `main` for a Crisp one C cannot start from as it is, named
+    /// `name`: one that takes the command-line arguments, which it is handed
+    /// as strings, or returns nothing, which exits with 0.
+    fn emit_entry(&mut self, function: &Function, name: &str) {
+        self.out.push('\n');
+        self.marks.push((self.out.len(), function.loc));
+        let mut args = String::new();
+        match function.params.first() {
+            Some(param) => {
+                let ty = self.c_type(&function.locals[*param].ty, function.loc);
+                self.out.push_str("int main(int argc, char** argv) {\n");
+                self.out.push_str(
+                    "    crisp_string* args = crisp_alloc(sizeof(crisp_string) * (uint64_t)argc);\n",
+                );
+                self.out.push_str("    for (int i = 0; i < argc; i++) {\n");
+                self.out
+                    .push_str("        args[i] = crisp_string_from_c(argv[i]);\n");
+                self.out.push_str("    }\n");
+                args = format!("({}){{args, (uint64_t)argc}}", ty);
+            }
+            None => self.out.push_str("int main(void) {\n"),
+        }
+        if function.returns == Type::Void {
+            let _ = writeln!(self.out, "    {}({});", name, args);
+            self.out.push_str("    return 0;\n");
+        } else {
+            let _ = writeln!(self.out, "    return {}({});", name, args);
+        }
+        self.out.push_str("}\n");
+    }
+
+    fn emit_fn_signature(&mut self, function: &Function, name: &str) {
+        let names = local_names(function, &self.file_scope);
+        let params: Vec<(String, Type)> = function
+            .params
+            .iter()
+            .map(|param| (names[*param].clone(), function.locals[*param].ty.clone()))
+            .collect();
+        self.emit_signature(name, &function.returns, &params, false, function.loc);
+    }
+
+    /// Emits a prototype. That of a C function is emitted as it was
+    /// declared, so the C compiler holds it to the header that also
+    /// declares it.
+    fn emit_signature(
+        &mut self,
+        name: &str,
+        returns: &Type,
+        params: &[(String, Type)],
+        external: bool,
+        loc: Location,
+    ) {
+        let mut c_type = |ty: &Type| {
+            if external && *ty == Type::String {
+                // C libraries take plain NUL-terminated strings.
+                "const char*".to_string()
+            } else {
+                self.c_type(ty, loc)
+            }
+        };
+        let returns = c_type(returns);
+        let params: Vec<String> = params
+            .iter()
+            .map(|(param, ty)| format!("{} {}", c_type(ty), c_ident(param)))
+            .collect();
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params.join(", ")
+        };
+        let _ = write!(self.out, "{} {}({})", returns, name, params);
+    }
+
+    /// Emits the blocks of a function as labelled runs of statements, with
+    /// the jumps between them as gotos. A block that only continues with the
+    /// next one needs neither.
+    fn emit_body(&mut self, function: &Function) {
+        self.locals = local_names(function, &self.file_scope);
+        self.declarations = declarations(function, self.module);
+        let mut temporaries = 0;
+        for (local, declaration) in self.declarations.iter().enumerate() {
+            if function.locals[local].name.is_none() && *declaration != Declaration::Folded {
+                temporaries += 1;
+                self.locals[local] = format!("_crisp_tmp{}", temporaries);
+            }
+        }
+        for local in 0..function.locals.len() {
+            if self.declarations[local] == Declaration::Top {
+                let ty = self.c_type(&function.locals[local].ty, function.loc);
+                let line = format!("{} {};", ty, self.locals[local]);
+                self.line(&line, function.loc);
+            }
+        }
+        let labels = labels(function);
+        for (id, block) in function.blocks.iter().enumerate() {
+            if labels.contains(&id) {
+                // a label has to be followed by a statement, not a declaration
+                let _ = writeln!(self.out, "bb{}:;", id);
+            }
+            for (index, statement) in block.statements.iter().enumerate() {
+                self.emit_statement(function, statement, (id, index));
+            }
+            self.emit_terminator(function, id, &block.terminator);
+        }
+        self.folded.clear();
+    }
+
+    fn emit_statement(&mut self, function: &Function, statement: &Statement, at: (BlockId, usize)) {
+        let loc = statement.loc;
+        match &statement.kind {
+            StatementKind::Assign(place, rvalue) => {
+                let whole = match (&place.base, place.projections.is_empty()) {
+                    (Base::Local(local), true) => Some(*local),
+                    _ => None,
+                };
+                if let Some(local) = whole {
+                    let ty = &function.locals[local].ty;
+                    match self.declarations[local] {
+                        Declaration::Folded => {
+                            let value = self.rvalue(function, rvalue, ty);
+                            self.folded.insert(local, value);
+                            return;
+                        }
+                        Declaration::At {
+                            block,
+                            statement,
+                            constant,
+                        } if (block, statement) == at => {
+                            let value = self.initializer(function, rvalue, ty);
+                            let mut ty = self.c_type(ty, loc);
+                            if constant {
+                                ty = const_type(&ty);
+                            }
+                            let line = format!("{} {} = {};", ty, self.locals[local], value);
+                            self.line(&line, loc);
+                            return;
+                        }
+                        _ => {}
+                    }
+                }
+                let target = self.place(function, place);
+                let ty = self.module.place_type(function, place);
+                let value = self.rvalue(function, rvalue, &ty);
+                self.line(&format!("{} = {};", target, value), loc);
+            }
+            StatementKind::Eval(rvalue) => {
+                let value = self.rvalue(function, rvalue, &Type::Void);
+                self.line(&format!("{};", value), loc);
+            }
+        }
+    }
+
+    fn emit_terminator(&mut self, function: &Function, id: BlockId, terminator: &Terminator) {
+        let loc = terminator.loc;
+        let next = id + 1;
+        match &terminator.kind {
+            TerminatorKind::Goto(target) if *target == next => {}
+            TerminatorKind::Goto(target) => self.line(&format!("goto bb{};", target), loc),
+            TerminatorKind::Branch { condition, yes, no } => {
+                let condition = self.operand(function, condition);
+                if *yes == next {
+                    self.line(&format!("if (!{}) goto bb{};", condition, no), loc);
+                } else {
+                    let condition = without_parens(&condition);
+                    self.line(&format!("if ({}) goto bb{};", condition, yes), loc);
+                    if *no != next {
+                        self.line(&format!("goto bb{};", no), loc);
+                    }
+                }
+            }
+            TerminatorKind::Return(Some(value)) => {
+                let value = self.operand(function, value);
+                self.line(&format!("return {};", value), loc);
+            }
+            // falling off the end returns anyway
+            TerminatorKind::Return(None) if next == function.blocks.len() => {}
+            TerminatorKind::Return(None) => self.line("return;", loc),
+            TerminatorKind::Unreachable => {
+                let (file, line) = self.source_position(loc);
+                let message = format!(
+                    "reached the end of {} without returning a value",
+                    function.name
+                );
+                self.line(
+                    &format!("crisp_panic({}, {}, {});", file, line, c_string(&message)),
+                    loc,
+                );
+            }
+        }
+    }
+
+    /// An initializer for a declaration. Aggregates use the plain brace
+    /// form, as globals have to.
+    fn initializer(&mut self, function: &Function, rvalue: &Rvalue, ty: &Type) -> String {
+        match rvalue {
+            Rvalue::Use(Operand::Const(constant)) => self.constant_initializer(constant),
+            Rvalue::Aggregate { kind, operands } => {
+                let values: Vec<String> = operands
+                    .iter()
+                    .map(|operand| match operand {
+                        Operand::Const(constant) => self.constant_initializer(constant),
+                        _ => self.operand(function, operand),
+                    })
+                    .collect();
+                self.aggregate_initializer(kind, values)
+            }
+            _ => self.rvalue(function, rvalue, ty),
+        }
+    }
+
+    /// The C expression computing an rvalue of type `ty`.
+    fn rvalue(&mut self, function: &Function, rvalue: &Rvalue, ty: &Type) -> String {
+        match rvalue {
+            Rvalue::Use(operand) => self.operand(function, operand),
+            Rvalue::Binary {
+                op,
+                left,
+                right,
+                loc,
+            } => {
+                let operand_ty = self.module.operand_type(function, left);
+                let operands = vec![self.operand(function, left), self.operand(function, right)];
+                self.operator(*op, &operand_ty, operands, *loc)
+            }
+            Rvalue::Unary { op, operand, loc } => {
+                let operand_ty = self.module.operand_type(function, operand);
+                let operand = self.operand(function, operand);
+                self.operator(*op, &operand_ty, vec![operand], *loc)
+            }
+            Rvalue::Increment(operand) => format!("({} + 1)", self.operand(function, operand)),
+            Rvalue::Cast { operand, ty } => {
+                let actual = self.module.operand_type(function, operand);
+                let value = self.operand(function, operand);
+                let target = self.c_type(ty, function.loc);
+                match actual {
+                    Type::Array(_, len) => format!("({}){{{}.data, {}}}", target, value, len),
+                    _ => format!("(({}){})", target, value),
+                }
+            }
+            Rvalue::Aggregate { kind, operands } => {
+                let values = operands
+                    .iter()
+                    .map(|operand| self.operand(function, operand))
+                    .collect();
+                let ty = self.aggregate_type(kind, operands.len());
+                let ty = self.c_type(&ty, function.loc);
+                format!("({}){}", ty, self.aggregate_initializer(kind, values))
+            }
+            Rvalue::Len(operand) => format!("{}.len", self.operand(function, operand)),
+            Rvalue::CheckIndex { index, len, loc } => {
+                let index = self.operand(function, index);
+                let len = self.operand(function, len);
+                if !self.options.bounds_checks {
+                    return index;
+                }
+                let (file, line) = self.source_position(*loc);
+                format!("crisp_check_index({}, {}, {}, {})", index, len, file, line)
+            }
+            Rvalue::IsVariant { operand, variant } => {
+                let enum_name = match self.module.operand_type(function, operand) {
+                    Type::Named(name) => name,
+                    _ => String::new(),
+                };
+                let value = self.operand(function, operand);
+                format!("({}.tag == {})", value, tag_name(&enum_name, variant))
+            }
+            Rvalue::Call { callee, args } => self.call(function, callee, args, ty),
+        }
+    }
+
+    /// Lowers an operator to its C counterpart. C does arithmetic on
+    /// anything narrower than an int as an int, so narrow results are cast
+    /// back to keep wrapping around where Crisp expects it to.
+    fn operator(
+        &mut self,
+        operator: Operator,
+        ty: &Type,
+        operands: Vec<String>,
+        loc: Location,
+    ) -> String {
+        let comparison = operator.kind() == OperatorKind::Comparison;
+        if let Some(op) = runtime_operation(operator, operands.len())
+            && ty.is_integer()
+            && self.options.overflow != Overflow::Unchecked
+        {
+            return self.runtime_arithmetic(op, ty, operands, loc);
+        }
+        if matches!(operator, Operator::Eq | Operator::Ne)
+            && *ty == Type::String
+            && let [a, b] = operands.as_slice()
+        {
+            let eq = format!("crisp_string_eq({}, {})", a, b);
+            return match operator {
+                Operator::Ne => format!("(!{})", eq),
+                _ => eq,
+            };
+        }
+        let value = match operands.as_slice() {
+            [operand] => format!("({}{})", operator.c_operator(), operand),
+            _ => format!(
+                "({})",
+                operands.join(&format!(" {} ", operator.c_operator()))
+            ),
+        };
+        match ty {
+            Type::I8 | Type::I16 | Type::U8 | Type::U16 if !comparison => {
+                format!("(({}){})", self.c_type(ty, loc), value)
+            }
+            _ => value,
+        }
+    }
+
+    /// Lowers integer arithmetic that can overflow or divide by zero onto
+    /// the checked or wrapping helpers of the runtime.
+    fn runtime_arithmetic(
+        &mut self,
+        op: &str,
+        ty: &Type,
+        operands: Vec<String>,
+        loc: Location,
+    ) -> String {
+        let checked = self.options.overflow == Overflow::Checked;
+        let mode = if checked { "checked" } else { "wrapping" };
+        // wrapping helpers only panic on division by zero
+        let position = if checked || op == "div" || op == "rem" {
+            let (file, line) = self.source_position(loc);
+            format!(", {}, {}", file, line)
+        } else {
+            String::new()
+        };
+        format!(
+            "crisp_{}_{}_{}({}{})",
+            mode,
+            op,
+            ty,
+            operands.join(", "),
+            position
+        )
+    }
+
+    fn call(
+        &mut self,
+        function: &Function,
+        callee: &Callee,
+        args: &[Operand],
+        ty: &Type,
+    ) -> String {
+        match callee {
+            Callee::Function { name, type_args } => {
+                let args: Vec<String> =
+                    args.iter().map(|arg| self.operand(function, arg)).collect();
+                let name = self.function_name(name, type_args);
+                format!("{}({})", name, args.join(", "))
+            }
+            Callee::Extern(name) => {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|arg| match arg {
+                        // C libraries take plain NUL-terminated strings
+                        Operand::Const(Constant::String(value)) => string_literal(value),
+                        _ if self.module.operand_type(function, arg) == Type::String => {
+                            format!("{}.data", self.operand(function, arg))
+                        }
+                        _ => self.operand(function, arg),
+                    })
+                    .collect();
+                let returns = self
+                    .module
+                    .externs
+                    .iter()
+                    .find(|function| function.name == *name)
+                    .map_or(ty, |function| &function.returns);
+                if *returns == Type::String {
+                    return format!("crisp_string_from_c({}({}))", name, args.join(", "));
+                }
+                format!("{}({})", name, args.join(", "))
+            }
+            Callee::Builtin(builtin) => self.builtin(function, *builtin, args),
+        }
+    }
+
+    /// Lowers a call to a builtin onto the runtime library.
+    fn builtin(&mut self, function: &Function, builtin: Builtin, args: &[Operand]) -> String {
+        let Some(first) = args.first() else {
+            return String::new();
+        };
+        let ty = self.module.operand_type(function, first);
+        let value = self.operand(function, first);
+        match builtin {
+            Builtin::Print | Builtin::Println => {
+                let print = match ty {
+                    Type::String => "crisp_print",
+                    Type::Bool => "crisp_print_bool",
+                    Type::F32 | Type::F64 => "crisp_print_float",
+                    Type::U8 | Type::U16 | Type::U32 | Type::U64 => "crisp_print_uint",
+                    _ => "crisp_print_int",
+                };
+                if builtin == Builtin::Println {
+                    format!("({}({}), crisp_print_newline())", print, value)
+                } else {
+                    format!("{}({})", print, value)
+                }
+            }
+            Builtin::Concat | Builtin::Compare => {
+                let other = match args.get(1) {
+                    Some(other) => self.operand(function, other),
+                    None => String::new(),
+                };
+                format!("crisp_string_{}({}, {})", builtin.name(), value, other)
+            }
+        }
+    }
+
+    fn operand(&mut self, function: &Function, operand: &Operand) -> String {
+        match operand {
+            Operand::Copy(place) => self.place(function, place),
+            Operand::Const(constant) => self.constant(constant),
+        }
+    }
+
+    fn place(&mut self, function: &Function, place: &Place) -> String {
+        let mut value = match &place.base {
+            Base::Local(local) => match self.folded.remove(local) {
+                Some(value) => value,
+                None => self.locals[*local].clone(),
+            },
+            Base::Global(name) => c_ident(name),
+        };
+        for projection in &place.projections {
+            match projection {
+                Projection::Field(field) => {
+                    let _ = write!(value, ".{}", c_ident(field));
+                }
+                Projection::Index(index) => {
+                    let index = self.operand(function, index);
+                    let _ = write!(value, ".data[{}]", index);
+                }
+                Projection::VariantField { variant, field } => {
+                    let _ = write!(value, ".as.{}.{}", c_ident(variant), c_ident(field));
+                }
+            }
+        }
+        value
+    }
+
+    /// A constant as an expression, with aggregates as compound literals.
+    fn constant(&mut self, constant: &Constant) -> String {
+        match constant {
+            // the negative limits cannot be written as a negated constant,
+            // which is too large for the type before it is negated
+            Constant::Int { value, ty } => match ty {
+                Type::I32 if *value == i32::MIN as i128 => "INT32_MIN".to_string(),
+                Type::I64 if *value == i64::MIN as i128 => "INT64_MIN".to_string(),
+                Type::U32 => format!("{}u", value),
+                Type::U64 => format!("UINT64_C({})", value),
+                Type::I64 => format!("INT64_C({})", value),
+                _ => value.to_string(),
+            },
+            Constant::Float { value, ty } => match ty {
+                Type::F32 => format!("{:?}f", *value as f32),
+                _ => format!("{:?}", value),
+            },
+            Constant::Bool(value) => value.to_string(),
+            Constant::String(value) => format!("CRISP_STRING({})", string_literal(value)),
+            Constant::Aggregate { kind, values } => {
+                let ty = self.c_type(&constant.ty(), Location::default());
+                format!("({}){}", ty, self.constant_initializer_of(kind, values))
+            }
+        }
+    }
+
+    /// A constant as an initializer, which unlike a compound literal is
+    /// also constant enough for globals.
+    fn constant_initializer(&mut self, constant: &Constant) -> String {
+        match constant {
+            Constant::String(value) => {
+                let literal = string_literal(value);
+                format!("{{{}, sizeof({}) - 1}}", literal, literal)
+            }
+            Constant::Aggregate { kind, values } => self.constant_initializer_of(kind, values),
+            _ => self.constant(constant),
+        }
+    }
+
+    fn constant_initializer_of(&mut self, kind: &Aggregate, values: &[Constant]) -> String {
+        let values = values
+            .iter()
+            .map(|value| self.constant_initializer(value))
+            .collect();
+        self.aggregate_initializer(kind, values)
+    }
+
+    /// The braced initializer of a struct, variant or array.
+    fn aggregate_initializer(&mut self, kind: &Aggregate, values: Vec<String>) -> String {
+        let fields = |names: &[(String, Type)], values: Vec<String>| {
+            names
+                .iter()
+                .zip(values)
+                .map(|((field, _), value)| format!(".{} = {}", c_ident(field), value))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match kind {
+            Aggregate::Array(_) => format!("{{{{{}}}}}", values.join(", ")),
+            Aggregate::Struct(name) => {
+                let names = self
+                    .module
+                    .struct_def(name)
+                    .map_or(&[][..], |def| &def.fields);
+                format!("{{{}}}", fields(names, values))
+            }
+            Aggregate::Variant { enum_name, variant } => {
+                let mut init = format!(".tag = {}", tag_name(enum_name, variant));
+                let names = self
+                    .module
+                    .enum_def(enum_name)
+                    .and_then(|def| def.variant(variant))
+                    .map_or(&[][..], |variant| &variant.fields);
+                if !names.is_empty() {
+                    let _ = write!(
+                        init,
+                        ", .as.{} = {{{}}}",
+                        c_ident(variant),
+                        fields(names, values)
+                    );
+                }
+                format!("{{{}}}", init)
+            }
+        }
+    }
+
+    /// The type of the value an aggregate of `len` operands builds.
+    fn aggregate_type(&self, kind: &Aggregate, len: usize) -> Type {
+        match kind {
+            Aggregate::Array(element) => Type::Array(Box::new(element.clone()), len),
+            Aggregate::Struct(name)
+            | Aggregate::Variant {
+                enum_name: name, ..
+            } => Type::Named(name.clone()),
+        }
+    }
+
+    /// Spells out a type, noting array and slice types that still need a
+    /// definition.
+    fn c_type(&mut self, ty: &Type, loc: Location) -> String {
+        if ty.element().is_some() && !self.sequences.contains(ty) {
+            self.pending_sequences.push(ty.clone());
+        }
+        match c_type(ty) {
+            Some(ty) => ty,
+            None => {
+                self.error(Diagnostic::error(
+                    "Cannot emit a value whose type is unknown",
+                    loc,
+                ));
+                "void".to_string()
+            }
+        }
+    }
+
+    /// The file name, as a C string, and line a runtime error at `loc`
+    /// reports.
+    fn source_position(&self, loc: Location) -> (String, usize) {
+        let file = self
+            .options
+            .file_names
+            .get(&loc.file)
+            .map_or("<unknown>", String::as_str);
+        (c_string(file), loc.line)
+    }
+
+    fn line(&mut self, text: &str, loc: Location) {
+        self.marks.push((self.out.len(), loc));
+        self.out.push_str("    ");
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+/// Whether C needs a `main` of its own to start from a Crisp `main`,
+/// rather than starting from it directly as it can from `(fn:i32 main ()
+/// ...)`.
+fn needs_entry(function: &Function) -> bool {
+    !function.params.is_empty() || function.returns != Type::I32
+}
+
+/// The C names of the locals of a function. A local keeps its own name
+/// unless an earlier one, or a definition outside the function, already
+/// took it, and temporaries are numbered.
+fn local_names(function: &Function, file_scope: &HashSet<String>) -> Vec<String> {
+    let mut taken = file_scope.clone();
+    let mut temporaries = 0;
+    function
+        .locals
+        .iter()
+        .map(|local| match &local.name {
+            Some(name) => {
+                let base = c_ident(name);
+                let mut name = base.clone();
+                let mut suffix = 1;
+                while !taken.insert(name.clone()) {
+                    name = format!("{}_{}", base, suffix);
+                    suffix += 1;
+                }
+                name
+            }
+            None => {
+                temporaries += 1;
+                format!("_crisp_tmp{}", temporaries)
+            }
+        })
+        .collect()
+}
+
+/// A C string literal holding exactly `text`, such as a file name.
+fn c_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Qualifies a C type as const. Strings already point at const chars, and
+/// repeating the qualifier only earns a warning.
+fn const_type(ty: &str) -> String {
+    if ty.starts_with("const ") {
+        ty.to_string()
+    } else {
+        format!("const {}", ty)
+    }
+}
+
+impl Emitter<'_> {
+    /// Emits the struct wrapping an array or slice type, after the ones its
+    /// element type needs. Arrays are wrapped so they can be copied, passed
+    /// and returned like any other value.
+    fn emit_sequence_type(&mut self, ty: &Type) {
+        let (Type::Array(element, _) | Type::Slice(element)) = ty else {
+            return;
+        };
+        let (Some(name), Some(element_ty)) = (c_type(ty), c_type(element)) else {
+            return;
+        };
+        if !self.sequences.insert(ty.clone()) {
+            return;
+        }
+        self.emit_sequence_type(element);
+        let _ = writeln!(self.out, "typedef struct {} {{", name);
+        match ty {
+            Type::Array(_, len) => {
+                let _ = writeln!(self.out, "    {} data[{}];", element_ty, len);
+            }
+            _ => {
+                let _ = writeln!(self.out, "    {} const* data;", element_ty);
+                self.out.push_str("    uint64_t len;\n");
+            }
+        }
+        let _ = writeln!(self.out, "}} {};\n", name);
+    }
+
+    fn emit_struct(&mut self, def: &StructDef) {
+        for (_, ty) in &def.fields {
+            self.emit_sequence_type(ty);
+        }
+        let name = c_ident(&def.name);
+        let _ = writeln!(self.out, "typedef struct {} {{", name);
+        for (field, ty) in &def.fields {
+            let ty = self.c_type(ty, def.loc);
+            let _ = writeln!(self.out, "    {} {};", ty, c_ident(field));
+        }
+        let _ = writeln!(self.out, "}} {};\n", name);
+    }
+
+    /// Emits an enum as a tag plus a union holding the payload of each
+    /// variant that has fields.
+    fn emit_enum(&mut self, def: &EnumDef) {
+        for variant in &def.variants {
+            for (_, ty) in &variant.fields {
+                self.emit_sequence_type(ty);
+            }
+        }
+        let name = c_ident(&def.name);
+        let _ = writeln!(self.out, "typedef enum {}_Tag {{", name);
+        for variant in &def.variants {
+            let _ = writeln!(self.out, "    {},", tag_name(&def.name, &variant.name));
+        }
+        let _ = writeln!(self.out, "}} {}_Tag;\n", name);
+        let _ = writeln!(self.out, "typedef struct {} {{", name);
+        let _ = writeln!(self.out, "    {}_Tag tag;", name);
+        if def
+            .variants
+            .iter()
+            .any(|variant| !variant.fields.is_empty())
+        {
+            self.out.push_str("    union {\n");
+            for variant in def.variants.iter().filter(|v| !v.fields.is_empty()) {
+                self.out.push_str("        struct {\n");
+                for (field, ty) in &variant.fields {
+                    let ty = self.c_type(ty, def.loc);
+                    let _ = writeln!(self.out, "            {} {};", ty, c_ident(field));
+                }
+                let _ = writeln!(self.out, "        }} {};", c_ident(&variant.name));
+            }
+            self.out.push_str("    } as;\n");
+        }
+        let _ = writeln!(self.out, "}} {};\n", name);
+    }
+}
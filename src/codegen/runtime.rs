@@ -0,0 +1,22 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The name generated code includes the runtime under.
+pub const HEADER_NAME: &str = "crisp_runtime.h";
+pub const SOURCE_NAME: &str = "crisp_runtime.c";
+
+/// Declares the strings, printing, allocation and panics generated code
+/// relies on.
+pub const HEADER: &str = include_str!("runtime/crisp_runtime.h");
+pub const SOURCE: &str = include_str!("runtime/crisp_runtime.c");
+
+/// Writes the runtime into `dir`, next to the generated code that includes
+/// it, and returns the path of its source.
+pub fn write_runtime(dir: &Path) -> std::io::Result<PathBuf> {
+    fs::write(dir.join(HEADER_NAME), HEADER)?;
+    let source = dir.join(SOURCE_NAME);
+    fs::write(&source, SOURCE)?;
+    Ok(source)
+}
@@ -0,0 +1,83 @@
+#include "crisp_runtime.h"
+
+#include <inttypes.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+_Noreturn void crisp_panic(const char* file, uint32_t line, const char* message) {
+    fflush(stdout);
+    fprintf(stderr, "panicked at %s:%" PRIu32 ": %s\n", file, line, message);
+    exit(101);
+}
+
+void* crisp_alloc(uint64_t size) {
+    void* memory = malloc(size > 0 ? size : 1);
+    if (memory == NULL) {
+        crisp_panic("<runtime>", 0, "out of memory");
+    }
+    return memory;
+}
+
+uint64_t crisp_check_index(uint64_t index, uint64_t len, const char* file, uint32_t line) {
+    if (index >= len) {
+        char message[96];
+        snprintf(message, sizeof message,
+                 "index out of bounds: the length is %" PRIu64 " but the index is %" PRIu64,
+                 len, index);
+        crisp_panic(file, line, message);
+    }
+    return index;
+}
+
+crisp_string crisp_string_from_c(const char* text) {
+    if (text == NULL) {
+        return (crisp_string){"", 0};
+    }
+    return (crisp_string){text, strlen(text)};
+}
+
+crisp_string crisp_string_concat(crisp_string a, crisp_string b) {
+    char* data = crisp_alloc(a.len + b.len + 1);
+    memcpy(data, a.data, a.len);
+    memcpy(data + a.len, b.data, b.len);
+    data[a.len + b.len] = '\0';
+    return (crisp_string){data, a.len + b.len};
+}
+
+int32_t crisp_string_compare(crisp_string a, crisp_string b) {
+    uint64_t shorter = a.len < b.len ? a.len : b.len;
+    int order = memcmp(a.data, b.data, shorter);
+    if (order != 0) {
+        return order < 0 ? -1 : 1;
+    }
+    return a.len < b.len ? -1 : a.len > b.len ? 1 : 0;
+}
+
+bool crisp_string_eq(crisp_string a, crisp_string b) {
+    return a.len == b.len && memcmp(a.data, b.data, a.len) == 0;
+}
+
+void crisp_print(crisp_string text) {
+    fwrite(text.data, 1, text.len, stdout);
+}
+
+void crisp_print_int(int64_t value) {
+    printf("%" PRId64, value);
+}
+
+void crisp_print_uint(uint64_t value) {
+    printf("%" PRIu64, value);
+}
+
+void crisp_print_float(double value) {
+    printf("%g", value);
+}
+
+void crisp_print_bool(bool value) {
+    fputs(value ? "true" : "false", stdout);
+}
+
+void crisp_print_newline(void) {
+    putchar('\n');
+}
@@ -0,0 +1,128 @@
+/* The Crisp runtime, which every generated program is built with. */
+#ifndef CRISP_RUNTIME_H
+#define CRISP_RUNTIME_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+/* A string that knows its length. Its bytes are always followed by a NUL,
+ * so they can be handed to C functions as they are. */
+typedef struct crisp_string {
+    const char* data;
+    uint64_t len;
+} crisp_string;
+
+/* A string literal, whose length the C compiler works out. */
+#define CRISP_STRING(literal) ((crisp_string){(literal), sizeof(literal) - 1})
+
+/* Reports a failure at a line of Crisp code and exits with status 101. */
+_Noreturn void crisp_panic(const char* file, uint32_t line, const char* message);
+
+/* Allocates memory that lives as long as the program, or panics. */
+void* crisp_alloc(uint64_t size);
+
+/* Returns index if it is less than len, and panics otherwise. */
+uint64_t crisp_check_index(uint64_t index, uint64_t len, const char* file, uint32_t line);
+
+/* Wraps a C string, taking NULL, as some C functions return, to be empty. */
+crisp_string crisp_string_from_c(const char* text);
+crisp_string crisp_string_concat(crisp_string a, crisp_string b);
+/* Orders strings bytewise, returning a negative number, zero or a positive
+ * number like strcmp. */
+int32_t crisp_string_compare(crisp_string a, crisp_string b);
+bool crisp_string_eq(crisp_string a, crisp_string b);
+
+void crisp_print(crisp_string text);
+void crisp_print_int(int64_t value);
+void crisp_print_uint(uint64_t value);
+void crisp_print_float(double value);
+void crisp_print_bool(bool value);
+void crisp_print_newline(void);
+
+/* Integer arithmetic that panics or wraps around on overflow, rather than
+ * leaving it undefined as C does for signed types. Dividing by zero panics
+ * either way. */
+#define CRISP_OVERFLOWING(op, verb, name, type)                                        \
+    static inline type crisp_checked_##op##_##name(type a, type b, const char* file,   \
+                                                   uint32_t line) {                    \
+        type result;                                                                   \
+        if (__builtin_##op##_overflow(a, b, &result)) {                                \
+            crisp_panic(file, line, "attempt to " verb " with overflow");              \
+        }                                                                              \
+        return result;                                                                 \
+    }                                                                                  \
+    static inline type crisp_wrapping_##op##_##name(type a, type b) {                  \
+        type result;                                                                   \
+        __builtin_##op##_overflow(a, b, &result);                                      \
+        return result;                                                                 \
+    }
+
+/* The only overflowing division is the most negative number by -1, whose
+ * wrapped quotient is the number itself and whose remainder is 0. */
+#define CRISP_DIVIDING(op, c_op, verb, zero, name, type, min, wrapped)                 \
+    static inline type crisp_checked_##op##_##name(type a, type b, const char* file,   \
+                                                   uint32_t line) {                    \
+        if (b == 0) {                                                                  \
+            crisp_panic(file, line, zero);                                             \
+        }                                                                              \
+        if (min != 0 && a == min && b == (type)-1) {                                   \
+            crisp_panic(file, line, "attempt to " verb " with overflow");              \
+        }                                                                              \
+        return a c_op b;                                                               \
+    }                                                                                  \
+    static inline type crisp_wrapping_##op##_##name(type a, type b, const char* file,  \
+                                                    uint32_t line) {                   \
+        if (b == 0) {                                                                  \
+            crisp_panic(file, line, zero);                                             \
+        }                                                                              \
+        if (min != 0 && a == min && b == (type)-1) {                                   \
+            return wrapped;                                                            \
+        }                                                                              \
+        return a c_op b;                                                               \
+    }
+
+/* Shifting by the width of the type or more is undefined in C. Wrapping
+ * shifts only use the low bits of the amount. */
+#define CRISP_SHIFTING(op, c_op, verb, name, type, utype, bits)                        \
+    static inline type crisp_checked_##op##_##name(type a, uint64_t b, const char* file, \
+                                                   uint32_t line) {                    \
+        if (b >= bits) {                                                               \
+            crisp_panic(file, line, "attempt to " verb " with overflow");              \
+        }                                                                              \
+        return (type)((utype)a c_op b);                                                \
+    }                                                                                  \
+    static inline type crisp_wrapping_##op##_##name(type a, uint64_t b) {              \
+        return (type)((utype)a c_op (b & (bits - 1)));                                 \
+    }
+
+#define CRISP_INTEGER(name, type, utype, bits, min)                                     \
+    CRISP_OVERFLOWING(add, "add", name, type)                                          \
+    CRISP_OVERFLOWING(sub, "subtract", name, type)                                     \
+    CRISP_OVERFLOWING(mul, "multiply", name, type)                                     \
+    CRISP_DIVIDING(div, /, "divide", "attempt to divide by zero", name, type, min, a)  \
+    CRISP_DIVIDING(rem, %, "calculate the remainder",                                  \
+                   "attempt to calculate the remainder with a divisor of zero", name,  \
+                   type, min, 0)                                                       \
+    CRISP_SHIFTING(shl, <<, "shift left", name, type, utype, bits)                     \
+    CRISP_SHIFTING(shr, >>, "shift right", name, type, type, bits)                     \
+    static inline type crisp_checked_neg_##name(type a, const char* file, uint32_t line) { \
+        type result;                                                                   \
+        if (__builtin_sub_overflow((type)0, a, &result)) {                             \
+            crisp_panic(file, line, "attempt to negate with overflow");                \
+        }                                                                              \
+        return result;                                                                 \
+    }                                                                                  \
+    static inline type crisp_wrapping_neg_##name(type a) {                             \
+        return crisp_wrapping_sub_##name(0, a);                                        \
+    }
+
+CRISP_INTEGER(i8, int8_t, uint8_t, 8, INT8_MIN)
+CRISP_INTEGER(i16, int16_t, uint16_t, 16, INT16_MIN)
+CRISP_INTEGER(i32, int32_t, uint32_t, 32, INT32_MIN)
+CRISP_INTEGER(i64, int64_t, uint64_t, 64, INT64_MIN)
+CRISP_INTEGER(u8, uint8_t, uint8_t, 8, 0)
+CRISP_INTEGER(u16, uint16_t, uint16_t, 16, 0)
+CRISP_INTEGER(u32, uint32_t, uint32_t, 32, 0)
+CRISP_INTEGER(u64, uint64_t, uint64_t, 64, 0)
+
+#endif
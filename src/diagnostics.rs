@@ -0,0 +1,131 @@
+use colored::Colorize;
+use log::{error, warn};
+
+use crate::{
+    parsing::ast::nodes::Location,
+    sources::{FileId, SourceFile, SourceMap},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A secondary location attached to a diagnostic, e.g. the original
+/// definition of a binding the primary location refers to.
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub message: String,
+    pub loc: Location,
+}
+
+/// An error or warning found after parsing, when the source text is no
+/// longer borrowed by the AST.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub loc: Location,
+    pub notes: Vec<Note>,
+}
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, loc: Location) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            loc,
+            notes: vec![],
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, loc: Location) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.into(),
+            loc,
+            notes: vec![],
+        }
+    }
+
+    pub fn with_note(mut self, message: impl Into<String>, loc: Location) -> Diagnostic {
+        self.notes.push(Note {
+            message: message.into(),
+            loc,
+        });
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn print(&self, sources: &SourceMap) {
+        self.log(self.render(sources));
+    }
+
+    /// Prints a diagnostic found while parsing `file`, before it is part of
+    /// a [`SourceMap`].
+    pub fn print_in(&self, file: &SourceFile) {
+        self.log(self.render_with(|id| (id == file.id()).then_some(file)));
+    }
+
+    fn log(&self, output: String) {
+        match self.severity {
+            Severity::Error => error!("{}", output),
+            Severity::Warning => warn!("{}", output),
+        }
+    }
+
+    /// The message with the source lines it points at, as it is printed.
+    pub fn render(&self, sources: &SourceMap) -> String {
+        self.render_with(|id| sources.get(id))
+    }
+
+    fn render_with<'a>(&self, file: impl Fn(FileId) -> Option<&'a SourceFile>) -> String {
+        let mut output = format!("{}\n", self.message.bold());
+        output.push_str(&render_location(file(self.loc.file), &self.loc));
+        for note in &self.notes {
+            output.push_str(&format!(
+                "\n     {} {}\n",
+                "=".blue(),
+                note.message.bold().yellow()
+            ));
+            output.push_str(&render_location(file(note.loc.file), &note.loc));
+        }
+        output
+    }
+}
+
+/// Prints every diagnostic and returns whether any of them was an error.
+pub fn print_all(diagnostics: &[Diagnostic], sources: &SourceMap) -> bool {
+    for diagnostic in diagnostics {
+        diagnostic.print(sources);
+    }
+    diagnostics.iter().any(Diagnostic::is_error)
+}
+
+fn render_location(file: Option<&SourceFile>, loc: &Location) -> String {
+    let Some(file) = file else {
+        return format!("--> <unknown file>[{}|{}]", loc.line, loc.col);
+    };
+    let (line_start, line_end) = file.line_span(loc.line);
+    let line_text = &file.source()[line_start..line_end];
+    let start = loc.start.clamp(line_start, line_end);
+
+    // only underline up to the end of the first line of a multi-line node
+    let span_len = (loc.end.min(line_end).saturating_sub(start)).max(1);
+    let indent = " ".repeat(loc.col.saturating_sub(1));
+    let pointer = "~".repeat(span_len).red();
+
+    format!(
+        "--> {}[{}|{}]\n     |\n{:>4} | {}\n     | {}{}",
+        file.name().blue(),
+        loc.line.to_string().red(),
+        loc.col.to_string().red(),
+        loc.line.to_string().red(),
+        line_text,
+        indent,
+        pointer
+    )
+}
@@ -0,0 +1,534 @@
+//! Documentation for a program, generated from the `;;;` comments above its
+//! definitions: a page for the entry file, which links to one page for each
+//! module it imports, as static HTML or Markdown.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::info;
+
+use crate::parsing::{
+    ast::nodes::{Node, NodeKind, Symbol},
+    docs::doc_comment,
+    modules::{Program, load_program},
+};
+
+/// What the pages are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Html,
+    Markdown,
+}
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "html" => Some(Format::Html),
+            "markdown" | "md" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Markdown => "md",
+        }
+    }
+}
+
+/// A page of documentation, by the name of its file.
+#[derive(Debug)]
+pub struct Page {
+    pub file: String,
+    pub content: String,
+}
+
+/// Loads the program starting at `entry` and writes its documentation to
+/// `dir`, returning the files written.
+pub fn generate(entry: &Path, format: Format, dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let program = load_program(entry).map_err(anyhow::Error::msg)?;
+    let title = entry.file_stem().unwrap_or_default().to_string_lossy();
+    let pages = document(&program, &title, format);
+    fs::create_dir_all(dir).with_context(|| format!("Could not create {:?}", dir))?;
+    let mut written = vec![];
+    for page in pages {
+        let path = dir.join(&page.file);
+        fs::write(&path, page.content).with_context(|| format!("Could not write {:?}", path))?;
+        info!("Wrote {:?}", path);
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// The documentation of a program: an index page titled `title` for its
+/// entry file, followed by a page for each module in the order of their
+/// names.
+pub fn document(program: &Program, title: &str, format: Format) -> Vec<Page> {
+    let mut modules: BTreeMap<Option<&str>, Vec<Item>> = BTreeMap::new();
+    modules.insert(None, vec![]);
+    for name in program.modules.values() {
+        modules.insert(Some(name), vec![]);
+    }
+    for node in program.ast.children() {
+        let module = program.modules.get(&node.loc.file).map(String::as_str);
+        let file = &program.sources[node.loc.file];
+        if let Some(mut item) = Item::from_node(node) {
+            item.doc = doc_comment(file, &node.loc);
+            modules.entry(module).or_default().push(item);
+        }
+    }
+
+    let mut links = Links::default();
+    for (module, items) in &modules {
+        for item in items {
+            links.add(item.name, *module, item.name);
+            for variant in &item.variants {
+                links.add(variant, *module, item.name);
+            }
+        }
+    }
+    let names: Vec<&str> = modules.keys().flatten().copied().collect();
+
+    modules
+        .iter()
+        .map(|(module, items)| {
+            let page = PageWriter {
+                module: *module,
+                links: &links,
+                format,
+            };
+            Page {
+                file: page_file(*module, format),
+                content: page.render(title, items, &names),
+            }
+        })
+        .collect()
+}
+
+/// A documented definition.
+struct Item<'a> {
+    /// `fn`, `struct`, `enum` or `let`.
+    kind: &'static str,
+    /// The name it has in the program, which in a module is `module/name`.
+    name: &'a str,
+    /// The names of its variants, for an enum, which link to it.
+    variants: Vec<&'a str>,
+    signature: Vec<Piece<'a>>,
+    doc: Option<String>,
+}
+impl<'a> Item<'a> {
+    fn from_node(node: &'a Node) -> Option<Item<'a>> {
+        let mut signature = Signature::default();
+        let (kind, name, variants) = match &node.kind {
+            NodeKind::Fn {
+                name: Some(name),
+                type_params,
+                returns,
+                params,
+                ..
+            } => {
+                signature.text("(fn:");
+                signature.annotation(returns);
+                signature.text(" ");
+                signature.name(name.name());
+                if !type_params.is_empty() {
+                    signature.text(format!(" [{}]", type_params.join(" ")));
+                }
+                signature.text(" (");
+                signature.symbols(params);
+                signature.text("))");
+                ("fn", name.name(), vec![])
+            }
+            NodeKind::Struct { name, fields } => {
+                signature.text("(struct ");
+                signature.name(name.name());
+                signature.text(" (");
+                signature.symbols(fields);
+                signature.text("))");
+                ("struct", name.name(), vec![])
+            }
+            NodeKind::Enum { name, variants } => {
+                signature.text("(enum ");
+                signature.name(name.name());
+                for variant in variants {
+                    signature.text(" ");
+                    if variant.fields.is_empty() {
+                        signature.name(&variant.name);
+                    } else {
+                        signature.text("(");
+                        signature.name(&variant.name);
+                        signature.text(" ");
+                        signature.symbols(&variant.fields);
+                        signature.text(")");
+                    }
+                }
+                signature.text(")");
+                let variants = variants.iter().map(|variant| variant.name.as_str());
+                ("enum", name.name(), variants.collect())
+            }
+            NodeKind::Let {
+                symbol, mutable, ..
+            } => {
+                signature.text(if *mutable { "(let mut " } else { "(let " });
+                signature.name(symbol.name());
+                if let Some(annotation) = symbol.annotation() {
+                    signature.text(":");
+                    signature.annotation(annotation);
+                }
+                signature.text(")");
+                ("let", symbol.name(), vec![])
+            }
+            _ => return None,
+        };
+        Some(Item {
+            kind,
+            name,
+            variants,
+            signature: signature.pieces,
+            doc: None,
+        })
+    }
+}
+
+/// Part of a signature.
+enum Piece<'a> {
+    Text(String),
+    /// A name defined by the program, which links to its definition if it
+    /// is documented.
+    Name(&'a str),
+}
+
+/// Builds a signature as it is written, from the annotations of its
+/// symbols.
+#[derive(Default)]
+struct Signature<'a> {
+    pieces: Vec<Piece<'a>>,
+}
+impl<'a> Signature<'a> {
+    fn text(&mut self, text: impl Into<String>) {
+        match self.pieces.last_mut() {
+            Some(Piece::Text(last)) => last.push_str(&text.into()),
+            _ => self.pieces.push(Piece::Text(text.into())),
+        }
+    }
+
+    fn name(&mut self, name: &'a str) {
+        self.pieces.push(Piece::Name(name));
+    }
+
+    fn symbols(&mut self, symbols: &'a [Symbol]) {
+        for (index, symbol) in symbols.iter().enumerate() {
+            if index > 0 {
+                self.text(" ");
+            }
+            self.text(symbol.name());
+            if let Some(annotation) = symbol.annotation() {
+                self.text(":");
+                self.annotation(annotation);
+            }
+        }
+    }
+
+    /// A type such as `Point`, `[Point]` or `[Point;2]`, with the name of
+    /// the type in it as a name.
+    fn annotation(&mut self, annotation: &'a str) {
+        let start = annotation.len() - annotation.trim_start_matches('[').len();
+        let end = annotation[start..]
+            .find([';', ']'])
+            .map_or(annotation.len(), |end| start + end);
+        self.text(&annotation[..start]);
+        self.name(&annotation[start..end]);
+        self.text(&annotation[end..]);
+    }
+}
+
+/// Where each documented name is described: the module of its page, and
+/// the definition on it, which for a variant is its enum.
+#[derive(Default)]
+struct Links<'a> {
+    targets: HashMap<&'a str, (Option<&'a str>, &'a str)>,
+}
+impl<'a> Links<'a> {
+    fn add(&mut self, name: &'a str, module: Option<&'a str>, definition: &'a str) {
+        self.targets.insert(name, (module, definition));
+    }
+}
+
+/// Renders the page of one module.
+struct PageWriter<'a> {
+    /// `None` for the entry file.
+    module: Option<&'a str>,
+    links: &'a Links<'a>,
+    format: Format,
+}
+impl PageWriter<'_> {
+    fn render(&self, title: &str, items: &[Item], modules: &[&str]) -> String {
+        let title = match self.module {
+            Some(module) => format!("Module {}", module),
+            None => title.to_string(),
+        };
+        let mut body = String::new();
+        if self.module.is_none() && !modules.is_empty() {
+            body.push_str(&self.heading(2, "Modules", None));
+            let entries: Vec<String> = modules
+                .iter()
+                .map(|module| self.link(module, &page_file(Some(module), self.format)))
+                .collect();
+            body.push_str(&self.list(&entries));
+        }
+        for item in items {
+            let name = self.short(item.name);
+            let heading = match self.format {
+                Format::Html => format!("{} {}", item.kind, name),
+                Format::Markdown => format!("{} `{}`", item.kind, name),
+            };
+            body.push_str(&self.heading(2, &heading, Some(name)));
+            body.push_str(&self.signature(item));
+            if let Some(doc) = &item.doc {
+                body.push_str(&self.doc(doc));
+            }
+        }
+        match self.format {
+            Format::Html => format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+                 <style>body {{ font-family: sans-serif; max-width: 50em; margin: auto; }} \
+                 pre {{ background: #f4f4f4; padding: 0.5em; }}</style>\n</head>\n<body>\n\
+                 <h1>{title}</h1>\n{body}</body>\n</html>\n",
+                title = escape(&title),
+            ),
+            Format::Markdown => format!("# {}\n\n{}", title, body),
+        }
+    }
+
+    /// A name as it is written on this page: without the module prefix if
+    /// it is one of this module's own.
+    fn short<'n>(&self, name: &'n str) -> &'n str {
+        self.module
+            .and_then(|module| name.strip_prefix(module)?.strip_prefix('/'))
+            .unwrap_or(name)
+    }
+
+    /// Where a name is documented, relative to this page.
+    fn href(&self, name: &str) -> Option<String> {
+        let (module, definition) = self.links.targets.get(name)?;
+        let anchor = match module {
+            Some(module) => definition.strip_prefix(module)?.strip_prefix('/')?,
+            None => definition,
+        };
+        if *module == self.module {
+            Some(format!("#{}", anchor))
+        } else {
+            Some(format!("{}#{}", page_file(*module, self.format), anchor))
+        }
+    }
+
+    /// Where a name written in a doc comment is documented: a name of this
+    /// module, or one as it is written in the entry file.
+    fn resolve(&self, name: &str) -> Option<String> {
+        self.module
+            .and_then(|module| self.href(&format!("{}/{}", module, name)))
+            .or_else(|| self.href(name))
+    }
+
+    fn heading(&self, level: usize, text: &str, anchor: Option<&str>) -> String {
+        match (self.format, anchor) {
+            (Format::Html, Some(anchor)) => format!(
+                "<h{level} id=\"{}\">{}</h{level}>\n",
+                escape(anchor),
+                escape(text)
+            ),
+            (Format::Html, None) => format!("<h{level}>{}</h{level}>\n", escape(text)),
+            (Format::Markdown, Some(anchor)) => {
+                format!(
+                    "<a id=\"{}\"></a>\n\n{} {}\n\n",
+                    anchor,
+                    "#".repeat(level),
+                    text
+                )
+            }
+            (Format::Markdown, None) => format!("{} {}\n\n", "#".repeat(level), text),
+        }
+    }
+
+    fn link(&self, text: &str, href: &str) -> String {
+        match self.format {
+            Format::Html => format!("<a href=\"{}\">{}</a>", escape(href), escape(text)),
+            Format::Markdown => format!("[{}]({})", text, href),
+        }
+    }
+
+    fn list(&self, entries: &[String]) -> String {
+        match self.format {
+            Format::Html => {
+                let entries: String = entries
+                    .iter()
+                    .map(|entry| format!("<li>{}</li>\n", entry))
+                    .collect();
+                format!("<ul>\n{}</ul>\n", entries)
+            }
+            Format::Markdown => {
+                let entries: String = entries
+                    .iter()
+                    .map(|entry| format!("- {}\n", entry))
+                    .collect();
+                format!("{}\n", entries)
+            }
+        }
+    }
+
+    /// The signature of an item, with the names in it linking to where
+    /// they are documented, unless that is the item itself.
+    fn signature(&self, item: &Item) -> String {
+        let mut code = String::new();
+        for piece in &item.signature {
+            let (text, href) = match piece {
+                Piece::Text(text) => (text.as_str(), None),
+                Piece::Name(name) => {
+                    let elsewhere = self
+                        .links
+                        .targets
+                        .get(name)
+                        .is_some_and(|(_, definition)| *definition != item.name);
+                    (self.short(name), self.href(name).filter(|_| elsewhere))
+                }
+            };
+            code.push_str(&match (self.format, href) {
+                (Format::Html, Some(href)) => {
+                    format!("<a href=\"{}\">{}</a>", escape(&href), escape(text))
+                }
+                (Format::Html, None) => escape(text),
+                // code spans cannot hold links, so a linked name is a code
+                // span of its own
+                (Format::Markdown, Some(href)) => format!("`[`{}`]({})`", text, href),
+                (Format::Markdown, None) => text.to_string(),
+            });
+        }
+        match self.format {
+            Format::Html => format!("<pre><code>{}</code></pre>\n", code),
+            Format::Markdown => format!("`{}`\n\n", code).replace("``", ""),
+        }
+    }
+
+    /// The text of a doc comment, in which a name written as [`name`] links
+    /// to where it is documented.
+    fn doc(&self, doc: &str) -> String {
+        if self.format == Format::Markdown {
+            return format!("{}\n\n", self.intra_links(doc));
+        }
+        let mut html = String::new();
+        let mut paragraph = vec![];
+        let mut in_code = false;
+        for line in doc.lines() {
+            if line.trim_start().starts_with("```") {
+                self.paragraph(&mut html, &mut paragraph);
+                html.push_str(if in_code {
+                    "</code></pre>\n"
+                } else {
+                    "<pre><code>"
+                });
+                in_code = !in_code;
+            } else if in_code {
+                html.push_str(&escape(line));
+                html.push('\n');
+            } else if line.trim().is_empty() {
+                self.paragraph(&mut html, &mut paragraph);
+            } else {
+                paragraph.push(line);
+            }
+        }
+        self.paragraph(&mut html, &mut paragraph);
+        if in_code {
+            html.push_str("</code></pre>\n");
+        }
+        html
+    }
+
+    /// Ends the paragraph made of `lines`, if there is one.
+    fn paragraph(&self, html: &mut String, lines: &mut Vec<&str>) {
+        if !lines.is_empty() {
+            html.push_str(&format!("<p>{}</p>\n", self.inline(&lines.join("\n"))));
+            lines.clear();
+        }
+    }
+
+    /// Turns the [`name`]s of Markdown text into links.
+    fn intra_links(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some((before, name, after)) = code_span(rest) {
+            match linked_name(before, after).and_then(|after| Some((after, self.resolve(name)?))) {
+                Some((after, href)) => {
+                    out.push_str(&before[..before.len() - 1]);
+                    out.push_str(&format!("[`{}`]({})", name, href));
+                    rest = after;
+                }
+                None => {
+                    out.push_str(&rest[..rest.len() - after.len()]);
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// A paragraph of Markdown as HTML: its code spans, and the links of
+    /// those written as [`name`], with everything else as plain text.
+    fn inline(&self, text: &str) -> String {
+        let mut html = String::new();
+        let mut rest = text;
+        while let Some((before, code, after)) = code_span(rest) {
+            match linked_name(before, after).and_then(|after| Some((after, self.resolve(code)?))) {
+                Some((after, href)) => {
+                    html.push_str(&escape(&before[..before.len() - 1]));
+                    html.push_str(&format!(
+                        "<a href=\"{}\"><code>{}</code></a>",
+                        escape(&href),
+                        escape(code)
+                    ));
+                    rest = after;
+                }
+                None => {
+                    html.push_str(&escape(before));
+                    html.push_str(&format!("<code>{}</code>", escape(code)));
+                    rest = after;
+                }
+            }
+        }
+        html.push_str(&escape(rest));
+        html
+    }
+}
+
+/// The text before the first code span of `text`, what is in it and the
+/// text after it.
+fn code_span(text: &str) -> Option<(&str, &str, &str)> {
+    let start = text.find('`')?;
+    let length = text[start + 1..].find('`')?;
+    let end = start + 1 + length;
+    Some((&text[..start], &text[start + 1..end], &text[end + 1..]))
+}
+
+/// Whether a code span is in brackets without a link after them, as a name
+/// to link is, returning the text after the closing bracket.
+fn linked_name<'t>(before: &str, after: &'t str) -> Option<&'t str> {
+    let after = after.strip_prefix(']')?;
+    (before.ends_with('[') && !after.starts_with('(')).then_some(after)
+}
+
+/// The file of the page of a module, or of the entry file for `None`.
+fn page_file(module: Option<&str>, format: Format) -> String {
+    format!("{}.{}", module.unwrap_or("index"), format.extension())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
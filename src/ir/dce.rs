@@ -0,0 +1,46 @@
+use std::collections::HashSet;
+
+use crate::{
+    ir::{Callee, Module, Rvalue, StatementKind},
+    semantics::types::Type,
+};
+
+/// Drops the functions the program can never call. The program starts at
+/// `main` and at its exported functions; one with neither is a collection
+/// of functions meant to be called from C, and keeps them all.
+pub fn remove_dead_functions(module: &mut Module) {
+    let mut stack: Vec<(String, Vec<Type>)> = module
+        .functions
+        .iter()
+        .filter(|function| function.exported || function.name == "main")
+        .map(|function| (function.name.clone(), function.type_args.clone()))
+        .collect();
+    if stack.is_empty() {
+        return;
+    }
+    let mut live = HashSet::new();
+    while let Some(key) = stack.pop() {
+        if live.contains(&key) {
+            continue;
+        }
+        if let Some(function) = module.function(&key.0, &key.1) {
+            for block in &function.blocks {
+                for statement in &block.statements {
+                    let (StatementKind::Assign(_, rvalue) | StatementKind::Eval(rvalue)) =
+                        &statement.kind;
+                    if let Rvalue::Call {
+                        callee: Callee::Function { name, type_args },
+                        ..
+                    } = rvalue
+                    {
+                        stack.push((name.clone(), type_args.clone()));
+                    }
+                }
+            }
+        }
+        live.insert(key);
+    }
+    module
+        .functions
+        .retain(|function| live.contains(&(function.name.clone(), function.type_args.clone())));
+}
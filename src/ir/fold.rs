@@ -0,0 +1,417 @@
+use crate::{
+    diagnostics::Diagnostic,
+    ir::{
+        Aggregate, Base, Constant, Function, LocalId, Module, Operand, Rvalue, StatementKind,
+        TerminatorKind,
+    },
+    parsing::ast::nodes::Location,
+    semantics::{operators::Operator, types::Type},
+};
+
+/// Evaluates what can be known before the program runs: operators and
+/// casts on constants, and the temporaries holding their results, and
+/// branches on constant conditions, along with the blocks they leave
+/// unreachable.
+///
+/// Integer arithmetic that would overflow is an error unless it is meant to
+/// wrap around, and so is arithmetic that could only panic.
+pub fn fold_constants(module: &mut Module, wrapping: bool) -> Result<(), Vec<Diagnostic>> {
+    let mut diagnostics = vec![];
+    for function in &mut module.functions {
+        let mut folder = Folder {
+            wrapping,
+            diagnostics: &mut diagnostics,
+        };
+        folder.function(function);
+    }
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(diagnostics)
+    }
+}
+
+struct Folder<'d> {
+    wrapping: bool,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl Folder<'_> {
+    fn function(&mut self, function: &mut Function) {
+        let mut known: Vec<Option<Constant>> = vec![None; function.locals.len()];
+        loop {
+            let mut changed = false;
+            for block in &mut function.blocks {
+                block.visit_operands_mut(&mut |operand| {
+                    if let Operand::Copy(place) = operand
+                        && place.projections.is_empty()
+                        && let Base::Local(local) = place.base
+                        && let Some(constant) = &known[local]
+                    {
+                        *operand = Operand::Const(constant.clone());
+                        changed = true;
+                    }
+                });
+                for statement in &mut block.statements {
+                    let rvalue = match &mut statement.kind {
+                        StatementKind::Assign(_, rvalue) | StatementKind::Eval(rvalue) => rvalue,
+                    };
+                    if let Some(constant) = self.rvalue(rvalue) {
+                        *rvalue = Rvalue::Use(Operand::Const(constant));
+                        changed = true;
+                    }
+                }
+                if let TerminatorKind::Branch {
+                    condition: Operand::Const(Constant::Bool(condition)),
+                    yes,
+                    no,
+                } = block.terminator.kind
+                {
+                    let target = if condition { yes } else { no };
+                    block.terminator.kind = TerminatorKind::Goto(target);
+                    changed = true;
+                }
+            }
+            for (local, constant) in constant_temporaries(function) {
+                if known[local].is_none() {
+                    known[local] = Some(constant);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        // the temporaries themselves are of no more use
+        for block in &mut function.blocks {
+            block.statements.retain(|statement| match &statement.kind {
+                StatementKind::Assign(place, _) => match place.base {
+                    Base::Local(local) => known[local].is_none(),
+                    Base::Global(_) => true,
+                },
+                // left with nothing to do
+                StatementKind::Eval(rvalue) => !matches!(rvalue, Rvalue::Use(_)),
+            });
+        }
+        function.remove_unreachable();
+        function.remove_unused_locals();
+    }
+
+    /// The constant an rvalue always evaluates to, if it can be known and is
+    /// not already a constant.
+    fn rvalue(&mut self, rvalue: &Rvalue) -> Option<Constant> {
+        match rvalue {
+            Rvalue::Binary {
+                op,
+                left: Operand::Const(left),
+                right: Operand::Const(right),
+                loc,
+            } => self.binary(*op, left, right, *loc),
+            Rvalue::Binary {
+                op: op @ (Operator::Div | Operator::Rem),
+                right: Operand::Const(Constant::Int { value: 0, .. }),
+                loc,
+                ..
+            } => {
+                self.division_by_zero(*op, *loc);
+                None
+            }
+            Rvalue::Unary {
+                op,
+                operand: Operand::Const(operand),
+                loc,
+            } => self.unary(*op, operand, *loc),
+            Rvalue::Cast {
+                operand: Operand::Const(operand),
+                ty,
+            } => cast(operand, ty),
+            Rvalue::IsVariant {
+                operand:
+                    Operand::Const(Constant::Aggregate {
+                        kind:
+                            Aggregate::Variant {
+                                variant: actual, ..
+                            },
+                        ..
+                    }),
+                variant,
+            } => Some(Constant::Bool(actual == variant)),
+            Rvalue::CheckIndex {
+                index: Operand::Const(Constant::Int { value: index, .. }),
+                len: Operand::Const(Constant::Int { value: len, .. }),
+                loc,
+            } => {
+                if index >= len {
+                    self.error(
+                        format!(
+                            "Index out of bounds: the length is {} but the index is {}",
+                            len, index
+                        ),
+                        *loc,
+                    );
+                    return None;
+                }
+                Some(Constant::Int {
+                    value: *index,
+                    ty: Type::U64,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn binary(
+        &mut self,
+        op: Operator,
+        left: &Constant,
+        right: &Constant,
+        loc: Location,
+    ) -> Option<Constant> {
+        if let Some(ordering) = compare(left, right) {
+            let result = match op {
+                Operator::Eq => ordering.is_eq(),
+                Operator::Ne => ordering.is_ne(),
+                Operator::Lt => ordering.is_lt(),
+                Operator::Le => ordering.is_le(),
+                Operator::Gt => ordering.is_gt(),
+                Operator::Ge => ordering.is_ge(),
+                _ => return self.arithmetic(op, left, right, loc),
+            };
+            return Some(Constant::Bool(result));
+        }
+        self.arithmetic(op, left, right, loc)
+    }
+
+    fn arithmetic(
+        &mut self,
+        op: Operator,
+        left: &Constant,
+        right: &Constant,
+        loc: Location,
+    ) -> Option<Constant> {
+        match (left, right) {
+            (Constant::Int { value: a, ty }, Constant::Int { value: b, .. }) => {
+                let (a, b) = (*a, *b);
+                let bits = bits(ty)?;
+                let value = match op {
+                    Operator::Add => a + b,
+                    Operator::Sub => a - b,
+                    Operator::Mul => a.checked_mul(b)?,
+                    Operator::Div | Operator::Rem if b == 0 => {
+                        self.division_by_zero(op, loc);
+                        return None;
+                    }
+                    Operator::Div => a / b,
+                    Operator::Rem => a % b,
+                    Operator::BitAnd => a & b,
+                    Operator::BitOr => a | b,
+                    Operator::BitXor => a ^ b,
+                    Operator::Shl | Operator::Shr => {
+                        let amount = if (0..bits as i128).contains(&b) {
+                            b as u32
+                        } else if self.wrapping {
+                            (b & (bits as i128 - 1)) as u32
+                        } else {
+                            self.overflow(op, ty, loc);
+                            return None;
+                        };
+                        // shifting left drops the bits that do not fit,
+                        // without counting as overflow
+                        return Some(Constant::Int {
+                            value: match op {
+                                Operator::Shl => wrap(a << amount, ty, bits),
+                                _ => a >> amount,
+                            },
+                            ty: ty.clone(),
+                        });
+                    }
+                    _ => return None,
+                };
+                self.fit(value, ty, bits, op, loc)
+            }
+            (Constant::Float { value: a, ty }, Constant::Float { value: b, .. }) => {
+                let value = match op {
+                    Operator::Add => a + b,
+                    Operator::Sub => a - b,
+                    Operator::Mul => a * b,
+                    Operator::Div => a / b,
+                    _ => return None,
+                };
+                float(value, ty)
+            }
+            _ => None,
+        }
+    }
+
+    fn unary(&mut self, op: Operator, operand: &Constant, loc: Location) -> Option<Constant> {
+        match (op, operand) {
+            (Operator::Not, Constant::Bool(value)) => Some(Constant::Bool(!value)),
+            (Operator::Sub, Constant::Int { value, ty }) => {
+                let bits = bits(ty)?;
+                self.fit(-value, ty, bits, op, loc)
+            }
+            (Operator::BitNot, Constant::Int { value, ty }) => {
+                let bits = bits(ty)?;
+                Some(Constant::Int {
+                    value: wrap(!value, ty, bits),
+                    ty: ty.clone(),
+                })
+            }
+            (Operator::Sub, Constant::Float { value, ty }) => float(-value, ty),
+            _ => None,
+        }
+    }
+
+    /// The result of integer arithmetic, if it fits its type or is meant to
+    /// wrap around.
+    fn fit(
+        &mut self,
+        value: i128,
+        ty: &Type,
+        bits: u32,
+        op: Operator,
+        loc: Location,
+    ) -> Option<Constant> {
+        let (min, max) = ty.integer_range()?;
+        if (min..=max).contains(&value) {
+            return Some(Constant::Int {
+                value,
+                ty: ty.clone(),
+            });
+        }
+        if !self.wrapping {
+            self.overflow(op, ty, loc);
+            return None;
+        }
+        Some(Constant::Int {
+            value: wrap(value, ty, bits),
+            ty: ty.clone(),
+        })
+    }
+
+    fn division_by_zero(&mut self, op: Operator, loc: Location) {
+        let message = match op {
+            Operator::Div => "attempt to divide by zero",
+            _ => "attempt to calculate the remainder with a divisor of zero",
+        };
+        self.error(format!("This always panics: {}", message), loc);
+    }
+
+    fn overflow(&mut self, op: Operator, ty: &Type, loc: Location) {
+        let verb = match op {
+            Operator::Add => "add",
+            Operator::Sub => "subtract",
+            Operator::Mul => "multiply",
+            Operator::Div => "divide",
+            Operator::Shl => "shift left",
+            Operator::Shr => "shift right",
+            _ => "negate",
+        };
+        self.error(
+            format!(
+                "This always panics: attempt to {} with overflow, as the result does not fit in `{}`",
+                verb, ty
+            ),
+            loc,
+        );
+    }
+
+    fn error(&mut self, message: String, loc: Location) {
+        // the same code is looked at again until nothing more folds
+        let seen = self
+            .diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.loc == loc && diagnostic.message == message);
+        if !seen {
+            self.diagnostics.push(Diagnostic::error(message, loc));
+        }
+    }
+}
+
+/// How two constants of the same type order, if they can be compared.
+fn compare(left: &Constant, right: &Constant) -> Option<std::cmp::Ordering> {
+    match (left, right) {
+        (Constant::Int { value: a, .. }, Constant::Int { value: b, .. }) => Some(a.cmp(b)),
+        (Constant::Float { value: a, .. }, Constant::Float { value: b, .. }) => a.partial_cmp(b),
+        (Constant::Bool(a), Constant::Bool(b)) => Some(a.cmp(b)),
+        (Constant::String(a), Constant::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// A numeric constant converted to another numeric type. The checker only
+/// converts to types that hold every value of the original.
+fn cast(constant: &Constant, ty: &Type) -> Option<Constant> {
+    match constant {
+        Constant::Int { value, .. } if ty.is_integer() => Some(Constant::Int {
+            value: *value,
+            ty: ty.clone(),
+        }),
+        Constant::Int { value, .. } => float(*value as f64, ty),
+        Constant::Float { value, .. } => float(*value, ty),
+        _ => None,
+    }
+}
+
+/// A float constant, unless the value is one C has no literal for.
+fn float(value: f64, ty: &Type) -> Option<Constant> {
+    let value = match ty {
+        Type::F32 => value as f32 as f64,
+        Type::F64 => value,
+        _ => return None,
+    };
+    value.is_finite().then(|| Constant::Float {
+        value,
+        ty: ty.clone(),
+    })
+}
+
+fn bits(ty: &Type) -> Option<u32> {
+    let bits = match ty {
+        Type::I8 | Type::U8 => 8,
+        Type::I16 | Type::U16 => 16,
+        Type::I32 | Type::U32 => 32,
+        Type::I64 | Type::U64 => 64,
+        _ => return None,
+    };
+    Some(bits)
+}
+
+/// Wraps an integer around to the range of its type, as two's complement
+/// does.
+fn wrap(value: i128, ty: &Type, bits: u32) -> i128 {
+    let modulus = 1i128 << bits;
+    let value = value.rem_euclid(modulus);
+    match ty.integer_range() {
+        Some((min, _)) if min < 0 && value >= modulus / 2 => value - modulus,
+        _ => value,
+    }
+}
+
+/// The temporaries assigned a constant, and nothing else, once.
+fn constant_temporaries(function: &Function) -> Vec<(LocalId, Constant)> {
+    let mut assignments = vec![0; function.locals.len()];
+    let mut values = vec![None; function.locals.len()];
+    for block in &function.blocks {
+        for statement in &block.statements {
+            if let StatementKind::Assign(place, rvalue) = &statement.kind
+                && let Base::Local(local) = place.base
+            {
+                assignments[local] += 1;
+                if let (true, Rvalue::Use(Operand::Const(constant))) =
+                    (place.projections.is_empty(), rvalue)
+                {
+                    values[local] = Some(constant.clone());
+                }
+            }
+        }
+    }
+    values
+        .into_iter()
+        .enumerate()
+        .filter(|(local, _)| function.locals[*local].name.is_none() && assignments[*local] == 1)
+        .filter_map(|(local, value)| Some((local, value?)))
+        // copying an aggregate into every use would only repeat it
+        .filter(|(_, value)| !matches!(value, Constant::Aggregate { .. }))
+        .collect()
+}
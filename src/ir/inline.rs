@@ -0,0 +1,229 @@
+use crate::ir::{
+    Base, Block, Callee, Function, Module, Place, Rvalue, Statement, StatementKind, TerminatorKind,
+};
+
+/// Which calls are replaced by the body of the function they call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Inlining {
+    /// None of them.
+    Off,
+    /// Calls to functions marked `(inline)`.
+    #[default]
+    Marked,
+    /// Those, and calls to functions of at most this many statements.
+    UpTo(usize),
+}
+impl Inlining {
+    /// What an `--opt-level` asks for: nothing at 0, what is marked at 1
+    /// and at s, which keeps the code small, and small functions as well
+    /// at 2 and 3.
+    pub fn for_opt_level(level: &str) -> Option<Inlining> {
+        match level {
+            "0" => Some(Inlining::Off),
+            "1" | "s" => Some(Inlining::Marked),
+            "2" => Some(Inlining::UpTo(8)),
+            "3" => Some(Inlining::UpTo(24)),
+            _ => None,
+        }
+    }
+
+    fn allows(self, function: &Function) -> bool {
+        match self {
+            Inlining::Off => false,
+            Inlining::Marked => function.inline,
+            Inlining::UpTo(limit) => function.inline || size(function) <= limit,
+        }
+    }
+}
+
+/// Replaces calls to functions that `inlining` allows with a copy of their
+/// body. The copied locals are new locals of the caller, so the names they
+/// share with the caller's own refer to different variables. Functions that
+/// call themselves, directly or through others, are never inlined.
+pub fn inline_calls(module: &mut Module, inlining: Inlining) {
+    if inlining == Inlining::Off {
+        return;
+    }
+    let calls: Vec<Vec<usize>> = module
+        .functions
+        .iter()
+        .map(|function| callees(module, function))
+        .collect();
+    let recursive: Vec<bool> = (0..calls.len())
+        .map(|function| reaches(&calls, function, function))
+        .collect();
+    // callees first, so a body is copied with its own calls inlined
+    let mut order = vec![];
+    let mut visited = vec![false; calls.len()];
+    for function in 0..calls.len() {
+        postorder(&calls, function, &mut visited, &mut order);
+    }
+    for function in order {
+        while let Some((block, statement, callee)) =
+            next_call(module, function, |module, callee| {
+                !recursive[callee] && inlining.allows(&module.functions[callee])
+            })
+        {
+            let body = module.functions[callee].clone();
+            inline_call(&mut module.functions[function], block, statement, &body);
+        }
+    }
+}
+
+/// The first call of a function that makes a call `inlinable` accepts, as
+/// its block, the statement in it and the function it calls.
+fn next_call(
+    module: &Module,
+    function: usize,
+    inlinable: impl Fn(&Module, usize) -> bool,
+) -> Option<(usize, usize, usize)> {
+    module.functions[function]
+        .blocks
+        .iter()
+        .enumerate()
+        .find_map(|(id, block)| {
+            block
+                .statements
+                .iter()
+                .enumerate()
+                .find_map(|(index, statement)| {
+                    let callee = called(module, statement)?;
+                    inlinable(module, callee).then_some((id, index, callee))
+                })
+        })
+}
+
+/// The number of statements in a function's body.
+fn size(function: &Function) -> usize {
+    function
+        .blocks
+        .iter()
+        .map(|block| block.statements.len())
+        .sum()
+}
+
+/// The function a statement calls, by its index in the module.
+fn called(module: &Module, statement: &Statement) -> Option<usize> {
+    let (StatementKind::Assign(_, rvalue) | StatementKind::Eval(rvalue)) = &statement.kind;
+    let Rvalue::Call {
+        callee: Callee::Function { name, type_args },
+        ..
+    } = rvalue
+    else {
+        return None;
+    };
+    module
+        .functions
+        .iter()
+        .position(|function| function.name == *name && function.type_args == *type_args)
+}
+
+fn callees(module: &Module, function: &Function) -> Vec<usize> {
+    function
+        .blocks
+        .iter()
+        .flat_map(|block| &block.statements)
+        .filter_map(|statement| called(module, statement))
+        .collect()
+}
+
+/// Whether `to` is called from `from`, directly or through other calls.
+fn reaches(calls: &[Vec<usize>], from: usize, to: usize) -> bool {
+    let mut visited = vec![false; calls.len()];
+    let mut stack = calls[from].clone();
+    while let Some(function) = stack.pop() {
+        if function == to {
+            return true;
+        }
+        if !std::mem::replace(&mut visited[function], true) {
+            stack.extend(&calls[function]);
+        }
+    }
+    false
+}
+
+fn postorder(calls: &[Vec<usize>], function: usize, visited: &mut [bool], order: &mut Vec<usize>) {
+    if std::mem::replace(&mut visited[function], true) {
+        return;
+    }
+    for callee in &calls[function] {
+        postorder(calls, *callee, visited, order);
+    }
+    order.push(function);
+}
+
+/// Replaces the call made by a statement of `block` with the blocks of
+/// `callee`, which come right after it, followed by a block with the
+/// statements after the call.
+fn inline_call(caller: &mut Function, block: usize, statement: usize, callee: &Function) {
+    let rest = caller.blocks[block].statements.split_off(statement + 1);
+    let Some(call) = caller.blocks[block].statements.pop() else {
+        return;
+    };
+    let (result, args) = match call.kind {
+        StatementKind::Assign(place, Rvalue::Call { args, .. }) => (Some(place), args),
+        StatementKind::Eval(Rvalue::Call { args, .. }) => (None, args),
+        _ => return,
+    };
+    let locals = caller.locals.len();
+    caller.locals.extend(callee.locals.iter().cloned());
+    let entry = block + 1;
+    let after = entry + callee.blocks.len();
+    // the blocks after the call move past the inlined ones
+    let shift = callee.blocks.len() + 1;
+    for other in &mut caller.blocks {
+        match &mut other.terminator.kind {
+            TerminatorKind::Goto(target) => *target += shift * (*target > block) as usize,
+            TerminatorKind::Branch { yes, no, .. } => {
+                *yes += shift * (*yes > block) as usize;
+                *no += shift * (*no > block) as usize;
+            }
+            TerminatorKind::Return(_) | TerminatorKind::Unreachable => {}
+        }
+    }
+
+    let mut body = vec![];
+    for original in &callee.blocks {
+        let mut copy = original.clone();
+        copy.visit_places_mut(&mut |place| {
+            if let Base::Local(local) = &mut place.base {
+                *local += locals;
+            }
+        });
+        match &mut copy.terminator.kind {
+            TerminatorKind::Goto(target) => *target += entry,
+            TerminatorKind::Branch { yes, no, .. } => {
+                *yes += entry;
+                *no += entry;
+            }
+            TerminatorKind::Return(value) => {
+                if let (Some(result), Some(value)) = (&result, value.take()) {
+                    copy.statements.push(Statement {
+                        kind: StatementKind::Assign(result.clone(), Rvalue::Use(value)),
+                        loc: copy.terminator.loc,
+                    });
+                }
+                copy.terminator.kind = TerminatorKind::Goto(after);
+            }
+            TerminatorKind::Unreachable => {}
+        }
+        body.push(copy);
+    }
+
+    let continuation = Block {
+        statements: rest,
+        terminator: caller.blocks[block].terminator.clone(),
+    };
+    let start = &mut caller.blocks[block];
+    for (param, arg) in callee.params.iter().zip(args) {
+        start.statements.push(Statement {
+            kind: StatementKind::Assign(Place::local(param + locals), Rvalue::Use(arg)),
+            loc: call.loc,
+        });
+    }
+    start.terminator.kind = TerminatorKind::Goto(entry);
+    start.terminator.loc = call.loc;
+    caller
+        .blocks
+        .splice(entry..entry, body.into_iter().chain([continuation]));
+}
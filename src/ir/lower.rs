@@ -0,0 +1,982 @@
+use std::collections::HashMap;
+
+use crate::{
+    diagnostics::Diagnostic,
+    ir::{
+        Aggregate, Base, Block, BlockId, Builtin, Callee, Constant, Extern, Function, Global,
+        Local, LocalId, Module, Operand, Place, Projection, Rvalue, Statement, StatementKind,
+        Terminator, TerminatorKind, mono::instances,
+    },
+    parsing::ast::nodes::{Attribute, Literal, Location, Node, NodeKind, Symbol},
+    semantics::{
+        operators::Operator,
+        typecheck::{BUILTINS, TypeInfo, VariantDef},
+        types::Type,
+    },
+};
+
+/// Lowers a checked program to IR, with a function for every function that
+/// is not generic and for every instance of one that is.
+pub fn lower(ast: &Node, types: &TypeInfo) -> Result<Module, Vec<Diagnostic>> {
+    let top_level: &[Node] = match &ast.kind {
+        NodeKind::Block { expressions } => expressions,
+        _ => std::slice::from_ref(ast),
+    };
+    let mut module = Module {
+        structs: types.structs.clone(),
+        enums: types.enums.clone(),
+        ..Module::default()
+    };
+    let mut diagnostics = vec![];
+    let instances = instances(top_level, types).map_err(|diagnostic| vec![diagnostic])?;
+    for node in top_level {
+        match &node.kind {
+            NodeKind::Struct { .. } | NodeKind::Enum { .. } | NodeKind::Import { .. } => {}
+            // C functions are only declared, and may be declared by several
+            // modules
+            NodeKind::Include { header } => {
+                if !module.includes.contains(header) {
+                    module.includes.push(header.clone());
+                }
+            }
+            NodeKind::Extern { functions } => {
+                for function in functions {
+                    if module
+                        .externs
+                        .iter()
+                        .any(|other| other.name == function.name)
+                    {
+                        continue;
+                    }
+                    module.externs.push(Extern {
+                        name: function.name.clone(),
+                        params: function
+                            .params
+                            .iter()
+                            .map(|param| (param.name().to_string(), param_type(param, &[])))
+                            .collect(),
+                        returns: Type::from_annotation(&function.returns),
+                        loc: function.loc,
+                    });
+                }
+            }
+            NodeKind::Let {
+                symbol,
+                mutable,
+                value,
+            } => {
+                let lowerer = FnLowerer::new(types, HashMap::new());
+                match lowerer.constant(value) {
+                    Some(constant) => module.globals.push(Global {
+                        name: symbol.name().to_string(),
+                        ty: types.type_of(node),
+                        mutable: *mutable,
+                        value: constant,
+                        loc: node.loc,
+                    }),
+                    None => diagnostics.push(Diagnostic::error(
+                        format!(
+                            "Global `{}` must be initialized with a constant",
+                            symbol.name()
+                        ),
+                        value.loc,
+                    )),
+                }
+            }
+            NodeKind::Fn {
+                name: Some(name),
+                type_params,
+                ..
+            } if type_params.is_empty() => {
+                let lowerer = FnLowerer::new(types, HashMap::new());
+                module
+                    .functions
+                    .push(lowerer.function(node, vec![], &mut diagnostics));
+            }
+            NodeKind::Fn {
+                name: Some(name),
+                type_params,
+                ..
+            } => {
+                for instance in instances
+                    .iter()
+                    .filter(|instance| instance.name == name.name())
+                {
+                    let lowerer = FnLowerer::new(types, instance.bindings(type_params));
+                    module.functions.push(lowerer.function(
+                        node,
+                        instance.type_args.clone(),
+                        &mut diagnostics,
+                    ));
+                }
+            }
+            NodeKind::Fn { name: None, .. } => diagnostics.push(Diagnostic::error(
+                "Anonymous functions cannot be compiled",
+                node.loc,
+            )),
+            _ => diagnostics.push(Diagnostic::error(
+                "Only definitions are allowed at the top level; statements go in `main`",
+                node.loc,
+            )),
+        }
+    }
+    if diagnostics.is_empty() {
+        Ok(module)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// The type of a parameter, as annotated.
+fn param_type(param: &Symbol, type_params: &[String]) -> Type {
+    Type::from_annotation(param.annotation().unwrap_or_default()).generalize(type_params)
+}
+
+/// A block whose terminator is only known once lowering moves past it.
+struct PartialBlock {
+    statements: Vec<Statement>,
+    terminator: Option<Terminator>,
+}
+
+/// Lowers the body of a single function, or the value of a global.
+struct FnLowerer<'t> {
+    types: &'t TypeInfo,
+    /// What the type parameters stand for in the instance being lowered.
+    bindings: HashMap<String, Type>,
+    locals: Vec<Local>,
+    blocks: Vec<PartialBlock>,
+    current: BlockId,
+    scopes: Vec<HashMap<String, LocalId>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'t> FnLowerer<'t> {
+    fn new(types: &'t TypeInfo, bindings: HashMap<String, Type>) -> FnLowerer<'t> {
+        FnLowerer {
+            types,
+            bindings,
+            locals: vec![],
+            blocks: vec![PartialBlock {
+                statements: vec![],
+                terminator: None,
+            }],
+            current: 0,
+            scopes: vec![HashMap::new()],
+            diagnostics: vec![],
+        }
+    }
+
+    fn function(
+        mut self,
+        node: &Node,
+        type_args: Vec<Type>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Function {
+        let NodeKind::Fn {
+            name,
+            type_params,
+            attributes,
+            returns,
+            params,
+            body,
+        } = &node.kind
+        else {
+            unreachable!("only functions are lowered as functions");
+        };
+        let params: Vec<LocalId> = params
+            .iter()
+            .map(|param| {
+                let ty = param_type(param, type_params).substitute(&self.bindings);
+                let local = self.local(Some(param.name()), ty, false);
+                self.declare(param.name(), local);
+                local
+            })
+            .collect();
+        let returns = Type::from_annotation(returns)
+            .generalize(type_params)
+            .substitute(&self.bindings);
+        self.stmt(body);
+        let end = if returns == Type::Void {
+            TerminatorKind::Return(None)
+        } else {
+            TerminatorKind::Unreachable
+        };
+        self.terminate(end, node.loc);
+        diagnostics.append(&mut self.diagnostics);
+        let blocks = self
+            .blocks
+            .into_iter()
+            .map(|block| Block {
+                statements: block.statements,
+                terminator: block.terminator.unwrap_or(Terminator {
+                    kind: TerminatorKind::Unreachable,
+                    loc: Location::default(),
+                }),
+            })
+            .collect();
+        let mut function = Function {
+            name: name.as_ref().map_or("", Symbol::name).to_string(),
+            type_args,
+            exported: attributes.contains(&Attribute::Export),
+            inline: attributes.contains(&Attribute::Inline),
+            params,
+            returns,
+            locals: self.locals,
+            blocks,
+            loc: node.loc,
+        };
+        // such as the code after a `ret`
+        function.remove_unreachable();
+        function
+    }
+
+    /// Lowers a node for its effects, dropping any value it has.
+    fn stmt(&mut self, node: &Node) {
+        match &node.kind {
+            NodeKind::Let {
+                symbol,
+                mutable,
+                value,
+            } => {
+                let value = self.rvalue(value);
+                let local = self.local(Some(symbol.name()), self.type_of(node), *mutable);
+                self.assign(Place::local(local), value, node.loc);
+                self.declare(symbol.name(), local);
+            }
+            NodeKind::Set { place, value } => {
+                let place = self.place(place);
+                let value = self.rvalue(value);
+                self.assign(place, value, node.loc);
+            }
+            NodeKind::Return { value } => {
+                let value = self.operand(value);
+                self.terminate(TerminatorKind::Return(Some(value)), node.loc);
+                // anything after a return is never run
+                let after = self.block();
+                self.current = after;
+            }
+            NodeKind::If { predicate, yes, no } => {
+                let condition = self.operand(predicate);
+                let (then, otherwise, join) = (self.block(), self.block(), self.block());
+                let no_target = if no.is_some() { otherwise } else { join };
+                self.branch(condition, then, no_target, node.loc);
+                self.current = then;
+                self.stmt(yes);
+                self.terminate(TerminatorKind::Goto(join), node.loc);
+                if let Some(no) = no {
+                    self.current = otherwise;
+                    self.stmt(no);
+                    self.terminate(TerminatorKind::Goto(join), node.loc);
+                }
+                self.current = join;
+            }
+            NodeKind::For {
+                dummy,
+                iterator,
+                body,
+            } => self.for_loop(node, dummy, iterator, body),
+            NodeKind::Given { predicate, cases } => self.given(predicate, cases),
+            NodeKind::Block { expressions } => {
+                self.scopes.push(HashMap::new());
+                for expression in expressions {
+                    self.stmt(expression);
+                }
+                self.scopes.pop();
+            }
+            NodeKind::Fn { .. } => self.error(Diagnostic::error(
+                "Functions can only be defined at the top level",
+                node.loc,
+            )),
+            NodeKind::Struct { .. }
+            | NodeKind::Enum { .. }
+            | NodeKind::Import { .. }
+            | NodeKind::Extern { .. }
+            | NodeKind::Include { .. }
+            | NodeKind::Invalid => {}
+            NodeKind::Call { .. }
+            | NodeKind::Identifier { .. }
+            | NodeKind::Literal(_)
+            | NodeKind::Array { .. }
+            | NodeKind::Field { .. }
+            | NodeKind::Index { .. } => match self.rvalue(node) {
+                Rvalue::Use(_) => {}
+                value => self.push(StatementKind::Eval(value), node.loc),
+            },
+        }
+    }
+
+    fn for_loop(&mut self, node: &Node, dummy: &Symbol, iterator: &Node, body: &Node) {
+        let sequence_ty = self.type_of(iterator);
+        if sequence_ty.element().is_some() {
+            return self.for_each(node, dummy, iterator, body);
+        }
+        let args = match &iterator.kind {
+            NodeKind::Call { name, args } if name.name() == "range" => args,
+            _ => {
+                self.error(Diagnostic::error(
+                    "Only (range ...) loops and loops over arrays can be compiled",
+                    iterator.loc,
+                ));
+                return;
+            }
+        };
+        let ty = self.type_of(node);
+        let (start, end) = match args.as_slice() {
+            [end] => (
+                Operand::Const(Constant::Int {
+                    value: 0,
+                    ty: ty.clone(),
+                }),
+                end,
+            ),
+            [start, end] => (self.operand(start), end),
+            _ => return,
+        };
+        // the body may change what the end was computed from
+        let end = match self.operand(end) {
+            Operand::Copy(place) if !self.is_immutable(&place) => {
+                self.temporary(Rvalue::Use(Operand::Copy(place)), ty.clone(), iterator.loc)
+            }
+            end => end,
+        };
+        let counter = self.local(Some(dummy.name()), ty, false);
+        self.assign(Place::local(counter), Rvalue::Use(start), node.loc);
+        let header = self.block();
+        self.terminate(TerminatorKind::Goto(header), node.loc);
+        self.current = header;
+        let condition = self.temporary(
+            Rvalue::Binary {
+                op: Operator::Lt,
+                left: Operand::Copy(Place::local(counter)),
+                right: end,
+                loc: node.loc,
+            },
+            Type::Bool,
+            node.loc,
+        );
+        let (looped, exit) = (self.block(), self.block());
+        self.branch(condition, looped, exit, node.loc);
+        self.current = looped;
+        self.scopes
+            .push(HashMap::from([(dummy.name().to_string(), counter)]));
+        self.stmt(body);
+        self.scopes.pop();
+        let step = Rvalue::Increment(Operand::Copy(Place::local(counter)));
+        self.assign(Place::local(counter), step, node.loc);
+        self.terminate(TerminatorKind::Goto(header), node.loc);
+        self.current = exit;
+    }
+
+    /// Loops over a copy of the array or slice, so the body can assign to
+    /// the original without changing what it iterates over.
+    fn for_each(&mut self, node: &Node, dummy: &Symbol, iterator: &Node, body: &Node) {
+        let sequence_ty = self.type_of(iterator);
+        let value = self.rvalue(iterator);
+        let sequence = self.local(None, sequence_ty.clone(), false);
+        self.assign(Place::local(sequence), value, iterator.loc);
+        let index = self.local(None, Type::U64, false);
+        let zero = Operand::Const(Constant::Int {
+            value: 0,
+            ty: Type::U64,
+        });
+        self.assign(Place::local(index), Rvalue::Use(zero), node.loc);
+        let header = self.block();
+        self.terminate(TerminatorKind::Goto(header), node.loc);
+        self.current = header;
+        let len = match sequence_ty {
+            Type::Array(_, len) => Operand::Const(Constant::Int {
+                value: len as i128,
+                ty: Type::U64,
+            }),
+            _ => self.temporary(
+                Rvalue::Len(Operand::Copy(Place::local(sequence))),
+                Type::U64,
+                node.loc,
+            ),
+        };
+        let condition = self.temporary(
+            Rvalue::Binary {
+                op: Operator::Lt,
+                left: Operand::Copy(Place::local(index)),
+                right: len,
+                loc: node.loc,
+            },
+            Type::Bool,
+            node.loc,
+        );
+        let (looped, exit) = (self.block(), self.block());
+        self.branch(condition, looped, exit, node.loc);
+        self.current = looped;
+        let element =
+            Place::local(sequence).project(Projection::Index(Operand::Copy(Place::local(index))));
+        let item = self.local(Some(dummy.name()), self.type_of(node), false);
+        self.assign(
+            Place::local(item),
+            Rvalue::Use(Operand::Copy(element)),
+            node.loc,
+        );
+        self.scopes
+            .push(HashMap::from([(dummy.name().to_string(), item)]));
+        self.stmt(body);
+        self.scopes.pop();
+        let step = Rvalue::Increment(Operand::Copy(Place::local(index)));
+        self.assign(Place::local(index), step, node.loc);
+        self.terminate(TerminatorKind::Goto(header), node.loc);
+        self.current = exit;
+    }
+
+    /// Lowers `given` to a chain of tests of the predicate, which is only
+    /// evaluated once.
+    fn given(&mut self, predicate: &Node, cases: &Node) {
+        let NodeKind::Block { expressions: cases } = &cases.kind else {
+            return;
+        };
+        let subject_ty = self.type_of(predicate);
+        let subject = match self.operand(predicate) {
+            Operand::Copy(place) => place,
+            value => {
+                let local = self.local(None, subject_ty.clone(), false);
+                self.assign(Place::local(local), Rvalue::Use(value), predicate.loc);
+                Place::local(local)
+            }
+        };
+        let enum_def = match &subject_ty {
+            Type::Named(name) => self.types.enum_def(name),
+            _ => None,
+        };
+        let join = self.block();
+        for (index, case) in cases.iter().enumerate() {
+            let NodeKind::Block { expressions } = &case.kind else {
+                continue;
+            };
+            let [pattern, body] = expressions.as_slice() else {
+                continue;
+            };
+            let (name, args): (&str, &[Node]) = match &pattern.kind {
+                NodeKind::Identifier { symbol } => (symbol.name(), &[]),
+                NodeKind::Call { name, args } => (name.name(), args),
+                _ => ("", &[]),
+            };
+            let test = match (enum_def, &pattern.kind) {
+                (_, NodeKind::Identifier { .. }) if name == "_" => None,
+                // given over an enum is exhaustive, so the last case needs no
+                // test
+                (Some(_), _) if index == cases.len() - 1 => None,
+                (Some(_), _) => Some(Rvalue::IsVariant {
+                    operand: Operand::Copy(subject.clone()),
+                    variant: name.to_string(),
+                }),
+                (None, _) => Some(Rvalue::Binary {
+                    op: Operator::Eq,
+                    left: Operand::Copy(subject.clone()),
+                    right: self.operand(pattern),
+                    loc: pattern.loc,
+                }),
+            };
+            let catch_all = test.is_none();
+            if let Some(test) = test {
+                let condition = self.temporary(test, Type::Bool, pattern.loc);
+                let (matched, next) = (self.block(), self.block());
+                self.branch(condition, matched, next, case.loc);
+                self.current = matched;
+                self.case(
+                    &subject,
+                    enum_def.and_then(|def| def.variant(name)),
+                    args,
+                    body,
+                );
+                self.terminate(TerminatorKind::Goto(join), case.loc);
+                self.current = next;
+            } else {
+                self.case(
+                    &subject,
+                    enum_def.and_then(|def| def.variant(name)),
+                    args,
+                    body,
+                );
+            }
+            // nothing after a catch-all can match
+            if catch_all {
+                break;
+            }
+        }
+        self.terminate(TerminatorKind::Goto(join), predicate.loc);
+        self.current = join;
+    }
+
+    /// Binds the fields a case pattern names and lowers its body.
+    fn case(&mut self, subject: &Place, variant: Option<&VariantDef>, args: &[Node], body: &Node) {
+        self.scopes.push(HashMap::new());
+        if let Some(variant) = variant {
+            for (arg, (field, ty)) in args.iter().zip(&variant.fields) {
+                let NodeKind::Identifier { symbol } = &arg.kind else {
+                    continue;
+                };
+                if symbol.name() == "_" {
+                    continue;
+                }
+                let value = subject.clone().project(Projection::VariantField {
+                    variant: variant.name.clone(),
+                    field: field.clone(),
+                });
+                let local = self.local(Some(symbol.name()), ty.clone(), false);
+                self.assign(
+                    Place::local(local),
+                    Rvalue::Use(Operand::Copy(value)),
+                    arg.loc,
+                );
+                self.declare(symbol.name(), local);
+            }
+        }
+        self.stmt(body);
+        self.scopes.pop();
+    }
+
+    /// Lowers a node to a single operand, computing it into a temporary if
+    /// it is not one already.
+    fn operand(&mut self, node: &Node) -> Operand {
+        let ty = self
+            .types
+            .coercion_of(node)
+            .map(|ty| ty.substitute(&self.bindings))
+            .unwrap_or_else(|| self.type_of(node));
+        let value = self.rvalue(node);
+        self.temporary(value, ty, node.loc)
+    }
+
+    /// Lowers a node to the computation of its value, converted to whatever
+    /// type it is used as.
+    fn rvalue(&mut self, node: &Node) -> Rvalue {
+        let value = self.uncoerced(node);
+        let Some(target) = self.types.coercion_of(node) else {
+            return value;
+        };
+        let target = target.substitute(&self.bindings);
+        let actual = self.type_of(node);
+        let convertible = match (&target, &actual) {
+            (Type::Slice(_), Type::Array(..)) => true,
+            (target, actual) => target.is_numeric() && actual.is_numeric(),
+        };
+        if !convertible {
+            return value;
+        }
+        let operand = self.temporary(value, actual, node.loc);
+        Rvalue::Cast {
+            operand,
+            ty: target,
+        }
+    }
+
+    fn uncoerced(&mut self, node: &Node) -> Rvalue {
+        if let Some(constant) = self.uncoerced_constant(node) {
+            return Rvalue::Use(Operand::Const(constant));
+        }
+        match &node.kind {
+            NodeKind::Identifier { symbol } => match self.lookup(symbol.name()) {
+                Some(local) => Rvalue::Use(Operand::Copy(Place::local(local))),
+                None => Rvalue::Use(Operand::Copy(Place {
+                    base: Base::Global(symbol.name().to_string()),
+                    projections: vec![],
+                })),
+            },
+            NodeKind::Field { .. } | NodeKind::Index { .. } => {
+                Rvalue::Use(Operand::Copy(self.place(node)))
+            }
+            NodeKind::Array { elements } => {
+                let element = self
+                    .type_of(node)
+                    .element()
+                    .cloned()
+                    .unwrap_or(Type::Unknown);
+                Rvalue::Aggregate {
+                    kind: Aggregate::Array(element),
+                    operands: elements
+                        .iter()
+                        .map(|element| self.operand(element))
+                        .collect(),
+                }
+            }
+            NodeKind::Call { name, args } => self.call(node, name.name(), args),
+            NodeKind::If {
+                predicate,
+                yes,
+                no: Some(no),
+            } if self.type_of(node) != Type::Void => {
+                let condition = self.operand(predicate);
+                let result = self.local(None, self.type_of(node), false);
+                let (then, otherwise, join) = (self.block(), self.block(), self.block());
+                self.branch(condition, then, otherwise, node.loc);
+                for (block, branch) in [(then, yes), (otherwise, no)] {
+                    self.current = block;
+                    let value = self.block_value(branch);
+                    self.assign(Place::local(result), value, branch.loc);
+                    self.terminate(TerminatorKind::Goto(join), node.loc);
+                }
+                self.current = join;
+                Rvalue::Use(Operand::Copy(Place::local(result)))
+            }
+            NodeKind::Block { .. } => self.block_value(node),
+            NodeKind::If { no: None, .. } => self.not_a_value(node, "an if without an else"),
+            NodeKind::If { .. } => self.not_a_value(node, "an if without a value"),
+            NodeKind::Let { .. } => self.not_a_value(node, "a let"),
+            NodeKind::Set { .. } => self.not_a_value(node, "a set!"),
+            NodeKind::For { .. } => self.not_a_value(node, "a for loop"),
+            NodeKind::Given { .. } => self.not_a_value(node, "a given"),
+            NodeKind::Return { .. } => self.not_a_value(node, "a ret"),
+            NodeKind::Fn { .. } => self.not_a_value(node, "a function"),
+            NodeKind::Struct { .. } | NodeKind::Enum { .. } => {
+                self.not_a_value(node, "a type definition")
+            }
+            NodeKind::Import { .. } => self.not_a_value(node, "an import"),
+            NodeKind::Extern { .. } | NodeKind::Include { .. } => {
+                self.not_a_value(node, "a C declaration")
+            }
+            NodeKind::Literal(_) | NodeKind::Invalid => {
+                Rvalue::Use(Operand::Const(Constant::Bool(false)))
+            }
+        }
+    }
+
+    /// The value of a block, which is that of its last expression.
+    fn block_value(&mut self, node: &Node) -> Rvalue {
+        let NodeKind::Block { expressions } = &node.kind else {
+            return self.rvalue(node);
+        };
+        let Some((last, rest)) = expressions.split_last() else {
+            return self.not_a_value(node, "an empty block");
+        };
+        self.scopes.push(HashMap::new());
+        for expression in rest {
+            self.stmt(expression);
+        }
+        let value = self.rvalue(last);
+        self.scopes.pop();
+        value
+    }
+
+    fn call(&mut self, node: &Node, name: &str, args: &[Node]) -> Rvalue {
+        let user_defined = self.types.functions.contains_key(name);
+        if let Some(operator) = Operator::from_name(name)
+            && !user_defined
+        {
+            return self.operator(node, operator, args);
+        }
+        if BUILTINS.contains(&name) && !user_defined {
+            return self.builtin(name, args);
+        }
+        if let Some(kind) = self.aggregate(node, name) {
+            let operands = args.iter().map(|arg| self.operand(arg)).collect();
+            return Rvalue::Aggregate { kind, operands };
+        }
+        let callee = match self.types.functions.get(name) {
+            Some(sig) if sig.external => Callee::Extern(name.to_string()),
+            _ => Callee::Function {
+                name: name.to_string(),
+                type_args: self
+                    .types
+                    .type_args(node)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|ty| ty.substitute(&self.bindings))
+                    .collect(),
+            },
+        };
+        let args = args.iter().map(|arg| self.operand(arg)).collect();
+        Rvalue::Call { callee, args }
+    }
+
+    /// What a call to a struct or variant constructor builds, or `None` if
+    /// `name` constructs nothing.
+    fn aggregate(&self, node: &Node, name: &str) -> Option<Aggregate> {
+        if let Some(def) = self.types.struct_def(name) {
+            return Some(Aggregate::Struct(def.name.clone()));
+        }
+        let (def, _) = self.types.variant(name)?;
+        // a local binding can shadow a fieldless variant
+        if self.type_of(node) != Type::Named(def.name.clone()) {
+            return None;
+        }
+        Some(Aggregate::Variant {
+            enum_name: def.name.clone(),
+            variant: name.to_string(),
+        })
+    }
+
+    /// Lowers an operator. Those that take any number of operands fold them
+    /// left to right, and `and` and `or` only evaluate as many as they need.
+    fn operator(&mut self, node: &Node, operator: Operator, args: &[Node]) -> Rvalue {
+        let ty = self.type_of(node);
+        if matches!(operator, Operator::And | Operator::Or) {
+            let result = self.local(None, Type::Bool, false);
+            let join = self.block();
+            for (index, arg) in args.iter().enumerate() {
+                let value = self.rvalue(arg);
+                self.assign(Place::local(result), value, arg.loc);
+                if index + 1 < args.len() {
+                    let next = self.block();
+                    let (yes, no) = match operator {
+                        Operator::And => (next, join),
+                        _ => (join, next),
+                    };
+                    self.branch(Operand::Copy(Place::local(result)), yes, no, node.loc);
+                    self.current = next;
+                }
+            }
+            self.terminate(TerminatorKind::Goto(join), node.loc);
+            self.current = join;
+            return Rvalue::Use(Operand::Copy(Place::local(result)));
+        }
+        let mut args = args.iter();
+        let first = match args.next() {
+            Some(arg) => self.operand(arg),
+            None => Operand::Const(Constant::Bool(false)),
+        };
+        if args.len() == 0 {
+            return Rvalue::Unary {
+                op: operator,
+                operand: first,
+                loc: node.loc,
+            };
+        }
+        let mut value = Rvalue::Use(first);
+        for arg in args {
+            let left = self.temporary(value, ty.clone(), node.loc);
+            let right = self.operand(arg);
+            value = Rvalue::Binary {
+                op: operator,
+                left,
+                right,
+                loc: node.loc,
+            };
+        }
+        value
+    }
+
+    fn builtin(&mut self, name: &str, args: &[Node]) -> Rvalue {
+        let Some(builtin) = Builtin::from_name(name) else {
+            // len
+            let Some(first) = args.first() else {
+                return Rvalue::Use(Operand::Const(Constant::Bool(false)));
+            };
+            if let Type::Array(_, len) = self.type_of(first) {
+                return Rvalue::Use(Operand::Const(Constant::Int {
+                    value: len as i128,
+                    ty: Type::U64,
+                }));
+            }
+            return Rvalue::Len(self.operand(first));
+        };
+        let args = args.iter().map(|arg| self.operand(arg)).collect();
+        Rvalue::Call {
+            callee: Callee::Builtin(builtin),
+            args,
+        }
+    }
+
+    /// The value of a literal, or of an array or constructor of nothing but
+    /// literals, which needs no code to compute.
+    fn constant(&self, node: &Node) -> Option<Constant> {
+        if self.types.coercion_of(node).is_some() {
+            return None;
+        }
+        self.uncoerced_constant(node)
+    }
+
+    fn uncoerced_constant(&self, node: &Node) -> Option<Constant> {
+        match &node.kind {
+            NodeKind::Literal(Literal::Boolean(value)) => Some(Constant::Bool(*value)),
+            NodeKind::Literal(Literal::String(value)) => Some(Constant::String(value.clone())),
+            NodeKind::Literal(Literal::Number { literal }) => {
+                let ty = match self.type_of(node) {
+                    Type::Unknown => Type::of_number(literal),
+                    ty => ty,
+                };
+                match literal.as_i128() {
+                    Some(value) if ty.is_integer() => Some(Constant::Int { value, ty }),
+                    _ => Some(Constant::Float {
+                        value: literal.as_f64(),
+                        ty,
+                    }),
+                }
+            }
+            NodeKind::Array { elements } => {
+                let element = self.type_of(node).element().cloned()?;
+                Some(Constant::Aggregate {
+                    kind: Aggregate::Array(element),
+                    values: elements
+                        .iter()
+                        .map(|element| self.constant(element))
+                        .collect::<Option<_>>()?,
+                })
+            }
+            NodeKind::Call { name, args }
+                if !self.types.functions.contains_key(name.name())
+                    && Operator::from_name(name.name()).is_none() =>
+            {
+                Some(Constant::Aggregate {
+                    kind: self.aggregate(node, name.name())?,
+                    values: args
+                        .iter()
+                        .map(|arg| self.constant(arg))
+                        .collect::<Option<_>>()?,
+                })
+            }
+            NodeKind::Identifier { symbol } if self.lookup(symbol.name()).is_none() => {
+                match self.aggregate(node, symbol.name())? {
+                    kind @ Aggregate::Variant { .. } => Some(Constant::Aggregate {
+                        kind,
+                        values: vec![],
+                    }),
+                    Aggregate::Struct(_) | Aggregate::Array(_) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Lowers something that can be assigned to or read in place, such as
+    /// `p.x` or `(at xs i)`. Anything else is computed into a temporary.
+    fn place(&mut self, node: &Node) -> Place {
+        match &node.kind {
+            NodeKind::Identifier { symbol } if self.constant(node).is_none() => {
+                match self.lookup(symbol.name()) {
+                    Some(local) => Place::local(local),
+                    None => Place {
+                        base: Base::Global(symbol.name().to_string()),
+                        projections: vec![],
+                    },
+                }
+            }
+            NodeKind::Field { target, field } => {
+                self.place(target).project(Projection::Field(field.clone()))
+            }
+            NodeKind::Index { target, index } => {
+                let target_ty = self.type_of(target);
+                let place = self.place(target);
+                let index_operand = self.operand(index);
+                // the checker already proved a literal index into an array
+                // in bounds
+                if matches!(target_ty, Type::Array(..))
+                    && matches!(index.kind, NodeKind::Literal(_))
+                {
+                    return place.project(Projection::Index(index_operand));
+                }
+                let len = match target_ty {
+                    Type::Array(_, len) => Operand::Const(Constant::Int {
+                        value: len as i128,
+                        ty: Type::U64,
+                    }),
+                    _ => self.temporary(
+                        Rvalue::Len(Operand::Copy(place.clone())),
+                        Type::U64,
+                        node.loc,
+                    ),
+                };
+                let checked = self.temporary(
+                    Rvalue::CheckIndex {
+                        index: index_operand,
+                        len,
+                        loc: node.loc,
+                    },
+                    Type::U64,
+                    node.loc,
+                );
+                place.project(Projection::Index(checked))
+            }
+            _ => match self.operand(node) {
+                Operand::Copy(place) => place,
+                value => {
+                    let local = self.local(None, self.type_of(node), false);
+                    self.assign(Place::local(local), Rvalue::Use(value), node.loc);
+                    Place::local(local)
+                }
+            },
+        }
+    }
+
+    /// Whether nothing can assign to a place while it is in use.
+    fn is_immutable(&self, place: &Place) -> bool {
+        match &place.base {
+            Base::Local(local) => !self.locals[*local].mutable,
+            Base::Global(_) => false,
+        }
+    }
+
+    fn not_a_value(&mut self, node: &Node, what: &str) -> Rvalue {
+        self.error(Diagnostic::error(
+            format!("Cannot use {} as a value", what),
+            node.loc,
+        ));
+        Rvalue::Use(Operand::Const(Constant::Bool(false)))
+    }
+
+    /// The type the checker gave a node, in the instance being lowered.
+    fn type_of(&self, node: &Node) -> Type {
+        self.types.type_of(node).substitute(&self.bindings)
+    }
+
+    fn local(&mut self, name: Option<&str>, ty: Type, mutable: bool) -> LocalId {
+        self.locals.push(Local {
+            name: name.map(str::to_string),
+            ty,
+            mutable,
+        });
+        self.locals.len() - 1
+    }
+
+    /// The operand a value is, computing it into a new temporary unless it
+    /// is already one.
+    fn temporary(&mut self, value: Rvalue, ty: Type, loc: Location) -> Operand {
+        if let Rvalue::Use(operand) = value {
+            return operand;
+        }
+        let local = self.local(None, ty, false);
+        self.assign(Place::local(local), value, loc);
+        Operand::Copy(Place::local(local))
+    }
+
+    fn declare(&mut self, name: &str, local: LocalId) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), local);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<LocalId> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    fn block(&mut self) -> BlockId {
+        self.blocks.push(PartialBlock {
+            statements: vec![],
+            terminator: None,
+        });
+        self.blocks.len() - 1
+    }
+
+    fn assign(&mut self, place: Place, value: Rvalue, loc: Location) {
+        self.push(StatementKind::Assign(place, value), loc);
+    }
+
+    fn push(&mut self, kind: StatementKind, loc: Location) {
+        self.blocks[self.current]
+            .statements
+            .push(Statement { kind, loc });
+    }
+
+    fn branch(&mut self, condition: Operand, yes: BlockId, no: BlockId, loc: Location) {
+        self.terminate(TerminatorKind::Branch { condition, yes, no }, loc);
+    }
+
+    /// Ends the current block, unless something already did.
+    fn terminate(&mut self, kind: TerminatorKind, loc: Location) {
+        let block = &mut self.blocks[self.current];
+        block.terminator.get_or_insert(Terminator { kind, loc });
+    }
+
+    fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+}
@@ -0,0 +1,828 @@
+//! A lowered, typed form of a checked program that backends generate code
+//! from. Every function body is a list of basic blocks of simple statements
+//! over locals and constants, ending in explicit control flow, and every
+//! type in it is concrete: generic functions appear once per instance.
+
+use std::fmt;
+
+use crate::{
+    diagnostics::Diagnostic,
+    ir::inline::Inlining,
+    parsing::ast::nodes::Location,
+    semantics::{
+        operators::Operator,
+        typecheck::{EnumDef, StructDef},
+        types::Type,
+    },
+};
+
+pub mod dce;
+pub mod fold;
+pub mod inline;
+pub mod lower;
+pub mod mono;
+pub mod tail;
+
+/// Prepares a freshly lowered module for code generation: evaluates what
+/// can be known before the program runs, turns self tail calls into loops,
+/// inlines the calls `inlining` allows and drops the functions it never
+/// calls. Arithmetic on constants is meant to wrap around if `wrapping`.
+pub fn simplify(
+    module: &mut Module,
+    wrapping: bool,
+    inlining: Inlining,
+) -> Result<(), Vec<Diagnostic>> {
+    fold::fold_constants(module, wrapping)?;
+    tail::eliminate_tail_calls(module);
+    inline::inline_calls(module, inlining);
+    dce::remove_dead_functions(module);
+    Ok(())
+}
+
+pub type LocalId = usize;
+pub type BlockId = usize;
+
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub structs: Vec<StructDef>,
+    pub enums: Vec<EnumDef>,
+    /// C headers the program includes, as written after `include`.
+    pub includes: Vec<String>,
+    pub externs: Vec<Extern>,
+    pub globals: Vec<Global>,
+    pub functions: Vec<Function>,
+}
+
+/// A function implemented in C.
+#[derive(Debug, Clone)]
+pub struct Extern {
+    pub name: String,
+    pub params: Vec<(String, Type)>,
+    pub returns: Type,
+    pub loc: Location,
+}
+
+/// A top-level binding, which is initialized before the program runs and
+/// so has to be a constant.
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub name: String,
+    pub ty: Type,
+    pub mutable: bool,
+    pub value: Constant,
+    pub loc: Location,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    /// The types an instance of a generic function was made for.
+    pub type_args: Vec<Type>,
+    pub exported: bool,
+    /// Whether the function is marked `(inline)`.
+    pub inline: bool,
+    /// The locals holding the arguments, which come first.
+    pub params: Vec<LocalId>,
+    pub returns: Type,
+    pub locals: Vec<Local>,
+    /// The body, entered at the first block.
+    pub blocks: Vec<Block>,
+    pub loc: Location,
+}
+
+/// A named variable of the source, or a temporary the lowering introduced.
+#[derive(Debug, Clone)]
+pub struct Local {
+    pub name: Option<String>,
+    pub ty: Type,
+    pub mutable: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub statements: Vec<Statement>,
+    pub terminator: Terminator,
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub loc: Location,
+}
+
+#[derive(Debug, Clone)]
+pub enum StatementKind {
+    Assign(Place, Rvalue),
+    /// Evaluates for the side effects alone, as a call to a void function.
+    Eval(Rvalue),
+}
+
+#[derive(Debug, Clone)]
+pub struct Terminator {
+    pub kind: TerminatorKind,
+    pub loc: Location,
+}
+
+#[derive(Debug, Clone)]
+pub enum TerminatorKind {
+    Goto(BlockId),
+    Branch {
+        condition: Operand,
+        yes: BlockId,
+        no: BlockId,
+    },
+    Return(Option<Operand>),
+    /// The end of a function that has to return a value but did not.
+    Unreachable,
+}
+
+/// Something that can be assigned to: a local or global, or a part of one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+    pub base: Base,
+    pub projections: Vec<Projection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Base {
+    Local(LocalId),
+    Global(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Projection {
+    Field(String),
+    /// An element of an array or slice. The index has already been checked
+    /// where it needs to be.
+    Index(Operand),
+    /// A field of the payload of an enum value known to be `variant`.
+    VariantField {
+        variant: String,
+        field: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Copy(Place),
+    Const(Constant),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Int {
+        value: i128,
+        ty: Type,
+    },
+    Float {
+        value: f64,
+        ty: Type,
+    },
+    Bool(bool),
+    String(String),
+    Aggregate {
+        kind: Aggregate,
+        values: Vec<Constant>,
+    },
+}
+
+/// What a list of values builds.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aggregate {
+    /// The elements of an array of the given type.
+    Array(Type),
+    /// The fields of a struct, in order.
+    Struct(String),
+    /// The fields of a variant of an enum, in order.
+    Variant { enum_name: String, variant: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rvalue {
+    Use(Operand),
+    /// An operator of two operands of the same type. `and` and `or` never
+    /// appear here, they are lowered to branches. `loc` is the code a panic
+    /// on overflow or division by zero reports.
+    Binary {
+        op: Operator,
+        left: Operand,
+        right: Operand,
+        loc: Location,
+    },
+    /// `-`, `not` or `~` of a single operand.
+    Unary {
+        op: Operator,
+        operand: Operand,
+        loc: Location,
+    },
+    /// The operand plus one. Loops step with it, where their condition has
+    /// already ruled out overflow.
+    Increment(Operand),
+    /// Converts a number to a wider type, or views an array as a slice.
+    Cast {
+        operand: Operand,
+        ty: Type,
+    },
+    Aggregate {
+        kind: Aggregate,
+        operands: Vec<Operand>,
+    },
+    /// The length of a slice or string.
+    Len(Operand),
+    /// The index, if it is less than the length, and a panic at `loc`
+    /// otherwise.
+    CheckIndex {
+        index: Operand,
+        len: Operand,
+        loc: Location,
+    },
+    /// Whether an enum value is the given variant.
+    IsVariant {
+        operand: Operand,
+        variant: String,
+    },
+    Call {
+        callee: Callee,
+        args: Vec<Operand>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Callee {
+    Function { name: String, type_args: Vec<Type> },
+    Extern(String),
+    Builtin(Builtin),
+}
+
+/// The functions the runtime provides, whose behavior depends on the types
+/// they are called with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    Print,
+    Println,
+    Concat,
+    Compare,
+}
+impl Block {
+    /// Calls `visit` on every operand the block reads, those inside a place
+    /// before the place itself.
+    pub fn visit_operands_mut(&mut self, visit: &mut impl FnMut(&mut Operand)) {
+        for statement in &mut self.statements {
+            match &mut statement.kind {
+                StatementKind::Assign(place, rvalue) => {
+                    visit_place(place, visit);
+                    visit_rvalue(rvalue, visit);
+                }
+                StatementKind::Eval(rvalue) => visit_rvalue(rvalue, visit),
+            }
+        }
+        match &mut self.terminator.kind {
+            TerminatorKind::Branch { condition, .. } => visit_operand(condition, visit),
+            TerminatorKind::Return(Some(value)) => visit_operand(value, visit),
+            _ => {}
+        }
+    }
+
+    /// Calls `visit` on every place the block reads or assigns to.
+    pub fn visit_places_mut(&mut self, visit: &mut impl FnMut(&mut Place)) {
+        self.visit_operands_mut(&mut |operand| {
+            if let Operand::Copy(place) = operand {
+                visit(place);
+            }
+        });
+        for statement in &mut self.statements {
+            if let StatementKind::Assign(place, _) = &mut statement.kind {
+                visit(place);
+            }
+        }
+    }
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        match name {
+            "print" => Some(Builtin::Print),
+            "println" => Some(Builtin::Println),
+            "concat" => Some(Builtin::Concat),
+            "compare" => Some(Builtin::Compare),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Print => "print",
+            Builtin::Println => "println",
+            Builtin::Concat => "concat",
+            Builtin::Compare => "compare",
+        }
+    }
+}
+
+impl Constant {
+    pub fn ty(&self) -> Type {
+        match self {
+            Constant::Int { ty, .. } | Constant::Float { ty, .. } => ty.clone(),
+            Constant::Bool(_) => Type::Bool,
+            Constant::String(_) => Type::String,
+            Constant::Aggregate { kind, values } => match kind {
+                Aggregate::Array(ty) => Type::Array(Box::new(ty.clone()), values.len()),
+                Aggregate::Struct(name) => Type::Named(name.clone()),
+                Aggregate::Variant { enum_name, .. } => Type::Named(enum_name.clone()),
+            },
+        }
+    }
+}
+
+impl Place {
+    pub fn local(local: LocalId) -> Place {
+        Place {
+            base: Base::Local(local),
+            projections: vec![],
+        }
+    }
+
+    pub fn project(mut self, projection: Projection) -> Place {
+        self.projections.push(projection);
+        self
+    }
+}
+
+impl Module {
+    pub fn struct_def(&self, name: &str) -> Option<&StructDef> {
+        self.structs.iter().find(|def| def.name == name)
+    }
+
+    pub fn enum_def(&self, name: &str) -> Option<&EnumDef> {
+        self.enums.iter().find(|def| def.name == name)
+    }
+
+    pub fn function(&self, name: &str, type_args: &[Type]) -> Option<&Function> {
+        self.functions
+            .iter()
+            .find(|function| function.name == name && function.type_args == type_args)
+    }
+
+    pub fn place_type(&self, function: &Function, place: &Place) -> Type {
+        let mut ty = match &place.base {
+            Base::Local(local) => function.locals[*local].ty.clone(),
+            Base::Global(name) => self
+                .globals
+                .iter()
+                .find(|global| global.name == *name)
+                .map_or(Type::Unknown, |global| global.ty.clone()),
+        };
+        for projection in &place.projections {
+            ty = match (projection, &ty) {
+                (Projection::Field(field), Type::Named(name)) => self
+                    .struct_def(name)
+                    .and_then(|def| def.field(field))
+                    .cloned()
+                    .unwrap_or(Type::Unknown),
+                (Projection::Index(_), ty) => ty.element().cloned().unwrap_or(Type::Unknown),
+                (Projection::VariantField { variant, field }, Type::Named(name)) => self
+                    .enum_def(name)
+                    .and_then(|def| def.variant(variant))
+                    .and_then(|variant| variant.fields.iter().find(|(name, _)| name == field))
+                    .map_or(Type::Unknown, |(_, ty)| ty.clone()),
+                _ => Type::Unknown,
+            };
+        }
+        ty
+    }
+
+    pub fn operand_type(&self, function: &Function, operand: &Operand) -> Type {
+        match operand {
+            Operand::Copy(place) => self.place_type(function, place),
+            Operand::Const(constant) => constant.ty(),
+        }
+    }
+
+    /// The user-defined types a type holds by value, including those held
+    /// inside arrays.
+    pub fn contained_types(&self, name: &str) -> Vec<&str> {
+        let fields: Vec<&(String, Type)> = match (self.struct_def(name), self.enum_def(name)) {
+            (Some(def), _) => def.fields.iter().collect(),
+            (_, Some(def)) => def
+                .variants
+                .iter()
+                .flat_map(|variant| &variant.fields)
+                .collect(),
+            _ => vec![],
+        };
+        fields
+            .into_iter()
+            .filter_map(|(_, ty)| {
+                let mut ty = ty;
+                while let Type::Array(element, _) = ty {
+                    ty = element.as_ref();
+                }
+                match ty {
+                    Type::Named(name) => Some(name.as_str()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Function {
+    /// The blocks some terminator jumps to.
+    pub fn targets(&self) -> Vec<BlockId> {
+        let mut targets = vec![];
+        for block in &self.blocks {
+            match &block.terminator.kind {
+                TerminatorKind::Goto(target) => targets.push(*target),
+                TerminatorKind::Branch { yes, no, .. } => targets.extend([*yes, *no]),
+                TerminatorKind::Return(_) | TerminatorKind::Unreachable => {}
+            }
+        }
+        targets
+    }
+
+    /// Drops the blocks nothing jumps to from the entry, and numbers the
+    /// rest in order.
+    pub fn remove_unreachable(&mut self) {
+        let mut reached = vec![false; self.blocks.len()];
+        let mut stack = vec![0];
+        while let Some(block) = stack.pop() {
+            if !std::mem::replace(&mut reached[block], true) {
+                match &self.blocks[block].terminator.kind {
+                    TerminatorKind::Goto(target) => stack.push(*target),
+                    TerminatorKind::Branch { yes, no, .. } => stack.extend([*yes, *no]),
+                    TerminatorKind::Return(_) | TerminatorKind::Unreachable => {}
+                }
+            }
+        }
+        let mut numbers = vec![0; self.blocks.len()];
+        let mut next = 0;
+        for (block, number) in numbers.iter_mut().enumerate() {
+            *number = next;
+            next += reached[block] as usize;
+        }
+        let blocks = std::mem::take(&mut self.blocks);
+        self.blocks = blocks
+            .into_iter()
+            .zip(reached)
+            .filter(|(_, reached)| *reached)
+            .map(|(mut block, _)| {
+                match &mut block.terminator.kind {
+                    TerminatorKind::Goto(target) => *target = numbers[*target],
+                    TerminatorKind::Branch { yes, no, .. } => {
+                        *yes = numbers[*yes];
+                        *no = numbers[*no];
+                    }
+                    TerminatorKind::Return(_) | TerminatorKind::Unreachable => {}
+                }
+                block
+            })
+            .collect();
+    }
+
+    /// Drops the locals nothing mentions any more, and numbers the rest in
+    /// order.
+    pub fn remove_unused_locals(&mut self) {
+        let mut used = vec![false; self.locals.len()];
+        for param in &self.params {
+            used[*param] = true;
+        }
+        for block in &mut self.blocks {
+            block.visit_places_mut(&mut |place| {
+                if let Base::Local(local) = place.base {
+                    used[local] = true;
+                }
+            });
+        }
+        let mut numbers = vec![0; self.locals.len()];
+        let mut next = 0;
+        for (local, number) in numbers.iter_mut().enumerate() {
+            *number = next;
+            next += used[local] as usize;
+        }
+        for block in &mut self.blocks {
+            block.visit_places_mut(&mut |place| {
+                if let Base::Local(local) = &mut place.base {
+                    *local = numbers[*local];
+                }
+            });
+        }
+        for param in &mut self.params {
+            *param = numbers[*param];
+        }
+        let locals = std::mem::take(&mut self.locals);
+        self.locals = locals
+            .into_iter()
+            .zip(used)
+            .filter_map(|(local, used)| used.then_some(local))
+            .collect();
+    }
+
+    /// A name for every local that is unique within the function: its own
+    /// name where no other local shares it, its name and number where one
+    /// does, and `_` and its number for temporaries.
+    pub fn local_names(&self) -> Vec<String> {
+        self.locals
+            .iter()
+            .enumerate()
+            .map(|(id, local)| match &local.name {
+                Some(name)
+                    if self
+                        .locals
+                        .iter()
+                        .filter(|other| other.name.as_ref() == Some(name))
+                        .count()
+                        == 1 =>
+                {
+                    name.clone()
+                }
+                Some(name) => format!("{}.{}", name, id),
+                None => format!("_{}", id),
+            })
+            .collect()
+    }
+}
+
+fn visit_rvalue(rvalue: &mut Rvalue, visit: &mut impl FnMut(&mut Operand)) {
+    match rvalue {
+        Rvalue::Use(operand)
+        | Rvalue::Unary { operand, .. }
+        | Rvalue::Increment(operand)
+        | Rvalue::Cast { operand, .. }
+        | Rvalue::Len(operand)
+        | Rvalue::IsVariant { operand, .. } => visit_operand(operand, visit),
+        Rvalue::Binary { left, right, .. } => {
+            visit_operand(left, visit);
+            visit_operand(right, visit);
+        }
+        Rvalue::CheckIndex { index, len, .. } => {
+            visit_operand(index, visit);
+            visit_operand(len, visit);
+        }
+        Rvalue::Aggregate { operands, .. } | Rvalue::Call { args: operands, .. } => {
+            for operand in operands {
+                visit_operand(operand, visit);
+            }
+        }
+    }
+}
+
+fn visit_operand(operand: &mut Operand, visit: &mut impl FnMut(&mut Operand)) {
+    if let Operand::Copy(place) = operand {
+        visit_place(place, visit);
+    }
+    visit(operand);
+}
+
+fn visit_place(place: &mut Place, visit: &mut impl FnMut(&mut Operand)) {
+    for projection in &mut place.projections {
+        if let Projection::Index(index) = projection {
+            visit_operand(index, visit);
+        }
+    }
+}
+
+/// Writes the IR in a readable form, with rvalues in the prefix notation of
+/// the source.
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = |fields: &[(String, Type)]| {
+            fields
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", name, ty))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        for def in &self.structs {
+            writeln!(f, "struct {}({})", def.name, fields(&def.fields))?;
+        }
+        for def in &self.enums {
+            let variants: Vec<String> = def
+                .variants
+                .iter()
+                .map(|variant| match variant.fields.as_slice() {
+                    [] => variant.name.clone(),
+                    variant_fields => format!("{}({})", variant.name, fields(variant_fields)),
+                })
+                .collect();
+            writeln!(f, "enum {}({})", def.name, variants.join(", "))?;
+        }
+        for header in &self.includes {
+            writeln!(f, "include {}", header)?;
+        }
+        for function in &self.externs {
+            let params: Vec<String> = function
+                .params
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", name, ty))
+                .collect();
+            writeln!(
+                f,
+                "extern fn {}({}) -> {}",
+                function.name,
+                params.join(", "),
+                function.returns
+            )?;
+        }
+        for global in &self.globals {
+            let mutable = if global.mutable { "mut " } else { "" };
+            writeln!(
+                f,
+                "global {}{}: {} = {}",
+                mutable,
+                global.name,
+                global.ty,
+                ConstantText(&global.value)
+            )?;
+        }
+        let mut separate = !(self.structs.is_empty()
+            && self.enums.is_empty()
+            && self.includes.is_empty()
+            && self.externs.is_empty()
+            && self.globals.is_empty());
+        for function in &self.functions {
+            if separate {
+                writeln!(f)?;
+            }
+            separate = true;
+            write!(f, "{}", FunctionText(function))?;
+        }
+        Ok(())
+    }
+}
+
+struct FunctionText<'f>(&'f Function);
+impl fmt::Display for FunctionText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let function = self.0;
+        let names = function.local_names();
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .map(|param| format!("{}: {}", names[*param], function.locals[*param].ty))
+            .collect();
+        let type_args: Vec<String> = function.type_args.iter().map(Type::to_string).collect();
+        let type_args = if type_args.is_empty() {
+            String::new()
+        } else {
+            format!("[{}]", type_args.join(" "))
+        };
+        let export = if function.exported { "export " } else { "" };
+        let inline = if function.inline { "inline " } else { "" };
+        writeln!(
+            f,
+            "{}{}fn {}{}({}) -> {} {{",
+            export,
+            inline,
+            function.name,
+            type_args,
+            params.join(", "),
+            function.returns
+        )?;
+        for (id, local) in function.locals.iter().enumerate() {
+            if function.params.contains(&id) {
+                continue;
+            }
+            let mutable = if local.mutable { "mut " } else { "" };
+            writeln!(f, "    let {}{}: {}", mutable, names[id], local.ty)?;
+        }
+        let text = |operand: &Operand| OperandText(operand, &names).to_string();
+        for (id, block) in function.blocks.iter().enumerate() {
+            writeln!(f, "  bb{}:", id)?;
+            for statement in &block.statements {
+                match &statement.kind {
+                    StatementKind::Assign(place, rvalue) => writeln!(
+                        f,
+                        "    {} = {}",
+                        PlaceText(place, &names),
+                        RvalueText(rvalue, &names)
+                    )?,
+                    StatementKind::Eval(rvalue) => {
+                        writeln!(f, "    {}", RvalueText(rvalue, &names))?
+                    }
+                }
+            }
+            match &block.terminator.kind {
+                TerminatorKind::Goto(target) => writeln!(f, "    goto bb{}", target)?,
+                TerminatorKind::Branch { condition, yes, no } => {
+                    writeln!(f, "    branch {} bb{} bb{}", text(condition), yes, no)?
+                }
+                TerminatorKind::Return(Some(value)) => writeln!(f, "    return {}", text(value))?,
+                TerminatorKind::Return(None) => writeln!(f, "    return")?,
+                TerminatorKind::Unreachable => writeln!(f, "    unreachable")?,
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+struct PlaceText<'p>(&'p Place, &'p [String]);
+impl fmt::Display for PlaceText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let PlaceText(place, names) = self;
+        match &place.base {
+            Base::Local(local) => write!(f, "{}", names[*local])?,
+            Base::Global(name) => write!(f, "{}", name)?,
+        }
+        for projection in &place.projections {
+            match projection {
+                Projection::Field(field) => write!(f, ".{}", field)?,
+                Projection::Index(index) => write!(f, "[{}]", OperandText(index, names))?,
+                Projection::VariantField { variant, field } => write!(f, ".{}.{}", variant, field)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct OperandText<'o>(&'o Operand, &'o [String]);
+impl fmt::Display for OperandText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Operand::Copy(place) => write!(f, "{}", PlaceText(place, self.1)),
+            Operand::Const(constant) => write!(f, "{}", ConstantText(constant)),
+        }
+    }
+}
+
+struct ConstantText<'c>(&'c Constant);
+impl fmt::Display for ConstantText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Constant::Int { value, ty } => write!(f, "{}:{}", value, ty),
+            Constant::Float { value, ty } => write!(f, "{:?}:{}", value, ty),
+            Constant::Bool(value) => write!(f, "{}", value),
+            Constant::String(value) => write!(f, "{:?}", value),
+            Constant::Aggregate { kind, values } => {
+                let values: Vec<String> = values
+                    .iter()
+                    .map(|value| ConstantText(value).to_string())
+                    .collect();
+                write!(f, "{}", AggregateText(kind, &values))
+            }
+        }
+    }
+}
+
+struct AggregateText<'a>(&'a Aggregate, &'a [String]);
+impl fmt::Display for AggregateText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = self.1.join(" ");
+        match self.0 {
+            Aggregate::Array(_) => write!(f, "[{}]", values),
+            Aggregate::Struct(name) | Aggregate::Variant { variant: name, .. } => {
+                if values.is_empty() {
+                    write!(f, "({})", name)
+                } else {
+                    write!(f, "({} {})", name, values)
+                }
+            }
+        }
+    }
+}
+
+struct RvalueText<'r>(&'r Rvalue, &'r [String]);
+impl fmt::Display for RvalueText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self.1;
+        let text = |operand: &Operand| OperandText(operand, names).to_string();
+        match self.0 {
+            Rvalue::Use(operand) => write!(f, "{}", text(operand)),
+            Rvalue::Binary {
+                op, left, right, ..
+            } => {
+                write!(f, "({} {} {})", op.name(), text(left), text(right))
+            }
+            Rvalue::Unary { op, operand, .. } => write!(f, "({} {})", op.name(), text(operand)),
+            Rvalue::Increment(operand) => write!(f, "(+ {} 1)", text(operand)),
+            Rvalue::Cast { operand, ty } => write!(f, "(cast {} {})", text(operand), ty),
+            Rvalue::Aggregate { kind, operands } => {
+                let operands: Vec<String> = operands.iter().map(text).collect();
+                write!(f, "{}", AggregateText(kind, &operands))
+            }
+            Rvalue::Len(operand) => write!(f, "(len {})", text(operand)),
+            Rvalue::CheckIndex { index, len, .. } => {
+                write!(f, "(check-index {} {})", text(index), text(len))
+            }
+            Rvalue::IsVariant { operand, variant } => {
+                write!(f, "(is {} {})", variant, text(operand))
+            }
+            Rvalue::Call { callee, args } => {
+                let name = match callee {
+                    Callee::Function { name, type_args } if !type_args.is_empty() => {
+                        let type_args: Vec<String> =
+                            type_args.iter().map(Type::to_string).collect();
+                        format!("{}[{}]", name, type_args.join(" "))
+                    }
+                    Callee::Function { name, .. } | Callee::Extern(name) => name.clone(),
+                    Callee::Builtin(builtin) => builtin.name().to_string(),
+                };
+                let mut call = format!("(call {}", name);
+                for arg in args {
+                    call.push(' ');
+                    call.push_str(&text(arg));
+                }
+                write!(f, "{})", call)
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    diagnostics::Diagnostic,
+    parsing::ast::nodes::{Location, Node, NodeKind},
+    semantics::{typecheck::TypeInfo, types::Type},
+};
+
+/// How many instantiations of a single generic function are allowed before
+/// it is assumed to recurse with ever larger type arguments.
+const MAX_INSTANCES: usize = 64;
+
+/// A generic function together with the concrete types it is used with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Instance {
+    pub name: String,
+    pub type_args: Vec<Type>,
+}
+impl Instance {
+    /// Binds the type parameters of the function to the type arguments.
+    pub fn bindings(&self, type_params: &[String]) -> HashMap<String, Type> {
+        type_params
+            .iter()
+            .cloned()
+            .zip(self.type_args.iter().cloned())
+            .collect()
+    }
+}
+
+/// Finds every instantiation of a generic function the program can reach,
+/// in the order they are first used. Calls inside a generic function are
+/// followed once for each of its instances, since their type arguments may
+/// depend on its own. What the function does to values of its type
+/// parameters is checked for the types of each instance.
+pub fn instances(top_level: &[Node], types: &TypeInfo) -> Result<Vec<Instance>, Diagnostic> {
+    let generics: HashMap<&str, &Node> = top_level
+        .iter()
+        .filter_map(|node| match &node.kind {
+            NodeKind::Fn {
+                name: Some(name),
+                type_params,
+                ..
+            } if !type_params.is_empty() => Some((name.name(), node)),
+            _ => None,
+        })
+        .collect();
+    let mut queue = VecDeque::new();
+    for node in top_level {
+        if let NodeKind::Fn {
+            name: Some(name), ..
+        } = &node.kind
+            && generics.contains_key(name.name())
+        {
+            continue;
+        }
+        calls(node, types, &HashMap::new(), &mut queue);
+    }
+    let mut found: Vec<Instance> = vec![];
+    while let Some((instance, call)) = queue.pop_front() {
+        if found.contains(&instance) {
+            continue;
+        }
+        let Some(node) = generics.get(instance.name.as_str()) else {
+            continue;
+        };
+        let NodeKind::Fn {
+            type_params, body, ..
+        } = &node.kind
+        else {
+            continue;
+        };
+        if found
+            .iter()
+            .filter(|other| other.name == instance.name)
+            .count()
+            == MAX_INSTANCES
+        {
+            return Err(Diagnostic::error(
+                format!(
+                    "`{}` is instantiated with ever larger types; a generic function cannot call itself with type arguments built from its own",
+                    instance.name
+                ),
+                node.loc,
+            ));
+        }
+        let bindings = instance.bindings(type_params);
+        // what the body does to its type parameters was left to be checked
+        // with the types they are bound to
+        for param_use in types.param_uses(&instance.name) {
+            let Some(ty) = bindings.get(&param_use.param) else {
+                continue;
+            };
+            if let Some(message) = param_use.check(ty) {
+                return Err(Diagnostic::error(message, param_use.loc).with_note(
+                    format!(
+                        "`{}` is `{}` in this call to `{}`",
+                        param_use.param, ty, instance.name
+                    ),
+                    call,
+                ));
+            }
+        }
+        calls(body, types, &bindings, &mut queue);
+        found.push(instance);
+    }
+    Ok(found)
+}
+
+/// Queues the instances of the generic calls below `node`, with where they
+/// are called.
+fn calls(
+    node: &Node,
+    types: &TypeInfo,
+    bindings: &HashMap<String, Type>,
+    queue: &mut VecDeque<(Instance, Location)>,
+) {
+    if let NodeKind::Call { name, .. } = &node.kind
+        && let Some(type_args) = types.type_args(node)
+    {
+        let instance = Instance {
+            name: name.name().to_string(),
+            type_args: type_args.iter().map(|ty| ty.substitute(bindings)).collect(),
+        };
+        queue.push_back((instance, node.loc));
+    }
+    for child in node.children() {
+        calls(child, types, bindings, queue);
+    }
+}
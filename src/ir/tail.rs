@@ -0,0 +1,126 @@
+use crate::ir::{
+    Base, BlockId, Callee, Function, Local, LocalId, Module, Operand, Place, Projection, Rvalue,
+    Statement, StatementKind, TerminatorKind,
+};
+
+/// Turns calls a function makes to itself as the last thing it does into
+/// jumps back to its start, with the arguments assigned to its parameters,
+/// so recursion that goes on for a long time does not run out of stack.
+pub fn eliminate_tail_calls(module: &mut Module) {
+    for function in &mut module.functions {
+        let mut changed = false;
+        for block in 0..function.blocks.len() {
+            if let Some(args) = tail_call(function, block) {
+                jump_to_start(function, block, args);
+                changed = true;
+            }
+        }
+        if changed {
+            // the blocks that only led to the return, and what the call
+            // returned into
+            function.remove_unreachable();
+            function.remove_unused_locals();
+        }
+    }
+}
+
+/// The arguments of the call to itself a block of a function ends with,
+/// if the function returns what that call does.
+fn tail_call(function: &Function, block: BlockId) -> Option<Vec<Operand>> {
+    let statement = function.blocks[block].statements.last()?;
+    let (result, rvalue) = match &statement.kind {
+        StatementKind::Assign(place, rvalue) if place.projections.is_empty() => match place.base {
+            Base::Local(local) => (Some(local), rvalue),
+            Base::Global(_) => return None,
+        },
+        StatementKind::Assign(..) => return None,
+        StatementKind::Eval(rvalue) => (None, rvalue),
+    };
+    let Rvalue::Call {
+        callee: Callee::Function { name, type_args },
+        args,
+    } = rvalue
+    else {
+        return None;
+    };
+    if *name != function.name || *type_args != function.type_args {
+        return None;
+    }
+    // the value can pass through blocks that only lead on to the return
+    let mut next = block;
+    let mut steps = 0;
+    loop {
+        let target = &function.blocks[next];
+        if next != block && !target.statements.is_empty() {
+            return None;
+        }
+        match &target.terminator.kind {
+            TerminatorKind::Goto(target) if steps < function.blocks.len() => {
+                next = *target;
+                steps += 1;
+            }
+            TerminatorKind::Return(None) => return Some(args.clone()),
+            TerminatorKind::Return(Some(Operand::Copy(place)))
+                if result.is_some_and(|result| *place == Place::local(result)) =>
+            {
+                return Some(args.clone());
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Replaces the call a block ends with by assignments of its arguments to
+/// the parameters, and a jump back to the start of the function.
+fn jump_to_start(function: &mut Function, block: BlockId, args: Vec<Operand>) {
+    let Some(call) = function.blocks[block].statements.pop() else {
+        return;
+    };
+    let mut assignments = vec![];
+    for (index, (param, arg)) in function.params.clone().into_iter().zip(args).enumerate() {
+        if arg == Operand::Copy(Place::local(param)) {
+            continue;
+        }
+        function.locals[param].mutable = true;
+        // an argument that reads a parameter assigned before it has to be
+        // saved first, as in swapping two of them
+        let clobbered = function.params[..index]
+            .iter()
+            .any(|earlier| reads_local(&arg, *earlier));
+        let value = if clobbered {
+            let ty = function.locals[param].ty.clone();
+            function.locals.push(Local {
+                name: None,
+                ty,
+                mutable: false,
+            });
+            let temporary = function.locals.len() - 1;
+            function.blocks[block].statements.push(Statement {
+                kind: StatementKind::Assign(Place::local(temporary), Rvalue::Use(arg)),
+                loc: call.loc,
+            });
+            Operand::Copy(Place::local(temporary))
+        } else {
+            arg
+        };
+        assignments.push(Statement {
+            kind: StatementKind::Assign(Place::local(param), Rvalue::Use(value)),
+            loc: call.loc,
+        });
+    }
+    let block = &mut function.blocks[block];
+    block.statements.extend(assignments);
+    block.terminator.kind = TerminatorKind::Goto(0);
+    block.terminator.loc = call.loc;
+}
+
+fn reads_local(operand: &Operand, local: LocalId) -> bool {
+    let Operand::Copy(place) = operand else {
+        return false;
+    };
+    place.base == Base::Local(local)
+        || place.projections.iter().any(|projection| match projection {
+            Projection::Index(index) => reads_local(index, local),
+            _ => false,
+        })
+}
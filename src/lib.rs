@@ -1,2 +1,11 @@
+pub mod build;
 pub mod cli;
+pub mod codegen;
+pub mod diagnostics;
+pub mod doc;
+pub mod ir;
+pub mod lsp;
 pub mod parsing;
+pub mod project;
+pub mod semantics;
+pub mod sources;
@@ -0,0 +1,495 @@
+//! A language server for editors, speaking the Language Server Protocol over
+//! stdin and stdout. Open files are parsed and checked as they change, and
+//! the editor can ask where a name is defined, what it was declared as,
+//! what a file defines and what names can be used where.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use log::{debug, info, warn};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DiagnosticRelatedInformation, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentSymbol, DocumentSymbolParams,
+    DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, SymbolKind,
+    TextDocumentContentChangeEvent, TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics,
+    },
+    request::{Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as _},
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    diagnostics::{Diagnostic, Severity},
+    lsp::navigation::{Definition, DefinitionKind},
+    parsing::{
+        ast::nodes::{Location, Node, NodeKind},
+        docs::doc_comment,
+        incremental::{ParsedFile, TextEdit},
+    },
+    semantics::{check_program, typecheck::BUILTINS},
+    sources::SourceFile,
+};
+
+pub mod navigation;
+
+/// Serves an editor over stdin and stdout until it asks the server to exit.
+pub fn run() -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(&connection)?;
+    // the thread writing to stdout runs until the connection is gone
+    drop(connection);
+    io_threads
+        .join()
+        .context("Could not shut down the connection")?;
+    Ok(())
+}
+
+/// Serves an editor on a connection, from the initialization handshake
+/// until the editor shuts the server down.
+pub fn serve(connection: &Connection) -> anyhow::Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        ..ServerCapabilities::default()
+    };
+    connection
+        .initialize(serde_json::to_value(capabilities)?)
+        .context("The editor did not initialize the server")?;
+    info!("Language server initialized");
+    let mut server = Server {
+        connection,
+        documents: HashMap::new(),
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    info!("Language server shutting down");
+                    return Ok(());
+                }
+                server.request(request)?;
+            }
+            Message::Notification(notification) => server.notification(notification)?,
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// An open file, as it was last changed.
+struct Document {
+    parsed: ParsedFile,
+    diagnostics: Vec<Diagnostic>,
+}
+impl Document {
+    fn new(uri: &Uri, text: String) -> Document {
+        let mut document = Document {
+            parsed: ParsedFile::new(uri.as_str(), text),
+            diagnostics: vec![],
+        };
+        document.check();
+        document
+    }
+
+    /// Applies a change the editor made, which replaces either a range of
+    /// the text or all of it.
+    fn change(&mut self, change: TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => {
+                let file = self.file();
+                let range = offset(file, range.start)..offset(file, range.end);
+                self.parsed.edit(TextEdit {
+                    range,
+                    text: change.text,
+                });
+            }
+            None => self.parsed = ParsedFile::new(self.file().name(), change.text),
+        }
+    }
+
+    /// Finds what is wrong with the file, checking it if it parses cleanly.
+    /// A file that imports others is only parsed, as what it imports is not
+    /// known here.
+    fn check(&mut self) {
+        let ast = self.parsed.ast();
+        self.diagnostics = self.parsed.diagnostics().cloned().collect();
+        if self.diagnostics.is_empty()
+            && !ast.contains_invalid()
+            && !ast
+                .children()
+                .iter()
+                .any(|node| matches!(node.kind, NodeKind::Import { .. }))
+        {
+            self.diagnostics = check_program(ast).diagnostics;
+        }
+    }
+
+    fn file(&self) -> &SourceFile {
+        self.parsed.file()
+    }
+
+    fn ast(&self) -> &Node {
+        self.parsed.ast()
+    }
+}
+
+struct Server<'c> {
+    connection: &'c Connection,
+    documents: HashMap<Uri, Document>,
+}
+
+impl Server<'_> {
+    fn notification(&mut self, notification: Notification) -> anyhow::Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Some(params) = from_json::<DidOpenTextDocumentParams>(
+                    &notification.method,
+                    notification.params,
+                ) else {
+                    return Ok(());
+                };
+                let document = params.text_document;
+                let uri = document.uri;
+                self.documents
+                    .insert(uri.clone(), Document::new(&uri, document.text));
+                self.publish_document(uri)
+            }
+            DidChangeTextDocument::METHOD => {
+                let Some(params) = from_json::<DidChangeTextDocumentParams>(
+                    &notification.method,
+                    notification.params,
+                ) else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                let Some(document) = self.documents.get_mut(&uri) else {
+                    warn!("Ignoring a change to {}, which is not open", uri.as_str());
+                    return Ok(());
+                };
+                // in order, each on the text the one before left
+                for change in params.content_changes {
+                    document.change(change);
+                }
+                document.check();
+                self.publish_document(uri)
+            }
+            DidCloseTextDocument::METHOD => {
+                let Some(params) = from_json::<DidCloseTextDocumentParams>(
+                    &notification.method,
+                    notification.params,
+                ) else {
+                    return Ok(());
+                };
+                self.documents.remove(&params.text_document.uri);
+                self.publish(params.text_document.uri, vec![])
+            }
+            method => {
+                debug!("Ignoring notification {}", method);
+                Ok(())
+            }
+        }
+    }
+
+    fn request(&mut self, request: Request) -> anyhow::Result<()> {
+        let id = request.id.clone();
+        match request.method.as_str() {
+            GotoDefinition::METHOD => {
+                let Some(params) = self.params::<GotoDefinitionParams>(request)? else {
+                    return Ok(());
+                };
+                let at = params.text_document_position_params;
+                let result = self
+                    .definition(&at.text_document.uri, at.position)
+                    .map(|loc| {
+                        GotoDefinitionResponse::Scalar(lsp_types::Location {
+                            uri: at.text_document.uri.clone(),
+                            range: loc,
+                        })
+                    });
+                self.respond(id, result)
+            }
+            HoverRequest::METHOD => {
+                let Some(params) = self.params::<HoverParams>(request)? else {
+                    return Ok(());
+                };
+                let at = params.text_document_position_params;
+                let result = self.hover(&at.text_document.uri, at.position);
+                self.respond(id, result)
+            }
+            DocumentSymbolRequest::METHOD => {
+                let Some(params) = self.params::<DocumentSymbolParams>(request)? else {
+                    return Ok(());
+                };
+                let result = self
+                    .document_symbols(&params.text_document.uri)
+                    .map(DocumentSymbolResponse::Nested);
+                self.respond(id, result)
+            }
+            Completion::METHOD => {
+                let Some(params) = self.params::<CompletionParams>(request)? else {
+                    return Ok(());
+                };
+                let at = params.text_document_position;
+                let result = self
+                    .completions(&at.text_document.uri, at.position)
+                    .map(CompletionResponse::Array);
+                self.respond(id, result)
+            }
+            method => {
+                warn!("Unsupported request {}", method);
+                let response = Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("Unsupported request {}", method),
+                );
+                self.send(Message::Response(response))
+            }
+        }
+    }
+
+    /// Publishes what is wrong with an open file.
+    fn publish_document(&self, uri: Uri) -> anyhow::Result<()> {
+        let Some(document) = self.documents.get(&uri) else {
+            return Ok(());
+        };
+        let diagnostics = document
+            .diagnostics
+            .iter()
+            .map(|diagnostic| lsp_diagnostic(&uri, document.file(), diagnostic))
+            .collect();
+        self.publish(uri, diagnostics)
+    }
+
+    fn publish(&self, uri: Uri, diagnostics: Vec<lsp_types::Diagnostic>) -> anyhow::Result<()> {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        self.send(Message::Notification(Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            params,
+        )))
+    }
+
+    /// The document at `uri`, its AST and the byte offset of `position`.
+    fn at(&self, uri: &Uri, position: Position) -> Option<(&Document, &Node, usize)> {
+        let document = self.documents.get(uri)?;
+        Some((document, document.ast(), offset(document.file(), position)))
+    }
+
+    /// Where the name at `position` is defined.
+    fn definition(&self, uri: &Uri, position: Position) -> Option<Range> {
+        let (document, ast, offset) = self.at(uri, position)?;
+        let (name, _) = navigation::reference_at(ast, offset)?;
+        let definition = navigation::resolve(ast, name, offset)?;
+        Some(range(document.file(), &definition.loc))
+    }
+
+    /// What the name at `position` was defined as, with the annotations it
+    /// was written with.
+    fn hover(&self, uri: &Uri, position: Position) -> Option<Hover> {
+        let (document, ast, offset) = self.at(uri, position)?;
+        let (name, loc) = navigation::reference_at(ast, offset)?;
+        let value = match navigation::resolve(ast, name, offset) {
+            Some(definition) => {
+                let mut value = format!("```crisp\n{}\n```", definition.detail);
+                // only what is defined at the top level is documented
+                let top_level = !matches!(
+                    definition.kind,
+                    DefinitionKind::Variable | DefinitionKind::Parameter
+                );
+                if top_level && let Some(doc) = doc_comment(document.file(), &definition.loc) {
+                    value.push_str("\n\n");
+                    value.push_str(&doc);
+                }
+                value
+            }
+            None if BUILTINS.contains(&name) => format!("```crisp\n{} (built in)\n```", name),
+            None => return None,
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(range(document.file(), &loc)),
+        })
+    }
+
+    fn document_symbols(&self, uri: &Uri) -> Option<Vec<DocumentSymbol>> {
+        let document = self.documents.get(uri)?;
+        let symbols = navigation::top_level(document.ast())
+            .into_iter()
+            .filter(|definition| definition.kind != DefinitionKind::Variant)
+            .map(|definition| {
+                let range = range(document.file(), &definition.loc);
+                #[allow(deprecated)]
+                DocumentSymbol {
+                    name: definition.name,
+                    detail: Some(definition.detail),
+                    kind: symbol_kind(definition.kind),
+                    tags: None,
+                    deprecated: None,
+                    range,
+                    selection_range: range,
+                    children: None,
+                }
+            })
+            .collect();
+        Some(symbols)
+    }
+
+    /// The names in scope at `position`, and the built-in functions.
+    fn completions(&self, uri: &Uri, position: Position) -> Option<Vec<CompletionItem>> {
+        let (_, ast, offset) = self.at(uri, position)?;
+        let mut items: Vec<CompletionItem> = navigation::completions(ast, offset)
+            .into_iter()
+            .map(completion_item)
+            .collect();
+        for builtin in BUILTINS {
+            if !items.iter().any(|item| item.label == *builtin) {
+                items.push(CompletionItem {
+                    label: builtin.to_string(),
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some("built in".to_string()),
+                    ..CompletionItem::default()
+                });
+            }
+        }
+        Some(items)
+    }
+
+    /// The parameters of a request, or `None` after telling the editor they
+    /// are malformed.
+    fn params<P: DeserializeOwned>(&self, request: Request) -> anyhow::Result<Option<P>> {
+        let params = from_json(&request.method, request.params);
+        if params.is_none() {
+            let response = Response::new_err(
+                request.id,
+                ErrorCode::InvalidParams as i32,
+                format!("Malformed parameters for {}", request.method),
+            );
+            self.send(Message::Response(response))?;
+        }
+        Ok(params)
+    }
+
+    fn respond(&self, id: RequestId, result: Option<impl Serialize>) -> anyhow::Result<()> {
+        self.send(Message::Response(Response::new_ok(id, result)))
+    }
+
+    fn send(&self, message: Message) -> anyhow::Result<()> {
+        self.connection
+            .sender
+            .send(message)
+            .context("The editor closed the connection")
+    }
+}
+
+/// The parameters of a message, or `None` if they are not what the method
+/// takes.
+fn from_json<P: DeserializeOwned>(method: &str, params: serde_json::Value) -> Option<P> {
+    match serde_json::from_value(params) {
+        Ok(params) => Some(params),
+        Err(e) => {
+            warn!("Malformed parameters for {}: {}", method, e);
+            None
+        }
+    }
+}
+
+fn completion_item(definition: Definition) -> CompletionItem {
+    let kind = match definition.kind {
+        DefinitionKind::Function => CompletionItemKind::FUNCTION,
+        DefinitionKind::Struct => CompletionItemKind::STRUCT,
+        DefinitionKind::Enum => CompletionItemKind::ENUM,
+        DefinitionKind::Variant => CompletionItemKind::ENUM_MEMBER,
+        DefinitionKind::Global => CompletionItemKind::CONSTANT,
+        DefinitionKind::Variable | DefinitionKind::Parameter => CompletionItemKind::VARIABLE,
+    };
+    CompletionItem {
+        label: definition.name,
+        kind: Some(kind),
+        detail: Some(definition.detail),
+        ..CompletionItem::default()
+    }
+}
+
+fn symbol_kind(kind: DefinitionKind) -> SymbolKind {
+    match kind {
+        DefinitionKind::Function => SymbolKind::FUNCTION,
+        DefinitionKind::Struct => SymbolKind::STRUCT,
+        DefinitionKind::Enum => SymbolKind::ENUM,
+        DefinitionKind::Variant => SymbolKind::ENUM_MEMBER,
+        DefinitionKind::Global => SymbolKind::CONSTANT,
+        DefinitionKind::Variable | DefinitionKind::Parameter => SymbolKind::VARIABLE,
+    }
+}
+
+fn lsp_diagnostic(uri: &Uri, file: &SourceFile, diagnostic: &Diagnostic) -> lsp_types::Diagnostic {
+    let severity = match diagnostic.severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+    };
+    let related: Vec<DiagnosticRelatedInformation> = diagnostic
+        .notes
+        .iter()
+        .map(|note| DiagnosticRelatedInformation {
+            location: lsp_types::Location {
+                uri: uri.clone(),
+                range: range(file, &note.loc),
+            },
+            message: note.message.clone(),
+        })
+        .collect();
+    lsp_types::Diagnostic {
+        range: range(file, &diagnostic.loc),
+        severity: Some(severity),
+        source: Some("crisp".to_string()),
+        message: diagnostic.message.clone(),
+        related_information: (!related.is_empty()).then_some(related),
+        ..lsp_types::Diagnostic::default()
+    }
+}
+
+/// The editor's position of a byte offset: a 0-based line, and a column
+/// counted in UTF-16 code units.
+pub fn position(file: &SourceFile, offset: usize) -> Position {
+    let offset = offset.min(file.source().len());
+    let (line, _) = file.line_col(offset);
+    let (start, _) = file.line_span(line);
+    let character = file.source()[start..offset].encode_utf16().count();
+    Position::new(line as u32 - 1, character as u32)
+}
+
+/// The byte offset of an editor's position, clamped to the end of its line.
+pub fn offset(file: &SourceFile, position: Position) -> usize {
+    if position.line as usize >= file.line_count() {
+        return file.source().len();
+    }
+    let (start, end) = file.line_span(position.line as usize + 1);
+    let mut units = 0;
+    for (index, c) in file.source()[start..end].char_indices() {
+        if units >= position.character as usize {
+            return start + index;
+        }
+        units += c.len_utf16();
+    }
+    end
+}
+
+fn range(file: &SourceFile, loc: &Location) -> Range {
+    Range::new(position(file, loc.start), position(file, loc.end))
+}
@@ -0,0 +1,285 @@
+use crate::parsing::ast::nodes::{Location, Node, NodeKind, Symbol};
+
+/// What sort of thing a name refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Function,
+    Struct,
+    Enum,
+    /// A variant of an enum, which is also its constructor.
+    Variant,
+    Global,
+    Variable,
+    Parameter,
+}
+
+/// A name a program defines, and where it does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    /// How the definition reads in the source, with the annotations it was
+    /// written with, such as `(fn:i32 add (a:i32 b:i32))` or `x:i32`.
+    pub detail: String,
+    /// The whole definition. A parameter has no node of its own, and is
+    /// placed at its function.
+    pub loc: Location,
+}
+
+/// The definitions at the top level of a file, in order: functions,
+/// including those implemented in C, types, variants and globals.
+pub fn top_level(ast: &Node) -> Vec<Definition> {
+    let NodeKind::Block { expressions } = &ast.kind else {
+        return vec![];
+    };
+    let mut definitions = vec![];
+    for node in expressions {
+        match &node.kind {
+            NodeKind::Fn {
+                name: Some(name),
+                returns,
+                params,
+                ..
+            } => definitions.push(Definition {
+                name: name.name().to_string(),
+                kind: DefinitionKind::Function,
+                detail: format!("(fn:{} {} ({}))", returns, name.name(), symbols(params)),
+                loc: node.loc,
+            }),
+            NodeKind::Extern { functions } => {
+                for function in functions {
+                    definitions.push(Definition {
+                        name: function.name.clone(),
+                        kind: DefinitionKind::Function,
+                        detail: format!(
+                            "(fn:{} {} ({}))",
+                            function.returns,
+                            function.name,
+                            symbols(&function.params)
+                        ),
+                        loc: function.loc,
+                    });
+                }
+            }
+            NodeKind::Struct { name, fields } => definitions.push(Definition {
+                name: name.name().to_string(),
+                kind: DefinitionKind::Struct,
+                detail: format!("(struct {} ({}))", name.name(), symbols(fields)),
+                loc: node.loc,
+            }),
+            NodeKind::Enum { name, variants } => {
+                definitions.push(Definition {
+                    name: name.name().to_string(),
+                    kind: DefinitionKind::Enum,
+                    detail: format!("(enum {})", name.name()),
+                    loc: node.loc,
+                });
+                for variant in variants {
+                    let detail = if variant.fields.is_empty() {
+                        variant.name.clone()
+                    } else {
+                        format!("({} {})", variant.name, symbols(&variant.fields))
+                    };
+                    definitions.push(Definition {
+                        name: variant.name.clone(),
+                        kind: DefinitionKind::Variant,
+                        detail: format!("{} of {}", detail, name.name()),
+                        loc: variant.loc,
+                    });
+                }
+            }
+            NodeKind::Let {
+                symbol, mutable, ..
+            } => definitions.push(let_binding(
+                symbol,
+                *mutable,
+                DefinitionKind::Global,
+                node.loc,
+            )),
+            _ => {}
+        }
+    }
+    definitions
+}
+
+/// Every definition visible at a byte offset: those at the top level, then
+/// those of the scopes around the offset from the outside in, so a later
+/// one shadows an earlier one of the same name.
+pub fn definitions_at(ast: &Node, offset: usize) -> Vec<Definition> {
+    let mut definitions = top_level(ast);
+    for node in ast.children() {
+        if inside(node, offset) {
+            locals(node, offset, &mut definitions);
+        }
+    }
+    definitions
+}
+
+/// The definition a name at a byte offset refers to.
+pub fn resolve(ast: &Node, name: &str, offset: usize) -> Option<Definition> {
+    definitions_at(ast, offset)
+        .into_iter()
+        .rev()
+        .find(|definition| definition.name == name)
+}
+
+/// The names that can be used at a byte offset, each once, as the
+/// innermost definition of it.
+pub fn completions(ast: &Node, offset: usize) -> Vec<Definition> {
+    let mut names = vec![];
+    for definition in definitions_at(ast, offset).into_iter().rev() {
+        if !names
+            .iter()
+            .any(|other: &Definition| other.name == definition.name)
+        {
+            names.push(definition);
+        }
+    }
+    names.reverse();
+    names
+}
+
+/// The name used at a byte offset, as a variable or the function of a
+/// call, and where it is written.
+pub fn reference_at(node: &Node, offset: usize) -> Option<(&str, Location)> {
+    for child in node.children() {
+        if touches(child, offset)
+            && let Some(found) = reference_at(child, offset)
+        {
+            return Some(found);
+        }
+    }
+    match &node.kind {
+        NodeKind::Identifier { symbol } if touches(node, offset) => Some((symbol.name(), node.loc)),
+        NodeKind::Call { name, .. } => {
+            // the name follows the opening parenthesis
+            let start = node.loc.start + 1;
+            let end = start + name.name().len();
+            (start..=end).contains(&offset).then(|| {
+                let loc = Location {
+                    col: node.loc.col + 1,
+                    start,
+                    end,
+                    ..node.loc
+                };
+                (name.name(), loc)
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Adds the definitions made by the scopes of `node` that enclose `offset`.
+fn locals(node: &Node, offset: usize, definitions: &mut Vec<Definition>) {
+    match &node.kind {
+        NodeKind::Fn { params, body, .. } => {
+            for param in params {
+                definitions.push(binding(param, DefinitionKind::Parameter, node.loc));
+            }
+            if inside(body, offset) {
+                locals(body, offset, definitions);
+            }
+        }
+        NodeKind::Block { expressions } => {
+            for expression in expressions {
+                if inside(expression, offset) {
+                    locals(expression, offset, definitions);
+                    break;
+                }
+                if expression.loc.end > offset {
+                    break;
+                }
+                if let NodeKind::Let {
+                    symbol, mutable, ..
+                } = &expression.kind
+                {
+                    definitions.push(let_binding(
+                        symbol,
+                        *mutable,
+                        DefinitionKind::Variable,
+                        expression.loc,
+                    ));
+                }
+            }
+        }
+        NodeKind::For {
+            dummy,
+            iterator,
+            body,
+        } => {
+            if inside(body, offset) {
+                definitions.push(binding(dummy, DefinitionKind::Variable, node.loc));
+                locals(body, offset, definitions);
+            } else if inside(iterator, offset) {
+                locals(iterator, offset, definitions);
+            }
+        }
+        NodeKind::Given { predicate, cases } => {
+            if inside(predicate, offset) {
+                locals(predicate, offset, definitions);
+            }
+            for case in cases.children() {
+                let [pattern, body] = case.children()[..] else {
+                    continue;
+                };
+                if !inside(body, offset) {
+                    continue;
+                }
+                if let NodeKind::Call { args, .. } = &pattern.kind {
+                    for arg in args {
+                        if let NodeKind::Identifier { symbol } = &arg.kind {
+                            definitions.push(binding(symbol, DefinitionKind::Variable, arg.loc));
+                        }
+                    }
+                }
+                locals(body, offset, definitions);
+            }
+        }
+        _ => {
+            for child in node.children() {
+                if inside(child, offset) {
+                    locals(child, offset, definitions);
+                }
+            }
+        }
+    }
+}
+
+fn binding(symbol: &Symbol, kind: DefinitionKind, loc: Location) -> Definition {
+    Definition {
+        name: symbol.name().to_string(),
+        kind,
+        detail: symbols(std::slice::from_ref(symbol)),
+        loc,
+    }
+}
+
+fn let_binding(symbol: &Symbol, mutable: bool, kind: DefinitionKind, loc: Location) -> Definition {
+    let mut definition = binding(symbol, kind, loc);
+    let keyword = if mutable { "let mut" } else { "let" };
+    definition.detail = format!("({} {})", keyword, definition.detail);
+    definition
+}
+
+fn symbols(symbols: &[Symbol]) -> String {
+    symbols
+        .iter()
+        .map(|symbol| match symbol.annotation() {
+            Some(annotation) => format!("{}:{}", symbol.name(), annotation),
+            None => symbol.name().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether `offset` is strictly within a node, where the scopes it opens
+/// apply.
+fn inside(node: &Node, offset: usize) -> bool {
+    node.loc.start < offset && offset < node.loc.end
+}
+
+/// Whether `offset` is within a node or at either end of it, as a cursor
+/// just after a name still points at it.
+fn touches(node: &Node, offset: usize) -> bool {
+    node.loc.start <= offset && offset <= node.loc.end
+}
@@ -1,13 +1,20 @@
-#![feature(path_absolute_method)]
-
 use clap::Parser as CLIParser;
 use crisp::{
+    build::{CCompiler, build, check_entry, lower_program, transpile},
     cli::{Args, Command},
-    parsing::parse_file,
+    codegen::{
+        c::{EmitOptions, Overflow},
+        runtime,
+    },
+    doc::{self, Format},
+    ir::inline::Inlining,
+    lsp,
+    project::{Project, scaffold},
 };
 use log::{debug, error, info};
+use std::{path::Path, process::ExitCode};
 
-fn main() {
+fn main() -> ExitCode {
     // Parse CLI args and set up logging env
     let args = Args::parse();
     let log_level = match args.verbose {
@@ -21,24 +28,201 @@ fn main() {
         .write_style(env_logger::WriteStyle::Always)
         .init();
     info!("Log level: {}", log_level.to_string().to_uppercase());
-    std::panic::set_hook(Box::new(|info| {
-        log::error!("Exiting because: {info}");
-    }));
     let cmd = args.command;
-    let path: &'static str;
+    // the language server catches the panics of parsing text as it is
+    // typed, which are not the reason it exits
+    if !matches!(cmd, Command::Lsp) {
+        std::panic::set_hook(Box::new(|info| {
+            log::error!("Exiting because: {info}");
+        }));
+    }
     match cmd {
-        Command::T { input } => {
+        Command::T {
+            input,
+            output,
+            emit,
+            no_bounds_checks,
+            overflow,
+            line_directives,
+            compile,
+            opt_level,
+            debug,
+            cflags,
+        } => {
             debug!("Opening file: {:?}", input);
             // Check if the file exists
             if !input.exists() {
                 error!("File {:?} not found, exiting", input);
-                return;
-            } else {
-                let path_str = input.absolute().unwrap().to_string_lossy().into_owned();
-                path = Box::leak(path_str.into_boxed_str());
+                return ExitCode::FAILURE;
+            }
+            let options = EmitOptions {
+                bounds_checks: !no_bounds_checks,
+                overflow: Overflow::from_name(&overflow).unwrap_or_default(),
+                line_directives,
+                inlining: opt_level
+                    .as_deref()
+                    .and_then(Inlining::for_opt_level)
+                    .unwrap_or_default(),
+                ..EmitOptions::default()
+            };
+            if emit == "ir" {
+                let module = match lower_program(&input, &options) {
+                    Ok((_, module)) => module,
+                    Err(e) => {
+                        error!("{}, exiting", e);
+                        return ExitCode::FAILURE;
+                    }
+                };
+                let ir_file = output.unwrap_or_else(|| input.with_extension("ir"));
+                return match std::fs::write(&ir_file, module.to_string()) {
+                    Ok(()) => {
+                        info!("Wrote {:?}", ir_file);
+                        ExitCode::SUCCESS
+                    }
+                    Err(e) => {
+                        error!("Could not write {:?}: {}", ir_file, e);
+                        ExitCode::FAILURE
+                    }
+                };
+            }
+            let (program, c) = match transpile(&input, &options) {
+                Ok(transpiled) => transpiled,
+                Err(e) => {
+                    error!("{}, exiting", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            // with --compile the output is the executable
+            let executable =
+                compile.then(|| output.clone().unwrap_or_else(|| input.with_extension("")));
+            let c_file = match &executable {
+                Some(executable) => executable.with_extension("c"),
+                None => output.unwrap_or_else(|| input.with_extension("c")),
+            };
+            if executable.as_ref() == Some(&c_file) {
+                error!(
+                    "The executable cannot be written to {:?}, which is where the C goes, exiting",
+                    c_file
+                );
+                return ExitCode::FAILURE;
+            }
+            if let Err(e) = std::fs::write(&c_file, &c.code) {
+                error!("Could not write {:?}: {}", c_file, e);
+                return ExitCode::FAILURE;
+            }
+            info!("Wrote {:?}", c_file);
+            // the generated code includes the runtime from next to itself
+            let dir = match c_file.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            if let Err(e) = runtime::write_runtime(dir) {
+                error!("Could not write the runtime to {:?}: {}", dir, e);
+                return ExitCode::FAILURE;
+            }
+            // exported functions are declared for C next to the code
+            let stem = c_file.file_stem().unwrap_or_default().to_string_lossy();
+            if let Some(header) = c.header(&stem) {
+                let header_file = c_file.with_extension("h");
+                if let Err(e) = std::fs::write(&header_file, header) {
+                    error!("Could not write {:?}: {}", header_file, e);
+                    return ExitCode::FAILURE;
+                }
+                info!("Wrote {:?}", header_file);
+            }
+            let Some(executable) = executable else {
+                return ExitCode::SUCCESS;
+            };
+            if let Err(e) = check_entry(&program) {
+                error!("{}, exiting", e);
+                return ExitCode::FAILURE;
+            }
+            let mut compiler = CCompiler::from_env();
+            if let Some(level) = opt_level {
+                compiler.flags.push(format!("-O{}", level));
+            }
+            if debug {
+                compiler.flags.push("-g".to_string());
+            }
+            compiler.flags.extend(cflags);
+            match compiler.compile(&c_file, &executable, false, &c, &program.sources) {
+                Ok(()) => {
+                    info!("Wrote {:?}", executable);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    error!("{:#}, exiting", e);
+                    ExitCode::FAILURE
+                }
             }
         }
+        Command::Build {
+            manifest_path,
+            no_bounds_checks,
+            overflow,
+            line_directives,
+        } => {
+            let project = match manifest_path {
+                Some(path) => Project::load(&path),
+                None => Project::find(Path::new(".")),
+            };
+            let options = EmitOptions {
+                bounds_checks: !no_bounds_checks,
+                overflow: Overflow::from_name(&overflow).unwrap_or_default(),
+                line_directives,
+                ..EmitOptions::default()
+            };
+            match project.and_then(|project| build(&project, &options)) {
+                Ok(_) => ExitCode::SUCCESS,
+                Err(e) => {
+                    error!("{:#}, exiting", e);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Doc {
+            input,
+            output,
+            format,
+        } => {
+            let (entry, dir) = match input {
+                Some(input) => {
+                    let dir = input.parent().unwrap_or(Path::new(".")).join("doc");
+                    (input, dir)
+                }
+                None => match Project::find(Path::new(".")) {
+                    Ok(project) => (project.entry(), project.output_dir().join("doc")),
+                    Err(e) => {
+                        error!("{:#}, exiting", e);
+                        return ExitCode::FAILURE;
+                    }
+                },
+            };
+            let format = Format::from_name(&format).unwrap_or_default();
+            match doc::generate(&entry, format, &output.unwrap_or(dir)) {
+                Ok(_) => ExitCode::SUCCESS,
+                Err(e) => {
+                    error!("{:#}, exiting", e);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Lsp => match lsp::run() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                error!("{:#}, exiting", e);
+                ExitCode::FAILURE
+            }
+        },
+        Command::New { name } => match scaffold(Path::new(&name), &name) {
+            Ok(()) => {
+                info!("Created project `{}`", name);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                error!("{:#}, exiting", e);
+                ExitCode::FAILURE
+            }
+        },
     }
-    debug!("Parsing input");
-    let _parse_result = parse_file(path);
 }
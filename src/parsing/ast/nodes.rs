@@ -1,6 +1,9 @@
-use crate::parsing::{
-    Rule,
-    ast::{print_error, validation::*},
+use crate::{
+    parsing::{
+        Rule,
+        ast::{print_error, validation::*},
+    },
+    sources::{FileId, SourceFile},
 };
 use log::trace;
 use pest::{Span, iterators::Pair};
@@ -12,7 +15,7 @@ pub enum Literal {
     Boolean(bool),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Number {
     F32(f32),
     F64(f64),
@@ -26,48 +29,66 @@ pub enum Number {
     U64(u64),
 }
 impl Number {
-    pub fn from_str(s: &str) -> Self {
+    /// The literal written as `s`, in the smallest type that holds it, or
+    /// `None` for an integer too large for 64 bits.
+    pub fn from_str(s: &str) -> Option<Self> {
         if s.contains('.') || s.contains('e') || s.contains('E') {
             if let Ok(f) = s.parse::<f32>() {
-                return Number::F32(f);
+                return Some(Number::F32(f));
             }
-            return Number::F64(
-                s.parse::<f64>()
-                    .unwrap_or_else(|_| core::panic!("Invalid float literal: {}", s)),
-            );
+            return s.parse::<f64>().ok().map(Number::F64);
         }
 
         if let Ok(v) = s.parse::<u8>() {
-            return Number::U8(v);
+            return Some(Number::U8(v));
         }
         if let Ok(v) = s.parse::<u16>() {
-            return Number::U16(v);
+            return Some(Number::U16(v));
         }
         if let Ok(v) = s.parse::<u32>() {
-            return Number::U32(v);
+            return Some(Number::U32(v));
         }
         if let Ok(v) = s.parse::<u64>() {
-            return Number::U64(v);
+            return Some(Number::U64(v));
         }
 
         if let Ok(v) = s.parse::<i8>() {
-            return Number::I8(v);
+            return Some(Number::I8(v));
         }
         if let Ok(v) = s.parse::<i16>() {
-            return Number::I16(v);
+            return Some(Number::I16(v));
         }
         if let Ok(v) = s.parse::<i32>() {
-            return Number::I32(v);
-        }
-        if let Ok(v) = s.parse::<i64>() {
-            return Number::I64(v);
+            return Some(Number::I32(v));
         }
+        s.parse::<i64>().ok().map(Number::I64)
+    }
 
-        if let Ok(f) = s.parse::<f64>() {
-            return Number::F64(f);
+    pub fn is_float(&self) -> bool {
+        matches!(self, Number::F32(_) | Number::F64(_))
+    }
+
+    /// The integer value of the literal, or `None` for floats.
+    pub fn as_i128(&self) -> Option<i128> {
+        match *self {
+            Number::F32(_) | Number::F64(_) => None,
+            Number::I8(v) => Some(v as i128),
+            Number::I16(v) => Some(v as i128),
+            Number::I32(v) => Some(v as i128),
+            Number::I64(v) => Some(v as i128),
+            Number::U8(v) => Some(v as i128),
+            Number::U16(v) => Some(v as i128),
+            Number::U32(v) => Some(v as i128),
+            Number::U64(v) => Some(v as i128),
         }
+    }
 
-        core::panic!("Numeric literal out of range or invalid: {}", s);
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            Number::F32(v) => v as f64,
+            Number::F64(v) => v,
+            _ => self.as_i128().unwrap() as f64,
+        }
     }
 }
 
@@ -77,6 +98,19 @@ pub enum Symbol {
     Untyped { name: String },
 }
 impl Symbol {
+    pub fn name(&self) -> &str {
+        match self {
+            Symbol::Typed { name, .. } | Symbol::Untyped { name } => name,
+        }
+    }
+
+    pub fn annotation(&self) -> Option<&str> {
+        match self {
+            Symbol::Typed { annotation, .. } => Some(annotation),
+            Symbol::Untyped { .. } => None,
+        }
+    }
+
     pub fn from_pair(pair: &Pair<Rule>) -> Symbol {
         let pair_str = pair.as_str();
         if pair_str.len() < 2 {
@@ -91,7 +125,7 @@ impl Symbol {
                 let (name, annotation) = pair_str.split_at(i);
                 Self::Typed {
                     name: name.to_string(),
-                    annotation: annotation.to_string(),
+                    annotation: annotation[1..].to_string(),
                 }
             }
             _ => Symbol::Untyped {
@@ -106,26 +140,131 @@ pub struct SourceInfo<'a> {
     pub line: usize,
     pub col: usize,
     pub span: Span<'a>,
-    pub path: &'a str,
+    pub file: &'a SourceFile,
 }
 impl<'a> SourceInfo<'a> {
-    pub fn from_pair(pair: &Pair<'a, Rule>, path: &'static str) -> SourceInfo<'a> {
+    pub fn from_pair(pair: &Pair<'a, Rule>, file: &'a SourceFile) -> SourceInfo<'a> {
+        let span = pair.as_span();
+        let (line, col) = file.line_col(span.start());
         SourceInfo {
-            line: pair.line_col().0,
-            col: pair.line_col().1,
-            span: pair.as_span(),
-            path,
+            line,
+            col,
+            span,
+            file,
         }
     }
 }
 
+/// Position of a node in its source file. Unlike [`SourceInfo`] this does
+/// not borrow the source text, so it can outlive the parse.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub file: FileId,
+    pub line: usize,
+    pub col: usize,
+    pub start: usize,
+    pub end: usize,
+}
+impl Location {
+    pub fn from_info(info: &SourceInfo) -> Location {
+        Location {
+            file: info.file.id(),
+            line: info.line,
+            col: info.col,
+            start: info.span.start(),
+            end: info.span.end(),
+        }
+    }
+
+    pub fn from_pair(pair: &Pair<Rule>, file: &SourceFile) -> Location {
+        let span = pair.as_span();
+        Location::from_offsets(file, span.start(), span.end())
+    }
+
+    /// The location of a range of bytes of a file.
+    pub fn from_offsets(file: &SourceFile, start: usize, end: usize) -> Location {
+        let (line, col) = file.line_col(start);
+        Location {
+            file: file.id(),
+            line,
+            col,
+            start,
+            end,
+        }
+    }
+}
+
+/// One alternative of an enum, with the fields it carries.
 #[derive(Debug)]
-pub enum Node {
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<Symbol>,
+    pub loc: Location,
+}
+
+/// A marker on a function that changes how it is compiled, written as a
+/// list of its name after the function's name, as in
+/// `(fn:i32 add (export) (a:i32 b:i32) ...)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    /// Gives the function its plain name in C and declares it in the
+    /// generated header, so C code can call it.
+    Export,
+    /// Asks for calls to the function to be replaced by its body.
+    Inline,
+}
+impl Attribute {
+    pub fn from_name(name: &str) -> Option<Attribute> {
+        match name {
+            "export" => Some(Attribute::Export),
+            "inline" => Some(Attribute::Inline),
+            _ => None,
+        }
+    }
+}
+
+/// A function implemented in C, of which only the signature is known.
+#[derive(Debug)]
+pub struct ExternFn {
+    pub name: String,
+    pub returns: String,
+    pub params: Vec<Symbol>,
+    pub loc: Location,
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub loc: Location,
+}
+
+#[derive(Debug)]
+pub enum NodeKind {
     Fn {
         name: Option<Symbol>,
+        /// The names in `[T U]` after the name of a generic function.
+        type_params: Vec<String>,
+        attributes: Vec<Attribute>,
+        returns: String,
         params: Vec<Symbol>,
         body: Box<Node>,
     },
+    Struct {
+        name: Symbol,
+        fields: Vec<Symbol>,
+    },
+    Enum {
+        name: Symbol,
+        variants: Vec<Variant>,
+    },
+    Field {
+        target: Box<Node>,
+        field: String,
+    },
+    Index {
+        target: Box<Node>,
+        index: Box<Node>,
+    },
     If {
         predicate: Box<Node>,
         yes: Box<Node>,
@@ -133,6 +272,12 @@ pub enum Node {
     },
     Let {
         symbol: Symbol,
+        mutable: bool,
+        value: Box<Node>,
+    },
+    /// Assigns to a variable, a field or an array element.
+    Set {
+        place: Box<Node>,
         value: Box<Node>,
     },
     For {
@@ -151,6 +296,9 @@ pub enum Node {
         symbol: Symbol,
     },
     Literal(Literal),
+    Array {
+        elements: Vec<Node>,
+    },
     Call {
         name: Symbol,
         args: Vec<Node>,
@@ -158,52 +306,175 @@ pub enum Node {
     Block {
         expressions: Vec<Node>,
     },
+    /// Makes every top-level definition of another file available as
+    /// `module/name`. Modules have no private definitions.
+    Import {
+        module: String,
+        path: String,
+    },
+    /// Declares functions implemented in C, which are called like any other
+    /// function but have no body in the program.
+    Extern {
+        functions: Vec<ExternFn>,
+    },
+    /// Includes a C header in the generated code, either a system header
+    /// such as `<stdio.h>` or a local one such as `mylib.h`.
+    Include {
+        header: String,
+    },
     Invalid,
 }
 impl Node {
-    pub fn from_pair(pair: Pair<Rule>, path: &'static str) -> Node {
+    pub fn new(kind: NodeKind, loc: Location) -> Node {
+        Node { kind, loc }
+    }
+
+    /// The nodes directly below this one, in source order.
+    pub fn children(&self) -> Vec<&Node> {
+        match &self.kind {
+            NodeKind::Fn { body, .. } => vec![body],
+            NodeKind::Field { target, .. } => vec![target],
+            NodeKind::Index { target, index } => vec![target, index],
+            NodeKind::If { predicate, yes, no } => {
+                let mut children = vec![predicate.as_ref(), yes.as_ref()];
+                children.extend(no.as_deref());
+                children
+            }
+            NodeKind::Let { value, .. } => vec![value],
+            NodeKind::Set { place, value } => vec![place, value],
+            NodeKind::For { iterator, body, .. } => vec![iterator, body],
+            NodeKind::Given { predicate, cases } => vec![predicate, cases],
+            NodeKind::Return { value } => vec![value],
+            NodeKind::Call { args, .. } => args.iter().collect(),
+            NodeKind::Array { elements } => elements.iter().collect(),
+            NodeKind::Block { expressions } => expressions.iter().collect(),
+            NodeKind::Struct { .. }
+            | NodeKind::Enum { .. }
+            | NodeKind::Identifier { .. }
+            | NodeKind::Literal(_)
+            | NodeKind::Import { .. }
+            | NodeKind::Extern { .. }
+            | NodeKind::Include { .. }
+            | NodeKind::Invalid => vec![],
+        }
+    }
+
+    /// The nodes directly below this one, in source order, to change them.
+    pub fn children_mut(&mut self) -> Vec<&mut Node> {
+        match &mut self.kind {
+            NodeKind::Fn { body, .. } => vec![body],
+            NodeKind::Field { target, .. } => vec![target],
+            NodeKind::Index { target, index } => vec![target, index],
+            NodeKind::If { predicate, yes, no } => {
+                let mut children = vec![predicate.as_mut(), yes.as_mut()];
+                children.extend(no.as_deref_mut());
+                children
+            }
+            NodeKind::Let { value, .. } => vec![value],
+            NodeKind::Set { place, value } => vec![place, value],
+            NodeKind::For { iterator, body, .. } => vec![iterator, body],
+            NodeKind::Given { predicate, cases } => vec![predicate, cases],
+            NodeKind::Return { value } => vec![value],
+            NodeKind::Call { args, .. } => args.iter_mut().collect(),
+            NodeKind::Array { elements } => elements.iter_mut().collect(),
+            NodeKind::Block { expressions } => expressions.iter_mut().collect(),
+            NodeKind::Struct { .. }
+            | NodeKind::Enum { .. }
+            | NodeKind::Identifier { .. }
+            | NodeKind::Literal(_)
+            | NodeKind::Import { .. }
+            | NodeKind::Extern { .. }
+            | NodeKind::Include { .. }
+            | NodeKind::Invalid => vec![],
+        }
+    }
+
+    /// Changes every location in this node and below it, including those
+    /// of variants and C functions, as when the text before it changes.
+    pub fn visit_locations_mut(&mut self, f: &mut impl FnMut(&mut Location)) {
+        f(&mut self.loc);
+        match &mut self.kind {
+            NodeKind::Enum { variants, .. } => {
+                variants.iter_mut().for_each(|variant| f(&mut variant.loc))
+            }
+            NodeKind::Extern { functions } => functions
+                .iter_mut()
+                .for_each(|function| f(&mut function.loc)),
+            _ => {}
+        }
+        for child in self.children_mut() {
+            child.visit_locations_mut(f);
+        }
+    }
+
+    /// Whether this node or any node below it failed validation.
+    pub fn contains_invalid(&self) -> bool {
+        matches!(self.kind, NodeKind::Invalid)
+            || self.children().into_iter().any(Node::contains_invalid)
+    }
+
+    pub fn from_pair(pair: Pair<Rule>, file: &SourceFile) -> Node {
+        let loc = Location::from_pair(&pair, file);
         match pair.as_rule() {
-            Rule::file => parse_program(pair, path),
-            Rule::list => parse_list(pair, path),
-            Rule::symbol => Node::Identifier {
-                symbol: Symbol::from_pair(&pair),
+            Rule::file => parse_program(pair, file),
+            Rule::list => parse_list(pair, file),
+            Rule::array => parse_array(pair, file),
+            Rule::symbol => identifier_from_pair(&pair, file),
+            Rule::number => match Number::from_str(pair.as_str()) {
+                Some(literal) => Node::new(NodeKind::Literal(Literal::Number { literal }), loc),
+                None => {
+                    print_error(
+                        &format!(
+                            "Integer literal `{}` does not fit in 64 bits",
+                            pair.as_str()
+                        ),
+                        &SourceInfo::from_pair(&pair, file),
+                    );
+                    Node::new(NodeKind::Invalid, loc)
+                }
             },
-            Rule::number => Node::Literal(Literal::Number {
-                literal: Number::from_str(pair.as_str()),
-            }),
-            Rule::string => {
-                Node::Literal(Literal::String(pair.as_str().trim_matches('"').to_string()))
-            }
-            Rule::boolean => Node::Literal(Literal::Boolean(pair.as_str().parse().unwrap())),
+            Rule::string => Node::new(
+                NodeKind::Literal(Literal::String(pair.as_str().trim_matches('"').to_string())),
+                loc,
+            ),
+            Rule::boolean => Node::new(
+                NodeKind::Literal(Literal::Boolean(pair.as_str().parse().unwrap())),
+                loc,
+            ),
             _ => {
-                print_error("Unexpected syntax", &SourceInfo::from_pair(&pair, path));
+                print_error("Unexpected syntax", &SourceInfo::from_pair(&pair, file));
                 core::panic!("AST construction failed: rule {:?}", pair.as_rule());
             }
         }
     }
 }
 
-fn parse_program(pair: Pair<Rule>, path: &'static str) -> Node {
+fn parse_program(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
     let mut expressions = Vec::new();
     for inner_pair in pair.into_inner() {
         match inner_pair.as_rule() {
             Rule::EOI => continue,
-            _ => expressions.push(Node::from_pair(inner_pair, path)),
+            _ => expressions.push(Node::from_pair(inner_pair, file)),
         }
     }
-    Node::Block { expressions }
+    Node::new(NodeKind::Block { expressions }, loc)
 }
 
-fn parse_list(pair: Pair<Rule>, path: &'static str) -> Node {
-    if !validate_list(&pair, path) {
-        return Node::Invalid;
+fn parse_list(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_list(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
     }
     let mut inner = pair.clone().into_inner();
 
     if inner.is_empty() {
-        return Node::Block {
-            expressions: vec![],
-        };
+        return Node::new(
+            NodeKind::Block {
+                expressions: vec![],
+            },
+            loc,
+        );
     }
 
     let first_element = inner.next().unwrap();
@@ -216,158 +487,443 @@ fn parse_list(pair: Pair<Rule>, path: &'static str) -> Node {
         {
             let name_str = name.as_str();
             if name_str == "fn" {
-                return parse_fn(pair, path);
+                return parse_fn(pair, file);
             }
         }
         if let Symbol::Untyped { ref name } = first_symbol {
             let name_str = name.as_str();
             match name_str {
-                "if" => return parse_if(pair, path),
-                "for" => return parse_for(pair, path),
-                "let" => return parse_let(pair, path),
-                "given" => return parse_given(pair, path),
-                "ret" => return parse_ret(pair, path),
-                _ => return parse_call(pair, path),
+                "if" => return parse_if(pair, file),
+                "for" => return parse_for(pair, file),
+                "let" => return parse_let(pair, file),
+                "set!" => return parse_set(pair, file),
+                "struct" => return parse_struct(pair, file),
+                "enum" => return parse_enum(pair, file),
+                "." => return parse_field(pair, file),
+                "at" => return parse_index(pair, file),
+                "given" => return parse_given(pair, file),
+                "ret" => return parse_ret(pair, file),
+                "import" => return parse_import(pair, file),
+                "extern" => return parse_extern(pair, file),
+                "include" => return parse_include(pair, file),
+                _ => return parse_call(pair, file),
             }
         }
     }
 
-    let mut expressions = vec![];
-    for pair in inner.clone().into_iter() {
-        expressions.push(Node::from_pair(pair, path));
-    }
-    return Node::Block { expressions };
+    block_from_pair(pair, file)
 }
 
-fn parse_fn(pair: Pair<Rule>, path: &'static str) -> Node {
-    if !validate_fn(&pair, path) {
-        return Node::Invalid;
+fn parse_fn(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_fn(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
     }
     let mut pairs = pair.clone().into_inner().peekable();
-    pairs.next();
+    let returns = Symbol::from_pair(&pairs.next().unwrap())
+        .annotation()
+        .unwrap_or_default()
+        .to_string();
     let mut name = None;
-    if let Some(p) = pairs.peek() {
-        if p.as_rule() == Rule::symbol {
-            name = Some(Symbol::from_pair(&pairs.next().unwrap()));
-        }
+    if let Some(p) = pairs.peek()
+        && p.as_rule() == Rule::symbol
+    {
+        name = Some(Symbol::from_pair(&pairs.next().unwrap()));
+    }
+    // attributes come before the type parameters, parameters and body
+    let mut attributes = vec![];
+    while name.is_some()
+        && let Some(p) = pairs.peek()
+        && is_attribute(p)
+        && pairs.len() > 2
+    {
+        let attribute = pairs.next().unwrap().into_inner().next().unwrap();
+        attributes.extend(Attribute::from_name(attribute.as_str()));
+    }
+    let mut type_params = vec![];
+    if let Some(p) = pairs.peek()
+        && p.as_rule() == Rule::array
+    {
+        type_params = pairs
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(|param| param.as_str().to_string())
+            .collect();
     }
     let params_pair = pairs.next().unwrap_or_else(|| {
-        core::panic!("Function missing parameter list at {}", path);
+        core::panic!("Function missing parameter list at {}", file.name());
     });
-    if !validate_params(&params_pair, path) {
-        return Node::Invalid;
+    if !validate_params(&params_pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
     }
     let params = params_pair
         .into_inner()
         .map(|pair| Symbol::from_pair(&pair))
         .collect();
     let body_pair = pairs.next().unwrap_or_else(|| {
-        core::panic!("Function missing body at {}", path);
+        core::panic!("Function missing body at {}", file.name());
     });
-    if !validate_block(&body_pair, path) {
-        return Node::Invalid;
+    if !validate_block(&body_pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
     }
-    let body = Box::new(block_from_pairs(body_pair.into_inner(), path));
+    let body = Box::new(block_from_pair(body_pair, file));
     trace!("Function definition detected");
-    Node::Fn { name, params, body }
+    Node::new(
+        NodeKind::Fn {
+            name,
+            type_params,
+            attributes,
+            returns,
+            params,
+            body,
+        },
+        loc,
+    )
 }
 
-fn parse_if(pair: Pair<Rule>, path: &'static str) -> Node {
-    if !validate_if(&pair, path) {
-        return Node::Invalid;
+fn parse_if(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_if(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
     }
     let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
-    let predicate = Box::new(Node::from_pair(pairs[1].clone(), path));
-    let yes = Box::new(block_from_pairs(pairs[2].clone().into_inner(), path));
-    let no = pairs.get(3).map(|pair| {
-        Box::new(Node::Block {
-            expressions: pair
-                .clone()
-                .into_inner()
-                .map(|pair| Node::from_pair(pair, path))
-                .collect(),
-        })
-    });
+    let predicate = Box::new(Node::from_pair(pairs[1].clone(), file));
+    let yes = Box::new(block_from_pair(pairs[2].clone(), file));
+    let no = pairs
+        .get(3)
+        .map(|pair| Box::new(block_from_pair(pair.clone(), file)));
     trace!("If statement detected\n{}", pair.as_str());
-    Node::If { predicate, yes, no }
+    Node::new(NodeKind::If { predicate, yes, no }, loc)
 }
 
-fn parse_let(pair: Pair<Rule>, path: &'static str) -> Node {
-    if !validate_let(&pair, path) {
-        print_error("Invalid assignment", &SourceInfo::from_pair(&pair, path));
-        return Node::Invalid;
+fn parse_let(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_let(&pair, file) {
+        print_error("Invalid assignment", &SourceInfo::from_pair(&pair, file));
+        return Node::new(NodeKind::Invalid, loc);
     }
     let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
-    let symbol = Symbol::from_pair(&pairs[1]);
-    let value = Box::new(block_from_pairs(pairs[2].clone().into_inner(), path));
+    // (let mut name value) shifts the name and value along by one
+    let mutable = pairs.len() == 4;
+    let offset = mutable as usize;
+    let symbol = Symbol::from_pair(&pairs[1 + offset]);
+    let value = Box::new(Node::from_pair(pairs[2 + offset].clone(), file));
     trace!("Let statement detected\n{}", pair.as_str());
-    Node::Let { symbol, value }
+    Node::new(
+        NodeKind::Let {
+            symbol,
+            mutable,
+            value,
+        },
+        loc,
+    )
+}
+
+fn parse_set(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_set(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
+    }
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    let place = Box::new(Node::from_pair(pairs[1].clone(), file));
+    let value = Box::new(Node::from_pair(pairs[2].clone(), file));
+    trace!("Set statement detected\n{}", pair.as_str());
+    Node::new(NodeKind::Set { place, value }, loc)
+}
+
+fn parse_struct(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_struct(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
+    }
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    let name = Symbol::from_pair(&pairs[1]);
+    let fields = pairs[2]
+        .clone()
+        .into_inner()
+        .map(|pair| Symbol::from_pair(&pair))
+        .collect();
+    trace!("Struct definition detected\n{}", pair.as_str());
+    Node::new(NodeKind::Struct { name, fields }, loc)
+}
+
+fn parse_enum(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_enum(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
+    }
+    let mut pairs = pair.clone().into_inner().skip(1);
+    let name = Symbol::from_pair(&pairs.next().unwrap());
+    let variants = pairs
+        .map(|variant| {
+            let variant_loc = Location::from_pair(&variant, file);
+            match variant.as_rule() {
+                // (Circle r:f64) carries fields, a bare Red does not
+                Rule::list => {
+                    let mut inner = variant.into_inner();
+                    Variant {
+                        name: inner.next().unwrap().as_str().to_string(),
+                        fields: inner.map(|field| Symbol::from_pair(&field)).collect(),
+                        loc: variant_loc,
+                    }
+                }
+                _ => Variant {
+                    name: variant.as_str().to_string(),
+                    fields: vec![],
+                    loc: variant_loc,
+                },
+            }
+        })
+        .collect();
+    trace!("Enum definition detected\n{}", pair.as_str());
+    Node::new(NodeKind::Enum { name, variants }, loc)
+}
+
+fn parse_field(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_field(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
+    }
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    let target = Box::new(Node::from_pair(pairs[1].clone(), file));
+    let field = pairs[2].as_str().to_string();
+    Node::new(NodeKind::Field { target, field }, loc)
 }
 
-fn parse_for(pair: Pair<Rule>, path: &'static str) -> Node {
-    if !validate_for(&pair, path) {
-        print_error("Invalid for loop", &SourceInfo::from_pair(&pair, path));
-        return Node::Invalid;
+fn parse_index(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_index(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
+    }
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    let target = Box::new(Node::from_pair(pairs[1].clone(), file));
+    let index = Box::new(Node::from_pair(pairs[2].clone(), file));
+    Node::new(NodeKind::Index { target, index }, loc)
+}
+
+fn parse_array(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_array(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
+    }
+    let elements = pair
+        .clone()
+        .into_inner()
+        .map(|p| Node::from_pair(p, file))
+        .collect();
+    trace!("Array literal detected\n{}", pair.as_str());
+    Node::new(NodeKind::Array { elements }, loc)
+}
+
+fn parse_for(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_for(&pair, file) {
+        print_error("Invalid for loop", &SourceInfo::from_pair(&pair, file));
+        return Node::new(NodeKind::Invalid, loc);
     }
     let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
     let dummy = Symbol::from_pair(&pairs[1]);
-    let iterator = Box::new(block_from_pairs(pairs[2].clone().into_inner(), path));
-    let body = Box::new(block_from_pairs(pairs[3].clone().into_inner(), path));
+    let iterator = Box::new(Node::from_pair(pairs[2].clone(), file));
+    let body = Box::new(block_from_pair(pairs[3].clone(), file));
     trace!("For loop detected\n{}", pair.as_str());
-    Node::For {
-        dummy,
-        iterator,
-        body,
-    }
+    Node::new(
+        NodeKind::For {
+            dummy,
+            iterator,
+            body,
+        },
+        loc,
+    )
 }
 
-fn parse_given(pair: Pair<Rule>, path: &'static str) -> Node {
-    if !validate_given(&pair, path) {
-        return Node::Invalid;
+fn parse_given(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_given(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
     }
     let mut inner = pair.into_inner();
     inner.next();
     let predicate_pair = inner.next().unwrap();
-    let predicate = Box::new(Node::from_pair(predicate_pair, path));
+    let predicate = Box::new(Node::from_pair(predicate_pair, file));
     let cases_nodes: Vec<Node> = inner
         .map(|case_pair| {
+            let case_loc = Location::from_pair(&case_pair, file);
             let mut case_inner = case_pair.into_inner();
-            let pattern = Node::from_pair(case_inner.next().unwrap(), path);
-            let body = Node::from_pair(case_inner.next().unwrap(), path);
-            Node::Block {
-                expressions: vec![pattern, body],
-            }
+            let pattern = Node::from_pair(case_inner.next().unwrap(), file);
+            let body = Node::from_pair(case_inner.next().unwrap(), file);
+            Node::new(
+                NodeKind::Block {
+                    expressions: vec![pattern, body],
+                },
+                case_loc,
+            )
         })
         .collect();
-    Node::Given {
-        predicate,
-        cases: Box::new(Node::Block {
-            expressions: cases_nodes,
-        }),
-    }
+    Node::new(
+        NodeKind::Given {
+            predicate,
+            cases: Box::new(Node::new(
+                NodeKind::Block {
+                    expressions: cases_nodes,
+                },
+                loc,
+            )),
+        },
+        loc,
+    )
 }
 
-fn parse_ret(pair: Pair<Rule>, path: &'static str) -> Node {
-    if !validate_ret(&pair, path) {
-        return Node::Invalid;
+fn parse_ret(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_ret(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
     }
     trace!("Return keyword detected\n{}", pair.as_str());
-    Node::Return {
-        value: Box::new(block_from_pairs(pair.into_inner().skip(1), path)),
+    let value_pair = pair.into_inner().nth(1).unwrap();
+    Node::new(
+        NodeKind::Return {
+            value: Box::new(Node::from_pair(value_pair, file)),
+        },
+        loc,
+    )
+}
+
+fn parse_import(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_import(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
+    }
+    let target = pair.into_inner().nth(1).unwrap();
+    // (import math) is math.crisp next to the importing file, while a path
+    // names its module after the file
+    let (module, path) = match target.as_rule() {
+        Rule::string => {
+            let path = target.as_str().trim_matches('"').to_string();
+            let module = std::path::Path::new(&path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.clone());
+            (module, path)
+        }
+        _ => (
+            target.as_str().to_string(),
+            format!("{}.crisp", target.as_str()),
+        ),
+    };
+    trace!("Import detected: {module} from {path}");
+    Node::new(NodeKind::Import { module, path }, loc)
+}
+
+fn parse_extern(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_extern(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
+    }
+    // skip over extern and the "C"
+    let functions = pair
+        .clone()
+        .into_inner()
+        .skip(2)
+        .map(|declaration| {
+            let declaration_loc = Location::from_pair(&declaration, file);
+            let mut inner = declaration.into_inner();
+            let returns = Symbol::from_pair(&inner.next().unwrap())
+                .annotation()
+                .unwrap_or_default()
+                .to_string();
+            let name = inner.next().unwrap().as_str().to_string();
+            let params = inner
+                .next()
+                .unwrap()
+                .into_inner()
+                .map(|param| Symbol::from_pair(&param))
+                .collect();
+            ExternFn {
+                name,
+                returns,
+                params,
+                loc: declaration_loc,
+            }
+        })
+        .collect();
+    trace!("Extern block detected\n{}", pair.as_str());
+    Node::new(NodeKind::Extern { functions }, loc)
+}
+
+fn parse_include(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    if !validate_include(&pair, file) {
+        return Node::new(NodeKind::Invalid, loc);
     }
+    let header = pair.into_inner().nth(1).unwrap().as_str().trim_matches('"');
+    trace!("Include detected: {header}");
+    Node::new(
+        NodeKind::Include {
+            header: header.to_string(),
+        },
+        loc,
+    )
 }
 
-fn parse_call(pair: Pair<Rule>, path: &'static str) -> Node {
+fn parse_call(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
     let mut pairs = pair.clone().into_inner();
     let name_pair = pairs.next().unwrap();
     let name = Symbol::from_pair(&name_pair);
-    let args = pairs.map(|p| Node::from_pair(p, path)).collect();
+    let args = pairs.map(|p| Node::from_pair(p, file)).collect();
     trace!("Function call detected\n{}", pair.as_str());
-    Node::Call { name, args }
+    Node::new(NodeKind::Call { name, args }, loc)
 }
 
-fn block_from_pairs<'a>(pairs: impl Iterator<Item = Pair<'a, Rule>>, path: &'static str) -> Node {
-    Node::Block {
-        expressions: pairs.map(|p| Node::from_pair(p, path)).collect(),
+/// Turns `p.x.y` into nested field accesses on `p`, each covering its own
+/// prefix of the symbol. Anything else is a plain identifier.
+fn identifier_from_pair(pair: &Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(pair, file);
+    let symbol = Symbol::from_pair(pair);
+    let segments: Vec<&str> = match &symbol {
+        Symbol::Untyped { name } => name.split('.').collect(),
+        Symbol::Typed { .. } => vec![],
+    };
+    if segments.len() < 2 || segments.iter().any(|segment| segment.is_empty()) {
+        return Node::new(NodeKind::Identifier { symbol }, loc);
     }
+    let prefix_loc = |len: usize| Location {
+        end: loc.start + len,
+        ..loc
+    };
+    let mut len = segments[0].len();
+    let mut node = Node::new(
+        NodeKind::Identifier {
+            symbol: Symbol::Untyped {
+                name: segments[0].to_string(),
+            },
+        },
+        prefix_loc(len),
+    );
+    for segment in &segments[1..] {
+        len += 1 + segment.len();
+        node = Node::new(
+            NodeKind::Field {
+                target: Box::new(node),
+                field: segment.to_string(),
+            },
+            prefix_loc(len),
+        );
+    }
+    node
+}
+
+/// Builds a block out of the elements of a list, keeping the list's location.
+fn block_from_pair(pair: Pair<Rule>, file: &SourceFile) -> Node {
+    let loc = Location::from_pair(&pair, file);
+    Node::new(
+        NodeKind::Block {
+            expressions: pair
+                .into_inner()
+                .map(|p| Node::from_pair(p, file))
+                .collect(),
+        },
+        loc,
+    )
 }
@@ -1,22 +1,26 @@
 use std::collections::HashSet;
 
-use colored::Colorize;
-use log::error;
 use pest::iterators::Pair;
 
-use crate::parsing::{
-    Rule,
-    ast::nodes::{SourceInfo, Symbol},
+use crate::{
+    parsing::{
+        Rule,
+        ast::{
+            nodes::{Attribute, SourceInfo, Symbol},
+            print_error,
+        },
+    },
+    sources::SourceFile,
 };
 
-pub fn validate_fn(pair: &Pair<Rule>, path: &'static str) -> bool {
+pub fn validate_fn(pair: &Pair<Rule>, file: &SourceFile) -> bool {
     let mut inner = pair.clone().into_inner();
     let fn_op = inner.next().unwrap();
     // function definition has to start with fn:type
     if !matches!(fn_op.as_rule(), Rule::symbol) {
         print_ast_error(
             "Function definition must start with a typed fn symbol",
-            &SourceInfo::from_pair(&fn_op, path),
+            &SourceInfo::from_pair(&fn_op, file),
         );
         return false;
     }
@@ -26,13 +30,13 @@ pub fn validate_fn(pair: &Pair<Rule>, path: &'static str) -> bool {
         None => {
             print_ast_error(
                 "Function definition must be of the shape (fn:type name (param:type...) (body))",
-                &SourceInfo::from_pair(pair, path),
+                &SourceInfo::from_pair(pair, file),
             );
             return false;
         }
     };
     // second part might be a name or a parameter list
-    let params;
+    let mut params;
     match second.as_rule() {
         Rule::symbol => {
             params = match inner.next() {
@@ -40,22 +44,54 @@ pub fn validate_fn(pair: &Pair<Rule>, path: &'static str) -> bool {
                 None => {
                     print_ast_error(
                         "Function definition must be of the shape (fn:type name (param:type...) (body))",
-                        &SourceInfo::from_pair(pair, path),
+                        &SourceInfo::from_pair(pair, file),
                     );
                     return false;
                 }
             };
+            // attributes such as (export) sit between the name and the
+            // rest, which still has to leave room for parameters and a body
+            let mut attributes = HashSet::new();
+            while is_attribute(&params) && inner.len() >= 2 {
+                if !validate_attribute(&params, &mut attributes, file) {
+                    return false;
+                }
+                params = inner.next().unwrap();
+            }
+            if attributes.contains("export") && !is_c_ident(second.as_str()) {
+                print_ast_error(
+                    "Exported function name must be a valid C identifier",
+                    &SourceInfo::from_pair(&second, file),
+                );
+                return false;
+            }
+            // a generic function lists its type parameters after the name
+            if params.as_rule() == Rule::array {
+                if !validate_type_params(&params, file) {
+                    return false;
+                }
+                params = match inner.next() {
+                    Some(pair) => pair,
+                    None => {
+                        print_ast_error(
+                            "Function definition must be of the shape (fn:type name [T...] (param:type...) (body))",
+                            &SourceInfo::from_pair(pair, file),
+                        );
+                        return false;
+                    }
+                };
+            }
             match inner.next() {
                 Some(pair) => {
                     if !matches!(pair.as_rule(), Rule::list) {
-                        print_ast_error("Body must be a list", &SourceInfo::from_pair(&pair, path));
+                        print_ast_error("Body must be a list", &SourceInfo::from_pair(&pair, file));
                         return false;
                     }
                 }
                 None => {
                     print_ast_error(
                         "Function definition must be of the shape (fn:type name (param:type...) (body))",
-                        &SourceInfo::from_pair(pair, path),
+                        &SourceInfo::from_pair(pair, file),
                     );
                     return false;
                 }
@@ -66,14 +102,14 @@ pub fn validate_fn(pair: &Pair<Rule>, path: &'static str) -> bool {
             match inner.next() {
                 Some(pair) => {
                     if !matches!(pair.as_rule(), Rule::list) {
-                        print_ast_error("Body must be a list", &SourceInfo::from_pair(&pair, path));
+                        print_ast_error("Body must be a list", &SourceInfo::from_pair(&pair, file));
                         return false;
                     }
                 }
                 None => {
                     print_ast_error(
                         "Function definition must include a body which is a list",
-                        &SourceInfo::from_pair(pair, path),
+                        &SourceInfo::from_pair(pair, file),
                     );
                     return false;
                 }
@@ -82,7 +118,7 @@ pub fn validate_fn(pair: &Pair<Rule>, path: &'static str) -> bool {
         _ => {
             print_ast_error(
                 "Unexpected function structure",
-                &SourceInfo::from_pair(&second, path),
+                &SourceInfo::from_pair(&second, file),
             );
             return false;
         }
@@ -90,7 +126,7 @@ pub fn validate_fn(pair: &Pair<Rule>, path: &'static str) -> bool {
     if inner.next().is_some() {
         print_ast_error(
             "Too many components in function definition",
-            &SourceInfo::from_pair(pair, path),
+            &SourceInfo::from_pair(pair, file),
         );
         return false;
     }
@@ -98,7 +134,7 @@ pub fn validate_fn(pair: &Pair<Rule>, path: &'static str) -> bool {
     if !matches!(params.as_rule(), Rule::list) {
         print_ast_error(
             "Parameters must be a list",
-            &SourceInfo::from_pair(&params, path),
+            &SourceInfo::from_pair(&params, file),
         );
         return false;
     }
@@ -108,7 +144,7 @@ pub fn validate_fn(pair: &Pair<Rule>, path: &'static str) -> bool {
         if !matches!(pair.as_rule(), Rule::symbol) {
             print_ast_error(
                 "Parameter must be a symbol",
-                &SourceInfo::from_pair(&pair, path),
+                &SourceInfo::from_pair(&pair, file),
             );
             is_params_valid &= false;
             continue;
@@ -117,7 +153,7 @@ pub fn validate_fn(pair: &Pair<Rule>, path: &'static str) -> bool {
         if let Symbol::Untyped { name: _ } = param_symbol {
             print_ast_error(
                 "Parameter must be typed",
-                &SourceInfo::from_pair(&pair, path),
+                &SourceInfo::from_pair(&pair, file),
             );
             is_params_valid &= false;
         }
@@ -128,20 +164,82 @@ pub fn validate_fn(pair: &Pair<Rule>, path: &'static str) -> bool {
     true
 }
 
-pub fn validate_if(pair: &Pair<Rule>, path: &'static str) -> bool {
+/// Whether a list looks like an attribute: a single untyped symbol.
+pub fn is_attribute(pair: &Pair<Rule>) -> bool {
+    let mut inner = pair.clone().into_inner();
+    pair.as_rule() == Rule::list
+        && inner.len() == 1
+        && inner.next().is_some_and(|symbol| {
+            symbol.as_rule() == Rule::symbol
+                && matches!(Symbol::from_pair(&symbol), Symbol::Untyped { .. })
+        })
+}
+
+fn validate_attribute<'a>(
+    pair: &Pair<'a, Rule>,
+    seen: &mut HashSet<&'a str>,
+    file: &SourceFile,
+) -> bool {
+    let name = pair.clone().into_inner().next().unwrap().as_str();
+    if Attribute::from_name(name).is_none() {
+        print_ast_error(
+            &format!("Unknown attribute: {}", name),
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    if !seen.insert(name) {
+        print_ast_error(
+            &format!("Duplicate attribute: {}", name),
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    true
+}
+
+/// Whether a name can be used as it is in C.
+fn is_c_ident(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn validate_type_params(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let mut names = HashSet::new();
+    for param in pair.clone().into_inner() {
+        if param.as_rule() != Rule::symbol
+            || matches!(Symbol::from_pair(&param), Symbol::Typed { .. })
+        {
+            print_ast_error(
+                "Type parameter must be an untyped symbol",
+                &SourceInfo::from_pair(&param, file),
+            );
+            return false;
+        }
+        if !names.insert(param.as_str()) {
+            print_ast_error(
+                &format!("Duplicate type parameter: {}", param.as_str()),
+                &SourceInfo::from_pair(&param, file),
+            );
+            return false;
+        }
+    }
+    true
+}
+
+pub fn validate_if(pair: &Pair<Rule>, file: &SourceFile) -> bool {
     let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
     // if must be a list in the first place
     if !matches!(pair.as_rule(), Rule::list) {
         print_ast_error(
             "If statement must be a list",
-            &SourceInfo::from_pair(&pair, path),
+            &SourceInfo::from_pair(&pair, file),
         );
         return false;
     }
     // if must be 3 or 4 elements
     if pairs.len() != 3 && pairs.len() != 4 {
-        println!("{}", pairs.len());
-        print_ast_error("Invalid if statement", &SourceInfo::from_pair(pair, path));
+        print_ast_error("Invalid if statement", &SourceInfo::from_pair(pair, file));
         return false;
     }
     // keyword is a symbol and strictly "if"
@@ -149,7 +247,7 @@ pub fn validate_if(pair: &Pair<Rule>, path: &'static str) -> bool {
     if head.as_rule() != Rule::symbol || head.as_str() != "if" {
         print_ast_error(
             "If statement must start with the \"if\" keyword",
-            &SourceInfo::from_pair(head, path),
+            &SourceInfo::from_pair(head, file),
         );
         return false;
     }
@@ -160,7 +258,7 @@ pub fn validate_if(pair: &Pair<Rule>, path: &'static str) -> bool {
     ) {
         print_ast_error(
             "Predicate must be a list",
-            &SourceInfo::from_pair(&pairs[1], path),
+            &SourceInfo::from_pair(&pairs[1], file),
         );
         return false;
     }
@@ -168,7 +266,7 @@ pub fn validate_if(pair: &Pair<Rule>, path: &'static str) -> bool {
     if !matches!(pairs[2].as_rule(), Rule::list) {
         print_ast_error(
             "Then block must be a list",
-            &SourceInfo::from_pair(&pairs[2], path),
+            &SourceInfo::from_pair(&pairs[2], file),
         );
         return false;
     }
@@ -176,7 +274,7 @@ pub fn validate_if(pair: &Pair<Rule>, path: &'static str) -> bool {
         if !matches!(pairs[3].as_rule(), Rule::list) {
             print_ast_error(
                 "Invalid else block",
-                &SourceInfo::from_pair(&pairs[3], path),
+                &SourceInfo::from_pair(&pairs[3], file),
             );
             return false;
         }
@@ -184,99 +282,483 @@ pub fn validate_if(pair: &Pair<Rule>, path: &'static str) -> bool {
     true
 }
 
-pub fn validate_for(pair: &Pair<Rule>, path: &'static str) -> bool {
+pub fn validate_for(pair: &Pair<Rule>, file: &SourceFile) -> bool {
     let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
     if pairs.len() != 4 {
-        print_ast_error("Invalid for loop", &SourceInfo::from_pair(pair, path));
+        print_ast_error("Invalid for loop", &SourceInfo::from_pair(pair, file));
         return false;
     }
     let for_op = &pairs[0];
     if !matches!(for_op.as_rule(), Rule::symbol) {
         print_ast_error(
             "For loop must start with a for symbol",
-            &SourceInfo::from_pair(&for_op, path),
+            &SourceInfo::from_pair(&for_op, file),
         );
         return false;
     }
     if for_op.as_str() != "for" {
         print_ast_error(
             "For loop must start with a for symbol",
-            &SourceInfo::from_pair(&for_op, path),
+            &SourceInfo::from_pair(&for_op, file),
         );
         return false;
     }
     if !matches!(pairs[1].as_rule(), Rule::symbol) {
         print_ast_error(
             "Dummy index is not a symbol",
-            &SourceInfo::from_pair(&pairs[1], path),
+            &SourceInfo::from_pair(&pairs[1], file),
         );
         return false;
     }
     if !matches!(
         pairs[2].as_rule(),
-        Rule::list | Rule::symbol | Rule::boolean
+        Rule::list | Rule::symbol | Rule::boolean | Rule::array
     ) {
         print_ast_error(
             "Iterator is invalid",
-            &SourceInfo::from_pair(&pairs[2], path),
+            &SourceInfo::from_pair(&pairs[2], file),
         );
         return false;
     }
     if !matches!(pairs[3].as_rule(), Rule::list) {
         print_ast_error(
             "Body is not a block",
-            &SourceInfo::from_pair(&pairs[3], path),
+            &SourceInfo::from_pair(&pairs[3], file),
         );
         return false;
     }
     true
 }
 
-pub fn validate_let(pair: &Pair<Rule>, path: &'static str) -> bool {
-    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+pub fn validate_let(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let mut pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    // let mut shares the same shape once the modifier is dropped
+    if pairs.len() == 4 && pairs[1].as_rule() == Rule::symbol && pairs[1].as_str() == "mut" {
+        pairs.remove(1);
+    }
     // let must be 3 elements
     if pairs.len() != 3 {
-        print_ast_error("Invalid let statement", &SourceInfo::from_pair(pair, path));
+        print_ast_error("Invalid let statement", &SourceInfo::from_pair(pair, file));
         return false;
     }
     // variable name must be symbol or typed symbol
     if !matches!(pairs[1].as_rule(), Rule::symbol) {
         print_ast_error(
             "Variable name is not a symbol or type annotated symbol",
-            &SourceInfo::from_pair(&pairs[1], path),
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    // mut is the modifier, never a variable name
+    if Symbol::from_pair(&pairs[1]).name() == "mut" {
+        print_ast_error(
+            "`mut` is reserved and cannot name a variable, use (let mut name value)",
+            &SourceInfo::from_pair(&pairs[1], file),
         );
         return false;
     }
     // value must be literal, symbol or list
     if !matches!(
         pairs[2].as_rule(),
-        Rule::number | Rule::string | Rule::boolean | Rule::symbol | Rule::list
+        Rule::number | Rule::string | Rule::boolean | Rule::symbol | Rule::list | Rule::array
     ) {
         print_ast_error(
             "Value is not a literal, untyped symbol or list",
-            &SourceInfo::from_pair(&pairs[2], path),
+            &SourceInfo::from_pair(&pairs[2], file),
+        );
+        return false;
+    }
+    true
+}
+
+pub fn validate_set(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    // set! must be 3 elements
+    if pairs.len() != 3 {
+        print_ast_error(
+            "Assignment must be of the shape (set! name value)",
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    // target must be a plain symbol, an element or a field, the type comes
+    // from the original let
+    let is_place = pairs[1].as_rule() == Rule::list
+        && pairs[1]
+            .clone()
+            .into_inner()
+            .next()
+            .is_some_and(|head| matches!(head.as_str(), "at" | "."));
+    if !matches!(pairs[1].as_rule(), Rule::symbol) && !is_place {
+        print_ast_error(
+            "Assignment target is not a symbol, an (at ...) element or a (. ...) field",
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    if pairs[1].as_rule() == Rule::symbol
+        && let Symbol::Typed { .. } = Symbol::from_pair(&pairs[1])
+    {
+        print_ast_error(
+            "Assignment target cannot carry a type annotation",
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    // value must be literal, symbol or list
+    if !matches!(
+        pairs[2].as_rule(),
+        Rule::number | Rule::string | Rule::boolean | Rule::symbol | Rule::list | Rule::array
+    ) {
+        print_ast_error(
+            "Value is not a literal, untyped symbol or list",
+            &SourceInfo::from_pair(&pairs[2], file),
+        );
+        return false;
+    }
+    true
+}
+
+pub fn validate_struct(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    // struct must be 3 elements
+    if pairs.len() != 3 {
+        print_ast_error(
+            "Struct definition must be of the shape (struct Name (field:type...))",
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    // name must be a plain symbol
+    if !matches!(pairs[1].as_rule(), Rule::symbol)
+        || matches!(Symbol::from_pair(&pairs[1]), Symbol::Typed { .. })
+    {
+        print_ast_error(
+            "Struct name must be an untyped symbol",
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    // fields must be a non-empty list of typed symbols
+    if !matches!(pairs[2].as_rule(), Rule::list) {
+        print_ast_error(
+            "Struct fields must be a list",
+            &SourceInfo::from_pair(&pairs[2], file),
+        );
+        return false;
+    }
+    if pairs[2].clone().into_inner().next().is_none() {
+        print_ast_error(
+            "Struct must have at least one field",
+            &SourceInfo::from_pair(&pairs[2], file),
+        );
+        return false;
+    }
+    for field in pairs[2].clone().into_inner() {
+        if !matches!(field.as_rule(), Rule::symbol)
+            || matches!(Symbol::from_pair(&field), Symbol::Untyped { .. })
+        {
+            print_ast_error(
+                "Struct field must be a typed symbol",
+                &SourceInfo::from_pair(&field, file),
+            );
+            return false;
+        }
+    }
+    validate_params(&pairs[2], file)
+}
+
+pub fn validate_enum(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    // enum needs a name and at least one variant
+    if pairs.len() < 3 {
+        print_ast_error(
+            "Enum definition must be of the shape (enum Name (Variant field:type...)...)",
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    // name must be a plain symbol
+    if !matches!(pairs[1].as_rule(), Rule::symbol)
+        || matches!(Symbol::from_pair(&pairs[1]), Symbol::Typed { .. })
+    {
+        print_ast_error(
+            "Enum name must be an untyped symbol",
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    let mut names = HashSet::new();
+    for variant in &pairs[2..] {
+        // a variant is either a bare name or (Name field:type...)
+        let name = match variant.as_rule() {
+            Rule::symbol => variant.clone(),
+            Rule::list => match variant.clone().into_inner().next() {
+                Some(name) => name,
+                None => {
+                    print_ast_error(
+                        "Enum variant is missing a name",
+                        &SourceInfo::from_pair(variant, file),
+                    );
+                    return false;
+                }
+            },
+            _ => {
+                print_ast_error(
+                    "Enum variant must be a symbol or a list",
+                    &SourceInfo::from_pair(variant, file),
+                );
+                return false;
+            }
+        };
+        if !matches!(name.as_rule(), Rule::symbol)
+            || matches!(Symbol::from_pair(&name), Symbol::Typed { .. })
+        {
+            print_ast_error(
+                "Enum variant name must be an untyped symbol",
+                &SourceInfo::from_pair(&name, file),
+            );
+            return false;
+        }
+        if !names.insert(name.as_str()) {
+            print_ast_error(
+                &format!("Duplicate enum variant: {}", name.as_str()),
+                &SourceInfo::from_pair(&name, file),
+            );
+            return false;
+        }
+        if variant.as_rule() != Rule::list {
+            continue;
+        }
+        let mut fields = HashSet::new();
+        for field in variant.clone().into_inner().skip(1) {
+            if !matches!(field.as_rule(), Rule::symbol)
+                || matches!(Symbol::from_pair(&field), Symbol::Untyped { .. })
+            {
+                print_ast_error(
+                    "Enum variant field must be a typed symbol",
+                    &SourceInfo::from_pair(&field, file),
+                );
+                return false;
+            }
+            if !fields.insert(Symbol::from_pair(&field).name().to_string()) {
+                print_ast_error(
+                    &format!("Duplicate variant field: {}", field.as_str()),
+                    &SourceInfo::from_pair(&field, file),
+                );
+                return false;
+            }
+        }
+    }
+    true
+}
+
+pub fn validate_field(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    // field access must be 3 elements
+    if pairs.len() != 3 {
+        print_ast_error(
+            "Field access must be of the shape (. value field)",
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    // target can be anything that evaluates to a struct
+    if !matches!(pairs[1].as_rule(), Rule::symbol | Rule::list) {
+        print_ast_error(
+            "Field access target must be a symbol or list",
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    // field must be a plain symbol
+    if !matches!(pairs[2].as_rule(), Rule::symbol)
+        || matches!(Symbol::from_pair(&pairs[2]), Symbol::Typed { .. })
+    {
+        print_ast_error(
+            "Field name must be an untyped symbol",
+            &SourceInfo::from_pair(&pairs[2], file),
         );
         return false;
     }
     true
 }
 
-pub fn validate_given(pair: &Pair<Rule>, path: &'static str) -> bool {
+pub fn validate_index(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    // indexing must be 3 elements
+    if pairs.len() != 3 {
+        print_ast_error(
+            "Indexing must be of the shape (at array index)",
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    // target can be anything that evaluates to an array
+    if !matches!(pairs[1].as_rule(), Rule::symbol | Rule::list | Rule::array) {
+        print_ast_error(
+            "Indexing target must be a symbol, list or array",
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    if !matches!(pairs[2].as_rule(), Rule::symbol | Rule::list | Rule::number) {
+        print_ast_error(
+            "Index must be a number, symbol or list",
+            &SourceInfo::from_pair(&pairs[2], file),
+        );
+        return false;
+    }
+    true
+}
+
+pub fn validate_array(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    for element in pair.clone().into_inner() {
+        match element.as_rule() {
+            Rule::list if !validate_list(&element, file) => return false,
+            Rule::array if !validate_array(&element, file) => return false,
+            Rule::symbol if matches!(Symbol::from_pair(&element), Symbol::Typed { .. }) => {
+                print_ast_error(
+                    "Array elements cannot carry a type annotation",
+                    &SourceInfo::from_pair(&element, file),
+                );
+                return false;
+            }
+            _ => {}
+        }
+    }
+    true
+}
+
+pub fn validate_import(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    // import must be 2 elements
+    if pairs.len() != 2 {
+        print_ast_error(
+            "Import must be of the shape (import module) or (import \"path.crisp\")",
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    // module is a plain name or a path
+    let valid = match pairs[1].as_rule() {
+        Rule::symbol => matches!(Symbol::from_pair(&pairs[1]), Symbol::Untyped { .. }),
+        Rule::string => pairs[1].as_str().len() > 2,
+        _ => false,
+    };
+    if !valid {
+        print_ast_error(
+            "Imported module must be an untyped symbol or a non-empty string",
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    true
+}
+
+pub fn validate_extern(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    if pairs.len() < 3 {
+        print_ast_error(
+            "Extern block must be of the shape (extern \"C\" (fn:type name (param:type...))...)",
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    if pairs[1].as_str() != "\"C\"" {
+        print_ast_error(
+            "Only extern \"C\" is supported",
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    pairs[2..]
+        .iter()
+        .all(|declaration| validate_extern_fn(declaration, file))
+}
+
+/// Checks a single `(fn:type name (param:type...))` of an extern block,
+/// whose name has to be usable as it is in C.
+fn validate_extern_fn(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    let shaped = pair.as_rule() == Rule::list
+        && pairs.len() == 3
+        && pairs[0].as_rule() == Rule::symbol
+        && matches!(Symbol::from_pair(&pairs[0]), Symbol::Typed { ref name, .. } if name == "fn")
+        && pairs[1].as_rule() == Rule::symbol
+        && pairs[2].as_rule() == Rule::list;
+    if !shaped {
+        print_ast_error(
+            "Extern function must be of the shape (fn:type name (param:type...)) without a body",
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    if !is_c_ident(pairs[1].as_str()) {
+        print_ast_error(
+            "Extern function name must be a valid C identifier",
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    if !validate_params(&pairs[2], file) {
+        return false;
+    }
+    for param in pairs[2].clone().into_inner() {
+        if let Symbol::Untyped { .. } = Symbol::from_pair(&param) {
+            print_ast_error(
+                "Parameter must be typed",
+                &SourceInfo::from_pair(&param, file),
+            );
+            return false;
+        }
+    }
+    true
+}
+
+pub fn validate_include(pair: &Pair<Rule>, file: &SourceFile) -> bool {
+    let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+    if pairs.len() != 2 || pairs[1].as_rule() != Rule::string {
+        print_ast_error(
+            "Include must be of the shape (include \"<header.h>\") or (include \"header.h\")",
+            &SourceInfo::from_pair(pair, file),
+        );
+        return false;
+    }
+    let header = pairs[1].as_str().trim_matches('"');
+    // the header ends up inside <> or "" in the generated C
+    let valid = match header.strip_prefix('<') {
+        Some(rest) => rest
+            .strip_suffix('>')
+            .is_some_and(|name| !name.is_empty() && !name.contains(['<', '>', '\n'])),
+        None => !header.is_empty() && !header.contains(['<', '>', '\n', '\\']),
+    };
+    if !valid {
+        print_ast_error(
+            "Header must be a non-empty file name, in angle brackets for a system header",
+            &SourceInfo::from_pair(&pairs[1], file),
+        );
+        return false;
+    }
+    true
+}
+
+pub fn validate_given(pair: &Pair<Rule>, file: &SourceFile) -> bool {
     let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
     if pairs.len() < 2 {
         print_ast_error(
             "Given statement is missing a predicate",
-            &SourceInfo::from_pair(pair, path),
+            &SourceInfo::from_pair(pair, file),
         );
         return false;
     }
     let predicate = &pairs[1];
     match predicate.as_rule() {
-        Rule::list | Rule::symbol | Rule::boolean | Rule::number => {}
+        Rule::list | Rule::symbol | Rule::boolean | Rule::number | Rule::array => {}
         _ => {
             print_ast_error(
                 "Predicate must be an expression (atom or list)",
-                &SourceInfo::from_pair(predicate, path),
+                &SourceInfo::from_pair(predicate, file),
             );
             return false;
         }
@@ -285,14 +767,14 @@ pub fn validate_given(pair: &Pair<Rule>, path: &'static str) -> bool {
         if !matches!(case.as_rule(), Rule::list) {
             print_ast_error(
                 "Each case in a given statement must be a list: (pattern (body))",
-                &SourceInfo::from_pair(case, path),
+                &SourceInfo::from_pair(case, file),
             );
             return false;
         }
         if case.clone().into_inner().count() != 2 {
             print_ast_error(
                 "Each case must have exactly a pattern and a body",
-                &SourceInfo::from_pair(case, path),
+                &SourceInfo::from_pair(case, file),
             );
             return false;
         }
@@ -300,39 +782,39 @@ pub fn validate_given(pair: &Pair<Rule>, path: &'static str) -> bool {
     true
 }
 
-pub fn validate_ret(pair: &Pair<Rule>, path: &'static str) -> bool {
+pub fn validate_ret(pair: &Pair<Rule>, file: &SourceFile) -> bool {
     let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
     // ret must be 2 elements
     if pairs.len() != 2 {
-        print_ast_error("Invalid return call", &SourceInfo::from_pair(pair, path));
+        print_ast_error("Invalid return call", &SourceInfo::from_pair(pair, file));
         return false;
     }
     // value must be symbol, literal or list
     if !matches!(
         pairs[1].as_rule(),
-        Rule::string | Rule::number | Rule::boolean | Rule::list | Rule::symbol
+        Rule::string | Rule::number | Rule::boolean | Rule::list | Rule::symbol | Rule::array
     ) {
         print_ast_error(
             "Invalid return call value",
-            &SourceInfo::from_pair(pair, path),
+            &SourceInfo::from_pair(pair, file),
         );
         return false;
     }
     true
 }
 
-pub fn validate_call(pair: &Pair<Rule>, path: &'static str) -> bool {
+pub fn validate_call(pair: &Pair<Rule>, file: &SourceFile) -> bool {
     let pairs: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
     // call must be 1 or more elements
     if pairs.len() < 1 {
-        print_ast_error("Invalid call", &SourceInfo::from_pair(pair, path));
+        print_ast_error("Invalid call", &SourceInfo::from_pair(pair, file));
         return false;
     }
     // first element should be a symbol
     if !matches!(pairs[0].as_rule(), Rule::symbol) {
         print_ast_error(
             "Invalid call identifier",
-            &SourceInfo::from_pair(&pairs[0], path),
+            &SourceInfo::from_pair(&pairs[0], file),
         );
         return false;
     }
@@ -340,31 +822,31 @@ pub fn validate_call(pair: &Pair<Rule>, path: &'static str) -> bool {
     for pair in pairs[1..].iter() {
         if !matches!(
             pair.as_rule(),
-            Rule::symbol | Rule::list | Rule::boolean | Rule::string | Rule::number
+            Rule::symbol | Rule::list | Rule::boolean | Rule::string | Rule::number | Rule::array
         ) {
-            print_ast_error("Invalid call argument", &SourceInfo::from_pair(pair, path));
+            print_ast_error("Invalid call argument", &SourceInfo::from_pair(pair, file));
             return false;
         }
     }
     true
 }
 
-pub fn validate_block(pair: &Pair<Rule>, path: &'static str) -> bool {
+pub fn validate_block(pair: &Pair<Rule>, file: &SourceFile) -> bool {
     let inner = pair.clone().into_inner();
     if inner.len() == 0 {
         print_ast_error(
             "Empty blocks are not allowed",
-            &SourceInfo::from_pair(pair, path),
+            &SourceInfo::from_pair(pair, file),
         );
         return false;
     }
     for pair in inner {
         match pair.as_rule() {
-            Rule::list | Rule::symbol | Rule::number | Rule::string => continue,
+            Rule::list | Rule::symbol | Rule::number | Rule::string | Rule::array => continue,
             _ => {
                 print_ast_error(
                     "Invalid expression inside block",
-                    &SourceInfo::from_pair(&pair, path),
+                    &SourceInfo::from_pair(&pair, file),
                 );
                 return false;
             }
@@ -373,7 +855,7 @@ pub fn validate_block(pair: &Pair<Rule>, path: &'static str) -> bool {
     true
 }
 
-pub fn validate_params(pair: &Pair<Rule>, path: &'static str) -> bool {
+pub fn validate_params(pair: &Pair<Rule>, file: &SourceFile) -> bool {
     let inner = pair.clone().into_inner();
     let mut names = HashSet::new();
     for param in inner {
@@ -382,7 +864,7 @@ pub fn validate_params(pair: &Pair<Rule>, path: &'static str) -> bool {
             _ => {
                 print_ast_error(
                     "Parameter must be a symbol",
-                    &SourceInfo::from_pair(&param, path),
+                    &SourceInfo::from_pair(&param, file),
                 );
                 return false;
             }
@@ -390,7 +872,7 @@ pub fn validate_params(pair: &Pair<Rule>, path: &'static str) -> bool {
         if !names.insert(name) {
             print_ast_error(
                 &format!("Duplicate parameter name: {}", name),
-                &SourceInfo::from_pair(&param, path),
+                &SourceInfo::from_pair(&param, file),
             );
             return false;
         }
@@ -398,13 +880,13 @@ pub fn validate_params(pair: &Pair<Rule>, path: &'static str) -> bool {
     true
 }
 
-pub fn validate_list(pair: &Pair<Rule>, path: &'static str) -> bool {
+pub fn validate_list(pair: &Pair<Rule>, file: &SourceFile) -> bool {
     let span = pair.as_span();
     let content = span.as_str().trim();
     if !content.starts_with('(') || !content.ends_with(')') {
         print_ast_error(
             "Missing surrounding parentheses",
-            &SourceInfo::from_pair(pair, path),
+            &SourceInfo::from_pair(pair, file),
         );
         return false;
     }
@@ -414,15 +896,20 @@ pub fn validate_list(pair: &Pair<Rule>, path: &'static str) -> bool {
                 let _ = Symbol::from_pair(&inner_pair);
             }
             Rule::list => {
-                if !validate_list(&inner_pair, path) {
+                if !validate_list(&inner_pair, file) {
+                    return false;
+                }
+            }
+            Rule::array => {
+                if !validate_array(&inner_pair, file) {
                     return false;
                 }
             }
-            Rule::EOI => {}
+            Rule::number | Rule::string | Rule::boolean | Rule::EOI => {}
             _ => {
                 print_ast_error(
                     "Unexpected token in list",
-                    &SourceInfo::from_pair(&inner_pair, path),
+                    &SourceInfo::from_pair(&inner_pair, file),
                 );
                 return false;
             }
@@ -433,31 +920,5 @@ pub fn validate_list(pair: &Pair<Rule>, path: &'static str) -> bool {
 }
 
 pub fn print_ast_error(msg: &str, info: &SourceInfo) {
-    let span = info.span;
-    let input = span.get_input();
-    let start = span.start();
-
-    let line_start = input[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
-    let line_end = input[start..]
-        .find('\n')
-        .map(|i| start + i)
-        .unwrap_or(input.len());
-    let line_text = &input[line_start..line_end];
-
-    let indent = " ".repeat(info.col - 1);
-    let span_len = (span.end() - span.start()).max(1);
-    let pointer = "~".repeat(span_len).red();
-
-    error!(
-        "{}\n--> {}[{}|{}]\n{:>4} |\n{:>4} | {}\n     | {}{}",
-        msg.bold(),
-        info.path.blue(),
-        info.line.to_string().red(),
-        info.col.to_string().red(),
-        "|",
-        info.line.to_string().red(),
-        line_text,
-        indent,
-        pointer
-    );
+    print_error(msg, info);
 }
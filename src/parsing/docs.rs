@@ -0,0 +1,31 @@
+use crate::{parsing::ast::nodes::Location, sources::SourceFile};
+
+/// The doc comment of the definition at `loc`: the lines starting with
+/// `;;;` right above it, without the `;;;` and the space after it, as in
+///
+/// ```text
+/// ;;; Adds two numbers.
+/// (fn:i32 add (a:i32 b:i32) ((ret (+ a b))))
+/// ```
+///
+/// The grammar drops these with every other comment, so they are read from
+/// the text. A blank line between the comment and the definition, or
+/// anything before the definition on its line, leaves it undocumented.
+pub fn doc_comment(file: &SourceFile, loc: &Location) -> Option<String> {
+    let (line_start, _) = file.line_span(loc.line);
+    if !file.source()[line_start..loc.start].trim().is_empty() {
+        return None;
+    }
+    let mut lines = vec![];
+    for line in (1..loc.line).rev() {
+        let Some(text) = file.line_text(line).trim().strip_prefix(";;;") else {
+            break;
+        };
+        lines.push(text.strip_prefix(' ').unwrap_or(text).trim_end());
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
@@ -0,0 +1,225 @@
+use std::ops::Range;
+
+use crate::{
+    diagnostics::Diagnostic,
+    parsing::{
+        ast::nodes::{Location, Node, NodeKind},
+        parse_collecting,
+        recovery::next_form,
+    },
+    sources::{FileId, SourceFile, SourceMap},
+};
+
+/// A change to the text of a file, replacing the bytes in `range` with
+/// `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// A top-level form of a [`ParsedFile`]: a list, an atom, or a closing
+/// bracket that closes nothing.
+#[derive(Debug)]
+struct Form {
+    range: Range<usize>,
+    /// Whether the form depends on the text after it, and is parsed again
+    /// whatever changes there.
+    open_ended: bool,
+    /// How many nodes of the AST it parsed to, which is one unless it is a
+    /// stray bracket.
+    nodes: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// A file kept parsed as it is edited, for editors that change it on every
+/// keystroke. Each top-level form is parsed and validated on its own, as
+/// [`parse_collecting`] would a file of just that form, so an edit only
+/// parses again the forms it touches. The others are kept as they are, and
+/// those after the edit are moved to where it left them.
+///
+/// Only parsing and validation are incremental: the language server still
+/// type checks the whole file with [`check_program`] after every edit.
+///
+/// [`check_program`]: crate::semantics::check_program
+#[derive(Debug)]
+pub struct ParsedFile {
+    sources: SourceMap,
+    file: FileId,
+    /// A block of the nodes of every form, in order.
+    ast: Node,
+    forms: Vec<Form>,
+}
+impl ParsedFile {
+    pub fn new(name: &str, text: String) -> ParsedFile {
+        let mut sources = SourceMap::default();
+        let file = sources.add(name, text);
+        let mut parsed = ParsedFile {
+            ast: Node::new(
+                NodeKind::Block {
+                    expressions: vec![],
+                },
+                Location::default(),
+            ),
+            sources,
+            file,
+            forms: vec![],
+        };
+        parsed.reparse(0, 0, 0);
+        parsed
+    }
+
+    pub fn file(&self) -> &SourceFile {
+        &self.sources[self.file]
+    }
+
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    /// The whole file, as a block of its top-level nodes.
+    pub fn ast(&self) -> &Node {
+        &self.ast
+    }
+
+    /// What parsing and validating the file found wrong with it, in the
+    /// order of the forms.
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.forms.iter().flat_map(|form| &form.diagnostics)
+    }
+
+    /// Applies an edit, whose range must lie on character boundaries of
+    /// the text, and parses again what it changed. Returns how many
+    /// top-level forms that was.
+    pub fn edit(&mut self, edit: TextEdit) -> usize {
+        let file = self.file();
+        let Range { start, end } = edit.range;
+        let mut text = String::with_capacity(file.source().len() + edit.text.len());
+        text.push_str(&file.source()[..start]);
+        text.push_str(&edit.text);
+        text.push_str(&file.source()[end..]);
+        let name = file.name().to_string();
+        self.sources = SourceMap::default();
+        self.file = self.sources.add(name, text);
+
+        // a form that ends where the edit starts may go on into it, and one
+        // that starts where it ends may be cut short by it
+        let first = self
+            .forms
+            .iter()
+            .position(|form| form.range.end >= start || form.open_ended)
+            .unwrap_or(self.forms.len());
+        let after = self
+            .forms
+            .iter()
+            .position(|form| form.range.start > end)
+            .unwrap_or(self.forms.len());
+        let delta = edit.text.len() as isize - (end - start) as isize;
+        self.reparse(first, after, delta)
+    }
+
+    /// Parses the text from the end of the form before `first` again, up to
+    /// where a form from `after` on starts, `delta` bytes from where it used
+    /// to. Those forms are unchanged, and the rest of the file with them.
+    /// Returns how many forms were parsed.
+    fn reparse(&mut self, first: usize, after: usize, delta: isize) -> usize {
+        let file = &self.sources[self.file];
+        let moved = |offset: usize| offset.wrapping_add_signed(delta);
+        let mut at = match first {
+            0 => 0,
+            _ => self.forms[first - 1].range.end,
+        };
+        // the first form from `after` on that is still where it was, if
+        // any, and how many of them the edit swallowed
+        let mut kept = after;
+        let mut reused = self.forms.len();
+        let mut forms = vec![];
+        let mut nodes = vec![];
+        while let Some((range, open_ended)) = next_form(file, at) {
+            while kept < self.forms.len() && moved(self.forms[kept].range.start) < range.start {
+                kept += 1;
+            }
+            if kept < self.forms.len() && moved(self.forms[kept].range.start) == range.start {
+                reused = kept;
+                break;
+            }
+            let (parsed, diagnostics) = parse_form(file, range.clone());
+            forms.push(Form {
+                range: range.clone(),
+                open_ended,
+                nodes: parsed.len(),
+                diagnostics,
+            });
+            nodes.extend(parsed);
+            at = range.end;
+        }
+
+        let NodeKind::Block { expressions } = &mut self.ast.kind else {
+            unreachable!("a file is a block of its forms")
+        };
+        let node_index = |forms: &[Form], form: usize| -> usize {
+            forms[..form].iter().map(|form| form.nodes).sum()
+        };
+        let replaced = node_index(&self.forms, first)..node_index(&self.forms, reused);
+        let later_nodes = replaced.start + nodes.len();
+        expressions.splice(replaced, nodes);
+        let parsed = forms.len();
+        self.forms.splice(first..reused, forms);
+
+        // the forms after the edit keep their text, but not its offsets or
+        // lines
+        for node in &mut expressions[later_nodes..] {
+            node.visit_locations_mut(&mut |loc| relocate(loc, file, delta));
+        }
+        for form in &mut self.forms[first + parsed..] {
+            form.range = moved(form.range.start)..moved(form.range.end);
+            for diagnostic in &mut form.diagnostics {
+                relocate(&mut diagnostic.loc, file, delta);
+                for note in &mut diagnostic.notes {
+                    relocate(&mut note.loc, file, delta);
+                }
+            }
+        }
+        self.ast.loc = Location::from_offsets(file, 0, file.source().len());
+        parsed
+    }
+}
+
+/// Parses and validates one form of a file on its own, placing what it
+/// finds at the form's offset in the file.
+fn parse_form(file: &SourceFile, range: Range<usize>) -> (Vec<Node>, Vec<Diagnostic>) {
+    let mut sources = SourceMap::default();
+    let form = sources.add(file.name(), &file.source()[range.clone()]);
+    let (ast, mut diagnostics) = parse_collecting(&sources[form]);
+    let shift = range.start as isize;
+    let mut nodes = match ast {
+        Some(Node {
+            kind: NodeKind::Block { expressions },
+            ..
+        }) => expressions,
+        _ => vec![Node::new(
+            NodeKind::Invalid,
+            Location::from_offsets(&sources[form], 0, range.len()),
+        )],
+    };
+    for node in &mut nodes {
+        node.visit_locations_mut(&mut |loc| relocate(loc, file, shift));
+    }
+    for diagnostic in &mut diagnostics {
+        relocate(&mut diagnostic.loc, file, shift);
+        for note in &mut diagnostic.notes {
+            relocate(&mut note.loc, file, shift);
+        }
+    }
+    (nodes, diagnostics)
+}
+
+/// Moves a location `delta` bytes into `file`, finding its line and column
+/// there.
+fn relocate(loc: &mut Location, file: &SourceFile, delta: isize) {
+    *loc = Location::from_offsets(
+        file,
+        loc.start.wrapping_add_signed(delta),
+        loc.end.wrapping_add_signed(delta),
+    );
+}
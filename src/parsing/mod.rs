@@ -1,5 +1,5 @@
 use colored::Colorize;
-use std::{fs::File, io::Read};
+use std::{cell::RefCell, path::Path};
 
 use log::error;
 use pest::{
@@ -8,40 +8,127 @@ use pest::{
 };
 use pest_derive::Parser;
 
-use crate::parsing::ast::{cst_to_ast, nodes::Node};
+use crate::{
+    diagnostics::Diagnostic,
+    parsing::ast::{
+        cst_to_ast,
+        nodes::{Location, Node},
+    },
+    sources::{SourceFile, SourceMap},
+};
 
 pub mod ast;
+pub mod docs;
+pub mod incremental;
+pub mod modules;
+pub mod recovery;
 
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
 pub struct CrispParser;
 
-pub fn parse_file<'a>(path: &'static str) -> Result<Node, String> {
-    let mut source = "".to_string();
-    File::open(&path)
-        .unwrap()
-        .read_to_string(&mut source)
-        .expect("Could not read the file as source code");
-    return parse_str(source, path);
+/// Reads a file into `sources` and parses it.
+pub fn parse_file(sources: &mut SourceMap, path: &Path) -> Result<Node, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let file = sources.add(path.to_string_lossy(), source);
+    parse_source(&sources[file])
+}
+
+/// Parses source text that is not backed by a file, as the only file of its
+/// own source map.
+pub fn parse_str(source: String, name: &str) -> Result<Node, String> {
+    let mut sources = SourceMap::default();
+    let file = sources.add(name, source);
+    parse_source(&sources[file])
 }
 
-pub fn parse_str(source: String, path: &'static str) -> Result<Node, String> {
-    if source.is_empty() {
+pub fn parse_source(file: &SourceFile) -> Result<Node, String> {
+    if file.source().is_empty() {
         error!("Source file is empty!");
         return Err("Source file is empty".to_string());
     }
-    let pest_cst = CrispParser::parse(Rule::file, &source);
+    let pest_cst = CrispParser::parse(Rule::file, file.source());
 
     match pest_cst {
-        Ok(mut pairs) => Ok(cst_to_ast(pairs.next().unwrap(), path)),
+        Ok(mut pairs) => {
+            let ast = cst_to_ast(pairs.next().unwrap(), file);
+            // validation errors have already been printed for these
+            if ast.contains_invalid() {
+                return Err("Validation failed; see logs for details.".to_string());
+            }
+            Ok(ast)
+        }
         Err(e) => {
-            print_pest_error(e, path, &source);
+            print_pest_error(e, file);
             Err("Parse failed; see logs for details.".to_string())
         }
     }
 }
 
-pub fn print_pest_error(err: Error<Rule>, path: &str, source: &str) {
+thread_local! {
+    /// The errors found while [`parse_collecting`] runs, which are kept
+    /// here instead of printed.
+    static COLLECTED: RefCell<Option<Vec<Diagnostic>>> = const { RefCell::new(None) };
+}
+
+/// Keeps an error found while parsing if [`parse_collecting`] is running,
+/// returning whether it did. Otherwise the error is for the caller to print.
+pub(crate) fn collect(diagnostic: Diagnostic) -> bool {
+    COLLECTED.with_borrow_mut(|collected| match collected {
+        Some(collected) => {
+            collected.push(diagnostic);
+            true
+        }
+        None => false,
+    })
+}
+
+/// Parses a file, returning the errors found as diagnostics instead of
+/// printing them, for tools such as editors that show them on their own.
+/// The AST is returned even if parts of it failed validation, and a file
+/// pest rejects is parsed again one form at a time, repairing what it can
+/// (see [`recovery::recover`]). It is `None` only if the parser fails
+/// outright.
+pub fn parse_collecting(file: &SourceFile) -> (Option<Node>, Vec<Diagnostic>) {
+    collecting(|| {
+        // an editor sends text in every state, and some of it still makes
+        // the parser give up with a panic
+        let parsed =
+            std::panic::catch_unwind(|| match CrispParser::parse(Rule::file, file.source()) {
+                Ok(mut pairs) => cst_to_ast(pairs.next().unwrap(), file),
+                Err(_) => recovery::recover(file),
+            });
+        parsed.ok().or_else(|| {
+            let loc = Location::from_offsets(file, 0, 0);
+            collect(Diagnostic::error("The parser failed on this file", loc));
+            None
+        })
+    })
+}
+
+/// Runs `f`, returning the errors [`collect`] kept meanwhile.
+pub(crate) fn collecting<T>(f: impl FnOnce() -> T) -> (T, Vec<Diagnostic>) {
+    let previous = COLLECTED.replace(Some(vec![]));
+    let result = f();
+    let diagnostics = COLLECTED.replace(previous).unwrap_or_default();
+    (result, diagnostics)
+}
+
+/// A pest error as a diagnostic.
+fn pest_diagnostic(err: &Error<Rule>, file: &SourceFile) -> Diagnostic {
+    let (start, end) = match err.location {
+        InputLocation::Pos(pos) => (pos, pos),
+        InputLocation::Span((s, e)) => (s, e),
+    };
+    let message = match &err.variant {
+        ErrorVariant::ParsingError { .. } => "Parsing error".to_string(),
+        ErrorVariant::CustomError { message } => message.clone(),
+    };
+    Diagnostic::error(message, Location::from_offsets(file, start, end))
+}
+
+pub fn print_pest_error(err: Error<Rule>, file: &SourceFile) {
     let (start, end) = match err.location {
         InputLocation::Pos(pos) => (pos, pos),
         InputLocation::Span((s, e)) => (s, e),
@@ -52,13 +139,9 @@ pub fn print_pest_error(err: Error<Rule>, path: &str, source: &str) {
         ErrorVariant::CustomError { message } => message.clone(),
     };
 
-    let line_num = source[..start].chars().filter(|&c| c == '\n').count() + 1;
-    let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
-    let line_end = source[start..]
-        .find('\n')
-        .map(|i| start + i)
-        .unwrap_or(source.len());
-    let line_text = &source[line_start..line_end];
+    let (line_num, _) = file.line_col(start);
+    let (line_start, _) = file.line_span(line_num);
+    let line_text = file.line_text(line_num);
 
     let local_start = start - line_start;
     let local_end = (end - line_start).max(local_start + 1);
@@ -67,7 +150,7 @@ pub fn print_pest_error(err: Error<Rule>, path: &str, source: &str) {
 
     output.push_str(&format!(
         "  --> {}:{}:{}\n",
-        path.blue(),
+        file.name().blue(),
         line_num,
         local_start + 1
     ));
@@ -91,7 +174,7 @@ pub fn print_pest_error(err: Error<Rule>, path: &str, source: &str) {
         .collect();
     output.push_str(&format!("   {} {}\n", "|".blue(), underline.red().bold()));
 
-    let hints = detect_common_parse_issues(source);
+    let hints = detect_common_parse_issues(file.source());
     if !hints.is_empty() {
         output.push_str(&format!("   {}\n", "|").blue());
         for (index, hint) in hints.iter().enumerate() {
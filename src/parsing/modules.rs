@@ -0,0 +1,509 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use log::{debug, error};
+
+use crate::{
+    diagnostics::{Diagnostic, print_all},
+    parsing::{
+        ast::nodes::{Location, Node, NodeKind, Symbol},
+        parse_source,
+    },
+    sources::{FileId, SourceMap},
+};
+
+/// A program linked together from its entry file and every file it imports.
+#[derive(Debug)]
+pub struct Program {
+    /// The top-level definitions of all files, imported ones first.
+    pub ast: Node,
+    /// Every loaded file, for printing diagnostics.
+    pub sources: SourceMap,
+    /// The module name each imported file was imported as, which prefixes
+    /// its definitions. The entry file has none.
+    pub modules: HashMap<FileId, String>,
+}
+
+/// Loads `entry` and everything it imports, directly or not, into a single
+/// program. The definitions of an imported module are renamed to
+/// `module/name`, the only way other files can refer to them, while the
+/// entry file keeps its own names. Every top-level definition is visible to
+/// the files that import its module; `(export)` only keeps the C name of a
+/// function and does not hide the others. Problems are printed as they are
+/// found.
+pub fn load_program(entry: &Path) -> Result<Program, String> {
+    load_program_with(entry, &[])
+}
+
+/// Loads `entry` as [`load_program`] does, along with every file of
+/// `sources` it does not import, such as the modules of a library that
+/// nothing in it uses. Each of those is a module named after its file, as
+/// `(import "path")` would name it.
+pub fn load_program_with(entry: &Path, sources: &[PathBuf]) -> Result<Program, String> {
+    let mut loader = Loader::default();
+    let entry = match entry.canonicalize() {
+        Ok(entry) => entry,
+        Err(e) => {
+            error!("Could not open {}: {}", entry.display(), e);
+            return Err("Could not open the entry file".to_string());
+        }
+    };
+    let mut loaded = loader.load(&entry, None);
+    for source in sources {
+        let Ok(source) = source.canonicalize() else {
+            continue;
+        };
+        if source == entry || loader.names.contains_key(&source) {
+            continue;
+        }
+        let module = source
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if let Some((other, _)) = loader.names.iter().find(|(_, name)| **name == module) {
+            error!(
+                "{} would be module `{}`, which already refers to `{}`",
+                source.display(),
+                module,
+                display_name(other)
+            );
+            return Err("Loading failed; see logs for details.".to_string());
+        }
+        loaded &= loader.load(&source, Some((&module, Location::default())));
+    }
+    if !loader.diagnostics.is_empty() {
+        print_all(&loader.diagnostics, &loader.sources);
+    }
+    if !loaded || !loader.diagnostics.is_empty() {
+        return Err("Loading failed; see logs for details.".to_string());
+    }
+    let loc = loader
+        .modules
+        .iter()
+        .find(|module| module.name.is_none())
+        .map(|module| module.loc)
+        .unwrap_or_default();
+    let mut modules = HashMap::new();
+    let mut expressions = vec![];
+    for module in loader.modules {
+        if let Some(name) = module.name {
+            modules.insert(module.file, name);
+        }
+        expressions.extend(module.expressions);
+    }
+    Ok(Program {
+        ast: Node::new(NodeKind::Block { expressions }, loc),
+        sources: loader.sources,
+        modules,
+    })
+}
+
+/// The definitions of one file, already namespaced.
+#[derive(Debug)]
+struct Module {
+    /// `None` for the entry file.
+    name: Option<String>,
+    file: FileId,
+    expressions: Vec<Node>,
+    loc: Location,
+}
+
+#[derive(Debug, Default)]
+struct Loader {
+    sources: SourceMap,
+    /// Loaded files in the order they finished, so every module comes after
+    /// the ones it imports.
+    modules: Vec<Module>,
+    /// The module name each loaded file was imported as.
+    names: HashMap<PathBuf, String>,
+    /// The files currently being loaded, each importing the next.
+    stack: Vec<PathBuf>,
+    diagnostics: Vec<Diagnostic>,
+}
+impl Loader {
+    /// Loads a file unless it already has been, returning whether it parsed.
+    /// `import` is the module name and the import that asked for the file,
+    /// or `None` for the entry file.
+    fn load(&mut self, file: &Path, import: Option<(&str, Location)>) -> bool {
+        if let Some(position) = self.stack.iter().position(|open| open == file) {
+            let cycle: Vec<String> = self.stack[position..]
+                .iter()
+                .chain([&file.to_path_buf()])
+                .map(|path| display_name(path))
+                .collect();
+            let loc = import.map(|(_, loc)| loc).unwrap_or_default();
+            self.diagnostics.push(Diagnostic::error(
+                format!("Import cycle: {}", cycle.join(" -> ")),
+                loc,
+            ));
+            return false;
+        }
+        if let Some((module, loc)) = import {
+            match self.names.get(file) {
+                Some(name) if name == module => return true,
+                Some(name) => {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!(
+                            "`{}` is already imported as module `{}`",
+                            display_name(file),
+                            name
+                        ),
+                        loc,
+                    ));
+                    return false;
+                }
+                None => {}
+            }
+            if let Some((other, _)) = self.names.iter().find(|(_, name)| *name == module) {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "Module `{}` already refers to `{}`",
+                        module,
+                        display_name(other)
+                    ),
+                    loc,
+                ));
+                return false;
+            }
+            self.names.insert(file.to_path_buf(), module.to_string());
+        }
+
+        debug!("Loading {}", file.display());
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                let message = format!("Could not read `{}`: {}", display_name(file), e);
+                match import {
+                    Some((_, loc)) => self.diagnostics.push(Diagnostic::error(message, loc)),
+                    None => error!("{}", message),
+                }
+                return false;
+            }
+        };
+        let id = self.sources.add(file.to_string_lossy(), source);
+        let Ok(ast) = parse_source(&self.sources[id]) else {
+            return false;
+        };
+        let NodeKind::Block { expressions } = ast.kind else {
+            return false;
+        };
+
+        self.stack.push(file.to_path_buf());
+        let mut imports = HashSet::new();
+        let mut definitions = vec![];
+        let mut loaded = true;
+        for expression in expressions {
+            let NodeKind::Import { module, path } = &expression.kind else {
+                definitions.push(expression);
+                continue;
+            };
+            // imports are relative to the importing file
+            let target = file.parent().unwrap_or(Path::new(".")).join(path);
+            let Ok(target) = target.canonicalize() else {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("Cannot find module `{}` at `{}`", module, target.display()),
+                    expression.loc,
+                ));
+                continue;
+            };
+            loaded &= self.load(&target, Some((module, expression.loc)));
+            imports.insert(module.clone());
+        }
+        self.stack.pop();
+
+        let mut namespace = Namespace {
+            prefix: import.map(|(module, _)| module),
+            definitions: top_level_names(&definitions),
+            imports: &imports,
+            type_params: HashSet::new(),
+            scopes: vec![],
+            diagnostics: vec![],
+        };
+        for definition in &mut definitions {
+            namespace.rename(definition);
+        }
+        self.diagnostics.append(&mut namespace.diagnostics);
+        self.modules.push(Module {
+            name: import.map(|(module, _)| module.to_string()),
+            file: id,
+            expressions: definitions,
+            loc: ast.loc,
+        });
+        loaded
+    }
+}
+
+/// The file name of a path, which is enough to tell files apart in messages.
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// The names a file defines at the top level: functions, types, enum
+/// variants and globals.
+fn top_level_names(expressions: &[Node]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for expression in expressions {
+        match &expression.kind {
+            NodeKind::Fn {
+                name: Some(name), ..
+            } => {
+                names.insert(name.name().to_string());
+            }
+            NodeKind::Struct { name, .. } => {
+                names.insert(name.name().to_string());
+            }
+            NodeKind::Enum { name, variants } => {
+                names.insert(name.name().to_string());
+                names.extend(variants.iter().map(|variant| variant.name.clone()));
+            }
+            NodeKind::Let { symbol, .. } => {
+                names.insert(symbol.name().to_string());
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Renames the definitions of a module to `module/name`, along with every
+/// use of them in the module that is not shadowed by a local binding, and
+/// checks that each `module/name` it uses refers to an imported module.
+struct Namespace<'a> {
+    /// `None` for the entry file, whose names stay as they are.
+    prefix: Option<&'a str>,
+    definitions: HashSet<String>,
+    imports: &'a HashSet<String>,
+    /// The type parameters of the function being renamed.
+    type_params: HashSet<String>,
+    scopes: Vec<HashSet<String>>,
+    diagnostics: Vec<Diagnostic>,
+}
+impl Namespace<'_> {
+    fn rename(&mut self, node: &mut Node) {
+        let loc = node.loc;
+        match &mut node.kind {
+            NodeKind::Fn {
+                name,
+                type_params,
+                returns,
+                params,
+                body,
+                ..
+            } => {
+                if let Some(name) = name {
+                    self.definition(name, loc);
+                }
+                // a type parameter hides a type of the same name
+                self.type_params = type_params.iter().cloned().collect();
+                *returns = self.annotation(returns, loc);
+                self.scopes.push(HashSet::new());
+                for param in params {
+                    self.annotate(param, loc);
+                    self.bind(param.name());
+                }
+                self.rename(body);
+                self.scopes.pop();
+                self.type_params.clear();
+            }
+            NodeKind::Struct { name, fields } => {
+                self.definition(name, loc);
+                for field in fields {
+                    self.annotate(field, loc);
+                }
+            }
+            NodeKind::Enum { name, variants } => {
+                self.definition(name, loc);
+                for variant in variants {
+                    if let Some(prefix) = self.prefix {
+                        variant.name = format!("{}/{}", prefix, variant.name);
+                    }
+                    for field in &mut variant.fields {
+                        self.annotate(field, variant.loc);
+                    }
+                }
+            }
+            NodeKind::Let { symbol, value, .. } => {
+                self.rename(value);
+                if self.scopes.is_empty() {
+                    self.definition(symbol, loc);
+                } else {
+                    self.annotate(symbol, loc);
+                    self.bind(symbol.name());
+                }
+            }
+            NodeKind::For {
+                dummy,
+                iterator,
+                body,
+            } => {
+                self.rename(iterator);
+                self.scopes.push(HashSet::new());
+                self.bind(dummy.name());
+                self.rename(body);
+                self.scopes.pop();
+            }
+            NodeKind::Given { predicate, cases } => {
+                self.rename(predicate);
+                let NodeKind::Block { expressions } = &mut cases.kind else {
+                    return;
+                };
+                for case in expressions {
+                    let NodeKind::Block { expressions } = &mut case.kind else {
+                        continue;
+                    };
+                    let [pattern, body] = expressions.as_mut_slice() else {
+                        continue;
+                    };
+                    self.scopes.push(HashSet::new());
+                    // (Circle r) names a variant and binds r for the body
+                    match &mut pattern.kind {
+                        NodeKind::Call { name, args } => {
+                            self.reference(name, pattern.loc);
+                            for arg in args {
+                                if let NodeKind::Identifier { symbol } = &arg.kind {
+                                    self.bind(symbol.name());
+                                }
+                            }
+                        }
+                        _ => self.rename(pattern),
+                    }
+                    self.rename(body);
+                    self.scopes.pop();
+                }
+            }
+            NodeKind::Identifier { symbol } => {
+                if !self.is_local(symbol.name()) {
+                    self.reference(symbol, loc);
+                }
+            }
+            NodeKind::Call { name, args } => {
+                self.reference(name, loc);
+                for arg in args {
+                    self.rename(arg);
+                }
+            }
+            NodeKind::Block { expressions } => {
+                self.scopes.push(HashSet::new());
+                for expression in expressions {
+                    self.rename(expression);
+                }
+                self.scopes.pop();
+            }
+            NodeKind::Import { .. } => self.diagnostics.push(Diagnostic::error(
+                "Imports are only allowed at the top level of a file",
+                loc,
+            )),
+            NodeKind::Field { target, .. } => self.rename(target),
+            NodeKind::Index { target, index } => {
+                self.rename(target);
+                self.rename(index);
+            }
+            NodeKind::If { predicate, yes, no } => {
+                self.rename(predicate);
+                self.rename(yes);
+                if let Some(no) = no {
+                    self.rename(no);
+                }
+            }
+            NodeKind::Set { place, value } => {
+                self.rename(place);
+                self.rename(value);
+            }
+            NodeKind::Return { value } => self.rename(value),
+            NodeKind::Array { elements } => {
+                for element in elements {
+                    self.rename(element);
+                }
+            }
+            // C names are shared by the whole program, so externs are left
+            // as they are
+            NodeKind::Extern { .. }
+            | NodeKind::Include { .. }
+            | NodeKind::Literal(_)
+            | NodeKind::Invalid => {}
+        }
+    }
+
+    fn bind(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    /// The name a definition of this module ends up with.
+    fn qualified(&self, name: &str) -> Option<String> {
+        let prefix = self.prefix?;
+        self.definitions
+            .contains(name)
+            .then(|| format!("{}/{}", prefix, name))
+    }
+
+    fn definition(&mut self, symbol: &mut Symbol, loc: Location) {
+        self.annotate(symbol, loc);
+        rename_symbol(symbol, |name| self.qualified(name));
+    }
+
+    /// Renames a use of a top-level name and checks a qualified one.
+    fn reference(&mut self, symbol: &mut Symbol, loc: Location) {
+        self.check_module(symbol.name(), loc);
+        rename_symbol(symbol, |name| self.qualified(name));
+    }
+
+    fn annotate(&mut self, symbol: &mut Symbol, loc: Location) {
+        if let Symbol::Typed { annotation, .. } = symbol {
+            *annotation = self.annotation(annotation, loc);
+        }
+    }
+
+    /// Renames the type inside an annotation such as `Point` or `[Point;2]`.
+    fn annotation(&mut self, annotation: &str, loc: Location) -> String {
+        let start = annotation.len() - annotation.trim_start_matches('[').len();
+        let end = annotation[start..]
+            .find([';', ']'])
+            .map_or(annotation.len(), |end| start + end);
+        let name = &annotation[start..end];
+        self.check_module(name, loc);
+        match self.qualified(name) {
+            Some(qualified) if !self.type_params.contains(name) => {
+                format!(
+                    "{}{}{}",
+                    &annotation[..start],
+                    qualified,
+                    &annotation[end..]
+                )
+            }
+            _ => annotation.to_string(),
+        }
+    }
+
+    fn check_module(&mut self, name: &str, loc: Location) {
+        if let Some((module, rest)) = name.split_once('/')
+            && !module.is_empty()
+            && !rest.is_empty()
+            && !self.imports.contains(module)
+        {
+            self.diagnostics.push(Diagnostic::error(
+                format!(
+                    "`{}` refers to module `{}`, which is not imported here",
+                    name, module
+                ),
+                loc,
+            ));
+        }
+    }
+}
+
+fn rename_symbol(symbol: &mut Symbol, qualified: impl Fn(&str) -> Option<String>) {
+    let (Symbol::Typed { name, .. } | Symbol::Untyped { name }) = symbol;
+    if let Some(qualified) = qualified(name) {
+        *name = qualified;
+    }
+}
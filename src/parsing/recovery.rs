@@ -0,0 +1,337 @@
+use std::ops::Range;
+
+use pest::{Parser, error::InputLocation};
+
+use crate::{
+    diagnostics::Diagnostic,
+    parsing::{
+        CrispParser, Rule,
+        ast::{
+            cst_to_ast,
+            nodes::{Location, Node, NodeKind},
+        },
+        collect, collecting, pest_diagnostic,
+    },
+    sources::SourceFile,
+};
+
+/// A top-level form, or a run of text at the top level that is not one,
+/// such as a stray atom.
+#[derive(Debug)]
+struct Region {
+    start: usize,
+    end: usize,
+    /// What has to be added after the region to close it, if anything.
+    repair: String,
+    /// Closing brackets in the region that close nothing, and are left out.
+    stray: Vec<usize>,
+    /// Whether the region could not be repaired and is left out entirely.
+    failed: bool,
+}
+impl Region {
+    fn is_broken(&self) -> bool {
+        !self.repair.is_empty() || !self.stray.is_empty()
+    }
+}
+
+/// Parses a file that pest rejects as a whole, for editors that need an AST
+/// while the text is being typed. Each top-level form is parsed on its own,
+/// so one that is broken leaves the others intact:
+///
+/// - a closing bracket that closes nothing is skipped,
+/// - brackets still open at the end of a form are closed there, taking a
+///   `(` at the start of a line as the start of the next form unless the
+///   form closes properly later on,
+/// - a string that is never closed ends with its line,
+/// - and a form that still does not parse becomes a [`NodeKind::Invalid`].
+///
+/// Every repair is reported through [`collect`]. The locations of the nodes
+/// are those of the original text.
+pub(crate) fn recover(file: &SourceFile) -> Node {
+    let source = file.source();
+    let (mut regions, stray) = regions(file);
+
+    let mut expressions = vec![];
+    // the forms that need no repair are parsed together, leaving out the
+    // others and those that turn out not to parse, one at a time
+    loop {
+        let text = blanked(source, &regions, &stray);
+        match CrispParser::parse(Rule::file, &text) {
+            Ok(mut pairs) => {
+                if let NodeKind::Block { expressions: nodes } =
+                    cst_to_ast(pairs.next().unwrap(), file).kind
+                {
+                    expressions.extend(nodes);
+                }
+                break;
+            }
+            Err(err) => {
+                let at = match err.location {
+                    InputLocation::Pos(pos) => pos,
+                    InputLocation::Span((start, _)) => start,
+                };
+                let culprit = regions.iter().rposition(|region| {
+                    region.start <= at && !region.is_broken() && !region.failed
+                });
+                match culprit {
+                    Some(index) => fail(file, &mut regions[index], &err),
+                    None => {
+                        // nothing left to leave out, which should not happen
+                        collect(pest_diagnostic(&err, file));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    for region in &mut regions {
+        if region.is_broken() && !region.failed {
+            let text = repaired(source, region);
+            match CrispParser::parse(Rule::file, &text) {
+                Ok(mut pairs) => {
+                    if let NodeKind::Block { expressions: nodes } =
+                        cst_to_ast(pairs.next().unwrap(), file).kind
+                    {
+                        expressions.extend(nodes);
+                    }
+                }
+                Err(err) => fail(file, region, &err),
+            }
+        }
+        if region.failed {
+            expressions.push(Node::new(
+                NodeKind::Invalid,
+                Location::from_offsets(file, region.start, region.end),
+            ));
+        }
+    }
+    expressions.sort_by_key(|node| node.loc.start);
+    Node::new(
+        NodeKind::Block { expressions },
+        Location::from_offsets(file, 0, source.len()),
+    )
+}
+
+/// Leaves a region out, reporting why pest rejected it.
+fn fail(file: &SourceFile, region: &mut Region, err: &pest::error::Error<Rule>) {
+    region.failed = true;
+    collect(pest_diagnostic(err, file));
+}
+
+/// Splits a file into regions, reporting what is wrong with them. Also
+/// returns the closing brackets between regions, which close nothing.
+fn regions(file: &SourceFile) -> (Vec<Region>, Vec<usize>) {
+    let bytes = file.source().as_bytes();
+    let mut regions = vec![];
+    let mut stray = vec![];
+    let mut i = 0;
+    while let Some(start) = skip_space(bytes, i) {
+        if matches!(bytes[start], b')' | b']') {
+            collect(Diagnostic::error(
+                format!(
+                    "Unexpected `{}` with nothing to close",
+                    bytes[start] as char
+                ),
+                Location::from_offsets(file, start, start + 1),
+            ));
+            stray.push(start);
+            i = start + 1;
+        } else {
+            let region = region(file, start);
+            i = region.end;
+            regions.push(region);
+        }
+    }
+    (regions, stray)
+}
+
+/// The bytes of the first top-level form from `from` on, as [`recover`]
+/// splits a file: a form or atom, or a closing bracket between forms that
+/// closes nothing. What is wrong with it is not reported. Also returns
+/// whether the form depends on the text after it, as one that needs a
+/// repair does: a quote anywhere later would close a string left open, and
+/// brackets that close later would keep the form whole.
+pub(crate) fn next_form(file: &SourceFile, from: usize) -> Option<(Range<usize>, bool)> {
+    let bytes = file.source().as_bytes();
+    let start = skip_space(bytes, from)?;
+    if matches!(bytes[start], b')' | b']') {
+        return Some((start..start + 1, false));
+    }
+    let region = collecting(|| region(file, start)).0;
+    Some((start..region.end, region.is_broken()))
+}
+
+/// The offset of the first byte from `from` on that is neither whitespace
+/// nor in a comment.
+fn skip_space(bytes: &[u8], from: usize) -> Option<usize> {
+    let mut i = from;
+    while i < bytes.len() {
+        match bytes[i] {
+            b' ' | b'\t' | b'\r' | b'\n' => i += 1,
+            b';' => i = line_end(bytes, i),
+            _ => return Some(i),
+        }
+    }
+    None
+}
+
+/// Scans the region that starts at `start`, up to the end of the form or
+/// atom found there. A form that is not closed properly ends before the
+/// first `(` at the start of a line in it instead, but one that is stays
+/// whole, as pest would parse it.
+fn region(file: &SourceFile, start: usize) -> Region {
+    let (whole, _) = collecting(|| scan(file, start, false));
+    if whole.is_broken() {
+        scan(file, start, true)
+    } else {
+        whole
+    }
+}
+
+/// Scans a region as [`region`] does, ending a form that is still open at
+/// a `(` at the start of a line only if `cut` is set.
+fn scan(file: &SourceFile, start: usize, cut: bool) -> Region {
+    let bytes = file.source().as_bytes();
+    let mut region = Region {
+        start,
+        end: bytes.len(),
+        repair: String::new(),
+        stray: vec![],
+        failed: false,
+    };
+    // the brackets still open, by their offsets
+    let mut open: Vec<usize> = vec![];
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b' ' | b'\t' | b'\r' | b'\n' => i += 1,
+            b';' => i = line_end(bytes, i),
+            b'(' if cut && !open.is_empty() && (i == 0 || bytes[i - 1] == b'\n') => {
+                // most likely the next definition, after one left unfinished
+                region.end = i;
+                break;
+            }
+            b'(' | b'[' => {
+                open.push(i);
+                i += 1;
+            }
+            b')' | b']' => {
+                let closing = bytes[i];
+                match open.last() {
+                    Some(&opener) if closer(bytes[opener]) == closing => {
+                        open.pop();
+                    }
+                    Some(&opener) => {
+                        collect(
+                            Diagnostic::error(
+                                format!(
+                                    "Unexpected `{}`; the innermost open bracket needs a `{}`",
+                                    closing as char,
+                                    closer(bytes[opener]) as char
+                                ),
+                                Location::from_offsets(file, i, i + 1),
+                            )
+                            .with_note(
+                                "The open bracket",
+                                Location::from_offsets(file, opener, opener + 1),
+                            ),
+                        );
+                        region.stray.push(i);
+                    }
+                    None => unreachable!("a region starts with an atom or an open bracket"),
+                }
+                i += 1;
+            }
+            b'"' => match bytes[i + 1..].iter().position(|&byte| byte == b'"') {
+                Some(length) => i += length + 2,
+                None => {
+                    collect(Diagnostic::error(
+                        "This string is never closed, so it is taken to end with its line",
+                        Location::from_offsets(file, i, i + 1),
+                    ));
+                    i = line_end(bytes, i);
+                    region.end = i;
+                    region.repair.push('"');
+                    break;
+                }
+            },
+            _ => {
+                while i < bytes.len() && !is_delimiter(bytes[i]) {
+                    i += 1;
+                }
+            }
+        }
+        if open.is_empty() {
+            region.end = i;
+            break;
+        }
+    }
+    for &opener in open.iter().rev() {
+        let closing = closer(bytes[opener]) as char;
+        collect(Diagnostic::error(
+            format!(
+                "Missing `{}` to close this `{}`",
+                closing, bytes[opener] as char
+            ),
+            Location::from_offsets(file, opener, opener + 1),
+        ));
+        region.repair.push(closing);
+    }
+    region
+}
+
+fn closer(opener: u8) -> u8 {
+    if opener == b'(' { b')' } else { b']' }
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(
+        byte,
+        b' ' | b'\t' | b'\r' | b'\n' | b';' | b'"' | b'(' | b')' | b'[' | b']'
+    )
+}
+
+/// The offset of the line break that ends the line `at` is on, or the end
+/// of the text.
+fn line_end(bytes: &[u8], at: usize) -> usize {
+    bytes[at..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(bytes.len(), |length| at + length)
+}
+
+/// The text of a file without the regions that need repairs or failed, and
+/// without the stray closing brackets. What is left out is replaced by
+/// spaces, so everything else keeps its offset.
+fn blanked(source: &str, regions: &[Region], stray: &[usize]) -> String {
+    let mut text = source.as_bytes().to_vec();
+    for region in regions {
+        if region.is_broken() || region.failed {
+            blank(&mut text, region.start..region.end);
+        }
+    }
+    for &at in stray {
+        text[at] = b' ';
+    }
+    String::from_utf8(text).expect("only whole characters are blanked")
+}
+
+/// The text of one region with its repairs, at its offset in the file.
+fn repaired(source: &str, region: &Region) -> String {
+    let mut text = source.as_bytes()[..region.end].to_vec();
+    blank(&mut text, 0..region.start);
+    for &at in &region.stray {
+        text[at] = b' ';
+    }
+    text.extend(region.repair.bytes());
+    String::from_utf8(text).expect("only whole characters are blanked")
+}
+
+fn blank(text: &mut [u8], range: std::ops::Range<usize>) {
+    for byte in &mut text[range] {
+        // line breaks are kept, to keep the lines pest reports errors on
+        if *byte != b'\n' {
+            *byte = b' ';
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+
+/// The name of the manifest file at the root of every project.
+pub const MANIFEST: &str = "Crisp.toml";
+
+/// The contents of a `Crisp.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub package: Package,
+    #[serde(default)]
+    pub c: CConfig,
+}
+impl Manifest {
+    pub fn parse(text: &str) -> anyhow::Result<Manifest> {
+        let manifest: Manifest = toml::from_str(text)?;
+        if !is_valid_name(&manifest.package.name) {
+            bail!(
+                "Package name `{}` must start with a letter and only contain letters, digits, `-` and `_`",
+                manifest.package.name
+            );
+        }
+        Ok(manifest)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Package {
+    pub name: String,
+    #[serde(default)]
+    pub kind: Kind,
+    /// The file the program is linked from, `src/main.crisp` for an
+    /// executable and `src/lib.crisp` for a library by default.
+    pub entry: Option<PathBuf>,
+    #[serde(default = "default_source_dirs")]
+    pub source_dirs: Vec<PathBuf>,
+    #[serde(default = "default_output_dir")]
+    pub output_dir: PathBuf,
+}
+
+/// What `crisp build` produces.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    Bin,
+    Lib,
+}
+
+/// How to invoke the C compiler on the generated code.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CConfig {
+    /// Falls back to `$CC`, then `cc`.
+    pub compiler: Option<String>,
+    #[serde(default)]
+    pub flags: Vec<String>,
+    #[serde(default = "default_standard")]
+    pub standard: String,
+}
+impl Default for CConfig {
+    fn default() -> CConfig {
+        CConfig {
+            compiler: None,
+            flags: vec![],
+            standard: default_standard(),
+        }
+    }
+}
+
+fn default_source_dirs() -> Vec<PathBuf> {
+    vec![PathBuf::from("src")]
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from("build")
+}
+
+fn default_standard() -> String {
+    "c11".to_string()
+}
+
+fn is_valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// A manifest together with the directory it was found in, which every
+/// path in it is relative to.
+#[derive(Debug)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+impl Project {
+    /// Loads the manifest at `path`.
+    pub fn load(path: &Path) -> anyhow::Result<Project> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Could not read {:?}", path))?;
+        let manifest = Manifest::parse(&text).with_context(|| format!("Invalid {:?}", path))?;
+        let root = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .to_path_buf();
+        Ok(Project { root, manifest })
+    }
+
+    /// Loads the manifest in `dir` or the closest directory above it.
+    pub fn find(dir: &Path) -> anyhow::Result<Project> {
+        let dir = dir.canonicalize()?;
+        match dir
+            .ancestors()
+            .map(|ancestor| ancestor.join(MANIFEST))
+            .find(|path| path.is_file())
+        {
+            Some(path) => Project::load(&path),
+            None => bail!("Could not find {} in {:?} or above", MANIFEST, dir),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.manifest.package.name
+    }
+
+    pub fn entry(&self) -> PathBuf {
+        let package = &self.manifest.package;
+        let default = match package.kind {
+            Kind::Bin => "src/main.crisp",
+            Kind::Lib => "src/lib.crisp",
+        };
+        self.root
+            .join(package.entry.as_deref().unwrap_or(Path::new(default)))
+    }
+
+    pub fn output_dir(&self) -> PathBuf {
+        self.root.join(&self.manifest.package.output_dir)
+    }
+
+    /// Every `.crisp` file below the source directories, in a stable order.
+    pub fn sources(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let mut sources = vec![];
+        for dir in &self.manifest.package.source_dirs {
+            collect_sources(&self.root.join(dir), &mut sources)?;
+        }
+        sources.sort();
+        Ok(sources)
+    }
+}
+
+fn collect_sources(dir: &Path, sources: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Could not read {:?}", dir))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_sources(&path, sources)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "crisp")
+        {
+            sources.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Creates a new executable project called `name` in `dir`, which must not
+/// exist yet.
+pub fn scaffold(dir: &Path, name: &str) -> anyhow::Result<()> {
+    if !is_valid_name(name) {
+        bail!(
+            "Project name `{}` must start with a letter and only contain letters, digits, `-` and `_`",
+            name
+        );
+    }
+    if dir.exists() {
+        bail!("{:?} already exists", dir);
+    }
+    fs::create_dir_all(dir.join("src"))?;
+    fs::write(
+        dir.join(MANIFEST),
+        format!(
+            "[package]\nname = \"{name}\"\nkind = \"bin\"\nentry = \"src/main.crisp\"\nsource-dirs = [\"src\"]\noutput-dir = \"build\"\n\n[c]\n# compiler = \"cc\"  # defaults to $CC, then cc\nflags = [\"-O2\"]\nstandard = \"c11\"\n"
+        ),
+    )?;
+    fs::write(
+        dir.join("src/main.crisp"),
+        "(fn:i32 main () (\n  (ret 0)))\n",
+    )?;
+    fs::write(dir.join(".gitignore"), "/build\n")?;
+    Ok(())
+}
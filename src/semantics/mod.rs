@@ -0,0 +1,34 @@
+use crate::{
+    diagnostics::Diagnostic,
+    parsing::ast::nodes::Node,
+    semantics::typecheck::{TypeInfo, check_types},
+};
+
+pub mod mutability;
+pub mod operators;
+pub mod typecheck;
+pub mod types;
+pub mod unused;
+
+/// The result of checking a program: what the checker learned about it and
+/// everything it had to complain about.
+#[derive(Debug)]
+pub struct Analysis {
+    pub types: TypeInfo,
+    pub diagnostics: Vec<Diagnostic>,
+}
+impl Analysis {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+/// Runs every semantic check over a parsed program.
+pub fn check_program(ast: &Node) -> Analysis {
+    let mut diagnostics = mutability::check_mutability(ast);
+    let (types, type_diagnostics) = check_types(ast);
+    diagnostics.extend(type_diagnostics);
+    diagnostics.extend(unused::check_unused(ast));
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.loc.file, diagnostic.loc.start));
+    Analysis { types, diagnostics }
+}
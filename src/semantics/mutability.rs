@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use crate::{
+    diagnostics::Diagnostic,
+    parsing::ast::nodes::{Location, Node, NodeKind},
+};
+
+#[derive(Debug, Clone, Copy)]
+enum BindingKind {
+    Let { mutable: bool },
+    Param,
+    Dummy,
+    Pattern,
+    Function,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Binding {
+    kind: BindingKind,
+    loc: Location,
+}
+
+struct Scopes {
+    stack: Vec<HashMap<String, Binding>>,
+}
+impl Scopes {
+    fn push(&mut self) {
+        self.stack.push(HashMap::new());
+    }
+
+    fn pop(&mut self) {
+        self.stack.pop();
+    }
+
+    fn declare(&mut self, name: &str, kind: BindingKind, loc: Location) {
+        self.stack
+            .last_mut()
+            .expect("scope stack is never empty")
+            .insert(name.to_string(), Binding { kind, loc });
+    }
+
+    fn lookup(&self, name: &str) -> Option<Binding> {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+}
+
+/// Rejects `set!` on anything that was not introduced with `let mut`.
+pub fn check_mutability(ast: &Node) -> Vec<Diagnostic> {
+    let mut scopes = Scopes {
+        stack: vec![HashMap::new()],
+    };
+    let mut diagnostics = vec![];
+    // top-level functions are visible before their definition
+    if let NodeKind::Block { expressions } = &ast.kind {
+        for expression in expressions {
+            match &expression.kind {
+                NodeKind::Fn {
+                    name: Some(name), ..
+                } => scopes.declare(name.name(), BindingKind::Function, expression.loc),
+                NodeKind::Extern { functions } => {
+                    for function in functions {
+                        scopes.declare(&function.name, BindingKind::Function, function.loc);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    walk(ast, &mut scopes, &mut diagnostics);
+    diagnostics
+}
+
+fn walk(node: &Node, scopes: &mut Scopes, diagnostics: &mut Vec<Diagnostic>) {
+    match &node.kind {
+        NodeKind::Fn {
+            name, params, body, ..
+        } => {
+            if let Some(name) = name {
+                scopes.declare(name.name(), BindingKind::Function, node.loc);
+            }
+            scopes.push();
+            for param in params {
+                scopes.declare(param.name(), BindingKind::Param, node.loc);
+            }
+            walk(body, scopes, diagnostics);
+            scopes.pop();
+        }
+        NodeKind::If { predicate, yes, no } => {
+            walk(predicate, scopes, diagnostics);
+            walk(yes, scopes, diagnostics);
+            if let Some(no) = no {
+                walk(no, scopes, diagnostics);
+            }
+        }
+        NodeKind::Let {
+            symbol,
+            mutable,
+            value,
+        } => {
+            walk(value, scopes, diagnostics);
+            scopes.declare(
+                symbol.name(),
+                BindingKind::Let { mutable: *mutable },
+                node.loc,
+            );
+        }
+        NodeKind::Set { place, value } => {
+            walk(place, scopes, diagnostics);
+            walk(value, scopes, diagnostics);
+            // assigning to a field or element needs the whole value to be
+            // mutable
+            let Some(name) = root_name(place) else {
+                diagnostics.push(Diagnostic::error(
+                    "Cannot assign to a temporary value",
+                    place.loc,
+                ));
+                return;
+            };
+            match scopes.lookup(name) {
+                None => diagnostics.push(Diagnostic::error(
+                    format!("Assignment to undeclared variable `{name}`"),
+                    node.loc,
+                )),
+                Some(Binding {
+                    kind: BindingKind::Let { mutable: true },
+                    ..
+                }) => {}
+                Some(Binding {
+                    kind: BindingKind::Let { mutable: false },
+                    loc,
+                }) => diagnostics.push(
+                    Diagnostic::error(
+                        format!("Cannot assign twice to immutable variable `{name}`"),
+                        node.loc,
+                    )
+                    .with_note(
+                        format!("`{name}` is declared here; use (let mut {name} ...) to allow assignment"),
+                        loc,
+                    ),
+                ),
+                Some(Binding {
+                    kind: BindingKind::Param,
+                    loc,
+                }) => diagnostics.push(
+                    Diagnostic::error(
+                        format!("Cannot assign to function parameter `{name}`"),
+                        node.loc,
+                    )
+                    .with_note(format!("`{name}` is a parameter of this function"), loc),
+                ),
+                Some(Binding {
+                    kind: BindingKind::Dummy,
+                    loc,
+                }) => diagnostics.push(
+                    Diagnostic::error(
+                        format!("Cannot assign to loop variable `{name}`"),
+                        node.loc,
+                    )
+                    .with_note(format!("`{name}` is bound by this loop"), loc),
+                ),
+                Some(Binding {
+                    kind: BindingKind::Pattern,
+                    loc,
+                }) => diagnostics.push(
+                    Diagnostic::error(
+                        format!("Cannot assign to pattern binding `{name}`"),
+                        node.loc,
+                    )
+                    .with_note(format!("`{name}` is bound by this pattern"), loc),
+                ),
+                Some(Binding {
+                    kind: BindingKind::Function,
+                    loc,
+                }) => diagnostics.push(
+                    Diagnostic::error(format!("Cannot assign to function `{name}`"), node.loc)
+                        .with_note(format!("`{name}` is defined here"), loc),
+                ),
+            }
+        }
+        NodeKind::For {
+            dummy,
+            iterator,
+            body,
+        } => {
+            walk(iterator, scopes, diagnostics);
+            scopes.push();
+            scopes.declare(dummy.name(), BindingKind::Dummy, node.loc);
+            walk(body, scopes, diagnostics);
+            scopes.pop();
+        }
+        NodeKind::Given { predicate, cases } => {
+            walk(predicate, scopes, diagnostics);
+            for case in cases.children() {
+                let [pattern, body] = case.children()[..] else {
+                    continue;
+                };
+                scopes.push();
+                // (Circle r) binds r for the body of its case
+                if let NodeKind::Call { args, .. } = &pattern.kind {
+                    for arg in args {
+                        if let NodeKind::Identifier { symbol } = &arg.kind {
+                            scopes.declare(symbol.name(), BindingKind::Pattern, arg.loc);
+                        }
+                    }
+                }
+                walk(body, scopes, diagnostics);
+                scopes.pop();
+            }
+        }
+        NodeKind::Return { value } => walk(value, scopes, diagnostics),
+        NodeKind::Field { target, .. } => walk(target, scopes, diagnostics),
+        NodeKind::Index { target, index } => {
+            walk(target, scopes, diagnostics);
+            walk(index, scopes, diagnostics);
+        }
+        NodeKind::Call { args, .. } => {
+            for arg in args {
+                walk(arg, scopes, diagnostics);
+            }
+        }
+        NodeKind::Array { elements } => {
+            for element in elements {
+                walk(element, scopes, diagnostics);
+            }
+        }
+        NodeKind::Block { expressions } => {
+            scopes.push();
+            for expression in expressions {
+                walk(expression, scopes, diagnostics);
+            }
+            scopes.pop();
+        }
+        NodeKind::Struct { .. }
+        | NodeKind::Enum { .. }
+        | NodeKind::Identifier { .. }
+        | NodeKind::Literal(_)
+        | NodeKind::Import { .. }
+        | NodeKind::Extern { .. }
+        | NodeKind::Include { .. }
+        | NodeKind::Invalid => {}
+    }
+}
+
+/// The variable an assignment target such as `p.x` or `(at xs i)` belongs to.
+fn root_name(place: &Node) -> Option<&str> {
+    match &place.kind {
+        NodeKind::Identifier { symbol } => Some(symbol.name()),
+        NodeKind::Field { target, .. } | NodeKind::Index { target, .. } => root_name(target),
+        _ => None,
+    }
+}
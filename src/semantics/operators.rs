@@ -0,0 +1,174 @@
+/// The operators built into the language. They are called like functions,
+/// as in `(+ a b c)`, but are checked by their own rules and lowered to the
+/// matching C operator rather than to a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Not,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    Shl,
+    Shr,
+}
+
+/// What an operator does to its operands, which decides what they may be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperatorKind {
+    /// Numbers in, a number of the promoted type out.
+    Arithmetic,
+    /// Two values of a common type in, a bool out.
+    Comparison,
+    /// Bools in, a bool out.
+    Logical,
+    /// Integers in, an integer of the promoted type out.
+    Bitwise,
+}
+
+impl Operator {
+    pub fn from_name(name: &str) -> Option<Operator> {
+        let operator = match name {
+            "+" => Operator::Add,
+            "-" => Operator::Sub,
+            "*" => Operator::Mul,
+            "/" => Operator::Div,
+            "%" => Operator::Rem,
+            "=" => Operator::Eq,
+            "!=" => Operator::Ne,
+            "<" => Operator::Lt,
+            "<=" => Operator::Le,
+            ">" => Operator::Gt,
+            ">=" => Operator::Ge,
+            "and" => Operator::And,
+            "or" => Operator::Or,
+            "not" => Operator::Not,
+            "&" => Operator::BitAnd,
+            "|" => Operator::BitOr,
+            "^" => Operator::BitXor,
+            "~" => Operator::BitNot,
+            "<<" => Operator::Shl,
+            ">>" => Operator::Shr,
+            _ => return None,
+        };
+        Some(operator)
+    }
+
+    /// The name the operator is called by in Crisp.
+    pub fn name(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+            Operator::Rem => "%",
+            Operator::Eq => "=",
+            Operator::Ne => "!=",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+            Operator::And => "and",
+            Operator::Or => "or",
+            Operator::Not => "not",
+            Operator::BitAnd => "&",
+            Operator::BitOr => "|",
+            Operator::BitXor => "^",
+            Operator::BitNot => "~",
+            Operator::Shl => "<<",
+            Operator::Shr => ">>",
+        }
+    }
+
+    pub fn kind(self) -> OperatorKind {
+        match self {
+            Operator::Add | Operator::Sub | Operator::Mul | Operator::Div | Operator::Rem => {
+                OperatorKind::Arithmetic
+            }
+            Operator::Eq
+            | Operator::Ne
+            | Operator::Lt
+            | Operator::Le
+            | Operator::Gt
+            | Operator::Ge => OperatorKind::Comparison,
+            Operator::And | Operator::Or | Operator::Not => OperatorKind::Logical,
+            Operator::BitAnd
+            | Operator::BitOr
+            | Operator::BitXor
+            | Operator::BitNot
+            | Operator::Shl
+            | Operator::Shr => OperatorKind::Bitwise,
+        }
+    }
+
+    /// The least and, if there is one, the most operands the operator takes.
+    /// `(- x)` negates and `(+ a b c)` folds left to right.
+    pub fn arity(self) -> (usize, Option<usize>) {
+        match self {
+            Operator::Not | Operator::BitNot => (1, Some(1)),
+            Operator::Sub => (1, None),
+            Operator::Rem
+            | Operator::Eq
+            | Operator::Ne
+            | Operator::Lt
+            | Operator::Le
+            | Operator::Gt
+            | Operator::Ge
+            | Operator::Shl
+            | Operator::Shr => (2, Some(2)),
+            _ => (2, None),
+        }
+    }
+
+    /// Whether the operator only makes sense on integers.
+    pub fn needs_integers(self) -> bool {
+        self == Operator::Rem || self.kind() == OperatorKind::Bitwise
+    }
+
+    /// Whether the operator orders its operands rather than just telling
+    /// them apart.
+    pub fn is_ordering(self) -> bool {
+        matches!(
+            self,
+            Operator::Lt | Operator::Le | Operator::Gt | Operator::Ge
+        )
+    }
+
+    /// The C operator it lowers to. A unary `-` is spelled the same.
+    pub fn c_operator(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+            Operator::Rem => "%",
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Not => "!",
+            Operator::BitAnd => "&",
+            Operator::BitOr => "|",
+            Operator::BitXor => "^",
+            Operator::BitNot => "~",
+            Operator::Shl => "<<",
+            Operator::Shr => ">>",
+        }
+    }
+}
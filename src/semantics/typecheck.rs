@@ -0,0 +1,1728 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    diagnostics::Diagnostic,
+    parsing::ast::nodes::{Attribute, ExternFn, Literal, Location, Node, NodeKind, Number, Symbol},
+    semantics::{
+        operators::{Operator, OperatorKind},
+        types::Type,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+    pub loc: Location,
+}
+impl StructDef {
+    pub fn field(&self, name: &str) -> Option<&Type> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, ty)| ty)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantDef {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumDef {
+    pub name: String,
+    pub variants: Vec<VariantDef>,
+    pub loc: Location,
+}
+impl EnumDef {
+    pub fn variant(&self, name: &str) -> Option<&VariantDef> {
+        self.variants.iter().find(|variant| variant.name == name)
+    }
+}
+
+/// Functions the runtime library provides. A user definition of the same
+/// name takes precedence.
+pub const BUILTINS: &[&str] = &["len", "print", "println", "concat", "compare"];
+
+#[derive(Debug, Clone)]
+pub struct FnSig {
+    /// Empty unless the function is generic.
+    pub type_params: Vec<String>,
+    pub params: Vec<(String, Type)>,
+    pub returns: Type,
+    /// Whether the function is implemented in C and only declared here.
+    pub external: bool,
+    /// Whether the function is marked `(export)` to be called from C.
+    pub exported: bool,
+    pub loc: Location,
+}
+
+/// Something a generic function does to values of one of its type
+/// parameters that not every type allows, such as comparing them. It is
+/// checked for each type the parameter is bound to, as the function is
+/// instantiated.
+#[derive(Debug, Clone)]
+pub struct ParamUse {
+    /// The generic function it is in.
+    pub function: String,
+    pub param: String,
+    pub kind: ParamUseKind,
+    pub loc: Location,
+}
+
+#[derive(Debug, Clone)]
+pub enum ParamUseKind {
+    /// An operator applied to values of the type, as in `(> a b)`.
+    Operator { operator: Operator, operands: usize },
+    /// A literal given the type, as the `1` in `(+ a 1)`.
+    Literal(Number),
+}
+
+impl ParamUse {
+    /// What is wrong with the use once its type parameter is bound to
+    /// `ty`, if anything.
+    pub fn check(&self, ty: &Type) -> Option<String> {
+        match &self.kind {
+            ParamUseKind::Operator { operator, operands } => {
+                let name = operator.name();
+                if matches!(operator, Operator::Eq | Operator::Ne)
+                    && matches!(ty, Type::Bool | Type::String)
+                {
+                    None
+                } else if operator.needs_integers() && !ty.is_integer() {
+                    Some(format!("`{}` expects integers, found `{}`", name, ty))
+                } else if !ty.is_numeric() {
+                    Some(format!("`{}` expects numbers, found `{}`", name, ty))
+                } else if *operator == Operator::Sub
+                    && *operands == 1
+                    && ty.integer_range().is_some_and(|(min, _)| min == 0)
+                {
+                    Some(format!("Cannot negate a value of type `{}`", ty))
+                } else {
+                    None
+                }
+            }
+            ParamUseKind::Literal(literal) => (!ty.fits(literal))
+                .then(|| format!("Literal `{}` does not fit in `{}`", literal.as_f64(), ty)),
+        }
+    }
+}
+
+/// Everything the type checker learned about a program: the user-defined
+/// types, the signatures of top-level functions and the type of every node
+/// it visited.
+#[derive(Debug, Default)]
+pub struct TypeInfo {
+    pub structs: Vec<StructDef>,
+    pub enums: Vec<EnumDef>,
+    pub functions: HashMap<String, FnSig>,
+    types: HashMap<Location, Type>,
+    coercions: HashMap<Location, Type>,
+    type_args: HashMap<Location, Vec<Type>>,
+    param_uses: Vec<ParamUse>,
+}
+impl TypeInfo {
+    pub fn struct_def(&self, name: &str) -> Option<&StructDef> {
+        self.structs.iter().find(|def| def.name == name)
+    }
+
+    pub fn enum_def(&self, name: &str) -> Option<&EnumDef> {
+        self.enums.iter().find(|def| def.name == name)
+    }
+
+    /// Finds the enum a variant constructor belongs to.
+    pub fn variant(&self, name: &str) -> Option<(&EnumDef, &VariantDef)> {
+        self.enums
+            .iter()
+            .find_map(|def| def.variant(name).map(|variant| (def, variant)))
+    }
+
+    /// Where a user-defined type or constructor name was defined, if it was.
+    fn definition(&self, name: &str) -> Option<Location> {
+        self.struct_def(name)
+            .map(|def| def.loc)
+            .or_else(|| self.enum_def(name).map(|def| def.loc))
+            .or_else(|| self.variant(name).map(|(def, _)| def.loc))
+    }
+
+    /// Every field of a struct, or of all the variants of an enum.
+    fn fields_of(&self, name: &str) -> Vec<&(String, Type)> {
+        match (self.struct_def(name), self.enum_def(name)) {
+            (Some(def), _) => def.fields.iter().collect(),
+            (_, Some(def)) => def
+                .variants
+                .iter()
+                .flat_map(|variant| &variant.fields)
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The user-defined types a type holds by value, including those held
+    /// inside arrays.
+    pub fn contained_types(&self, name: &str) -> Vec<&str> {
+        self.fields_of(name)
+            .into_iter()
+            .filter_map(|(_, ty)| {
+                let mut ty = ty;
+                while let Type::Array(element, _) = ty {
+                    ty = element.as_ref();
+                }
+                match ty {
+                    Type::Named(name) => Some(name.as_str()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// The type recorded for a node. For `let` and `for` this is the type of
+    /// the introduced binding rather than of the statement itself.
+    pub fn type_of(&self, node: &Node) -> Type {
+        self.types.get(&node.loc).cloned().unwrap_or(Type::Unknown)
+    }
+
+    /// The type a node's value is converted to where it is used, if that
+    /// differs from its own type, e.g. an array passed where a slice is
+    /// expected.
+    pub fn coercion_of(&self, node: &Node) -> Option<&Type> {
+        self.coercions.get(&node.loc)
+    }
+
+    /// The types a call to a generic function instantiates its type
+    /// parameters with, in the order they were declared.
+    pub fn type_args(&self, call: &Node) -> Option<&[Type]> {
+        self.type_args.get(&call.loc).map(Vec::as_slice)
+    }
+
+    /// What a generic function does to values of its type parameters that
+    /// has to be checked for each instance.
+    pub fn param_uses(&self, function: &str) -> impl Iterator<Item = &ParamUse> {
+        self.param_uses
+            .iter()
+            .filter(move |param_use| param_use.function == function)
+    }
+}
+
+struct Checker {
+    info: TypeInfo,
+    scopes: Vec<HashMap<String, Type>>,
+    /// The type parameters of the generic function being checked.
+    type_params: Vec<String>,
+    /// The name of that function.
+    generic: Option<String>,
+    returns: Option<Type>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+pub fn check_types(ast: &Node) -> (TypeInfo, Vec<Diagnostic>) {
+    let mut checker = Checker {
+        info: TypeInfo::default(),
+        scopes: vec![HashMap::new()],
+        type_params: vec![],
+        generic: None,
+        returns: None,
+        diagnostics: vec![],
+    };
+    let top_level: &[Node] = match &ast.kind {
+        NodeKind::Block { expressions } => expressions,
+        _ => std::slice::from_ref(ast),
+    };
+    checker.collect_types(top_level);
+    checker.collect_functions(top_level);
+    for node in top_level {
+        match &node.kind {
+            // already handled while collecting
+            NodeKind::Struct { .. }
+            | NodeKind::Enum { .. }
+            | NodeKind::Extern { .. }
+            | NodeKind::Include { .. } => checker.record(node, Type::Void),
+            _ => {
+                checker.check(node, None);
+            }
+        }
+    }
+    (checker.info, checker.diagnostics)
+}
+
+impl Checker {
+    fn collect_types(&mut self, top_level: &[Node]) {
+        for node in top_level {
+            match &node.kind {
+                NodeKind::Struct { name, fields } => {
+                    if !self.check_unique(name.name(), node.loc) {
+                        continue;
+                    }
+                    self.info.structs.push(StructDef {
+                        name: name.name().to_string(),
+                        fields: typed_fields(fields),
+                        loc: node.loc,
+                    });
+                }
+                NodeKind::Enum { name, variants } => {
+                    if !self.check_unique(name.name(), node.loc) {
+                        continue;
+                    }
+                    let mut defs = vec![];
+                    for variant in variants {
+                        if !self.check_unique(&variant.name, variant.loc) {
+                            continue;
+                        }
+                        defs.push(VariantDef {
+                            name: variant.name.clone(),
+                            fields: typed_fields(&variant.fields),
+                        });
+                    }
+                    self.info.enums.push(EnumDef {
+                        name: name.name().to_string(),
+                        variants: defs,
+                        loc: node.loc,
+                    });
+                }
+                _ => {}
+            }
+        }
+        // field types can only be checked once every type name is known
+        let mut defs: Vec<(String, Location)> = self
+            .info
+            .structs
+            .iter()
+            .map(|def| (def.name.clone(), def.loc))
+            .chain(
+                self.info
+                    .enums
+                    .iter()
+                    .map(|def| (def.name.clone(), def.loc)),
+            )
+            .collect();
+        defs.sort_by_key(|(_, loc)| loc.start);
+        for (name, loc) in defs {
+            let fields: Vec<(String, Type)> =
+                self.info.fields_of(&name).into_iter().cloned().collect();
+            for (field, ty) in &fields {
+                if *ty == Type::Void {
+                    self.error(Diagnostic::error(
+                        format!("Field `{}` of `{}` cannot be void", field, name),
+                        loc,
+                    ));
+                } else if ty.contains_slice() {
+                    self.error(Diagnostic::error(
+                        format!(
+                            "Field `{}` of `{}` cannot hold a slice; slices only live in parameters and local bindings",
+                            field, name
+                        ),
+                        loc,
+                    ));
+                } else {
+                    self.check_type_exists(ty, loc);
+                }
+            }
+            if self.type_contains(&name, &name, &mut HashSet::new()) {
+                self.error(Diagnostic::error(
+                    format!(
+                        "Recursive type `{}` has infinite size; it contains itself by value",
+                        name
+                    ),
+                    loc,
+                ));
+            }
+        }
+    }
+
+    /// Reports a type or constructor name that is already taken.
+    fn check_unique(&mut self, name: &str, loc: Location) -> bool {
+        match self.info.definition(name) {
+            Some(previous) => {
+                self.error(
+                    Diagnostic::error(format!("`{}` is defined twice", name), loc)
+                        .with_note("previous definition is here", previous),
+                );
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Whether `outer` contains a `target` by value, directly or through
+    /// another user-defined type.
+    fn type_contains(&self, outer: &str, target: &str, seen: &mut HashSet<String>) -> bool {
+        if !seen.insert(outer.to_string()) {
+            return false;
+        }
+        self.info
+            .contained_types(outer)
+            .into_iter()
+            .any(|name| name == target || self.type_contains(name, target, seen))
+    }
+
+    fn collect_functions(&mut self, top_level: &[Node]) {
+        for node in top_level {
+            if let NodeKind::Extern { functions } = &node.kind {
+                for function in functions {
+                    self.collect_extern(function);
+                }
+                continue;
+            }
+            let NodeKind::Fn {
+                name: Some(name),
+                type_params,
+                attributes,
+                returns,
+                params,
+                ..
+            } = &node.kind
+            else {
+                continue;
+            };
+            let exported = attributes.contains(&Attribute::Export);
+            if exported && !type_params.is_empty() {
+                self.error(Diagnostic::error(
+                    format!(
+                        "Generic function `{}` cannot be exported; C needs a single signature to call",
+                        name.name()
+                    ),
+                    node.loc,
+                ));
+            }
+            for param in type_params {
+                if let Some(previous) = self.info.definition(param) {
+                    self.error(
+                        Diagnostic::error(
+                            format!("Type parameter `{}` shadows a type or constructor", param),
+                            node.loc,
+                        )
+                        .with_note("that name is defined here", previous),
+                    );
+                }
+            }
+            self.type_params = type_params.clone();
+            let returns = self.annotation_type(returns);
+            self.check_type_exists(&returns, node.loc);
+            if returns.contains_slice() {
+                self.error(Diagnostic::error(
+                    format!(
+                        "Function `{}` cannot return a slice; the array it views may not outlive the call",
+                        name.name()
+                    ),
+                    node.loc,
+                ));
+            }
+            let params = params
+                .iter()
+                .map(|param| {
+                    let ty = self.annotation_type(param.annotation().unwrap_or_default());
+                    if ty == Type::Void {
+                        self.error(Diagnostic::error(
+                            format!("Parameter `{}` cannot be void", param.name()),
+                            node.loc,
+                        ));
+                    } else {
+                        self.check_type_exists(&ty, node.loc);
+                    }
+                    (param.name().to_string(), ty)
+                })
+                .collect::<Vec<_>>();
+            self.type_params.clear();
+            if name.name() == "main" {
+                self.check_entry(!type_params.is_empty(), &params, &returns, node.loc);
+            }
+            // type arguments are only ever inferred from the arguments
+            for param in type_params {
+                let generalized = Type::Param(param.clone());
+                if !params.iter().any(|(_, ty)| mentions(ty, &generalized)) {
+                    self.error(Diagnostic::error(
+                        format!(
+                            "Type parameter `{}` of `{}` is not used by any parameter, so it can never be inferred",
+                            param,
+                            name.name()
+                        ),
+                        node.loc,
+                    ));
+                }
+            }
+            if let Some(previous) = self.info.definition(name.name()) {
+                self.error(
+                    Diagnostic::error(
+                        format!(
+                            "Function `{}` has the same name as a type or constructor",
+                            name.name()
+                        ),
+                        node.loc,
+                    )
+                    .with_note("that name is defined here", previous),
+                );
+                continue;
+            }
+            if let Some(previous) = self.info.functions.get(name.name()) {
+                let previous = previous.loc;
+                self.error(
+                    Diagnostic::error(
+                        format!("Function `{}` is defined twice", name.name()),
+                        node.loc,
+                    )
+                    .with_note("previous definition is here", previous),
+                );
+                continue;
+            }
+            self.info.functions.insert(
+                name.name().to_string(),
+                FnSig {
+                    type_params: type_params.clone(),
+                    params,
+                    returns,
+                    external: false,
+                    exported,
+                    loc: node.loc,
+                },
+            );
+        }
+    }
+
+    /// Checks the signature of `main`, where the program starts. It may take
+    /// the command-line arguments, and what it returns is the exit code.
+    fn check_entry(
+        &mut self,
+        generic: bool,
+        params: &[(String, Type)],
+        returns: &Type,
+        loc: Location,
+    ) {
+        if generic {
+            self.error(Diagnostic::error("`main` cannot be generic", loc));
+        }
+        let valid = match params {
+            [] => true,
+            [(_, ty)] => *ty == Type::Slice(Box::new(Type::String)),
+            _ => false,
+        };
+        if !valid {
+            self.error(Diagnostic::error(
+                "`main` takes either no parameters or the command-line arguments as `[string]`",
+                loc,
+            ));
+        }
+        if !matches!(returns, Type::I32 | Type::Void) {
+            self.error(Diagnostic::error(
+                format!(
+                    "`main` must return `i32`, the exit code, or `void`, not `{}`",
+                    returns
+                ),
+                loc,
+            ));
+        }
+    }
+
+    /// Records the signature of a C function. Only types that mean the same
+    /// in C can cross over, and the same function may be declared more than
+    /// once, by several modules, as long as every declaration agrees.
+    fn collect_extern(&mut self, function: &ExternFn) {
+        let returns = Type::from_annotation(&function.returns);
+        if !(returns == Type::Void || is_c_compatible(&returns)) {
+            self.error(Diagnostic::error(
+                format!(
+                    "Extern function `{}` cannot return `{}`; only numbers, `bool`, `string` and `void` come back from C",
+                    function.name, returns
+                ),
+                function.loc,
+            ));
+        }
+        let params: Vec<(String, Type)> = function
+            .params
+            .iter()
+            .map(|param| {
+                let ty = Type::from_annotation(param.annotation().unwrap_or_default());
+                if !is_c_compatible(&ty) {
+                    self.error(Diagnostic::error(
+                        format!(
+                            "Parameter `{}` of extern function `{}` cannot be `{}`; only numbers, `bool` and `string` can be passed to C",
+                            param.name(),
+                            function.name,
+                            ty
+                        ),
+                        function.loc,
+                    ));
+                }
+                (param.name().to_string(), ty)
+            })
+            .collect();
+        if let Some(previous) = self.info.definition(&function.name) {
+            self.error(
+                Diagnostic::error(
+                    format!(
+                        "Function `{}` has the same name as a type or constructor",
+                        function.name
+                    ),
+                    function.loc,
+                )
+                .with_note("that name is defined here", previous),
+            );
+            return;
+        }
+        if let Some(previous) = self.info.functions.get(&function.name) {
+            let same = previous.external
+                && previous.returns == returns
+                && previous
+                    .params
+                    .iter()
+                    .map(|(_, ty)| ty)
+                    .eq(params.iter().map(|(_, ty)| ty));
+            if !same {
+                let (message, note) = if previous.external {
+                    (
+                        format!(
+                            "Extern function `{}` is declared again with a different signature",
+                            function.name
+                        ),
+                        "previous declaration is here",
+                    )
+                } else {
+                    (
+                        format!("Function `{}` is defined twice", function.name),
+                        "previous definition is here",
+                    )
+                };
+                let previous = previous.loc;
+                self.error(Diagnostic::error(message, function.loc).with_note(note, previous));
+            }
+            return;
+        }
+        self.info.functions.insert(
+            function.name.clone(),
+            FnSig {
+                type_params: vec![],
+                params,
+                returns,
+                external: true,
+                exported: false,
+                loc: function.loc,
+            },
+        );
+    }
+
+    fn check(&mut self, node: &Node, expected: Option<&Type>) -> Type {
+        let ty = match &node.kind {
+            NodeKind::Fn {
+                name,
+                type_params,
+                returns,
+                params,
+                body,
+                ..
+            } => {
+                let outer_params = std::mem::replace(&mut self.type_params, type_params.clone());
+                let outer_generic = self.generic.clone();
+                if let Some(name) = name
+                    && !type_params.is_empty()
+                {
+                    self.generic = Some(name.name().to_string());
+                }
+                self.scopes.push(HashMap::new());
+                for param in params {
+                    let ty = self.annotation_type(param.annotation().unwrap_or_default());
+                    self.declare(param.name(), ty);
+                }
+                let returns = self.annotation_type(returns);
+                let outer = self.returns.replace(returns);
+                self.check(body, None);
+                self.returns = outer;
+                self.scopes.pop();
+                self.type_params = outer_params;
+                self.generic = outer_generic;
+                Type::Void
+            }
+            NodeKind::Struct { name, .. } | NodeKind::Enum { name, .. } => {
+                self.error(Diagnostic::error(
+                    format!("Type `{}` must be defined at the top level", name.name()),
+                    node.loc,
+                ));
+                Type::Void
+            }
+            NodeKind::Field { target, field } => {
+                let target_ty = self.check(target, None);
+                self.field_type(&target_ty, field, node.loc)
+            }
+            NodeKind::Index { target, index } => {
+                let target_ty = self.check(target, None);
+                self.index_type(&target_ty, index, node.loc)
+            }
+            NodeKind::If { predicate, yes, no } => {
+                self.check(predicate, Some(&Type::Bool));
+                let yes_ty = self.check(yes, None);
+                match no {
+                    Some(no) if self.check(no, None) == yes_ty => yes_ty,
+                    _ => Type::Void,
+                }
+            }
+            NodeKind::Let { symbol, value, .. } => {
+                let ty = self.check_binding(symbol, value, node.loc);
+                self.declare(symbol.name(), ty.clone());
+                // the binding's type is what later passes need from a let
+                self.record(node, ty);
+                return Type::Void;
+            }
+            NodeKind::Set { place, value } => {
+                let target = self.check_place(place);
+                self.check(value, Some(&target));
+                Type::Void
+            }
+            NodeKind::For {
+                dummy,
+                iterator,
+                body,
+            } => {
+                let ty = self.check_iterator(dummy, iterator);
+                self.scopes.push(HashMap::new());
+                self.declare(dummy.name(), ty.clone());
+                self.check(body, None);
+                self.scopes.pop();
+                self.record(node, ty);
+                return Type::Void;
+            }
+            NodeKind::Given { predicate, cases } => {
+                let subject = self.check(predicate, None);
+                match &subject {
+                    Type::Named(name) if self.info.enum_def(name).is_some() => {
+                        let def = self.info.enum_def(name).unwrap().clone();
+                        self.check_variant_cases(&def, cases, node.loc);
+                    }
+                    _ => self.check_cases(&subject, cases),
+                }
+                Type::Void
+            }
+            NodeKind::Return { value } => {
+                match self.returns.clone() {
+                    Some(returns) => {
+                        self.check(value, Some(&returns));
+                    }
+                    None => {
+                        self.error(Diagnostic::error("`ret` outside of a function", node.loc));
+                        self.check(value, None);
+                    }
+                }
+                Type::Void
+            }
+            NodeKind::Identifier { symbol } => self.identifier_type(symbol, node.loc),
+            NodeKind::Literal(literal) => match literal {
+                Literal::String(_) => Type::String,
+                Literal::Boolean(_) => Type::Bool,
+                Literal::Number { literal } => match expected {
+                    Some(expected) if expected.is_numeric() => {
+                        if !expected.fits(literal) {
+                            self.error(Diagnostic::error(
+                                format!(
+                                    "Literal `{}` does not fit in `{}`",
+                                    literal.as_f64(),
+                                    expected
+                                ),
+                                node.loc,
+                            ));
+                        }
+                        // reported above, don't report a mismatch as well
+                        expected.clone()
+                    }
+                    Some(Type::Param(param)) => {
+                        self.defer(param, ParamUseKind::Literal(*literal), node.loc);
+                        Type::Param(param.clone())
+                    }
+                    _ => Type::of_number(literal),
+                },
+            },
+            NodeKind::Array { elements } => {
+                if elements.is_empty() {
+                    self.error(Diagnostic::error(
+                        "Array literals must have at least one element",
+                        node.loc,
+                    ));
+                    Type::Unknown
+                } else {
+                    let element = self.check_all(elements, expected.and_then(Type::element));
+                    match expected {
+                        Some(Type::Array(_, len)) if *len != elements.len() => {
+                            self.error(Diagnostic::error(
+                                format!(
+                                    "Expected an array of {} elements, found {}",
+                                    len,
+                                    elements.len()
+                                ),
+                                node.loc,
+                            ));
+                            // reported above, don't report a mismatch as well
+                            return Type::Unknown;
+                        }
+                        _ => Type::Array(Box::new(element), elements.len()),
+                    }
+                }
+            }
+            NodeKind::Call { name, args } => self.check_call(name.name(), args, expected, node.loc),
+            NodeKind::Block { expressions } => {
+                self.scopes.push(HashMap::new());
+                let mut ty = Type::Void;
+                for expression in expressions {
+                    ty = self.check(expression, None);
+                }
+                self.scopes.pop();
+                ty
+            }
+            // linking has already resolved these
+            NodeKind::Import { .. } => Type::Void,
+            NodeKind::Extern { .. } | NodeKind::Include { .. } => {
+                self.error(Diagnostic::error(
+                    "C declarations must be made at the top level",
+                    node.loc,
+                ));
+                Type::Void
+            }
+            NodeKind::Invalid => Type::Unknown,
+        };
+        if let Some(expected) = expected {
+            self.coerce(node, expected, &ty);
+        }
+        self.record(node, ty.clone());
+        ty
+    }
+
+    /// Checks that a value of type `actual` can be used where `expected` is
+    /// needed, remembering any conversion that takes.
+    fn coerce(&mut self, node: &Node, expected: &Type, actual: &Type) {
+        match (expected, actual) {
+            // arrays are viewed as slices wherever a slice is expected
+            (Type::Slice(element), Type::Array(actual, _)) if element == actual => {
+                self.info.coercions.insert(node.loc, expected.clone());
+            }
+            _ => self.expect(expected, actual, node.loc),
+        }
+    }
+
+    fn check_binding(&mut self, symbol: &Symbol, value: &Node, loc: Location) -> Type {
+        match symbol.annotation() {
+            Some(annotation) => {
+                let declared = self.annotation_type(annotation);
+                self.check_type_exists(&declared, loc);
+                self.check(value, Some(&declared));
+                declared
+            }
+            None => {
+                let inferred = self.check(value, None);
+                match inferred {
+                    Type::Void => self.error(Diagnostic::error(
+                        format!("Cannot bind `{}` to a value of type void", symbol.name()),
+                        loc,
+                    )),
+                    Type::Unknown => self.error(Diagnostic::error(
+                        format!(
+                            "Cannot infer the type of `{}`; add a type annotation",
+                            symbol.name()
+                        ),
+                        loc,
+                    )),
+                    _ => {}
+                }
+                inferred
+            }
+        }
+    }
+
+    /// Type of the loop variable bound by iterating over `iterator`.
+    fn check_iterator(&mut self, dummy: &Symbol, iterator: &Node) -> Type {
+        let annotated = dummy
+            .annotation()
+            .map(|annotation| self.annotation_type(annotation));
+        if let NodeKind::Call { name, args } = &iterator.kind
+            && name.name() == "range"
+        {
+            if args.is_empty() || args.len() > 2 {
+                self.error(Diagnostic::error(
+                    "Range must be of the shape (range end) or (range start end)",
+                    iterator.loc,
+                ));
+            }
+            let ty = self.check_all(args, annotated.as_ref());
+            if ty.is_known() && !ty.is_integer() {
+                self.error(Diagnostic::error(
+                    format!("Range bounds must be integers, found `{}`", ty),
+                    iterator.loc,
+                ));
+            }
+            self.record(iterator, ty.clone());
+            return ty;
+        }
+        let ty = self.check(iterator, None);
+        if let Some(element) = ty.element() {
+            if let Some(annotated) = &annotated {
+                self.expect(annotated, element, iterator.loc);
+            }
+            return element.clone();
+        }
+        if ty.is_known() {
+            self.error(Diagnostic::error(
+                format!("Cannot iterate over a value of type `{}`", ty),
+                iterator.loc,
+            ));
+        }
+        annotated.unwrap_or(Type::Unknown)
+    }
+
+    /// Checks values that have to share a type, such as the bounds of a
+    /// range or the elements of an array, and returns that type. Without an
+    /// expected type the first non-literal value decides it; otherwise the
+    /// literals get the type they would on their own, which is `i32` if it
+    /// holds them all.
+    fn check_all(&mut self, values: &[Node], expected: Option<&Type>) -> Type {
+        let mut decided_by = None;
+        let ty = match expected {
+            Some(ty) => ty.clone(),
+            None => match values
+                .iter()
+                .position(|value| !matches!(value.kind, NodeKind::Literal(Literal::Number { .. })))
+            {
+                Some(index) => {
+                    decided_by = Some(index);
+                    self.check(&values[index], None)
+                }
+                None => literal_type(values),
+            },
+        };
+        for (index, value) in values.iter().enumerate() {
+            if Some(index) != decided_by {
+                self.check(value, Some(&ty));
+            }
+        }
+        ty
+    }
+
+    fn check_cases(&mut self, subject: &Type, cases: &Node) {
+        let NodeKind::Block { expressions } = &cases.kind else {
+            return;
+        };
+        let mut seen = HashSet::new();
+        let mut has_default = false;
+        for case in expressions {
+            let NodeKind::Block { expressions } = &case.kind else {
+                continue;
+            };
+            let [pattern, body] = expressions.as_slice() else {
+                continue;
+            };
+            if has_default {
+                self.unreachable_case("an earlier `_` case matches everything", case.loc);
+            }
+            self.scopes.push(HashMap::new());
+            match &pattern.kind {
+                NodeKind::Identifier { symbol } if symbol.name() == "_" => {
+                    has_default = true;
+                    self.record(pattern, subject.clone());
+                }
+                NodeKind::Literal(literal) => {
+                    if !has_default && !seen.insert(format!("{:?}", literal)) {
+                        self.unreachable_case("the same value is matched above", case.loc);
+                    }
+                    self.check(pattern, Some(subject));
+                }
+                _ => self.error(Diagnostic::error(
+                    "Case pattern must be a literal or `_`",
+                    pattern.loc,
+                )),
+            }
+            self.check(body, None);
+            self.scopes.pop();
+            self.record(case, Type::Void);
+        }
+        self.record(cases, Type::Void);
+    }
+
+    /// Checks the cases of a `given` over an enum. Each pattern names a
+    /// variant and binds its fields in order, and together the cases have to
+    /// cover every variant.
+    fn check_variant_cases(&mut self, def: &EnumDef, cases: &Node, loc: Location) {
+        let NodeKind::Block { expressions } = &cases.kind else {
+            return;
+        };
+        let subject = Type::Named(def.name.clone());
+        let mut covered = HashSet::new();
+        let mut has_default = false;
+        for case in expressions {
+            let NodeKind::Block { expressions } = &case.kind else {
+                continue;
+            };
+            let [pattern, body] = expressions.as_slice() else {
+                continue;
+            };
+            self.scopes.push(HashMap::new());
+            let (name, bindings): (&str, &[Node]) = match &pattern.kind {
+                NodeKind::Identifier { symbol } => (symbol.name(), &[]),
+                NodeKind::Call { name, args } => (name.name(), args),
+                _ => ("", &[]),
+            };
+            if has_default {
+                self.unreachable_case("an earlier `_` case matches everything", case.loc);
+            } else if name == "_" && covered.len() == def.variants.len() {
+                self.unreachable_case(
+                    &format!("every variant of `{}` is matched above", def.name),
+                    case.loc,
+                );
+            } else if covered.contains(name) {
+                self.unreachable_case(&format!("`{}` is matched above", name), case.loc);
+            }
+            if name == "_" && bindings.is_empty() {
+                has_default = true;
+            } else if let Some(variant) = def.variant(name) {
+                covered.insert(name.to_string());
+                if bindings.len() != variant.fields.len() {
+                    self.error(Diagnostic::error(
+                        format!(
+                            "Variant `{}` has {} fields but the pattern binds {}",
+                            name,
+                            variant.fields.len(),
+                            bindings.len()
+                        ),
+                        pattern.loc,
+                    ));
+                }
+                for (binding, (_, ty)) in bindings.iter().zip(&variant.fields) {
+                    match &binding.kind {
+                        NodeKind::Identifier {
+                            symbol: Symbol::Untyped { name },
+                        } => {
+                            if name != "_" {
+                                self.declare(name, ty.clone());
+                            }
+                            self.record(binding, ty.clone());
+                        }
+                        _ => self.error(Diagnostic::error(
+                            "Variant fields can only be bound to plain names",
+                            binding.loc,
+                        )),
+                    }
+                }
+            } else {
+                self.error(Diagnostic::error(
+                    format!("Case pattern must be a variant of `{}` or `_`", def.name),
+                    pattern.loc,
+                ));
+            }
+            self.record(pattern, subject.clone());
+            self.check(body, None);
+            self.scopes.pop();
+            self.record(case, Type::Void);
+        }
+        self.record(cases, Type::Void);
+        let missing: Vec<String> = def
+            .variants
+            .iter()
+            .filter(|variant| !covered.contains(&variant.name))
+            .map(|variant| format!("`{}`", variant.name))
+            .collect();
+        if !has_default && !missing.is_empty() {
+            self.error(Diagnostic::error(
+                format!(
+                    "Non-exhaustive given over `{}`: missing {}",
+                    def.name,
+                    missing.join(", ")
+                ),
+                loc,
+            ));
+        }
+    }
+
+    fn check_call(
+        &mut self,
+        name: &str,
+        args: &[Node],
+        expected: Option<&Type>,
+        loc: Location,
+    ) -> Type {
+        if let Some(def) = self.info.struct_def(name) {
+            let def = def.clone();
+            if args.len() != def.fields.len() {
+                self.error(Diagnostic::error(
+                    format!(
+                        "Struct `{}` has {} fields but {} values were given",
+                        name,
+                        def.fields.len(),
+                        args.len()
+                    ),
+                    loc,
+                ));
+            }
+            for (arg, (_, ty)) in args.iter().zip(&def.fields) {
+                self.check(arg, Some(ty));
+            }
+            return Type::Named(def.name);
+        }
+        if let Some((def, variant)) = self.info.variant(name) {
+            let (enum_name, variant) = (def.name.clone(), variant.clone());
+            if args.len() != variant.fields.len() {
+                self.error(Diagnostic::error(
+                    format!(
+                        "Variant `{}` has {} fields but {} values were given",
+                        name,
+                        variant.fields.len(),
+                        args.len()
+                    ),
+                    loc,
+                ));
+            }
+            for (arg, (_, ty)) in args.iter().zip(&variant.fields) {
+                self.check(arg, Some(ty));
+            }
+            return Type::Named(enum_name);
+        }
+        if let Some(sig) = self.info.functions.get(name)
+            && !sig.type_params.is_empty()
+        {
+            let sig = sig.clone();
+            return self.check_generic_call(name, &sig, args, expected, loc);
+        }
+        if let Some(sig) = self.info.functions.get(name) {
+            let sig = sig.clone();
+            if args.len() != sig.params.len() {
+                self.error(
+                    Diagnostic::error(
+                        format!(
+                            "Function `{}` takes {} arguments but {} were given",
+                            name,
+                            sig.params.len(),
+                            args.len()
+                        ),
+                        loc,
+                    )
+                    .with_note("the function is defined here", sig.loc),
+                );
+            }
+            for (arg, (_, ty)) in args.iter().zip(&sig.params) {
+                self.check(arg, Some(ty));
+            }
+            return sig.returns;
+        }
+        if let Some(operator) = Operator::from_name(name) {
+            return self.check_operator(operator, name, args, expected, loc);
+        }
+        if BUILTINS.contains(&name) {
+            return self.check_builtin(name, args, loc);
+        }
+        self.error(Diagnostic::error(
+            format!("Unknown function `{}`", name),
+            loc,
+        ));
+        for arg in args {
+            self.check(arg, None);
+        }
+        Type::Unknown
+    }
+
+    fn check_operator(
+        &mut self,
+        operator: Operator,
+        name: &str,
+        args: &[Node],
+        expected: Option<&Type>,
+        loc: Location,
+    ) -> Type {
+        let (min, max) = operator.arity();
+        if args.len() < min || max.is_some_and(|max| args.len() > max) {
+            let count = match max {
+                Some(max) if max == min => min.to_string(),
+                _ => format!("at least {}", min),
+            };
+            self.error(Diagnostic::error(
+                format!(
+                    "`{}` takes {} argument{} but {} were given",
+                    name,
+                    count,
+                    if min == 1 { "" } else { "s" },
+                    args.len()
+                ),
+                loc,
+            ));
+        }
+        match operator.kind() {
+            OperatorKind::Logical => {
+                for arg in args {
+                    self.check(arg, Some(&Type::Bool));
+                }
+                Type::Bool
+            }
+            OperatorKind::Comparison => {
+                self.check_operands(operator, name, args, None);
+                Type::Bool
+            }
+            OperatorKind::Arithmetic | OperatorKind::Bitwise => {
+                let expected = expected.filter(|ty| ty.is_numeric());
+                if !matches!(operator, Operator::Shl | Operator::Shr) {
+                    let ty = self.check_operands(operator, name, args, expected);
+                    if operator == Operator::Sub
+                        && args.len() == 1
+                        && ty.is_integer()
+                        && ty.integer_range().is_some_and(|(min, _)| min == 0)
+                    {
+                        self.error(Diagnostic::error(
+                            format!("Cannot negate a value of type `{}`", ty),
+                            loc,
+                        ));
+                    }
+                    return ty;
+                }
+                // the shifted value decides the type, the amount is any integer
+                let (value, amounts) = args.split_at(args.len().min(1));
+                let ty = self.check_operands(operator, name, value, expected);
+                for amount in amounts {
+                    let amount_ty = self.check(amount, None);
+                    if amount_ty.is_known() && !amount_ty.is_integer() {
+                        self.error(Diagnostic::error(
+                            format!("`{}` expects integers, found `{}`", name, amount_ty),
+                            amount.loc,
+                        ));
+                    }
+                }
+                ty
+            }
+        }
+    }
+
+    /// Checks the operands of an operator and returns the type they are
+    /// promoted to. Like the arguments of a generic call, literals, and
+    /// arithmetic on nothing but literals, are checked last and take on the
+    /// type the other operands decided.
+    fn check_operands(
+        &mut self,
+        operator: Operator,
+        name: &str,
+        args: &[Node],
+        expected: Option<&Type>,
+    ) -> Type {
+        let is_literal = is_literal_arithmetic;
+        let mut operands = vec![];
+        for arg in args.iter().filter(|arg| !is_literal(arg)) {
+            let ty = self.check(arg, None);
+            operands.push((arg, ty));
+        }
+        // what a type parameter allows is only known once it is bound, so
+        // every operand has to be of the parameter, and each instance of
+        // the function checks the operator for the type it is made for
+        let param = operands
+            .iter()
+            .map(|(_, ty)| ty)
+            .chain(expected.filter(|_| operands.is_empty()))
+            .find(|ty| matches!(ty, Type::Param(_)))
+            .cloned();
+        if let Some(Type::Param(name)) = &param {
+            let ty = Type::Param(name.clone());
+            for (arg, actual) in &operands {
+                self.expect(&ty, actual, arg.loc);
+            }
+            for arg in args.iter().filter(|arg| is_literal(arg)) {
+                self.check(arg, Some(&ty));
+            }
+            let kind = ParamUseKind::Operator {
+                operator,
+                operands: args.len(),
+            };
+            let loc = args.first().map_or(Location::default(), |arg| arg.loc);
+            self.defer(name, kind, loc);
+            return ty;
+        }
+        // bools and strings can be told apart, but nothing else can be done
+        // to them
+        if matches!(operator, Operator::Eq | Operator::Ne)
+            && let Some((_, ty)) = operands
+                .iter()
+                .find(|(_, ty)| matches!(ty, Type::Bool | Type::String))
+        {
+            let ty = ty.clone();
+            for (arg, actual) in &operands {
+                self.expect(&ty, actual, arg.loc);
+            }
+            for arg in args.iter().filter(|arg| is_literal(arg)) {
+                self.check(arg, Some(&ty));
+            }
+            return ty;
+        }
+        let wanted = if operator.needs_integers() {
+            "integers"
+        } else {
+            "numbers"
+        };
+        let mut promoted: Option<Type> = None;
+        for (arg, ty) in &operands {
+            if !ty.is_known() {
+                return Type::Unknown;
+            }
+            if !ty.is_numeric() || (operator.needs_integers() && !ty.is_integer()) {
+                self.error(Diagnostic::error(
+                    format!("`{}` expects {}, found `{}`", name, wanted, ty),
+                    arg.loc,
+                ));
+                return Type::Unknown;
+            }
+            promoted = match promoted {
+                None => Some(ty.clone()),
+                Some(so_far) => match so_far.promote(ty) {
+                    Some(promoted) => Some(promoted),
+                    None => {
+                        self.error(Diagnostic::error(
+                            format!(
+                                "Cannot mix `{}` and `{}` in `{}`; no type holds both",
+                                so_far, ty, name
+                            ),
+                            arg.loc,
+                        ));
+                        return Type::Unknown;
+                    }
+                },
+            };
+        }
+        // the result widens to what the context wants where it can
+        let ty = match (promoted, expected) {
+            (Some(promoted), Some(expected)) => promoted.promote(expected).unwrap_or(promoted),
+            (Some(promoted), None) => promoted,
+            (None, Some(expected)) => expected.clone(),
+            (None, None) => literal_type(args),
+        };
+        if operator.needs_integers() && ty.is_float() {
+            self.error(Diagnostic::error(
+                format!("`{}` expects {}, found `{}`", name, wanted, ty),
+                args.first().map_or(Location::default(), |arg| arg.loc),
+            ));
+            return Type::Unknown;
+        }
+        for (arg, actual) in &operands {
+            if *actual != ty {
+                self.info.coercions.insert(arg.loc, ty.clone());
+            }
+        }
+        for arg in args.iter().filter(|arg| is_literal(arg)) {
+            self.check(arg, Some(&ty));
+        }
+        ty
+    }
+
+    /// Checks a call to one of the builtins the runtime library provides.
+    fn check_builtin(&mut self, name: &str, args: &[Node], loc: Location) -> Type {
+        let arity = match name {
+            "concat" | "compare" => 2,
+            _ => 1,
+        };
+        if args.len() != arity {
+            self.error(Diagnostic::error(
+                format!(
+                    "`{}` takes {} argument{} but {} were given",
+                    name,
+                    arity,
+                    if arity == 1 { "" } else { "s" },
+                    args.len()
+                ),
+                loc,
+            ));
+        }
+        match name {
+            "len" => {
+                for arg in args {
+                    let ty = self.check(arg, None);
+                    if ty.is_known() && ty.element().is_none() && ty != Type::String {
+                        self.error(Diagnostic::error(
+                            format!("Cannot take the length of a value of type `{}`", ty),
+                            arg.loc,
+                        ));
+                    }
+                }
+                Type::U64
+            }
+            "print" | "println" => {
+                for arg in args {
+                    let ty = self.check(arg, None);
+                    if ty.is_known() && !ty.is_numeric() && !matches!(ty, Type::Bool | Type::String)
+                    {
+                        self.error(Diagnostic::error(
+                            format!("Cannot print a value of type `{}`", ty),
+                            arg.loc,
+                        ));
+                    }
+                }
+                Type::Void
+            }
+            _ => {
+                for arg in args {
+                    self.check(arg, Some(&Type::String));
+                }
+                if name == "concat" {
+                    Type::String
+                } else {
+                    Type::I32
+                }
+            }
+        }
+    }
+
+    /// Infers the type arguments of a call to a generic function from its
+    /// arguments and checks the arguments against the instantiated
+    /// parameters. Literals are checked last so they can take on a type the
+    /// other arguments fixed, as in `(max x 1)`.
+    fn check_generic_call(
+        &mut self,
+        name: &str,
+        sig: &FnSig,
+        args: &[Node],
+        expected: Option<&Type>,
+        loc: Location,
+    ) -> Type {
+        if args.len() != sig.params.len() {
+            self.error(
+                Diagnostic::error(
+                    format!(
+                        "Function `{}` takes {} arguments but {} were given",
+                        name,
+                        sig.params.len(),
+                        args.len()
+                    ),
+                    loc,
+                )
+                .with_note("the function is defined here", sig.loc),
+            );
+        }
+        let (literals, others): (Vec<_>, Vec<_>) = args
+            .iter()
+            .zip(sig.params.iter().map(|(_, ty)| ty))
+            .partition(|(arg, _)| matches!(arg.kind, NodeKind::Literal(Literal::Number { .. })));
+        let mut bindings = HashMap::new();
+        let mut inferred = vec![];
+        for (arg, param) in &others {
+            if let Some((param, ty)) = self.check_generic_arg(arg, param, &mut bindings) {
+                inferred.push((*arg, param, ty));
+            }
+        }
+        // what the caller wants back decides before literals do, as in
+        // (let x:i64 (max 1 2))
+        if let Some(expected) = expected {
+            bind(&sig.returns, expected, &mut bindings);
+        }
+        // literals that are all there is to go by get the type they would
+        // on their own, `i32` if it holds every one of them
+        for (_, param) in &literals {
+            if let Type::Param(name) = param
+                && !bindings.contains_key(name)
+            {
+                let values = literals
+                    .iter()
+                    .filter(|(_, other)| other == param)
+                    .map(|(arg, _)| *arg);
+                bindings.insert(name.clone(), literal_type(values));
+            }
+        }
+        for (arg, param) in &literals {
+            if let Some((param, ty)) = self.check_generic_arg(arg, param, &mut bindings) {
+                inferred.push((*arg, param, ty));
+            }
+        }
+        let type_args: Option<Vec<Type>> = sig
+            .type_params
+            .iter()
+            .map(|param| bindings.get(param).cloned())
+            .collect();
+        // only happens when an argument's type is unknown, which has been
+        // reported already
+        let Some(type_args) = type_args else {
+            return Type::Unknown;
+        };
+        // later arguments may have fixed what the earlier ones are held to
+        for (arg, param, ty) in inferred {
+            self.coerce(arg, &param.substitute(&bindings), &ty);
+        }
+        self.info.type_args.insert(loc, type_args);
+        sig.returns.substitute(&bindings)
+    }
+
+    /// Checks an argument against a parameter as far as the bindings so far
+    /// allow. If the parameter still mentions a type parameter, the argument
+    /// goes towards binding it, and the pair is returned to be checked again
+    /// once all bindings are known.
+    fn check_generic_arg(
+        &mut self,
+        arg: &Node,
+        param: &Type,
+        bindings: &mut HashMap<String, Type>,
+    ) -> Option<(Type, Type)> {
+        let param = param.substitute(bindings);
+        if !param.has_params() {
+            self.check(arg, Some(&param));
+            return None;
+        }
+        let ty = self.check(arg, None);
+        bind(&param, &ty, bindings);
+        Some((param, ty))
+    }
+
+    fn identifier_type(&mut self, symbol: &Symbol, loc: Location) -> Type {
+        let name = symbol.name();
+        if let Some(ty) = self.lookup(name) {
+            return ty;
+        }
+        if let Some((def, variant)) = self.info.variant(name) {
+            if variant.fields.is_empty() {
+                return Type::Named(def.name.clone());
+            }
+            self.error(Diagnostic::error(
+                format!(
+                    "Variant `{}` carries fields; construct it with ({} ...)",
+                    name, name
+                ),
+                loc,
+            ));
+            return Type::Unknown;
+        }
+        if !self.info.functions.contains_key(name) {
+            self.error(Diagnostic::error(
+                format!("Unknown identifier `{}`", name),
+                loc,
+            ));
+        }
+        Type::Unknown
+    }
+
+    /// Resolves an assignment target such as `p`, `p.x.y` or `(at xs i)`.
+    fn check_place(&mut self, place: &Node) -> Type {
+        let ty = match &place.kind {
+            // undeclared targets are reported by the mutability check
+            NodeKind::Identifier { symbol } => self.lookup(symbol.name()).unwrap_or(Type::Unknown),
+            NodeKind::Field { target, field } => {
+                let target_ty = self.check_place(target);
+                self.field_type(&target_ty, field, place.loc)
+            }
+            NodeKind::Index { target, index } => {
+                let target_ty = self.check_place(target);
+                if let Type::Slice(_) = target_ty {
+                    self.error(Diagnostic::error(
+                        "Cannot assign to an element of a slice; slices are read-only",
+                        place.loc,
+                    ));
+                }
+                self.index_type(&target_ty, index, place.loc)
+            }
+            _ => self.check(place, None),
+        };
+        self.record(place, ty.clone());
+        ty
+    }
+
+    fn field_type(&mut self, target: &Type, field: &str, loc: Location) -> Type {
+        match target {
+            Type::Unknown => Type::Unknown,
+            Type::Named(name) => match self.info.struct_def(name).map(|def| def.field(field)) {
+                Some(Some(ty)) => ty.clone(),
+                Some(None) => {
+                    self.error(Diagnostic::error(
+                        format!("Struct `{}` has no field `{}`", name, field),
+                        loc,
+                    ));
+                    Type::Unknown
+                }
+                None if self.info.enum_def(name).is_some() => {
+                    self.error(Diagnostic::error(
+                        format!("Enum `{}` has no fields; destructure it with given", name),
+                        loc,
+                    ));
+                    Type::Unknown
+                }
+                None => Type::Unknown,
+            },
+            _ => {
+                self.error(Diagnostic::error(
+                    format!("Type `{}` has no fields", target),
+                    loc,
+                ));
+                Type::Unknown
+            }
+        }
+    }
+
+    fn index_type(&mut self, target: &Type, index: &Node, loc: Location) -> Type {
+        let index_ty = self.check(index, None);
+        if index_ty.is_known() && !index_ty.is_integer() {
+            self.error(Diagnostic::error(
+                format!("Array index must be an integer, found `{}`", index_ty),
+                index.loc,
+            ));
+        }
+        match target {
+            Type::Array(element, len) => {
+                if let NodeKind::Literal(Literal::Number { literal }) = &index.kind
+                    && let Some(value) = literal.as_i128()
+                    && (value < 0 || value >= *len as i128)
+                {
+                    self.error(Diagnostic::error(
+                        format!(
+                            "Index {} is out of bounds for an array of length {}",
+                            value, len
+                        ),
+                        index.loc,
+                    ));
+                }
+                element.as_ref().clone()
+            }
+            Type::Slice(element) => element.as_ref().clone(),
+            Type::Unknown => Type::Unknown,
+            _ => {
+                self.error(Diagnostic::error(
+                    format!("Cannot index into a value of type `{}`", target),
+                    loc,
+                ));
+                Type::Unknown
+            }
+        }
+    }
+
+    /// The type an annotation names, with the type parameters in scope
+    /// resolved.
+    fn annotation_type(&self, annotation: &str) -> Type {
+        Type::from_annotation(annotation).generalize(&self.type_params)
+    }
+
+    fn check_type_exists(&mut self, ty: &Type, loc: Location) {
+        match ty {
+            Type::Named(name)
+                if self.info.struct_def(name).is_none() && self.info.enum_def(name).is_none() =>
+            {
+                self.error(Diagnostic::error(format!("Unknown type `{}`", name), loc));
+            }
+            Type::Array(element, len) => {
+                if *len == 0 {
+                    self.error(Diagnostic::error(
+                        format!("Array type `{}` must have at least one element", ty),
+                        loc,
+                    ));
+                }
+                self.check_type_exists(element, loc);
+            }
+            Type::Slice(element) => self.check_type_exists(element, loc),
+            _ => {}
+        }
+    }
+
+    fn expect(&mut self, expected: &Type, actual: &Type, loc: Location) {
+        if expected.is_known() && actual.is_known() && expected != actual {
+            self.error(Diagnostic::error(
+                format!(
+                    "Mismatched types: expected `{}`, found `{}`",
+                    expected, actual
+                ),
+                loc,
+            ));
+        }
+    }
+
+    fn declare(&mut self, name: &str, ty: Type) {
+        self.scopes
+            .last_mut()
+            .expect("scope stack is never empty")
+            .insert(name.to_string(), ty);
+    }
+
+    fn lookup(&self, name: &str) -> Option<Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+    }
+
+    fn record(&mut self, node: &Node, ty: Type) {
+        self.info.types.insert(node.loc, ty);
+    }
+
+    fn unreachable_case(&mut self, reason: &str, loc: Location) {
+        self.error(Diagnostic::warning(
+            format!("Unreachable case: {}", reason),
+            loc,
+        ));
+    }
+
+    fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Leaves a use of a type parameter to be checked for each instance of
+    /// the generic function it is in.
+    fn defer(&mut self, param: &str, kind: ParamUseKind, loc: Location) {
+        if let Some(function) = &self.generic {
+            self.info.param_uses.push(ParamUse {
+                function: function.clone(),
+                param: param.to_string(),
+                kind,
+                loc,
+            });
+        }
+    }
+}
+
+/// Binds the type parameters in `param` to the matching parts of `actual`.
+/// The first binding wins; a conflicting one shows up as a mismatch once the
+/// parameters are substituted.
+fn bind(param: &Type, actual: &Type, bindings: &mut HashMap<String, Type>) {
+    match (param, actual) {
+        (Type::Param(name), actual) if actual.is_known() => {
+            bindings
+                .entry(name.clone())
+                .or_insert_with(|| actual.clone());
+        }
+        (
+            Type::Array(param, _) | Type::Slice(param),
+            Type::Array(actual, _) | Type::Slice(actual),
+        ) => bind(param, actual, bindings),
+        _ => {}
+    }
+}
+
+/// Whether `ty` is or holds `part`.
+fn mentions(ty: &Type, part: &Type) -> bool {
+    ty == part || ty.element().is_some_and(|element| mentions(element, part))
+}
+
+/// The type numeric literals among `values` share when nothing else decides
+/// it: `i32`, unless one of them does not fit, as [`Type::of_number`].
+fn literal_type<'a>(values: impl IntoIterator<Item = &'a Node>) -> Type {
+    let literals: Vec<&Number> = values
+        .into_iter()
+        .filter_map(|value| match &value.kind {
+            NodeKind::Literal(Literal::Number { literal }) => Some(literal),
+            _ => None,
+        })
+        .collect();
+    [Type::I32, Type::I64, Type::U64, Type::F64]
+        .into_iter()
+        .find(|ty| literals.iter().all(|literal| ty.fits(literal)))
+        .unwrap_or(Type::F64)
+}
+
+/// Whether a node is a numeric literal, or arithmetic on nothing but them
+/// such as `(<< 1 3)`, whose type the context decides.
+fn is_literal_arithmetic(node: &Node) -> bool {
+    match &node.kind {
+        NodeKind::Literal(Literal::Number { .. }) => true,
+        NodeKind::Call { name, args } => {
+            Operator::from_name(name.name()).is_some_and(|operator| {
+                matches!(
+                    operator.kind(),
+                    OperatorKind::Arithmetic | OperatorKind::Bitwise
+                )
+            }) && args.iter().all(is_literal_arithmetic)
+        }
+        _ => false,
+    }
+}
+
+/// Whether values of a type look the same to C as they do to Crisp.
+fn is_c_compatible(ty: &Type) -> bool {
+    ty.is_numeric() || matches!(ty, Type::Bool | Type::String)
+}
+
+fn typed_fields(fields: &[Symbol]) -> Vec<(String, Type)> {
+    fields
+        .iter()
+        .map(|field| {
+            (
+                field.name().to_string(),
+                Type::from_annotation(field.annotation().unwrap_or_default()),
+            )
+        })
+        .collect()
+}
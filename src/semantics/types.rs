@@ -0,0 +1,226 @@
+use std::{collections::HashMap, fmt};
+
+use crate::parsing::ast::nodes::Number;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Bool,
+    String,
+    Void,
+    /// A user-defined type, resolved against the struct definitions.
+    Named(String),
+    /// `[T;N]`, exactly `N` values of `T` held inline.
+    Array(Box<Type>, usize),
+    /// `[T]`, a read-only view of an array whose length is only known at
+    /// runtime.
+    Slice(Box<Type>),
+    /// A type parameter of a generic function, standing in for whatever
+    /// type a call instantiates it with.
+    Param(String),
+    /// The type of an expression the checker could not resolve, e.g. a call
+    /// to a function it knows nothing about.
+    Unknown,
+}
+impl Type {
+    pub fn from_annotation(annotation: &str) -> Type {
+        match annotation {
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "i32" => Type::I32,
+            "i64" => Type::I64,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
+            "bool" => Type::Bool,
+            "string" => Type::String,
+            "void" => Type::Void,
+            sequence if sequence.starts_with('[') && sequence.ends_with(']') => {
+                let inner = &sequence[1..sequence.len() - 1];
+                // the length follows the last `;`, unless that belongs to a
+                // nested array as in [[i32;2]]
+                if let Some((element, len)) = inner.rsplit_once(';')
+                    && let Ok(len) = len.parse()
+                {
+                    return Type::Array(Box::new(Type::from_annotation(element)), len);
+                }
+                Type::Slice(Box::new(Type::from_annotation(inner)))
+            }
+            name => Type::Named(name.to_string()),
+        }
+    }
+
+    /// The type of a numeric literal that nothing else decides: `i32`, or
+    /// the first of `i64` and `u64` that holds it if it does not fit, and
+    /// `f64` for a float.
+    pub fn of_number(number: &Number) -> Type {
+        match number {
+            Number::F32(_) | Number::F64(_) => Type::F64,
+            _ => [Type::I32, Type::I64, Type::U64]
+                .into_iter()
+                .find(|ty| ty.fits(number))
+                .unwrap_or(Type::U64),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.integer_range().is_some()
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Type::F32 | Type::F64)
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Type::Unknown)
+    }
+
+    /// The type of the elements of an array or slice.
+    pub fn element(&self) -> Option<&Type> {
+        match self {
+            Type::Array(element, _) | Type::Slice(element) => Some(element),
+            _ => None,
+        }
+    }
+
+    /// Turns the names of type parameters into [`Type::Param`]s.
+    pub fn generalize(self, params: &[String]) -> Type {
+        match self {
+            Type::Named(name) if params.contains(&name) => Type::Param(name),
+            Type::Array(element, len) => Type::Array(Box::new(element.generalize(params)), len),
+            Type::Slice(element) => Type::Slice(Box::new(element.generalize(params))),
+            ty => ty,
+        }
+    }
+
+    /// Replaces the type parameters that have a binding.
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Param(name) => bindings.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Type::Array(element, len) => Type::Array(Box::new(element.substitute(bindings)), *len),
+            Type::Slice(element) => Type::Slice(Box::new(element.substitute(bindings))),
+            ty => ty.clone(),
+        }
+    }
+
+    /// Whether a type parameter appears anywhere in this type.
+    pub fn has_params(&self) -> bool {
+        match self {
+            Type::Param(_) => true,
+            Type::Array(element, _) | Type::Slice(element) => element.has_params(),
+            _ => false,
+        }
+    }
+
+    /// Whether a slice appears anywhere in this type.
+    pub fn contains_slice(&self) -> bool {
+        match self {
+            Type::Slice(_) => true,
+            Type::Array(element, _) => element.contains_slice(),
+            _ => false,
+        }
+    }
+
+    /// Inclusive bounds of an integer type.
+    pub fn integer_range(&self) -> Option<(i128, i128)> {
+        match self {
+            Type::I8 => Some((i8::MIN as i128, i8::MAX as i128)),
+            Type::I16 => Some((i16::MIN as i128, i16::MAX as i128)),
+            Type::I32 => Some((i32::MIN as i128, i32::MAX as i128)),
+            Type::I64 => Some((i64::MIN as i128, i64::MAX as i128)),
+            Type::U8 => Some((0, u8::MAX as i128)),
+            Type::U16 => Some((0, u16::MAX as i128)),
+            Type::U32 => Some((0, u32::MAX as i128)),
+            Type::U64 => Some((0, u64::MAX as i128)),
+            _ => None,
+        }
+    }
+
+    /// The type two numbers meet at when an operator combines them: the
+    /// wider of the two, a float over an integer, and for a signed and an
+    /// unsigned integer the narrowest signed type that holds both. `None` if
+    /// either is not a number or no type holds both, as for `u64` and `i8`.
+    pub fn promote(&self, other: &Type) -> Option<Type> {
+        if !self.is_numeric() || !other.is_numeric() {
+            return None;
+        }
+        if self == other {
+            return Some(self.clone());
+        }
+        match (self.integer_range(), other.integer_range()) {
+            (Some((a_min, a_max)), Some((b_min, b_max))) => {
+                let (min, max) = (a_min.min(b_min), a_max.max(b_max));
+                [
+                    Type::I8,
+                    Type::U8,
+                    Type::I16,
+                    Type::U16,
+                    Type::I32,
+                    Type::U32,
+                    Type::I64,
+                    Type::U64,
+                ]
+                .into_iter()
+                .find(|ty| {
+                    ty.integer_range()
+                        .is_some_and(|(lo, hi)| lo <= min && max <= hi)
+                })
+            }
+            (Some(_), None) => Some(other.clone()),
+            (None, Some(_)) => Some(self.clone()),
+            (None, None) => Some(Type::F64),
+        }
+    }
+
+    /// Whether a numeric literal can be given this type without losing its
+    /// value. Integer literals may become floats, but not the other way round.
+    pub fn fits(&self, number: &Number) -> bool {
+        match number.as_i128() {
+            Some(value) => match self.integer_range() {
+                Some((min, max)) => value >= min && value <= max,
+                None => self.is_float(),
+            },
+            None => self.is_float(),
+        }
+    }
+}
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Type::Array(element, len) => return write!(f, "[{};{}]", element, len),
+            Type::Slice(element) => return write!(f, "[{}]", element),
+            Type::I8 => "i8",
+            Type::I16 => "i16",
+            Type::I32 => "i32",
+            Type::I64 => "i64",
+            Type::U8 => "u8",
+            Type::U16 => "u16",
+            Type::U32 => "u32",
+            Type::U64 => "u64",
+            Type::F32 => "f32",
+            Type::F64 => "f64",
+            Type::Bool => "bool",
+            Type::String => "string",
+            Type::Void => "void",
+            Type::Named(name) | Type::Param(name) => name,
+            Type::Unknown => "{unknown}",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{
+    diagnostics::Diagnostic,
+    parsing::ast::nodes::{Location, Node, NodeKind},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BindingKind {
+    Let,
+    Param,
+    /// Loop variables and pattern bindings, which are not reported.
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Binding {
+    kind: BindingKind,
+    loc: Location,
+    used: bool,
+}
+
+struct Scopes {
+    stack: Vec<Vec<(String, Binding)>>,
+    diagnostics: Vec<Diagnostic>,
+}
+impl Scopes {
+    fn push(&mut self) {
+        self.stack.push(vec![]);
+    }
+
+    /// Leaves a scope, warning about what it declared but never read.
+    fn pop(&mut self) {
+        let scope = self.stack.pop().unwrap_or_default();
+        for (name, binding) in scope {
+            // `_x` says it is meant to go unused
+            if binding.used || name.starts_with('_') {
+                continue;
+            }
+            let message = match binding.kind {
+                BindingKind::Let => format!("Unused variable `{name}`"),
+                BindingKind::Param => format!("Unused parameter `{name}`"),
+                BindingKind::Other => continue,
+            };
+            self.diagnostics
+                .push(Diagnostic::warning(message, binding.loc));
+        }
+    }
+
+    fn declare(&mut self, name: &str, kind: BindingKind, loc: Location) {
+        let binding = Binding {
+            kind,
+            loc,
+            used: false,
+        };
+        if let Some(scope) = self.stack.last_mut() {
+            scope.push((name.to_string(), binding));
+        }
+    }
+
+    /// Marks the innermost binding of `name` as read. Globals and functions
+    /// are not tracked.
+    fn use_name(&mut self, name: &str) {
+        let binding = self
+            .stack
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.iter_mut().rev().find(|(other, _)| other == name));
+        if let Some((_, binding)) = binding {
+            binding.used = true;
+        }
+    }
+}
+
+/// Warns about local variables and parameters that are never read.
+/// Assigning to a variable does not count as using it.
+pub fn check_unused(ast: &Node) -> Vec<Diagnostic> {
+    let mut scopes = Scopes {
+        stack: vec![],
+        diagnostics: vec![],
+    };
+    // top-level lets are globals, which other modules may use
+    match &ast.kind {
+        NodeKind::Block { expressions } => {
+            for expression in expressions {
+                match &expression.kind {
+                    NodeKind::Let { value, .. } => walk(value, &mut scopes),
+                    _ => walk(expression, &mut scopes),
+                }
+            }
+        }
+        _ => walk(ast, &mut scopes),
+    }
+    scopes.diagnostics
+}
+
+fn walk(node: &Node, scopes: &mut Scopes) {
+    match &node.kind {
+        NodeKind::Fn { params, body, .. } => {
+            scopes.push();
+            for param in params {
+                scopes.declare(param.name(), BindingKind::Param, node.loc);
+            }
+            walk(body, scopes);
+            scopes.pop();
+        }
+        NodeKind::If { predicate, yes, no } => {
+            walk(predicate, scopes);
+            walk(yes, scopes);
+            if let Some(no) = no {
+                walk(no, scopes);
+            }
+        }
+        NodeKind::Let { symbol, value, .. } => {
+            walk(value, scopes);
+            scopes.declare(symbol.name(), BindingKind::Let, node.loc);
+        }
+        NodeKind::Set { place, value } => {
+            walk(value, scopes);
+            walk_place(place, scopes);
+        }
+        NodeKind::For {
+            dummy,
+            iterator,
+            body,
+        } => {
+            walk(iterator, scopes);
+            scopes.push();
+            scopes.declare(dummy.name(), BindingKind::Other, node.loc);
+            walk(body, scopes);
+            scopes.pop();
+        }
+        NodeKind::Given { predicate, cases } => {
+            walk(predicate, scopes);
+            for case in cases.children() {
+                let [pattern, body] = case.children()[..] else {
+                    continue;
+                };
+                scopes.push();
+                if let NodeKind::Call { args, .. } = &pattern.kind {
+                    for arg in args {
+                        if let NodeKind::Identifier { symbol } = &arg.kind {
+                            scopes.declare(symbol.name(), BindingKind::Other, arg.loc);
+                        }
+                    }
+                }
+                walk(body, scopes);
+                scopes.pop();
+            }
+        }
+        NodeKind::Return { value } => walk(value, scopes),
+        NodeKind::Identifier { symbol } => scopes.use_name(symbol.name()),
+        NodeKind::Field { target, .. } => walk(target, scopes),
+        NodeKind::Index { target, index } => {
+            walk(target, scopes);
+            walk(index, scopes);
+        }
+        NodeKind::Call { args, .. } => {
+            for arg in args {
+                walk(arg, scopes);
+            }
+        }
+        NodeKind::Array { elements } => {
+            for element in elements {
+                walk(element, scopes);
+            }
+        }
+        NodeKind::Block { expressions } => {
+            scopes.push();
+            for expression in expressions {
+                walk(expression, scopes);
+            }
+            scopes.pop();
+        }
+        NodeKind::Struct { .. }
+        | NodeKind::Enum { .. }
+        | NodeKind::Literal(_)
+        | NodeKind::Import { .. }
+        | NodeKind::Extern { .. }
+        | NodeKind::Include { .. }
+        | NodeKind::Invalid => {}
+    }
+}
+
+/// Walks an assignment target, in which only the indices are read.
+fn walk_place(place: &Node, scopes: &mut Scopes) {
+    match &place.kind {
+        NodeKind::Identifier { .. } => {}
+        NodeKind::Field { target, .. } => walk_place(target, scopes),
+        NodeKind::Index { target, index } => {
+            walk_place(target, scopes);
+            walk(index, scopes);
+        }
+        _ => walk(place, scopes),
+    }
+}
@@ -0,0 +1,106 @@
+use std::ops::Index;
+
+/// Refers to a file in a [`SourceMap`]. Locations carry one so that they
+/// stay meaningful once several files are linked into one program.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u32);
+
+/// The name and text of a loaded file, with the offsets its lines start at.
+#[derive(Debug)]
+pub struct SourceFile {
+    id: FileId,
+    name: String,
+    source: String,
+    line_starts: Vec<usize>,
+}
+impl SourceFile {
+    pub fn id(&self) -> FileId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The 1-based line and column of a byte offset, counting columns in
+    /// characters.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let col = self.source[line_start..offset].chars().count() + 1;
+        (line, col)
+    }
+
+    /// The number of lines, counting the empty one after a final line
+    /// break.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The byte range of a 1-based line, without its line break.
+    pub fn line_span(&self, line: usize) -> (usize, usize) {
+        let start = self.line_starts[line.clamp(1, self.line_starts.len()) - 1];
+        let end = self.source[start..]
+            .find('\n')
+            .map_or(self.source.len(), |end| start + end);
+        (start, end)
+    }
+
+    /// The text of a 1-based line, without its line break.
+    pub fn line_text(&self, line: usize) -> &str {
+        let (start, end) = self.line_span(line);
+        &self.source[start..end]
+    }
+}
+
+/// Owns the text of every file a compile has read, so nothing has to borrow
+/// it or outlive it. Files can also be added straight from memory.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+impl SourceMap {
+    /// Adds a file under a name used in messages.
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        let source = source.into();
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        self.files.push(SourceFile {
+            id,
+            name: name.into(),
+            source,
+            line_starts,
+        });
+        id
+    }
+
+    /// Finds a file by the name it was added under.
+    pub fn find(&self, name: &str) -> Option<FileId> {
+        self.files
+            .iter()
+            .find(|file| file.name == name)
+            .map(|file| file.id)
+    }
+
+    pub fn get(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.0 as usize)
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &SourceFile> {
+        self.files.iter()
+    }
+}
+impl Index<FileId> for SourceMap {
+    type Output = SourceFile;
+
+    fn index(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+}
@@ -0,0 +1,457 @@
+use std::path::Path;
+
+use crisp::{
+    build::{CCompiler, c_diagnostic, transpile},
+    codegen::{
+        c::{CProgram, EmitOptions, Overflow, emit_c_program},
+        runtime::write_runtime,
+    },
+    ir::inline::Inlining,
+    parsing::parse_str,
+    semantics::check_program,
+    sources::SourceMap,
+};
+
+const SOURCE: &str = "(fn:i32 helper (x:i32) (
+  (let y:i32 x)
+  (ret (plus y 1))))
+(fn:i32 main () ((ret (helper 1))))";
+
+fn emit(source: &str) -> CProgram {
+    let ast = parse_str(source.to_string(), "test").expect("test source should parse");
+    let analysis = check_program(&ast);
+    emit_c_program(&ast, &analysis.types, &EmitOptions::default()).unwrap()
+}
+
+/// The 1-based line of the first line of C containing `text`.
+fn line_of(c: &CProgram, text: &str) -> usize {
+    c.code.lines().position(|line| line.contains(text)).unwrap() + 1
+}
+
+#[test]
+fn lines_of_c_map_back_to_crisp() {
+    let c = emit(SOURCE);
+    let at = |text| {
+        c.location_of(line_of(&c, text))
+            .map(|loc| (loc.line, loc.col))
+    };
+    assert_eq!(at("int32_t helper(int32_t x) {"), Some((1, 1)));
+    assert_eq!(at("const int32_t y = x;"), Some((2, 3)));
+    assert_eq!(at("return plus(y, 1);"), Some((3, 3)));
+    assert_eq!(at("return helper(1);"), Some((4, 18)));
+    // prototypes come before any code
+    assert_eq!(at("int32_t helper(int32_t x);"), None);
+}
+
+#[test]
+fn compiler_messages_are_read_from_gcc_and_clang_output() {
+    let c = emit(SOURCE);
+    let sources = SourceMap::default();
+    let line = line_of(&c, "return plus(y, 1);");
+    let gcc = format!(
+        "out/test.c:{line}:12: error: implicit declaration of function 'plus' [-Wimplicit-function-declaration]"
+    );
+    let diagnostic = c_diagnostic(&gcc, "out/test.c", &c, &sources).unwrap();
+    assert!(diagnostic.is_error());
+    assert_eq!(diagnostic.loc.line, 3);
+    assert_eq!(
+        diagnostic.message,
+        format!(
+            "implicit declaration of function 'plus' [-Wimplicit-function-declaration] (in the generated C, line {line})"
+        )
+    );
+    let clang = format!("out/test.c:{line}:12: warning: unused variable 'z' [-Wunused-variable]");
+    assert!(
+        !c_diagnostic(&clang, "out/test.c", &c, &sources)
+            .unwrap()
+            .is_error()
+    );
+    for other in [
+        format!("out/test.c:{line}:12: note: declared here"),
+        format!("other.c:{line}:12: error: something"),
+        "out/test.c: In function 'helper':".to_string(),
+        "collect2: error: ld returned 1 exit status".to_string(),
+    ] {
+        assert!(
+            c_diagnostic(&other, "out/test.c", &c, &sources).is_none(),
+            "{other}"
+        );
+    }
+}
+
+#[test]
+fn line_directives_point_at_the_crisp_source() {
+    let mut sources = SourceMap::default();
+    sources.add("src/say \"hi\".crisp", SOURCE);
+    let plain = emit(SOURCE);
+    assert!(!plain.code.contains("#line"));
+
+    let c = plain.with_line_directives(&sources);
+    let lines: Vec<&str> = c.code.lines().collect();
+    let name = "\"src/say \\\"hi\\\".crisp\"";
+    let before = |text: &str| lines[line_of(&c, text) - 2];
+    assert_eq!(
+        before("int32_t helper(int32_t x) {"),
+        format!("#line 1 {name}")
+    );
+    assert_eq!(before("return helper(1);"), format!("#line 4 {name}"));
+    // lines of C that carry on where the line count already is need no directive
+    assert_eq!(
+        before("const int32_t y = x;"),
+        "int32_t helper(int32_t x) {"
+    );
+    assert_eq!(before("return plus(y, 1);"), "    const int32_t y = x;");
+    // mapping still works with the directives in place
+    let loc = c.location_of(line_of(&c, "return plus(y, 1);")).unwrap();
+    assert_eq!((loc.line, loc.col), (3, 3));
+
+    // a compiler reading the directives reports positions in the Crisp file
+    let message = "src/say \"hi\".crisp:3:12: warning: implicit declaration of function 'plus'";
+    let diagnostic = c_diagnostic(message, "out/test.c", &c, &sources).unwrap();
+    assert_eq!((diagnostic.loc.line, diagnostic.loc.col), (3, 12));
+    assert_eq!(
+        diagnostic.message,
+        "implicit declaration of function 'plus'"
+    );
+}
+
+#[test]
+fn programs_compile_to_executables() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("compile");
+    std::fs::create_dir_all(&dir).unwrap();
+    write_runtime(&dir).unwrap();
+    let input = dir.join("seven.crisp");
+    std::fs::write(&input, "(fn:i32 main () ((ret 7)))").unwrap();
+    let (program, c) = transpile(&input, &EmitOptions::default()).unwrap();
+    let c_file = dir.join("seven.c");
+    std::fs::write(&c_file, &c.code).unwrap();
+    let executable = dir.join("seven");
+    let compiler = CCompiler {
+        program: "cc".to_string(),
+        flags: vec!["-O2".to_string()],
+    };
+    compiler
+        .compile(&c_file, &executable, false, &c, &program.sources)
+        .unwrap();
+    let status = std::process::Command::new(&executable).status().unwrap();
+    assert_eq!(status.code(), Some(7));
+
+    // C the compiler rejects fails the compile
+    std::fs::write(&c_file, format!("{}\nint broken = ;\n", c.code)).unwrap();
+    assert!(
+        compiler
+            .compile(&c_file, &executable, false, &c, &program.sources)
+            .is_err()
+    );
+}
+
+#[test]
+fn programs_call_into_the_c_library() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    std::fs::create_dir_all(&dir).unwrap();
+    write_runtime(&dir).unwrap();
+    let input = dir.join("ffi.crisp");
+    std::fs::write(
+        &input,
+        "(include \"<stdio.h>\")
+(include \"<stdlib.h>\")
+(extern \"C\"
+  (fn:i32 puts (s:string))
+  (fn:i32 abs (x:i32)))
+(fn:i32 main () (
+  (puts \"hello from C\")
+  (ret (abs -7))))",
+    )
+    .unwrap();
+    let (program, c) = transpile(&input, &EmitOptions::default()).unwrap();
+    let c_file = dir.join("ffi.c");
+    std::fs::write(&c_file, &c.code).unwrap();
+    let executable = dir.join("ffi");
+    let compiler = CCompiler {
+        program: "cc".to_string(),
+        flags: vec![],
+    };
+    compiler
+        .compile(&c_file, &executable, false, &c, &program.sources)
+        .unwrap();
+    let output = std::process::Command::new(&executable).output().unwrap();
+    assert_eq!(output.status.code(), Some(7));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello from C\n");
+}
+
+#[test]
+fn c_programs_call_exported_functions() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("exports");
+    std::fs::create_dir_all(&dir).unwrap();
+    write_runtime(&dir).unwrap();
+    let input = dir.join("geo.crisp");
+    std::fs::write(
+        &input,
+        "(struct Point (x:i32 y:i32))
+(fn:i32 manhattan (export) (p:Point) ((ret (plus p.x p.y))))
+(fn:i32 plus (a:i32 b:i32) ((ret a)))",
+    )
+    .unwrap();
+    let (program, c) = transpile(&input, &EmitOptions::default()).unwrap();
+    std::fs::write(dir.join("geo.c"), &c.code).unwrap();
+    std::fs::write(dir.join("geo.h"), c.header("geo").unwrap()).unwrap();
+    let compiler = CCompiler {
+        program: "cc".to_string(),
+        flags: vec![],
+    };
+    compiler
+        .compile(
+            &dir.join("geo.c"),
+            &dir.join("geo.o"),
+            true,
+            &c,
+            &program.sources,
+        )
+        .unwrap();
+    std::fs::write(
+        dir.join("main.c"),
+        "#include \"geo.h\"\nint main(void) { Point p = {3, 4}; return manhattan(p); }\n",
+    )
+    .unwrap();
+    let status = std::process::Command::new("cc")
+        .arg(dir.join("main.c"))
+        .arg(dir.join("geo.o"))
+        .arg(dir.join("crisp_runtime.c"))
+        .arg("-o")
+        .arg(dir.join("main"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = std::process::Command::new(dir.join("main"))
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(3));
+}
+
+/// Compiles `source` with the runtime and runs it.
+fn compile_and_run(name: &str, source: &str) -> std::process::Output {
+    compile_and_run_with(name, source, &EmitOptions::default())
+}
+
+fn compile_and_run_with(name: &str, source: &str, options: &EmitOptions) -> std::process::Output {
+    let executable = compile_with(name, source, options);
+    std::process::Command::new(&executable).output().unwrap()
+}
+
+/// Compiles `source` with the runtime into an executable, returning its path.
+fn compile_with(name: &str, source: &str, options: &EmitOptions) -> std::path::PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    write_runtime(&dir).unwrap();
+    let input = dir.join(format!("{}.crisp", name));
+    std::fs::write(&input, source).unwrap();
+    let (program, c) = transpile(&input, options).unwrap();
+    let c_file = dir.join(format!("{}.c", name));
+    std::fs::write(&c_file, &c.code).unwrap();
+    let executable = dir.join(name);
+    let compiler = CCompiler {
+        program: "cc".to_string(),
+        flags: vec![],
+    };
+    compiler
+        .compile(&c_file, &executable, false, &c, &program.sources)
+        .unwrap();
+    executable
+}
+
+#[test]
+fn programs_print_through_the_runtime() {
+    let output = compile_and_run(
+        "print",
+        "(let greeting:string \"hello\")
+(fn:i32 main () (
+  (print greeting)
+  (println \", world\")
+  (println -42)
+  (let big:u64 42)
+  (println big)
+  (let ratio:f64 1.5)
+  (println ratio)
+  (println true)
+  (println \"C:\\dir\\n\\\")
+  (ret 0)))",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "hello, world\n-42\n42\n1.5\ntrue\nC:\\dir\\n\\\n"
+    );
+}
+
+#[test]
+fn null_strings_from_c_are_empty() {
+    let output = compile_and_run(
+        "null_strings",
+        "(extern \"C\" (fn:string getenv (name:string)))
+(fn:i32 main () (
+  (let home:string (getenv \"CRISP_TEST_SURELY_UNSET\"))
+  (println home)
+  (println (len home))
+  (ret 0)))",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "\n0\n");
+}
+
+#[test]
+fn strings_are_concatenated_and_compared() {
+    let output = compile_and_run(
+        "strings",
+        "(fn:i32 main () (
+  (let both:string (concat \"abc\" \"def\"))
+  (println both)
+  (println (len both))
+  (ret (compare \"abc\" \"abd\"))))",
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "abcdef\n6\n");
+    // a negative comparison shows up as a failing exit code
+    assert_ne!(output.status.code(), Some(0));
+}
+
+#[test]
+fn out_of_bounds_indexing_panics_at_the_crisp_line() {
+    let output = compile_and_run(
+        "panic",
+        "(fn:i32 pick (xs:[i32] i:u64) ((ret (at xs i))))
+(fn:i32 main () (
+  (let xs:[i32;3] [1 2 3])
+  (ret (pick xs 3))))",
+    );
+    assert_eq!(output.status.code(), Some(101));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("panic.crisp:1: index out of bounds"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn operators_compute_like_crisp_says() {
+    let output = compile_and_run(
+        "operators",
+        "(fn:i32 main () (
+  (let mut total:i64 0)
+  (for i (range 0 10) ((set! total (+ total i))))
+  (println total)
+  (let big:u32 4000000000)
+  (let small:i32 -1)
+  (println (< small big))
+  (println (/ 7.0 2))
+  (ret (% 17 5))))",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "45\ntrue\n3.5\n");
+}
+
+#[test]
+fn overflow_panics_or_wraps_around() {
+    let source = "(fn:u8 add (a:u8 b:u8) ((ret (+ a b))))
+(fn:i32 div (a:i32 b:i32) ((ret (/ a b))))
+(fn:i32 main () (
+  (println (add 200 100))
+  (println (div 1 0))
+  (ret 0)))";
+    let output = compile_and_run("checked", source);
+    assert_eq!(output.status.code(), Some(101));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("checked.crisp:1: attempt to add with overflow"),
+        "{}",
+        stderr
+    );
+
+    let options = EmitOptions {
+        overflow: Overflow::Wrapping,
+        ..EmitOptions::default()
+    };
+    let output = compile_and_run_with("wrapping", source, &options);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "44\n");
+    // wrapping around does not make dividing by zero any less wrong
+    assert_eq!(output.status.code(), Some(101));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("wrapping.crisp:2: attempt to divide by zero"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn self_tail_calls_run_in_constant_stack() {
+    // far deeper than any C stack would take as real calls
+    let output = compile_and_run(
+        "tail_calls",
+        "(fn:i64 sum (n:i64 acc:i64) (
+  (if (= n 0) ((ret acc)))
+  (ret (sum (- n 1) (+ acc n)))))
+(fn:void count (n:i32) ((if (> n 0) ((count (- n 1))))))
+(fn:i32 main () ((println (sum 100000000 0)) (count 100000000) (ret 0)))",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "5000000050000000\n"
+    );
+}
+
+#[test]
+fn inlined_functions_run_like_calls() {
+    let source = "(fn:i32 bump (inline) (n:i32) (
+  (let x:i32 (+ n 1))
+  (if (> x 100) ((ret 100)))
+  (ret x)))
+(fn:i32 square (n:i32) ((ret (* n n))))
+(fn:i32 main () (
+  (let x:i32 10)
+  (let mut total:i32 0)
+  (for i (range 0 3) ((set! total (+ total (bump x)))))
+  (println (bump x))
+  (println (bump 500))
+  (println (square x))
+  (println total)
+  (ret 0)))";
+    for level in ["0", "1", "3"] {
+        let options = EmitOptions {
+            inlining: Inlining::for_opt_level(level).unwrap(),
+            ..EmitOptions::default()
+        };
+        let output = compile_and_run_with(&format!("inline_{level}"), source, &options);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "11\n100\n100\n33\n",
+            "at level {level}"
+        );
+    }
+}
+
+#[test]
+fn main_gets_the_arguments_and_returns_the_exit_code() {
+    let executable = compile_with(
+        "entry_args",
+        "(fn:i32 main (args:[string]) (
+  (for arg args ((println arg)))
+  (if (= (len args) 3) ((ret 3)))
+  (ret 0)))",
+        &EmitOptions::default(),
+    );
+    let output = std::process::Command::new(&executable)
+        .args(["one", "two three"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}\none\ntwo three\n", executable.display())
+    );
+
+    let output = compile_and_run("entry_void", "(fn:void main () ((println 7)))");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "7\n");
+}
@@ -0,0 +1,83 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Output},
+};
+
+/// A fresh directory for a test to run the compiler in.
+fn scratch(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("cli")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs `crisp` with `args` in `dir`.
+fn crisp(dir: &Path, args: &[&str]) -> ExitStatus {
+    run(dir, args).status
+}
+
+/// Runs `crisp` with `args` in `dir`, without colors in what it prints.
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_crisp"))
+        .args(args)
+        .current_dir(dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn failures_exit_with_an_error_status() {
+    let dir = scratch("failures");
+    assert!(crisp(&dir, &["new", "demo"]).success());
+    assert!(crisp(&dir, &["build", "--manifest-path", "demo/Crisp.toml"]).success());
+    // the project exists now
+    assert!(!crisp(&dir, &["new", "demo"]).success());
+    assert!(!crisp(&dir, &["t", "missing.crisp"]).success());
+    assert!(!crisp(&dir, &["build", "--manifest-path", "missing/Crisp.toml"]).success());
+    assert!(!crisp(&dir, &["doc"]).success());
+
+    std::fs::write(dir.join("broken.crisp"), "(fn:i32 main () ((ret x)))").unwrap();
+    assert!(!crisp(&dir, &["t", "broken.crisp"]).success());
+    assert!(!crisp(&dir, &["t", "broken.crisp", "--emit", "ir"]).success());
+}
+
+#[test]
+fn c_compiler_failures_exit_with_an_error_status() {
+    let dir = scratch("c_failures");
+    // declared for Crisp, but no C library defines it
+    let source = "(extern \"C\" (fn:i32 undefined_in_any_library ()))
+(fn:i32 main () ((ret (undefined_in_any_library))))";
+    std::fs::write(dir.join("link.crisp"), source).unwrap();
+    assert!(crisp(&dir, &["t", "link.crisp"]).success());
+    assert!(!crisp(&dir, &["t", "link.crisp", "-c"]).success());
+
+    assert!(crisp(&dir, &["new", "demo"]).success());
+    std::fs::write(dir.join("demo/src/main.crisp"), source).unwrap();
+    assert!(!crisp(&dir, &["build", "--manifest-path", "demo/Crisp.toml"]).success());
+}
+
+#[test]
+fn parse_errors_render_like_other_diagnostics() {
+    let dir = scratch("parse_errors");
+    std::fs::write(
+        dir.join("bad.crisp"),
+        "(fn:i32 main () (\n  (if true 1)\n  (ret 0)))\n",
+    )
+    .unwrap();
+    let output = run(&dir, &["t", "bad.crisp"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Then block must be a list\n    --> "),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains(
+            "bad.crisp[2|12]\n         |\n       2 |   (if true 1)\n         |            ~\n"
+        ),
+        "{stderr}"
+    );
+}
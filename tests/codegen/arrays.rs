@@ -0,0 +1,91 @@
+use crisp::{
+    codegen::c::{EmitOptions, emit_c},
+    parsing::parse_str,
+    semantics::check_program,
+};
+
+use crate::codegen::compile;
+
+#[test]
+fn arrays_and_slices_are_wrapped_in_structs() {
+    let c =
+        compile("(fn:u64 size (xs:[i32]) ((ret (len xs)))) (let grid:[[u8;2];2] [[1 2] [3 4]])");
+    assert!(
+        c.contains("typedef struct crisp_array_u8_2 {\n    uint8_t data[2];\n} crisp_array_u8_2;"),
+        "{c}"
+    );
+    assert!(
+        c.contains("typedef struct crisp_array_array_u8_2_2 {\n    crisp_array_u8_2 data[2];\n}"),
+        "{c}"
+    );
+    assert!(
+        c.contains(
+            "typedef struct crisp_slice_i32 {\n    int32_t const* data;\n    uint64_t len;\n}"
+        ),
+        "{c}"
+    );
+    assert!(
+        c.contains("static const crisp_array_array_u8_2_2 grid = {{{{1, 2}}, {{3, 4}}}};"),
+        "{c}"
+    );
+    assert!(c.contains("return xs.len;"), "{c}");
+}
+
+#[test]
+fn indexing_is_bounds_checked() {
+    let source = "(fn:i32 pick (xs:[i32;4] i:u8) ((ret (at xs i)))) (fn:i32 first (xs:[i32;4]) ((ret (at xs 0))))";
+    let c = compile(source);
+    assert!(
+        c.contains("return xs.data[crisp_check_index(i, UINT64_C(4), \"<unknown>\", 1)];"),
+        "{c}"
+    );
+    // the checker already proved a literal index in bounds
+    assert!(c.contains("return xs.data[0];"), "{c}");
+
+    let ast = parse_str(source.to_string(), "test").unwrap();
+    let analysis = check_program(&ast);
+    let options = EmitOptions {
+        bounds_checks: false,
+        ..EmitOptions::default()
+    };
+    let c = emit_c(&ast, &analysis.types, &options).unwrap();
+    assert!(!c.contains("crisp_check_index"), "{c}");
+    assert!(c.contains("return xs.data[i];"), "{c}");
+}
+
+#[test]
+fn arrays_are_passed_where_slices_are_expected() {
+    let c = compile(
+        "(fn:u64 size (xs:[i32]) ((ret (len xs)))) (fn:u64 run () ((let xs:[i32;2] [1 2]) (ret (size xs))))",
+    );
+    assert!(
+        c.contains("return size((crisp_slice_i32){xs.data, 2});"),
+        "{c}"
+    );
+}
+
+#[test]
+fn for_loops_walk_a_copy_of_the_array() {
+    let c = compile(
+        "(fn:void g (s:string) ((println s))) (fn:void f (xs:[string]) ((for x xs ((g x)))))",
+    );
+    assert!(
+        c.contains("const crisp_slice_string _crisp_tmp1 = xs;"),
+        "{c}"
+    );
+    assert!(
+        c.contains("if (!(_crisp_tmp2 < _crisp_tmp1.len)) goto bb3;"),
+        "{c}"
+    );
+    assert!(
+        c.contains("const crisp_string x = _crisp_tmp1.data[_crisp_tmp2];"),
+        "{c}"
+    );
+}
+
+#[test]
+fn elements_can_be_assigned() {
+    let c = compile("(fn:void f () ((let mut xs [1 2 3]) (set! (at xs 1) 5)))");
+    assert!(c.contains("crisp_array_i32_3 xs = {{1, 2, 3}};"), "{c}");
+    assert!(c.contains("xs.data[1] = 5;"), "{c}");
+}
@@ -0,0 +1,53 @@
+use crate::codegen::compile;
+
+const SHAPE: &str = "(enum Shape (Circle r:f64) (Rect w:f64 h:f64) Empty)\n";
+
+#[test]
+fn enums_become_tagged_unions() {
+    let c = compile(SHAPE);
+    assert!(
+        c.contains("typedef enum Shape_Tag {\n    Shape_Circle,\n    Shape_Rect,\n    Shape_Empty,\n} Shape_Tag;"),
+        "{c}"
+    );
+    assert!(c.contains("    Shape_Tag tag;\n    union {"), "{c}");
+    assert!(
+        c.contains(
+            "        struct {\n            double w;\n            double h;\n        } Rect;"
+        ),
+        "{c}"
+    );
+    assert!(c.contains("    } as;\n} Shape;"), "{c}");
+}
+
+#[test]
+fn constructors_set_the_tag_and_payload() {
+    let c = compile(&format!(
+        "{SHAPE}(fn:Shape make () ((let e Empty) (ret (Circle 2))))"
+    ));
+    assert!(c.contains("const Shape e = {.tag = Shape_Empty};"), "{c}");
+    assert!(
+        c.contains("return (Shape){.tag = Shape_Circle, .as.Circle = {.r = 2.0}};"),
+        "{c}"
+    );
+}
+
+#[test]
+fn given_destructures_the_payload() {
+    let c = compile(&format!(
+        "{SHAPE}(fn:f64 area (s:Shape) ((given s ((Circle r) ((ret r))) ((Rect w h) ((ret w))) (Empty ((ret 0))))))"
+    ));
+    assert!(c.contains("if (!(s.tag == Shape_Circle)) goto bb2;"), "{c}");
+    assert!(c.contains("const double r = s.as.Circle.r;"), "{c}");
+    assert!(c.contains("if (!(s.tag == Shape_Rect)) goto bb4;"), "{c}");
+    // the last case of an exhaustive given is the only thing left
+    assert!(c.contains("bb4:;\n    return 0.0;"), "{c}");
+}
+
+#[test]
+fn enum_payloads_are_defined_first() {
+    let c = compile("(enum Wrapper (Wrap p:Point))\n(struct Point (x:i32))");
+    assert!(
+        c.find("} Point;").unwrap() < c.find("} Wrapper_Tag;").unwrap(),
+        "{c}"
+    );
+}
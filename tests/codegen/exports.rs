@@ -0,0 +1,46 @@
+use crisp::{codegen::c::emit_c_program, parsing::parse_str, semantics::check_program};
+
+use crate::codegen::compile;
+
+#[test]
+fn exported_functions_are_declared_in_a_header() {
+    let source = "
+(struct Point (x:i32 y:i32))
+(struct Line (ends:[Point;2]))
+(struct Unused (z:i32))
+(fn:i32 helper (a:i32) ((ret a)))
+(fn:i32 start_x (export) (l:Line) ((ret (helper (. (at l.ends 0) x)))))
+(fn:i32 first (export) (xs:[i32]) ((ret (at xs 0))))";
+    let ast = parse_str(source.to_string(), "test").unwrap();
+    let analysis = check_program(&ast);
+    assert!(!analysis.has_errors(), "{:?}", analysis.diagnostics);
+    let c = emit_c_program(&ast, &analysis.types, &Default::default()).unwrap();
+    let header = c.header("geo").unwrap();
+    assert!(
+        header.starts_with("#ifndef GEO_H\n#define GEO_H\n"),
+        "{header}"
+    );
+    assert!(header.ends_with("#endif\n"), "{header}");
+    // the types come in the order C needs them
+    let at = |text: &str| {
+        header
+            .find(text)
+            .unwrap_or_else(|| panic!("{text}\n{header}"))
+    };
+    assert!(at("} Point;") < at("} crisp_array_Point_2;"));
+    assert!(at("} crisp_array_Point_2;") < at("} Line;"));
+    assert!(at("} crisp_slice_i32;") < at("int32_t first(crisp_slice_i32 xs);"));
+    assert!(header.contains("int32_t start_x(Line l);"), "{header}");
+    assert!(!header.contains("Unused"), "{header}");
+    assert!(!header.contains("helper"), "{header}");
+    assert!(c.code.contains("int32_t start_x(Line l) {"), "{}", c.code);
+}
+
+#[test]
+fn nothing_exported_means_no_header() {
+    let ast = parse_str("(fn:i32 main () ((ret 0)))".to_string(), "test").unwrap();
+    let analysis = check_program(&ast);
+    let c = emit_c_program(&ast, &analysis.types, &Default::default()).unwrap();
+    assert_eq!(c.header("main"), None);
+    assert!(!compile("(fn:i32 main () ((ret 0)))").contains("#ifndef"));
+}
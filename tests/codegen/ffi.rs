@@ -0,0 +1,41 @@
+use crate::codegen::compile;
+
+#[test]
+fn c_functions_are_declared_but_not_defined() {
+    let c = compile(
+        "
+(include \"<stdio.h>\")
+(include \"mylib.h\")
+(include \"<stdio.h>\")
+(extern \"C\"
+  (fn:i32 puts (s:string))
+  (fn:f64 scale (x:f64 by:f64)))
+(extern \"C\" (fn:i32 puts (s:string)))
+(fn:i32 main () (
+  (puts \"hi\")
+  (ret 0)))",
+    );
+    assert!(
+        c.contains("#include <stdio.h>\n#include \"mylib.h\"\n"),
+        "{c}"
+    );
+    assert_eq!(c.matches("#include <stdio.h>").count(), 1, "{c}");
+    assert_eq!(c.matches("int32_t puts(const char* s);").count(), 1, "{c}");
+    assert!(c.contains("double scale(double x, double by);"), "{c}");
+    assert!(!c.contains("puts(const char* s) {"), "{c}");
+    assert!(c.contains("    puts(\"hi\");"), "{c}");
+}
+
+#[test]
+fn strings_cross_into_c_as_plain_pointers() {
+    let c = compile(
+        "
+(extern \"C\" (fn:string getenv (name:string)))
+(fn:string home (name:string) ((ret (getenv name))))",
+    );
+    assert!(c.contains("const char* getenv(const char* name);"), "{c}");
+    assert!(
+        c.contains("return crisp_string_from_c(getenv(name.data));"),
+        "{c}"
+    );
+}
@@ -0,0 +1,64 @@
+use crate::codegen::compile;
+
+#[test]
+fn each_instance_gets_its_own_function() {
+    let c = compile(
+        "
+(fn:T pick [T] (c:bool a:T b:T) ((if c ((ret a)) ((ret b)))))
+(fn:i32 main () (
+  (let a:i32 3)
+  (let b (pick true a 4))
+  (let c (pick false 1.5 2))
+  (let d (pick true a 5))
+  (ret b)))",
+    );
+    assert!(
+        c.contains("int32_t pick__i32(bool c, int32_t a, int32_t b) {"),
+        "{c}"
+    );
+    assert!(
+        c.contains("double pick__f64(bool c, double a, double b) {"),
+        "{c}"
+    );
+    assert_eq!(c.matches("pick__i32(bool").count(), 2, "{c}");
+    assert!(
+        c.contains("const double c = pick__f64(false, 1.5, 2.0);"),
+        "{c}"
+    );
+    assert!(!c.contains("pick("), "{c}");
+}
+
+#[test]
+fn generic_bodies_are_instantiated_through_calls() {
+    let c = compile(
+        "
+(fn:T first [T] (xs:[T]) ((ret (at xs 0))))
+(fn:[T;2] twice [T] (x:T) ((ret [x x])))
+(fn:T first-of-pair [T] (x:T) ((let p (twice x)) (ret (first p))))
+(fn:i64 run () ((let e:i64 (first-of-pair 7)) (ret e)))",
+    );
+    assert!(
+        c.contains("int64_t first__i64(crisp_slice_i64 xs) {"),
+        "{c}"
+    );
+    assert!(
+        c.contains("crisp_array_i64_2 twice__i64(int64_t x) {"),
+        "{c}"
+    );
+    assert!(
+        c.contains("return first__i64((crisp_slice_i64){p.data, 2});"),
+        "{c}"
+    );
+    assert!(c.contains("first_of_pair__i64(INT64_C(7))"), "{c}");
+}
+
+#[test]
+fn operators_on_type_parameters_use_the_instance_type() {
+    let c = compile(
+        "(fn:T max [T] (a:T b:T) ((if (> a b) ((ret a)) ((ret b)))))
+(fn:f64 f (x:f64) ((ret (max x 2))))",
+    );
+    assert!(c.contains("double max__f64(double a, double b) {"), "{c}");
+    assert!(c.contains("a > b"), "{c}");
+    assert!(c.contains("max__f64(x, 2.0)"), "{c}");
+}
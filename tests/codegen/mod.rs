@@ -0,0 +1,22 @@
+pub mod arrays;
+pub mod enums;
+pub mod exports;
+pub mod ffi;
+pub mod generics;
+pub mod names;
+pub mod operators;
+pub mod strings;
+pub mod structs;
+
+use crisp::{
+    codegen::c::{EmitOptions, emit_c},
+    parsing::parse_str,
+    semantics::check_program,
+};
+
+pub fn compile(source: &str) -> String {
+    let ast = parse_str(source.to_string(), "test").expect("test source should parse");
+    let analysis = check_program(&ast);
+    assert!(!analysis.has_errors(), "{:?}", analysis.diagnostics);
+    emit_c(&ast, &analysis.types, &EmitOptions::default()).expect("test source should compile to C")
+}
@@ -0,0 +1,52 @@
+use crisp::{
+    codegen::c::{EmitOptions, emit_c},
+    parsing::parse_str,
+    semantics::check_program,
+};
+
+use crate::codegen::compile;
+
+fn clashes(source: &str) -> Vec<String> {
+    let ast = parse_str(source.to_string(), "test").expect("test source should parse");
+    let analysis = check_program(&ast);
+    assert!(!analysis.has_errors(), "{:?}", analysis.diagnostics);
+    emit_c(&ast, &analysis.types, &EmitOptions::default())
+        .expect_err("test source should not compile to C")
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect()
+}
+
+#[test]
+fn names_that_meet_in_c_are_reported() {
+    assert_eq!(
+        clashes(
+            "(fn:i32 my-f () ((ret 1)))
+(fn:i32 my_f () ((ret 2)))
+(fn:i32 main () ((ret (+ (my-f) (my_f)))))"
+        ),
+        ["Function `my_f` is called `my_f` in C, which clashes with `my-f`"]
+    );
+    // instances of generic functions are named after their types
+    assert_eq!(
+        clashes(
+            "(fn:T pick [T] (a:T) ((ret a)))
+(fn:i32 pick--i32 (a:i32) ((ret a)))
+(fn:i32 main () ((ret (+ (pick 1) (pick--i32 2)))))"
+        ),
+        ["Function `pick--i32` is called `pick__i32` in C, which clashes with `pick`"]
+    );
+    assert_eq!(
+        clashes("(fn:i32 crisp-main () ((ret 1)))"),
+        ["Function `crisp-main` is called `crisp_main` in C, which is kept for the runtime"]
+    );
+}
+
+#[test]
+fn locals_do_not_hide_what_they_call() {
+    let c = compile(
+        "(fn:i32 my-f () ((ret 1)))
+(fn:i32 g () ((let my_f (my-f)) (ret (+ my_f (my-f)))))",
+    );
+    assert!(c.contains("const int32_t my_f_1 = my_f();"), "{c}");
+}
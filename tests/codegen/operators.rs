@@ -0,0 +1,92 @@
+use crisp::{
+    codegen::c::{EmitOptions, Overflow, emit_c},
+    parsing::parse_str,
+    semantics::check_program,
+};
+
+use crate::codegen::compile;
+
+fn compile_with(source: &str, overflow: Overflow) -> String {
+    let ast = parse_str(source.to_string(), "test").unwrap();
+    let analysis = check_program(&ast);
+    let options = EmitOptions {
+        overflow,
+        ..EmitOptions::default()
+    };
+    emit_c(&ast, &analysis.types, &options).unwrap()
+}
+
+#[test]
+fn operators_lower_to_c_operators() {
+    let c = compile_with(
+        "
+(fn:i32 sum (a:i32 b:i32 c:i32) ((ret (+ a b c))))
+(fn:i32 negate (a:i32) ((ret (- a))))
+(fn:bool inside (x:i32 lo:i32 hi:i32) ((ret (and (<= lo x) (< x hi) (not (= x 0))))))
+(fn:u32 bits (x:u32) ((ret (| (& x 255) (<< x 2) (>> x 1)))))",
+        Overflow::Unchecked,
+    );
+    assert!(c.contains("return ((a + b) + c);"), "{c}");
+    assert!(c.contains("return (-a);"), "{c}");
+    // and and or only evaluate what they need to
+    assert!(c.contains("bool _crisp_tmp1 = (lo <= x);"), "{c}");
+    assert!(c.contains("if (!_crisp_tmp1) goto bb1;"), "{c}");
+    assert!(
+        c.contains("return (((x & 255u) | (x << 2)) | (x >> 1));"),
+        "{c}"
+    );
+}
+
+#[test]
+fn promoted_operands_are_cast() {
+    let c = compile_with(
+        "
+(fn:i64 widen (a:i32 b:u32) ((ret (+ a b))))
+(fn:u8 wrap (a:u8 b:u8) ((ret (* a b))))
+(fn:void check (s:string) ((if (= s \"yes\") ((wrap 1 2)))))",
+        Overflow::Unchecked,
+    );
+    assert!(c.contains("return (((int64_t)a) + ((int64_t)b));"), "{c}");
+    // C would do this arithmetic as int
+    assert!(c.contains("return ((uint8_t)(a * b));"), "{c}");
+    assert!(
+        c.contains("if (!crisp_string_eq(s, CRISP_STRING(\"yes\"))) goto bb2;"),
+        "{c}"
+    );
+}
+
+#[test]
+fn integer_arithmetic_is_checked_by_default() {
+    let source = "(fn:i32 f (a:i32 b:i32 c:i32) (
+  (ret (/ (+ a b c) (- c)))))
+(fn:f64 g (x:f64) ((ret (+ x 1))))";
+    let c = compile(source);
+    assert!(
+        c.contains(
+            "return crisp_checked_div_i32(crisp_checked_add_i32(crisp_checked_add_i32(a, b, \"<unknown>\", 2), c, \"<unknown>\", 2), crisp_checked_neg_i32(c, \"<unknown>\", 2), \"<unknown>\", 2);"
+        ),
+        "{c}"
+    );
+    // floats do not overflow
+    assert!(c.contains("return (x + 1.0);"), "{c}");
+
+    let c = compile_with(source, Overflow::Wrapping);
+    assert!(
+        c.contains(
+            "return crisp_wrapping_div_i32(crisp_wrapping_add_i32(crisp_wrapping_add_i32(a, b), c), crisp_wrapping_neg_i32(c), \"<unknown>\", 2);"
+        ),
+        "{c}"
+    );
+}
+
+#[test]
+fn the_lowest_integers_are_written_as_limits() {
+    let c = compile(
+        "(fn:i64 lowest () ((ret -9223372036854775808)))
+(fn:i32 low () ((ret -2147483648)))
+(fn:i64 near () ((ret -9223372036854775807)))",
+    );
+    assert!(c.contains("return INT64_MIN;"), "{c}");
+    assert!(c.contains("return INT32_MIN;"), "{c}");
+    assert!(c.contains("return INT64_C(-9223372036854775807);"), "{c}");
+}
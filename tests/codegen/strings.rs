@@ -0,0 +1,16 @@
+use crate::codegen::compile;
+
+#[test]
+fn strings_are_escaped_for_c() {
+    // Crisp strings have no escapes, a backslash is just a backslash
+    let c = compile(
+        "(fn:void f () (
+  (println \"back\\\")
+  (println \"a\\nb\")
+  (println \"two
+lines\")))",
+    );
+    assert!(c.contains(r#"CRISP_STRING("back\\")"#), "{c}");
+    assert!(c.contains(r#"CRISP_STRING("a\\nb")"#), "{c}");
+    assert!(c.contains(r#"CRISP_STRING("two\nlines")"#), "{c}");
+}
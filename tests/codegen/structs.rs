@@ -0,0 +1,42 @@
+use crate::codegen::compile;
+
+#[test]
+fn typedefs_follow_field_dependencies() {
+    let c = compile(
+        "(struct Line (start:Point end:Point))
+(struct Point (x:f64 y:f64))",
+    );
+    let point = c.find("typedef struct Point {").unwrap();
+    let line = c.find("typedef struct Line {").unwrap();
+    assert!(point < line, "{c}");
+    assert!(
+        c.contains("    Point start;\n    Point end;\n} Line;"),
+        "{c}"
+    );
+}
+
+#[test]
+fn constructors_and_fields_lower_to_c() {
+    let c = compile(
+        "(struct Point (x:f64 y:f64))
+(fn:Point flip (p:Point) ((let mut q (Point p.y (. p x))) (set! q.x 0) (set! (. q y) 1) (ret (Point q.x q.y))))",
+    );
+    assert!(c.contains("Point flip(Point p);"), "{c}");
+    assert!(c.contains("Point q = {.x = p.y, .y = p.x};"), "{c}");
+    assert!(c.contains("q.x = 0.0;"), "{c}");
+    assert!(c.contains("q.y = 1.0;"), "{c}");
+    assert!(c.contains("return (Point){.x = q.x, .y = q.y};"), "{c}");
+}
+
+#[test]
+fn dashed_names_become_c_identifiers() {
+    let c = compile("(struct two-d (x-pos:i32))\n(let origin:two-d (two-d 0))");
+    assert!(
+        c.contains("typedef struct two_d {\n    int32_t x_pos;\n} two_d;"),
+        "{c}"
+    );
+    assert!(
+        c.contains("static const two_d origin = {.x_pos = 0};"),
+        "{c}"
+    );
+}
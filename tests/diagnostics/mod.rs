@@ -0,0 +1,26 @@
+use crisp::{diagnostics::Diagnostic, parsing::ast::nodes::Location, sources::SourceMap};
+
+#[test]
+fn diagnostics_render_with_the_lines_they_point_at() {
+    colored::control::set_override(false);
+    let mut sources = SourceMap::default();
+    let file = sources.add("test.crisp", "(let a 1)\n(set! a 2)\n");
+    let diagnostic = Diagnostic::error(
+        "Cannot assign to immutable binding `a`",
+        Location::from_offsets(&sources[file], 16, 17),
+    )
+    .with_note("Defined here", Location::from_offsets(&sources[file], 5, 6));
+    assert_eq!(
+        diagnostic.render(&sources),
+        "Cannot assign to immutable binding `a`
+--> test.crisp[2|7]
+     |
+   2 | (set! a 2)
+     |       ~
+     = Defined here
+--> test.crisp[1|6]
+     |
+   1 | (let a 1)
+     |      ~"
+    );
+}
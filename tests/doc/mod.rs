@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use crisp::{
+    doc::{Format, Page, document, generate},
+    parsing::{ast::nodes::Node, docs::doc_comment, modules::load_program},
+    sources::SourceMap,
+};
+
+fn fixture(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/doc")
+        .join(path)
+}
+
+fn pages(format: Format) -> Vec<Page> {
+    let program = load_program(&fixture("main.crisp")).expect("fixture should load");
+    document(&program, "main", format)
+}
+
+fn page<'p>(pages: &'p [Page], file: &str) -> &'p str {
+    &pages
+        .iter()
+        .find(|page| page.file == file)
+        .unwrap_or_else(|| panic!("no page {}", file))
+        .content
+}
+
+/// The doc comment of each top-level form of a source.
+fn doc_comments(source: &str) -> Vec<Option<String>> {
+    let mut sources = SourceMap::default();
+    let file = sources.add("test", source);
+    let ast = crisp::parsing::parse_str(source.to_string(), "test").unwrap();
+    ast.children()
+        .into_iter()
+        .map(|node: &Node| doc_comment(&sources[file], &node.loc))
+        .collect()
+}
+
+#[test]
+fn doc_comments_are_the_lines_right_above_a_definition() {
+    let docs = doc_comments(
+        "; not a doc comment
+;;; Adds two numbers.
+;;;
+;;;    Indented.
+(fn:i32 add (a:i32 b:i32) ((ret (+ a b))))
+;;; Left apart.
+
+(let x 1)
+   ;;; Indented along with it.
+   (let y 2)",
+    );
+    assert_eq!(
+        docs,
+        [
+            Some("Adds two numbers.\n\n   Indented.".to_string()),
+            None,
+            Some("Indented along with it.".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn each_module_has_a_page() {
+    let pages = pages(Format::Markdown);
+    let files: Vec<&str> = pages.iter().map(|page| page.file.as_str()).collect();
+    assert_eq!(files, ["index.md", "geo.md"]);
+    let index = page(&pages, "index.md");
+    assert!(index.starts_with("# main\n"), "{index}");
+    assert!(index.contains("- [geo](geo.md)"), "{index}");
+    assert!(page(&pages, "geo.md").starts_with("# Module geo\n"));
+}
+
+#[test]
+fn signatures_link_to_the_types_in_their_annotations() {
+    let pages = pages(Format::Markdown);
+    let index = page(&pages, "index.md");
+    assert!(
+        index.contains("`(fn:i64 measure (p:`[`geo/Point`](geo.md#Point)`))`"),
+        "{index}"
+    );
+    // names are written without their module on its own page
+    let geo = page(&pages, "geo.md");
+    assert!(
+        geo.contains("`(fn:i64 norm1 (p:`[`Point`](#Point)`))`"),
+        "{geo}"
+    );
+    assert!(geo.contains("`(let pair:i64)`"), "{geo}");
+}
+
+#[test]
+fn doc_comments_link_to_the_names_they_mention() {
+    let pages = pages(Format::Markdown);
+    let index = page(&pages, "index.md");
+    assert!(
+        index.contains("Measures a point, see [`geo/norm1`](geo.md#norm1)."),
+        "{index}"
+    );
+    let geo = page(&pages, "geo.md");
+    // a variant is documented with its enum
+    assert!(
+        geo.contains("either a [`Circle`](#Shape) or nothing"),
+        "{geo}"
+    );
+    // undocumented definitions are listed all the same
+    assert!(geo.contains("## fn `abs`"), "{geo}");
+}
+
+#[test]
+fn html_pages_are_complete_documents() {
+    let pages = pages(Format::Html);
+    let geo = page(&pages, "geo.html");
+    assert!(geo.starts_with("<!DOCTYPE html>"), "{geo}");
+    assert!(geo.contains("<h2 id=\"norm1\">fn norm1</h2>"), "{geo}");
+    assert!(
+        geo.contains("<pre><code>(fn:i64 norm1 (p:<a href=\"#Point\">Point</a>))</code></pre>"),
+        "{geo}"
+    );
+    assert!(
+        geo.contains("<p>The distance of a <a href=\"#Point\"><code>Point</code></a> from"),
+        "{geo}"
+    );
+    // code in a doc comment is kept as it is written
+    assert!(
+        geo.contains("<pre><code>(geo/norm1 (geo/Point 1 -2)) ; 3\n</code></pre>"),
+        "{geo}"
+    );
+    assert!(geo.contains("<code>[Point;2]</code>"), "{geo}");
+}
+
+#[test]
+fn pages_are_written_to_a_directory() {
+    let dir = std::env::temp_dir().join(format!("crisp-doc-{}", std::process::id()));
+    let written = generate(&fixture("main.crisp"), Format::Html, &dir).unwrap();
+    assert_eq!(written, [dir.join("index.html"), dir.join("geo.html")]);
+    assert!(
+        std::fs::read_to_string(dir.join("index.html"))
+            .unwrap()
+            .contains("<a href=\"geo.html\">geo</a>")
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,57 @@
+use crisp::ir::{dce::remove_dead_functions, inline::Inlining, simplify};
+
+use crate::ir::{ir, lower_str};
+
+fn functions(source: &str) -> Vec<String> {
+    let mut module = lower_str(source).expect("test source should lower");
+    remove_dead_functions(&mut module);
+    module
+        .functions
+        .iter()
+        .map(|function| function.name.clone())
+        .collect()
+}
+
+#[test]
+fn functions_main_never_reaches_are_dropped() {
+    let source = "
+(fn:i32 used () ((ret (helper))))
+(fn:i32 helper () ((ret 1)))
+(fn:i32 unused () ((ret (helper))))
+(fn:i32 main () ((ret (used))))";
+    assert_eq!(functions(source), ["used", "helper", "main"]);
+}
+
+#[test]
+fn exported_functions_are_kept() {
+    let source = "
+(fn:i32 helper () ((ret 1)))
+(fn:i32 unused () ((ret 2)))
+(fn:i32 api (export) () ((ret (helper))))";
+    assert_eq!(functions(source), ["helper", "api"]);
+}
+
+#[test]
+fn without_an_entry_point_everything_is_kept() {
+    assert_eq!(
+        functions("(fn:i32 a () ((ret 1))) (fn:i32 b () ((ret 2)))"),
+        ["a", "b"]
+    );
+}
+
+#[test]
+fn calls_in_pruned_branches_do_not_keep_functions() {
+    let mut module = lower_str(
+        "(fn:i32 debug () ((ret 1))) (fn:i32 main () ((if false ((ret (debug)))) (ret 0)))",
+    )
+    .unwrap();
+    simplify(&mut module, false, Inlining::default()).unwrap();
+    assert_eq!(module.functions.len(), 1);
+}
+
+#[test]
+fn code_after_ret_is_dropped() {
+    let ir = ir("(fn:i32 f () ((ret 1) (println \"never\") (ret 2)))");
+    assert!(!ir.contains("println"), "{ir}");
+    assert!(!ir.contains("return 2"), "{ir}");
+}
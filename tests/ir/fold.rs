@@ -0,0 +1,110 @@
+use crisp::ir::{Module, fold::fold_constants};
+
+use crate::ir::lower_str;
+
+fn fold(source: &str, wrapping: bool) -> Result<Module, Vec<String>> {
+    let mut module = lower_str(source).expect("test source should lower");
+    match fold_constants(&mut module, wrapping) {
+        Ok(()) => Ok(module),
+        Err(diagnostics) => Err(diagnostics.into_iter().map(|d| d.message).collect()),
+    }
+}
+
+fn folded(source: &str) -> String {
+    fold(source, false)
+        .expect("test source should fold")
+        .to_string()
+}
+
+#[test]
+fn arithmetic_on_literals_is_evaluated() {
+    let ir = folded("(fn:i32 day () ((ret (* 60 60 24))))");
+    assert_eq!(ir, "fn day() -> i32 {\n  bb0:\n    return 86400:i32\n}\n");
+    let ir = folded(
+        "(fn:bool f () ((ret (< (- 10 3) (% 17 10)))))
+(fn:f64 g () ((ret (/ 1.0 4))))
+(fn:u8 h () ((ret (| (<< 1 7) (~ 254)))))",
+    );
+    assert!(ir.contains("return false"), "{ir}");
+    assert!(ir.contains("return 0.25:f64"), "{ir}");
+    assert!(ir.contains("return 129:u8"), "{ir}");
+}
+
+#[test]
+fn only_constants_are_folded() {
+    let ir = folded("(fn:i32 f (x:i32) ((ret (+ x (* 2 3)))))");
+    assert!(ir.contains("_1 = (+ x 6:i32)"), "{ir}");
+}
+
+#[test]
+fn constant_branches_are_pruned() {
+    let ir = folded("(fn:i32 f (a:i32 b:i32) ((if (= 1 1) ((ret a)) ((ret b)))))");
+    assert!(!ir.contains("branch"), "{ir}");
+    assert!(ir.contains("return a"), "{ir}");
+    assert!(!ir.contains("return b"), "{ir}");
+}
+
+#[test]
+fn overflow_is_reported() {
+    let errors = fold("(fn:i32 f () ((ret (* 100000 100000))))", false).unwrap_err();
+    assert_eq!(
+        errors,
+        [
+            "This always panics: attempt to multiply with overflow, as the result does not fit in `i32`"
+        ]
+    );
+    // the width is that of the literals
+    let errors = fold("(fn:u8 f () ((ret (- 0 1))))", false).unwrap_err();
+    assert!(errors[0].contains("`u8`"), "{errors:?}");
+    let errors = fold("(fn:i32 f (x:i32) ((ret (/ x 0))))", false).unwrap_err();
+    assert_eq!(errors, ["This always panics: attempt to divide by zero"]);
+}
+
+#[test]
+fn wrapping_arithmetic_wraps_at_compile_time() {
+    let ir = fold(
+        "(fn:i32 f () ((ret (* 100000 100000)))) (fn:u8 g () ((ret (- 0 1))))",
+        true,
+    )
+    .unwrap()
+    .to_string();
+    assert!(ir.contains("return 1410065408:i32"), "{ir}");
+    assert!(ir.contains("return 255:u8"), "{ir}");
+}
+
+#[test]
+fn unconstrained_arithmetic_folds_as_i32() {
+    let ir = folded(
+        "(fn:i32 main () (
+  (let secs (* 60 60 24))
+  (println (* 60 60 24))
+  (ret secs)))",
+    );
+    assert!(ir.contains("secs = 86400:i32"), "{ir}");
+    assert!(ir.contains("(call println 86400:i32)"), "{ir}");
+}
+
+#[test]
+fn unconstrained_float_arithmetic_folds_as_f64() {
+    let ir = folded(
+        "(fn:i32 main () (
+  (let third (/ 1.0 3.0))
+  (println third)
+  (ret 0)))",
+    );
+    assert!(ir.contains("third = 0.3333333333333333:f64"), "{ir}");
+}
+
+#[test]
+fn unconstrained_arithmetic_overflows_at_i32() {
+    let errors = fold("(fn:void f () ((println (* 65536 65536))))", false).unwrap_err();
+    assert_eq!(
+        errors,
+        [
+            "This always panics: attempt to multiply with overflow, as the result does not fit in `i32`"
+        ]
+    );
+    // a literal that does not fit in i32 makes the arithmetic i64
+    let ir = folded("(fn:void f () ((println (* 4294967296 2))))");
+    assert!(ir.contains("(call println 8589934592:i64)"), "{ir}");
+}
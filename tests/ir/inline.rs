@@ -0,0 +1,78 @@
+use crisp::ir::{inline::Inlining, simplify};
+
+use crate::ir::lower_str;
+
+fn simplified(source: &str, inlining: Inlining) -> String {
+    let mut module = lower_str(source).expect("test source should lower");
+    simplify(&mut module, false, inlining).expect("test source should simplify");
+    module.to_string()
+}
+
+const BUMP: &str = "(fn:i32 bump (inline) (n:i32) (
+  (let x:i32 (+ n 1))
+  (if (> x 100) ((ret 100)))
+  (ret x)))
+(fn:i32 main () ((let x:i32 10) (println (bump x)) (ret x)))";
+
+#[test]
+fn marked_functions_are_inlined() {
+    let ir = simplified(BUMP, Inlining::Marked);
+    assert!(!ir.contains("call bump"), "{ir}");
+    // nothing calls it any more
+    assert!(!ir.contains("fn bump"), "{ir}");
+    // both returns go on to the rest of the caller
+    assert!(ir.contains("    _1 = 100:i32\n    goto bb4\n"), "{ir}");
+    assert!(ir.contains("    _1 = x.3\n    goto bb4\n"), "{ir}");
+    assert!(ir.contains("  bb4:\n    (call println _1)\n"), "{ir}");
+}
+
+#[test]
+fn inlined_locals_do_not_clash_with_the_callers() {
+    let ir = simplified(BUMP, Inlining::Marked);
+    // the parameter is assigned the argument, and the callee's `x` is a
+    // variable of its own
+    assert!(ir.contains("    n = x.0\n"), "{ir}");
+    assert!(ir.contains("    x.3 = (+ n 1:i32)\n"), "{ir}");
+    assert!(ir.contains("    return x.0\n"), "{ir}");
+}
+
+#[test]
+fn small_functions_are_inlined_up_to_the_threshold() {
+    let source = "(fn:i32 twice (n:i32) ((ret (* n 2))))
+(fn:i32 main () ((ret (twice 3))))";
+    assert!(simplified(source, Inlining::Marked).contains("(call twice 3:i32)"));
+    assert!(simplified(source, Inlining::UpTo(0)).contains("(call twice 3:i32)"));
+    let ir = simplified(source, Inlining::UpTo(8));
+    assert!(!ir.contains("call twice"), "{ir}");
+    assert!(ir.contains("(* n 2:i32)"), "{ir}");
+}
+
+#[test]
+fn nothing_is_inlined_when_turned_off() {
+    let ir = simplified(BUMP, Inlining::Off);
+    assert!(ir.contains("(call bump x)"), "{ir}");
+}
+
+#[test]
+fn recursive_functions_are_not_inlined() {
+    let ir = simplified(
+        "(fn:i32 even (inline) (n:i32) ((if (= n 0) ((ret 1))) (ret (odd (- n 1)))))
+(fn:i32 odd (inline) (n:i32) ((if (= n 0) ((ret 0))) (ret (even (- n 1)))))
+(fn:i32 main () ((ret (even 10))))",
+        Inlining::UpTo(100),
+    );
+    assert!(ir.contains("(call even 10:i32)"), "{ir}");
+    assert!(ir.contains("fn odd"), "{ir}");
+}
+
+#[test]
+fn calls_inside_inlined_functions_are_inlined_too() {
+    let ir = simplified(
+        "(fn:i32 one (inline) () ((ret 1)))
+(fn:i32 two (inline) () ((ret (+ (one) (one)))))
+(fn:i32 main () ((ret (two))))",
+        Inlining::Marked,
+    );
+    assert!(!ir.contains("call"), "{ir}");
+    assert_eq!(ir.matches("fn ").count(), 1, "{ir}");
+}
@@ -0,0 +1,111 @@
+pub mod dce;
+pub mod fold;
+pub mod inline;
+pub mod tail;
+
+use crisp::{
+    diagnostics::Diagnostic,
+    ir::{Module, lower::lower},
+    parsing::parse_str,
+    semantics::check_program,
+};
+
+pub fn lower_str(source: &str) -> Result<Module, Vec<Diagnostic>> {
+    let ast = parse_str(source.to_string(), "test").expect("test source should parse");
+    let analysis = check_program(&ast);
+    assert!(!analysis.has_errors(), "{:?}", analysis.diagnostics);
+    lower(&ast, &analysis.types)
+}
+
+pub fn ir(source: &str) -> String {
+    lower_str(source)
+        .expect("test source should lower")
+        .to_string()
+}
+
+#[test]
+fn functions_become_basic_blocks() {
+    let ir = ir("(fn:i32 abs (x:i32) ((if (< x 0) ((ret (- x)))) (ret x)))");
+    assert_eq!(
+        ir,
+        "fn abs(x: i32) -> i32 {
+    let _1: bool
+    let _2: i32
+  bb0:
+    _1 = (< x 0:i32)
+    branch _1 bb1 bb2
+  bb1:
+    _2 = (- x)
+    return _2
+  bb2:
+    return x
+}
+"
+    );
+}
+
+#[test]
+fn loops_jump_back_to_their_header() {
+    let ir = ir(
+        "(fn:i32 sum (n:i32) ((let mut s:i32 0) (for i (range 0 n) ((set! s (+ s i)))) (ret s)))",
+    );
+    assert!(ir.contains("    i = 0:i32\n    goto bb1\n  bb1:\n"), "{ir}");
+    assert!(ir.contains("branch _3 bb2 bb3"), "{ir}");
+    assert!(ir.contains("    i = (+ i 1)\n    goto bb1\n"), "{ir}");
+}
+
+#[test]
+fn and_and_or_short_circuit() {
+    let ir = ir("(fn:bool both (a:bool b:bool) ((ret (and a b))))");
+    assert!(ir.contains("_2 = a\n    branch _2 bb2 bb1"), "{ir}");
+    assert!(ir.contains("bb2:\n    _2 = b\n    goto bb1"), "{ir}");
+}
+
+#[test]
+fn types_are_resolved() {
+    let ir = ir("(struct Point (x:i64 y:i64))
+(fn:T id [T] (a:T) ((ret a)))
+(fn:i64 run () ((let p (Point 1 2)) (ret (+ (id 3) p.x))))");
+    // every instance of a generic function is its own function
+    assert!(ir.contains("fn id[i32](a: i32) -> i32 {"), "{ir}");
+    assert!(!ir.contains("[T]"), "{ir}");
+    assert!(ir.contains("struct Point(x: i64, y: i64)"), "{ir}");
+    assert!(ir.contains("let p: Point"), "{ir}");
+    assert!(ir.contains("p = (Point 1:i64 2:i64)"), "{ir}");
+    assert!(ir.contains("_2 = (cast _1 i64)"), "{ir}");
+}
+
+#[test]
+fn globals_must_be_constant() {
+    let errors = lower_str("(fn:i32 f () ((ret 1))) (let x:i32 (f))").unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Global `x` must be initialized with a constant"
+    );
+}
+
+#[test]
+fn operators_on_type_parameters_are_checked_for_each_instance() {
+    let max = "(fn:T max [T] (a:T b:T) ((if (> a b) ((ret a)) ((ret b)))))\n";
+    let ir = ir(&format!("{max}(fn:f64 f () ((ret (max 1.5 2.5))))"));
+    assert!(ir.contains("fn max[f64](a: f64, b: f64) -> f64 {"), "{ir}");
+
+    let errors = |source: &str| -> Vec<(String, Vec<String>)> {
+        lower_str(source)
+            .expect_err("test source should not lower")
+            .into_iter()
+            .map(|d| (d.message, d.notes.into_iter().map(|n| n.message).collect()))
+            .collect()
+    };
+    assert_eq!(
+        errors(&format!("{max}(let b (max true false))")),
+        [(
+            "`>` expects numbers, found `bool`".to_string(),
+            vec!["`T` is `bool` in this call to `max`".to_string()]
+        )]
+    );
+    assert_eq!(
+        errors("(fn:T big [T] (a:T) ((ret (+ a 300))))\n(let x:u8 1)\n(let y (big x))")[0].0,
+        "Literal `300` does not fit in `u8`"
+    );
+}
@@ -0,0 +1,40 @@
+use crisp::ir::{inline::Inlining, simplify};
+
+use crate::ir::lower_str;
+
+fn simplified(source: &str) -> String {
+    let mut module = lower_str(source).expect("test source should lower");
+    simplify(&mut module, false, Inlining::default()).expect("test source should simplify");
+    module.to_string()
+}
+
+#[test]
+fn self_tail_calls_become_jumps() {
+    let ir =
+        simplified("(fn:i32 gcd (a:i32 b:i32) ((if (= b 0) ((ret a)) ((ret (gcd b (% a b)))))))");
+    assert!(!ir.contains("call"), "{ir}");
+    assert!(
+        ir.contains("    _3 = (% a b)\n    a = b\n    b = _3\n    goto bb0\n"),
+        "{ir}"
+    );
+}
+
+#[test]
+fn swapped_parameters_are_saved_first() {
+    let ir =
+        simplified("(fn:i32 f (a:i32 b:i32 n:i32) ((if (= n 0) ((ret a))) (ret (f b a (- n 1)))))");
+    assert!(ir.contains("a = b\n    b = _"), "{ir}");
+    // the old a, saved before a is assigned
+    assert!(ir.contains("= a\n    a = b"), "{ir}");
+}
+
+#[test]
+fn other_calls_are_left_alone() {
+    // the result is used after the call
+    let ir =
+        simplified("(fn:i32 fact (n:i32) ((if (= n 0) ((ret 1))) (ret (* n (fact (- n 1))))))");
+    assert!(ir.contains("(call fact _"), "{ir}");
+    // not a call to itself
+    let ir = simplified("(fn:i32 g (n:i32) ((ret n))) (fn:i32 f (n:i32) ((ret (g n))))");
+    assert!(ir.contains("(call g n)"), "{ir}");
+}
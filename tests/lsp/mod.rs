@@ -0,0 +1,140 @@
+use crisp::{
+    lsp::navigation::{DefinitionKind, completions, reference_at, resolve, top_level},
+    parsing::{ast::nodes::Node, parse_str},
+};
+
+pub mod protocol;
+
+const SOURCE: &str = "(struct Point (x:i64 y:i64))
+(enum Shape (Circle r:f64) Empty)
+(let origin:i64 0)
+(fn:i64 norm (p:Point) (
+  (let x:i64 p.x)
+  (let mut total x)
+  (for y [1 2] ((set! total (+ total y))))
+  (ret (add total origin))))
+(fn:i64 add (a:i64 b:i64) ((ret (+ a b))))";
+
+fn parse(source: &str) -> Node {
+    parse_str(source.to_string(), "test").expect("test source should parse")
+}
+
+/// The byte offset of the `nth` occurrence of `text` in `SOURCE`, counting
+/// from 0, plus `delta`.
+fn offset(text: &str, nth: usize, delta: usize) -> usize {
+    SOURCE.match_indices(text).nth(nth).unwrap().0 + delta
+}
+
+#[test]
+fn top_level_definitions_are_listed_with_their_signatures() {
+    let ast = parse(SOURCE);
+    let definitions: Vec<(String, DefinitionKind, String)> = top_level(&ast)
+        .into_iter()
+        .map(|definition| (definition.name, definition.kind, definition.detail))
+        .collect();
+    assert_eq!(
+        definitions,
+        [
+            (
+                "Point".to_string(),
+                DefinitionKind::Struct,
+                "(struct Point (x:i64 y:i64))".to_string()
+            ),
+            (
+                "Shape".to_string(),
+                DefinitionKind::Enum,
+                "(enum Shape)".to_string()
+            ),
+            (
+                "Circle".to_string(),
+                DefinitionKind::Variant,
+                "(Circle r:f64) of Shape".to_string()
+            ),
+            (
+                "Empty".to_string(),
+                DefinitionKind::Variant,
+                "Empty of Shape".to_string()
+            ),
+            (
+                "origin".to_string(),
+                DefinitionKind::Global,
+                "(let origin:i64)".to_string()
+            ),
+            (
+                "norm".to_string(),
+                DefinitionKind::Function,
+                "(fn:i64 norm (p:Point))".to_string()
+            ),
+            (
+                "add".to_string(),
+                DefinitionKind::Function,
+                "(fn:i64 add (a:i64 b:i64))".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn names_resolve_to_the_innermost_definition() {
+    let ast = parse(SOURCE);
+    // `x` in `(let mut total x)` is the local, not the field of Point
+    let at = offset("total x", 0, 6);
+    let (name, loc) = reference_at(&ast, at).unwrap();
+    assert_eq!(name, "x");
+    assert_eq!(&SOURCE[loc.start..loc.end], "x");
+    let definition = resolve(&ast, name, at).unwrap();
+    assert_eq!(definition.kind, DefinitionKind::Variable);
+    assert_eq!(definition.detail, "(let x:i64)");
+    assert_eq!(
+        &SOURCE[definition.loc.start..definition.loc.end],
+        "(let x:i64 p.x)"
+    );
+
+    // the loop variable, and a parameter, which is placed at its function
+    let at = offset("total y", 0, 6);
+    assert_eq!(resolve(&ast, "y", at).unwrap().detail, "y");
+    let at = offset("(+ a b)", 0, 3);
+    let definition = resolve(&ast, reference_at(&ast, at).unwrap().0, at).unwrap();
+    assert_eq!(definition.kind, DefinitionKind::Parameter);
+    assert_eq!(definition.detail, "a:i64");
+    assert!(SOURCE[definition.loc.start..].starts_with("(fn:i64 add"));
+}
+
+#[test]
+fn the_function_of_a_call_resolves_even_before_its_definition() {
+    let ast = parse(SOURCE);
+    let at = offset("(add total", 0, 2);
+    let (name, loc) = reference_at(&ast, at).unwrap();
+    assert_eq!(name, "add");
+    assert_eq!(&SOURCE[loc.start..loc.end], "add");
+    let definition = resolve(&ast, name, at).unwrap();
+    assert_eq!(definition.detail, "(fn:i64 add (a:i64 b:i64))");
+}
+
+#[test]
+fn completions_are_the_names_in_scope() {
+    let ast = parse(SOURCE);
+    let names = |at| -> Vec<String> {
+        completions(&ast, at)
+            .into_iter()
+            .map(|definition| definition.name)
+            .collect()
+    };
+    let inside_loop = names(offset("total y", 0, 6));
+    for name in [
+        "norm", "add", "origin", "Point", "Circle", "p", "x", "total", "y",
+    ] {
+        assert!(
+            inside_loop.contains(&name.to_string()),
+            "{name}: {inside_loop:?}"
+        );
+    }
+    // nothing of `norm` is in scope in `add`
+    let in_add = names(offset("(+ a b)", 0, 1));
+    assert!(in_add.contains(&"a".to_string()));
+    assert!(!in_add.contains(&"total".to_string()), "{in_add:?}");
+    assert!(!in_add.contains(&"p".to_string()), "{in_add:?}");
+    // a let is not in scope in its own value
+    let in_value = names(offset("p.x", 0, 1));
+    assert!(!in_value.contains(&"x".to_string()), "{in_value:?}");
+}
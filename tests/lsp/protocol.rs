@@ -0,0 +1,246 @@
+use std::thread;
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use serde_json::{Value, json};
+
+const URI: &str = "file:///project/main.crisp";
+
+/// An editor talking to a language server running on another thread.
+struct Editor {
+    connection: Connection,
+    next_id: i32,
+}
+impl Editor {
+    fn start() -> (Editor, thread::JoinHandle<()>) {
+        let (server, client) = Connection::memory();
+        let handle = thread::spawn(move || crisp::lsp::serve(&server).unwrap());
+        let mut editor = Editor {
+            connection: client,
+            next_id: 0,
+        };
+        editor.request("initialize", json!({ "capabilities": {} }));
+        editor.notify("initialized", json!({}));
+        (editor, handle)
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let request = Request::new(id.clone(), method.to_string(), params);
+        self.connection
+            .sender
+            .send(Message::Request(request))
+            .unwrap();
+        loop {
+            match self.connection.receiver.recv().unwrap() {
+                Message::Response(Response {
+                    id: response_id,
+                    result,
+                    error,
+                }) if response_id == id => {
+                    assert!(error.is_none(), "{error:?}");
+                    return result.unwrap_or(Value::Null);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        let notification = Notification::new(method.to_string(), params);
+        self.connection
+            .sender
+            .send(Message::Notification(notification))
+            .unwrap();
+    }
+
+    /// The diagnostics published next.
+    fn diagnostics(&self) -> Vec<Value> {
+        loop {
+            if let Message::Notification(notification) = self.connection.receiver.recv().unwrap()
+                && notification.method == "textDocument/publishDiagnostics"
+            {
+                return notification.params["diagnostics"]
+                    .as_array()
+                    .unwrap()
+                    .clone();
+            }
+        }
+    }
+
+    fn open(&self, text: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": URI, "languageId": "crisp", "version": 1, "text": text } }),
+        );
+    }
+
+    fn change(&self, text: &str) {
+        self.notify(
+            "textDocument/didChange",
+            json!({ "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": text }] }),
+        );
+    }
+
+    /// Replaces the text between two positions, given as line and
+    /// character.
+    fn edit(&self, start: (u32, u32), end: (u32, u32), text: &str) {
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{
+                    "range": {
+                        "start": { "line": start.0, "character": start.1 },
+                        "end": { "line": end.0, "character": end.1 }
+                    },
+                    "text": text
+                }]
+            }),
+        );
+    }
+
+    fn at(&mut self, method: &str, line: u32, character: u32) -> Value {
+        self.request(
+            method,
+            json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } }),
+        )
+    }
+
+    fn stop(mut self, handle: thread::JoinHandle<()>) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        handle.join().unwrap();
+    }
+}
+
+const SOURCE: &str = "(fn:i32 twice (n:i32) ((ret (* n 2))))
+(fn:i32 main () (
+  (let x:i32 (twice 4))
+  (ret x)))
+";
+
+#[test]
+fn diagnostics_are_published_as_files_change() {
+    let (editor, handle) = Editor::start();
+    editor.open("(fn:i32 main () ((ret (+ 1 true))))");
+    let diagnostics = editor.diagnostics();
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 0, "character": 27 })
+    );
+
+    // validation errors, which used to only be printed
+    editor.change("(fn:i32 main () ((if)))");
+    let diagnostics = editor.diagnostics();
+    assert_eq!(diagnostics[0]["message"], "Invalid if statement");
+
+    // and errors from the grammar itself, which leave the rest of the file
+    editor.change("(fn:i32 main () ((ret 0))");
+    let diagnostics = editor.diagnostics();
+    assert_eq!(diagnostics[0]["message"], "Missing `)` to close this `(`");
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 0, "character": 0 })
+    );
+
+    editor.change(SOURCE);
+    assert_eq!(editor.diagnostics(), Vec::<Value>::new());
+    editor.stop(handle);
+}
+
+#[test]
+fn editors_can_navigate_a_file() {
+    let (mut editor, handle) = Editor::start();
+    editor.open(SOURCE);
+    editor.diagnostics();
+
+    // from the use of `twice` in main to its definition
+    let definition = editor.at("textDocument/definition", 2, 15);
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(
+        definition["range"],
+        json!({ "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 38 } })
+    );
+
+    let hover = editor.at("textDocument/hover", 3, 8);
+    assert_eq!(hover["contents"]["value"], "```crisp\n(let x:i32)\n```");
+    let hover = editor.at("textDocument/hover", 0, 31);
+    assert_eq!(hover["contents"]["value"], "```crisp\nn:i32\n```");
+
+    let symbols = editor.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    let names: Vec<&str> = symbols
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| symbol["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["twice", "main"]);
+
+    // a file being typed still has the definitions around the broken one
+    editor.change(&SOURCE.replacen("(ret x)))", "(ret x", 1));
+    editor.diagnostics();
+    let symbols = editor.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    assert_eq!(symbols.as_array().unwrap().len(), 2, "{symbols:?}");
+
+    let completions = editor.at("textDocument/completion", 3, 7);
+    let labels: Vec<&str> = completions
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    for label in ["twice", "main", "x", "println"] {
+        assert!(labels.contains(&label), "{label}: {labels:?}");
+    }
+    assert!(!labels.contains(&"n"), "{labels:?}");
+    editor.stop(handle);
+}
+
+#[test]
+fn edits_apply_to_the_text_as_it_was_left() {
+    let (mut editor, handle) = Editor::start();
+    editor.open(SOURCE);
+    editor.diagnostics();
+
+    // `(* n 2)` becomes `(* n true)`
+    editor.edit((0, 33), (0, 34), "true");
+    let diagnostics = editor.diagnostics();
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 0);
+
+    // a line added above moves everything after it
+    editor.edit((0, 0), (0, 0), "(let limit:i32 10)\n");
+    assert_eq!(editor.diagnostics().len(), 1);
+    let hover = editor.at("textDocument/hover", 4, 8);
+    assert_eq!(hover["contents"]["value"], "```crisp\n(let x:i32)\n```");
+
+    editor.edit((1, 33), (1, 37), "limit");
+    assert_eq!(editor.diagnostics(), Vec::<Value>::new());
+    editor.stop(handle);
+}
+
+#[test]
+fn hovers_show_doc_comments() {
+    let (mut editor, handle) = Editor::start();
+    editor.open(
+        ";;; Doubles a number.
+(fn:i32 twice (n:i32) ((ret (* n 2))))
+(fn:i32 main () ((ret (twice 4))))",
+    );
+    editor.diagnostics();
+    let hover = editor.at("textDocument/hover", 2, 25);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```crisp\n(fn:i32 twice (n:i32))\n```\n\nDoubles a number."
+    );
+    editor.stop(handle);
+}
@@ -0,0 +1,152 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crisp::{
+    codegen::{
+        c::{EmitOptions, emit_c, emit_c_program},
+        runtime::write_runtime,
+    },
+    parsing::{
+        ast::nodes::NodeKind,
+        modules::{Program, load_program},
+    },
+    semantics::check_program,
+};
+
+fn fixture(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/modules")
+        .join(path)
+}
+
+fn load(path: &str) -> Program {
+    load_program(&fixture(path)).expect("fixture should load")
+}
+
+fn top_level_names(program: &Program) -> Vec<String> {
+    let NodeKind::Block { expressions } = &program.ast.kind else {
+        panic!("a program is a block");
+    };
+    expressions
+        .iter()
+        .filter_map(|expression| match &expression.kind {
+            NodeKind::Fn {
+                name: Some(name), ..
+            } => Some(name.name().to_string()),
+            NodeKind::Struct { name, .. } | NodeKind::Enum { name, .. } => {
+                Some(name.name().to_string())
+            }
+            NodeKind::Let { symbol, .. } => Some(symbol.name().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn imports_are_linked_in_dependency_order() {
+    let program = load("app/main.crisp");
+    assert_eq!(
+        top_level_names(&program),
+        [
+            "math/base",
+            "math/add",
+            "math/twice",
+            "geo/Point",
+            "geo/Shape",
+            "geo/norm1",
+            "geo/radius",
+            "main",
+        ]
+    );
+    // math is imported twice but loaded once
+    assert_eq!(program.sources.files().count(), 3);
+}
+
+#[test]
+fn linked_programs_check_and_compile() {
+    let program = load("app/main.crisp");
+    let analysis = check_program(&program.ast);
+    assert!(!analysis.has_errors(), "{:?}", analysis.diagnostics);
+    let c = emit_c(&program.ast, &analysis.types, &EmitOptions::default()).unwrap();
+    assert!(c.contains("int32_t math__twice(int32_t base) {"), "{c}");
+    // a parameter shadows the global of the same name
+    assert!(c.contains("return math__add(base, base);"), "{c}");
+    assert!(c.contains("int32_t geo__norm1(geo__Point p) {"), "{c}");
+    assert!(
+        c.contains("return math__add(geo__norm1(p), math__twice(math__base));"),
+        "{c}"
+    );
+    // nothing calls it
+    assert!(!c.contains("geo__radius"), "{c}");
+}
+
+#[test]
+fn broken_imports_are_rejected() {
+    for path in ["cycle/a.crisp", "app/stray.crisp", "app/missing.crisp"] {
+        assert!(
+            load_program(&fixture(path)).is_err(),
+            "{path} should not load"
+        );
+    }
+}
+
+#[test]
+fn exported_functions_keep_their_own_name() {
+    let program = load("exports/main.crisp");
+    let analysis = check_program(&program.ast);
+    assert!(!analysis.has_errors(), "{:?}", analysis.diagnostics);
+    let c = emit_c_program(&program.ast, &analysis.types, &EmitOptions::default()).unwrap();
+    assert!(
+        c.code.contains("int32_t dot(vec__V2 a, vec__V2 b) {"),
+        "{}",
+        c.code
+    );
+    assert!(c.code.contains("return dot(v, v);"), "{}", c.code);
+    let header = c.header("vec").unwrap();
+    assert!(header.contains("} vec__V2;"), "{header}");
+    assert!(
+        header.contains("int32_t dot(vec__V2 a, vec__V2 b);"),
+        "{header}"
+    );
+    // the program and a C file using the header both have to compile
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("module-exports");
+    std::fs::create_dir_all(&dir).unwrap();
+    write_runtime(&dir).unwrap();
+    std::fs::write(dir.join("main.c"), &c.code).unwrap();
+    std::fs::write(dir.join("vec.h"), &header).unwrap();
+    std::fs::write(
+        dir.join("use.c"),
+        "#include \"vec.h\"\nint32_t twice(vec__V2 v) { return 2 * dot(v, v); }\n",
+    )
+    .unwrap();
+    let status = Command::new("cc")
+        .arg(dir.join("main.c"))
+        .arg(dir.join("crisp_runtime.c"))
+        .arg("-o")
+        .arg(dir.join("main"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = Command::new(dir.join("main")).status().unwrap();
+    assert_eq!(status.code(), Some(5));
+    let status = Command::new("cc")
+        .arg("-c")
+        .arg(dir.join("use.c"))
+        .arg("-o")
+        .arg(dir.join("use.o"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let program = load("exports/clash.crisp");
+    let analysis = check_program(&program.ast);
+    assert!(!analysis.has_errors(), "{:?}", analysis.diagnostics);
+    let diagnostics =
+        emit_c_program(&program.ast, &analysis.types, &EmitOptions::default()).unwrap_err();
+    assert_eq!(
+        diagnostics[0].message,
+        "Exported function `vec/dot` is called `dot` in C, which clashes with `dot`"
+    );
+}
@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use crisp::{
+    build::build,
+    codegen::c::EmitOptions,
+    project::{Kind, Manifest, Project, scaffold},
+};
+
+/// A fresh directory for a test to create a project in.
+fn scratch(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn manifests_fill_in_defaults() {
+    let manifest = Manifest::parse("[package]\nname = \"demo\"").unwrap();
+    assert_eq!(manifest.package.kind, Kind::Bin);
+    assert_eq!(manifest.package.entry, None);
+    assert_eq!(manifest.package.source_dirs, [PathBuf::from("src")]);
+    assert_eq!(manifest.package.output_dir, PathBuf::from("build"));
+    assert_eq!(manifest.c.compiler, None);
+    assert!(manifest.c.flags.is_empty());
+    assert_eq!(manifest.c.standard, "c11");
+
+    let manifest = Manifest::parse(
+        "[package]\nname = \"demo\"\nkind = \"lib\"\n[c]\ncompiler = \"clang\"\nflags = [\"-O2\", \"-g\"]\nstandard = \"c99\"",
+    )
+    .unwrap();
+    assert_eq!(manifest.package.kind, Kind::Lib);
+    assert_eq!(manifest.c.compiler.as_deref(), Some("clang"));
+    assert_eq!(manifest.c.flags, ["-O2", "-g"]);
+    assert_eq!(manifest.c.standard, "c99");
+}
+
+#[test]
+fn bad_manifests_are_rejected() {
+    for text in [
+        "",
+        "[package]\nname = \"demo\"\nentry-point = \"main.crisp\"",
+        "[package]\nname = \"demo\"\nkind = \"dylib\"",
+        "[package]\nname = \"my project\"",
+    ] {
+        assert!(Manifest::parse(text).is_err(), "{text:?} should not parse");
+    }
+}
+
+#[test]
+fn new_projects_build_to_an_executable() {
+    let dir = scratch("new_project");
+    scaffold(&dir, "new_project").unwrap();
+    assert!(scaffold(&dir, "new_project").is_err());
+    let project = Project::find(&dir.join("src")).unwrap();
+    assert_eq!(project.name(), "new_project");
+    assert_eq!(project.sources().unwrap(), [dir.join("src/main.crisp")]);
+    let executable = build(&project, &EmitOptions::default()).unwrap();
+    assert_eq!(executable, dir.join("build/new_project"));
+    let status = std::process::Command::new(&executable).status().unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn libraries_build_to_a_static_archive() {
+    let dir = scratch("library");
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(
+        dir.join("Crisp.toml"),
+        "[package]\nname = \"shapes\"\nkind = \"lib\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src/lib.crisp"),
+        "(import area)\n(fn:i32 square (x:i32) ((ret (area/rect x x))))\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src/area.crisp"),
+        "(fn:i32 rect (w:i32 h:i32) ((ret w)))\n",
+    )
+    .unwrap();
+    let project = Project::load(&dir.join("Crisp.toml")).unwrap();
+    let library = build(&project, &EmitOptions::default()).unwrap();
+    assert_eq!(library, dir.join("build/libshapes.a"));
+    assert!(library.is_file());
+}
+
+#[test]
+fn libraries_include_modules_nothing_imports() {
+    let dir = scratch("library_modules");
+    std::fs::create_dir_all(dir.join("src/solids")).unwrap();
+    std::fs::write(
+        dir.join("Crisp.toml"),
+        "[package]\nname = \"solids\"\nkind = \"lib\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src/lib.crisp"),
+        "(fn:i32 square (export) (x:i32) ((ret (* x x))))\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src/solids/cube.crisp"),
+        "(fn:i32 volume (export) (x:i32) ((ret (* x (* x x)))))\n",
+    )
+    .unwrap();
+    let project = Project::load(&dir.join("Crisp.toml")).unwrap();
+    let library = build(&project, &EmitOptions::default()).unwrap();
+    let header = std::fs::read_to_string(dir.join("build/solids.h")).unwrap();
+    assert!(header.contains("int32_t volume(int32_t x);"), "{header}");
+    std::fs::write(
+        dir.join("build/use.c"),
+        "#include \"solids.h\"\nint main(void) { return volume(2) + square(1); }\n",
+    )
+    .unwrap();
+    let status = std::process::Command::new("cc")
+        .arg(dir.join("build/use.c"))
+        .arg(&library)
+        .arg("-o")
+        .arg(dir.join("build/use"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = std::process::Command::new(dir.join("build/use"))
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(9));
+}
+
+#[test]
+fn executables_need_a_main() {
+    let dir = scratch("no_main");
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("Crisp.toml"), "[package]\nname = \"no_main\"\n").unwrap();
+    std::fs::write(dir.join("src/main.crisp"), "(fn:i32 start () ((ret 0)))\n").unwrap();
+    let project = Project::find(&dir).unwrap();
+    let error = build(&project, &EmitOptions::default()).unwrap_err();
+    assert!(
+        error.to_string().contains("needs a `main` function"),
+        "{error}"
+    );
+}
@@ -0,0 +1,92 @@
+use crate::semantics::{check, messages};
+
+#[test]
+fn arrays_index_iterate_and_view_as_slices() {
+    let diagnostics = check(
+        "
+(fn:i32 sum (xs:[i32]) ((let mut total:i32 0) (for x xs ((set! total x))) (ret total)))
+(fn:u64 size (xs:[i32]) ((ret (len xs))))
+(fn:i32 main () (
+  (let mut xs:[i32;3] [1 2 3])
+  (set! (at xs 0) (at xs 2))
+  (for i (range 3) ((set! (at xs i) 0)))
+  (ret (sum xs))))",
+    );
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+}
+
+#[test]
+fn literals_pick_a_common_element_type() {
+    let diagnostics = check("(let xs:[u16;2] [1 300]) (let ys:[u8;2] [1 300])");
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(messages, ["Literal `300` does not fit in `u8`"]);
+    assert!(check("(let xs [1 3000000000]) (let ys:[i64;2] xs)").is_empty());
+}
+
+#[test]
+fn lengths_and_indices_are_checked() {
+    assert_eq!(
+        messages(
+            "
+(let xs:[i32;3] [1 2])
+(let ys:[i32;2] [1 2])
+(let a (at ys 2))
+(let b (at ys 1.5))
+(let n 5)
+(let c:i32 (at n 0))
+(let d (len n))"
+        ),
+        [
+            "Expected an array of 3 elements, found 2",
+            "Index 2 is out of bounds for an array of length 2",
+            "Array index must be an integer, found `f64`",
+            "Cannot index into a value of type `i32`",
+            "Cannot take the length of a value of type `i32`",
+        ]
+    );
+}
+
+#[test]
+fn slices_are_read_only_views() {
+    assert_eq!(
+        messages(
+            "
+(struct Holder (xs:[i32]))
+(fn:[i32] view (xs:[i32;2]) ((ret xs)))
+(fn:void clear (xs:[i32]) ((let mut ys xs) (set! (at ys 0) 0)))"
+        ),
+        [
+            "Field `xs` of `Holder` cannot hold a slice; slices only live in parameters and local bindings",
+            "Function `view` cannot return a slice; the array it views may not outlive the call",
+            "Cannot assign to an element of a slice; slices are read-only",
+        ]
+    );
+}
+
+#[test]
+fn elements_of_immutable_arrays_cannot_be_assigned() {
+    assert_eq!(
+        messages("(fn:void f () ((let xs [1 2]) (set! (at xs 0) 3)))"),
+        ["Cannot assign twice to immutable variable `xs`"]
+    );
+}
+
+#[test]
+fn arrays_and_slices_do_not_mix_up() {
+    assert_eq!(
+        messages("(fn:void f (xs:[i32]) ((let ys:[i32;2] xs) (let zs:[i64] [1 2])))"),
+        ["Mismatched types: expected `[i32;2]`, found `[i32]`"]
+    );
+    assert_eq!(
+        messages("(fn:void g (xs:[i32;2]) ((let ys:[i64] xs)))"),
+        ["Mismatched types: expected `[i64]`, found `[i32;2]`"]
+    );
+}
+
+#[test]
+fn recursion_through_arrays_is_detected() {
+    assert_eq!(
+        messages("(struct Tree (children:[Tree;2]))"),
+        ["Recursive type `Tree` has infinite size; it contains itself by value"]
+    );
+}
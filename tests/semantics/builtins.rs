@@ -0,0 +1,34 @@
+use crate::semantics::messages;
+
+#[test]
+fn builtins_are_checked() {
+    let diagnostics = messages(
+        "
+(struct Point (x:i32 y:i32))
+(fn:i32 main () (
+  (println \"hi\")
+  (print (len \"hi\"))
+  (print (Point 1 2))
+  (println)
+  (let s:string (concat \"a\" 1))
+  (ret (compare s \"b\"))))",
+    );
+    assert_eq!(
+        diagnostics,
+        [
+            "Cannot print a value of type `Point`",
+            "`println` takes 1 argument but 0 were given",
+            "Mismatched types: expected `string`, found `i32`",
+        ]
+    );
+}
+
+#[test]
+fn definitions_shadow_builtins() {
+    let diagnostics = messages(
+        "
+(fn:i32 print (a:i32 b:i32) ((ret a)))
+(fn:i32 main () ((ret (print 1 2))))",
+    );
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+}
@@ -0,0 +1,33 @@
+use crate::semantics::messages;
+
+#[test]
+fn main_may_take_the_arguments_and_return_the_exit_code() {
+    for source in [
+        "(fn:i32 main () ((ret 0)))",
+        "(fn:i32 main (args:[string]) ((ret 0)))",
+        "(fn:void main (args:[string]) ((println (at args 0))))",
+        "(fn:void main () ((println 1)))",
+    ] {
+        assert!(messages(source).is_empty(), "{source}");
+    }
+}
+
+#[test]
+fn other_signatures_of_main_are_rejected() {
+    assert_eq!(
+        messages("(fn:i32 main (n:i32) ((ret n)))"),
+        ["`main` takes either no parameters or the command-line arguments as `[string]`"]
+    );
+    assert_eq!(
+        messages("(fn:i32 main (args:[string] n:i32) ((ret n)))"),
+        ["`main` takes either no parameters or the command-line arguments as `[string]`"]
+    );
+    assert_eq!(
+        messages("(fn:u8 main () ((ret 0)))"),
+        ["`main` must return `i32`, the exit code, or `void`, not `u8`"]
+    );
+    assert!(
+        messages("(fn:T main [T] (x:T) ((ret 0)))")
+            .contains(&"`main` cannot be generic".to_string())
+    );
+}
@@ -0,0 +1,70 @@
+use crate::semantics::check;
+
+const SHAPE: &str = "(enum Shape (Circle r:f64) (Rect w:f64 h:f64) Empty)\n";
+
+#[test]
+fn destructuring_binds_variant_fields() {
+    let diagnostics = check(&format!(
+        "{SHAPE}
+(fn:f64 width (s:Shape) ((given s ((Circle r) ((ret r))) ((Rect w _) ((ret w))) (Empty ((ret 0))))))
+(let shapes:Shape (Rect 1 2))
+(let nothing Empty)"
+    ));
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+}
+
+#[test]
+fn missing_variants_are_reported() {
+    let diagnostics = check(&format!(
+        "{SHAPE}(fn:f64 f (s:Shape) ((given s ((Circle r) ((ret r))))))"
+    ));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "Non-exhaustive given over `Shape`: missing `Rect`, `Empty`"
+    );
+}
+
+#[test]
+fn wildcard_makes_given_exhaustive() {
+    let diagnostics = check(&format!(
+        "{SHAPE}(fn:f64 f (s:Shape) ((given s ((Circle r) ((ret r))) (_ ((ret 0))))))"
+    ));
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+}
+
+#[test]
+fn patterns_must_match_the_variant() {
+    let diagnostics = check(&format!(
+        "{SHAPE}(fn:f64 f (s:Shape) ((given s ((Circle) ((ret 0))) ((Square x) ((ret x))) (_ ((ret 1))))))"
+    ));
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Variant `Circle` has 1 fields but the pattern binds 0",
+            "Case pattern must be a variant of `Shape` or `_`",
+            "Unknown identifier `x`",
+        ]
+    );
+}
+
+#[test]
+fn pattern_bindings_are_immutable() {
+    let diagnostics = check(&format!(
+        "{SHAPE}(fn:f64 f (s:Shape) ((given s ((Circle r) ((set! r 1.0) (ret r))) (_ ((ret 0))))))"
+    ));
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message.contains("pattern binding `r`"));
+}
+
+#[test]
+fn variant_names_must_be_unique() {
+    let diagnostics = check("(enum A (X v:i32))\n(enum B X)\n(struct X (v:i32))");
+    assert_eq!(diagnostics.len(), 2);
+    assert!(
+        diagnostics
+            .iter()
+            .all(|d| d.message == "`X` is defined twice")
+    );
+}
@@ -0,0 +1,76 @@
+use crate::semantics::messages;
+
+#[test]
+fn calls_to_c_are_checked_against_their_declaration() {
+    let diagnostics = messages(
+        "
+(include \"<stdio.h>\")
+(extern \"C\" (fn:i32 puts (s:string)))
+(fn:i32 main () (
+  (let n:i32 (puts \"hi\"))
+  (puts 1)
+  (ret (puts \"a\" \"b\"))))",
+    );
+    assert_eq!(
+        diagnostics,
+        [
+            "Mismatched types: expected `string`, found `i32`",
+            "Function `puts` takes 1 arguments but 2 were given",
+        ]
+    );
+}
+
+#[test]
+fn only_c_compatible_types_cross_into_c() {
+    let diagnostics = messages(
+        "
+(struct Point (x:i32 y:i32))
+(extern \"C\"
+  (fn:void free (p:Point))
+  (fn:[i32;2] pair ()))",
+    );
+    assert_eq!(
+        diagnostics,
+        [
+            "Parameter `p` of extern function `free` cannot be `Point`; only numbers, `bool` and `string` can be passed to C",
+            "Extern function `pair` cannot return `[i32;2]`; only numbers, `bool`, `string` and `void` come back from C",
+        ]
+    );
+}
+
+#[test]
+fn declarations_of_the_same_function_must_agree() {
+    let diagnostics = messages(
+        "
+(extern \"C\" (fn:i32 abs (x:i32)))
+(extern \"C\" (fn:i32 abs (y:i32)))
+(extern \"C\" (fn:i64 abs (x:i64)))
+(fn:i32 puts () ((ret 0)))
+(extern \"C\" (fn:i32 puts (s:string)))
+(fn:i32 main () (
+  (extern \"C\" (fn:void exit (code:i32)))
+  (ret (abs 1))))",
+    );
+    assert_eq!(
+        diagnostics,
+        [
+            "Extern function `abs` is declared again with a different signature",
+            "Function `puts` is defined twice",
+            "C declarations must be made at the top level",
+        ]
+    );
+}
+
+#[test]
+fn calls_to_undeclared_functions_are_rejected() {
+    let diagnostics = messages(
+        "
+(extern \"C\" (fn:i32 abs (x:i32)))
+(fn:bool zero (n:i32) ((ret (== n 0))))
+(fn:i32 main () ((ret (+ (abs -1) (nosuchfn 1 2)))))",
+    );
+    assert_eq!(
+        diagnostics,
+        ["Unknown function `==`", "Unknown function `nosuchfn`"]
+    );
+}
@@ -0,0 +1,76 @@
+use crate::semantics::{check, errors, messages};
+
+#[test]
+fn type_arguments_are_inferred_from_arguments() {
+    let diagnostics = errors(
+        "
+(fn:T pick [T] (c:bool a:T b:T) ((if c ((ret a)) ((ret b)))))
+(fn:T first [T] (xs:[T]) ((ret (at xs 0))))
+(fn:i32 main () (
+  (let a:i32 3)
+  (let b:i32 (pick true a 4))
+  (let c:f32 (pick false 1.5 2))
+  (let d:i64 (pick true 1 2))
+  (let xs:[u8;2] [1 2])
+  (let e:u8 (first xs))
+  (ret b)))",
+    );
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+}
+
+#[test]
+fn conflicting_arguments_are_rejected() {
+    let diagnostics = messages(
+        "
+(fn:T pick [T] (c:bool a:T b:T) ((if c ((ret a)) ((ret b)))))
+(let a:i32 3)
+(let b:bool false)
+(let c (pick true a b))",
+    );
+    assert_eq!(
+        diagnostics,
+        ["Mismatched types: expected `i32`, found `bool`"]
+    );
+}
+
+#[test]
+fn type_parameters_must_be_inferable_and_fresh() {
+    assert_eq!(
+        messages(
+            "
+(struct Point (x:i32 y:i32))
+(fn:T make [T U] (x:T) ((ret x)))
+(fn:i32 f [Point] (x:Point) ((ret 0)))"
+        ),
+        [
+            "Type parameter `U` of `make` is not used by any parameter, so it can never be inferred",
+            "Type parameter `Point` shadows a type or constructor",
+        ]
+    );
+}
+
+#[test]
+fn generic_functions_cannot_be_exported() {
+    assert_eq!(
+        messages("(fn:T id (export) [T] (x:T) ((ret x)))"),
+        ["Generic function `id` cannot be exported; C needs a single signature to call"]
+    );
+}
+
+#[test]
+fn operators_apply_to_type_parameters() {
+    let diagnostics = check(
+        "
+(fn:T max [T] (a:T b:T) ((if (> a b) ((ret a)) ((ret b)))))
+(fn:T inc [T] (a:T) ((ret (+ a 1))))
+(fn:i32 main () (
+  (let a:i32 (max 1 2))
+  (ret (inc (max 3 (- a 2))))))",
+    );
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    // no promotion to or from a type parameter
+    assert_eq!(
+        messages("(fn:T add [T] (a:T b:i32) ((ret (+ a b))))"),
+        ["Mismatched types: expected `T`, found `i32`"]
+    );
+}
@@ -0,0 +1,44 @@
+pub mod arrays;
+pub mod builtins;
+pub mod entry;
+pub mod enums;
+pub mod ffi;
+pub mod generics;
+pub mod mutability;
+pub mod operators;
+pub mod structs;
+pub mod unused;
+
+use crisp::{diagnostics::Diagnostic, parsing::parse_str, semantics::check_program};
+
+pub fn check(source: &str) -> Vec<Diagnostic> {
+    let ast = parse_str(source.to_string(), "test").expect("test source should parse");
+    check_program(&ast).diagnostics
+}
+
+/// What [`check`] finds, without the warnings about unused definitions,
+/// for tests of programs that are not meant to be complete.
+pub fn errors(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = check(source);
+    diagnostics.retain(Diagnostic::is_error);
+    diagnostics
+}
+
+/// The messages of the errors in a program.
+pub fn messages(source: &str) -> Vec<String> {
+    errors(source)
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect()
+}
+
+/// The messages of the warnings about a program.
+pub fn warnings(source: &str) -> Vec<String> {
+    let ast = parse_str(source.to_string(), "test").expect("test source should parse");
+    check_program(&ast)
+        .diagnostics
+        .into_iter()
+        .filter(|diagnostic| !diagnostic.is_error())
+        .map(|diagnostic| diagnostic.message)
+        .collect()
+}
@@ -0,0 +1,47 @@
+use crate::semantics::{check, errors};
+
+#[test]
+fn mutable_let_can_be_assigned() {
+    let diagnostics = check(
+        "(fn:i32 sum (n:i32) ((let mut total 0) (for i (range 0 n) ((set! total (+ total i)))) (ret total)))",
+    );
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+}
+
+#[test]
+fn immutable_let_points_at_declaration() {
+    let diagnostics = check("(let x 1)\n(set! x 2)");
+    assert_eq!(diagnostics.len(), 1);
+    let diagnostic = &diagnostics[0];
+    assert!(diagnostic.message.contains("immutable variable `x`"));
+    assert_eq!(diagnostic.loc.line, 2);
+    assert_eq!(diagnostic.notes.len(), 1);
+    assert_eq!(diagnostic.notes[0].loc.line, 1);
+}
+
+#[test]
+fn parameters_are_immutable() {
+    let diagnostics = check("(fn:i32 inc (n:i32) ((set! n (+ n 1)) (ret n)))");
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message.contains("function parameter `n`"));
+}
+
+#[test]
+fn loop_variables_are_immutable() {
+    let diagnostics = check("(for i (range 0 10) ((set! i 0)))");
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message.contains("loop variable `i`"));
+}
+
+#[test]
+fn undeclared_and_out_of_scope_assignments_are_rejected() {
+    let diagnostics = errors("(set! y 1)\n(if true ((let mut z 1)) ((set! z 2)))");
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics.iter().all(|d| d.message.contains("undeclared")));
+}
+
+#[test]
+fn shadowing_with_let_mut_allows_assignment() {
+    let diagnostics = check("(let x 1)\n(let mut x x)\n(set! x 2)");
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+}
@@ -0,0 +1,56 @@
+use crisp::semantics::types::Type;
+
+use crate::semantics::messages;
+
+#[test]
+fn numbers_promote_to_a_type_that_holds_both() {
+    assert_eq!(Type::U8.promote(&Type::U16), Some(Type::U16));
+    assert_eq!(Type::U8.promote(&Type::I8), Some(Type::I16));
+    assert_eq!(Type::U32.promote(&Type::I32), Some(Type::I64));
+    assert_eq!(Type::I64.promote(&Type::F32), Some(Type::F32));
+    assert_eq!(Type::F32.promote(&Type::F64), Some(Type::F64));
+    assert_eq!(Type::U64.promote(&Type::I8), None);
+    assert_eq!(Type::Bool.promote(&Type::Bool), None);
+}
+
+#[test]
+fn operators_promote_their_operands() {
+    let diagnostics = messages(
+        "
+(fn:i64 widen (a:i32 b:u32) ((ret (+ a b 1))))
+(fn:f64 mean (a:i32 b:f64) ((ret (/ (+ a b) 2))))
+(fn:bool between (x:u8 lo:i16 hi:i16) ((ret (and (<= lo x) (< x hi)))))
+(fn:i64 grow (a:i32) ((ret (* a 2))))
+(fn:u8 mask (x:u8) ((ret (& (~ x) (<< 1 3)))))
+(fn:bool same (a:string b:string) ((ret (not (!= a b)))))",
+    );
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+}
+
+#[test]
+fn operands_are_checked() {
+    let diagnostics = messages(
+        "
+(fn:i32 run (a:u64 b:i8 f:f64 s:string) (
+  (let x:u64 (+ a b))
+  (let y:f64 (% f 2))
+  (let z (< s \"b\"))
+  (let w (and true 1))
+  (let v (- a))
+  (let u (+ a))
+  (let t (+ b 300))
+  (ret 0)))",
+    );
+    assert_eq!(
+        diagnostics,
+        [
+            "Cannot mix `u64` and `i8` in `+`; no type holds both",
+            "`%` expects integers, found `f64`",
+            "`<` expects numbers, found `string`",
+            "Mismatched types: expected `bool`, found `i32`",
+            "Cannot negate a value of type `u64`",
+            "`+` takes at least 2 arguments but 1 were given",
+            "Literal `300` does not fit in `i8`",
+        ]
+    );
+}
@@ -0,0 +1,76 @@
+use crate::semantics::{check, errors};
+
+const SHAPES: &str = "
+(struct Point (x:f64 y:f64))
+(struct Line (start:Point end:Point))
+";
+
+#[test]
+fn constructors_and_field_access_check() {
+    let diagnostics = check(&format!(
+        "{SHAPES}
+(fn:f64 length-x (l:Line) ((ret (. l.end x))))
+(fn:Line unit () ((let mut p (Point 0 0)) (set! p.x 1.0) (ret (Line (Point 0 0) p))))"
+    ));
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+}
+
+#[test]
+fn unknown_fields_and_types_are_rejected() {
+    let diagnostics = errors(&format!(
+        "{SHAPES}
+(fn:f64 f (p:Point) ((ret p.z)))
+(fn:f64 g (v:Vector) ((ret 0)))"
+    ));
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        ["Struct `Point` has no field `z`", "Unknown type `Vector`"]
+    );
+}
+
+#[test]
+fn constructor_arguments_are_checked() {
+    let diagnostics = check(&format!(
+        "{SHAPES}
+(let a (Point 1.0))
+(let b:Point (Point 1.0 true))
+(let c:Line (Point 1.0 2.0))"
+    ));
+    assert_eq!(diagnostics.len(), 3, "{diagnostics:?}");
+    assert!(diagnostics[0].message.contains("2 fields but 1"));
+    assert!(
+        diagnostics[1]
+            .message
+            .contains("expected `f64`, found `bool`")
+    );
+    assert!(
+        diagnostics[2]
+            .message
+            .contains("expected `Line`, found `Point`")
+    );
+}
+
+#[test]
+fn structs_cannot_contain_themselves() {
+    let diagnostics = check("(struct A (b:B))\n(struct B (a:A))\n(struct C (c:C))");
+    assert_eq!(diagnostics.len(), 3);
+    assert!(
+        diagnostics
+            .iter()
+            .all(|d| d.message.contains("infinite size"))
+    );
+}
+
+#[test]
+fn field_assignment_needs_a_mutable_struct() {
+    let diagnostics = check(&format!(
+        "{SHAPES}\n(let p (Point 0 0))\n(set! p.x 1.0)\n(set! (. p y) 1.0)"
+    ));
+    assert_eq!(diagnostics.len(), 2);
+    assert!(
+        diagnostics
+            .iter()
+            .all(|d| d.message.contains("immutable variable `p`"))
+    );
+}
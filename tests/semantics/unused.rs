@@ -0,0 +1,56 @@
+use crate::semantics::warnings;
+
+#[test]
+fn unused_variables_and_parameters_are_reported() {
+    assert_eq!(
+        warnings("(fn:i32 f (a:i32 b:i32) ((let x 1) (let y 2) (ret (+ a y))))"),
+        ["Unused parameter `b`", "Unused variable `x`"]
+    );
+    // assigning is not using
+    assert_eq!(
+        warnings("(fn:void f () ((let mut x 1) (set! x 2)))"),
+        ["Unused variable `x`"]
+    );
+}
+
+#[test]
+fn used_and_underscored_names_are_not_reported() {
+    let source = "
+(let limit:i32 10)
+(fn:i32 f (_unused:i32 xs:[i32;2] i:u8) (
+  (let mut total:i32 0)
+  (for x xs ((set! total (+ total x))))
+  (let mut ys xs)
+  (set! (at ys i) 1)
+  (ret (+ total (at ys 0)))))";
+    assert!(warnings(source).is_empty(), "{:?}", warnings(source));
+}
+
+#[test]
+fn shadowed_variables_are_tracked_separately() {
+    assert_eq!(
+        warnings("(fn:i32 f () ((let x 1) (let x (+ x 1)) (ret 0)))"),
+        ["Unused variable `x`"]
+    );
+}
+
+#[test]
+fn unreachable_cases_are_reported() {
+    let source = "
+(enum Shape (Circle r:f64) (Empty))
+(fn:i32 f (s:Shape n:i32) (
+  (given s ((Circle _) ((ret 1))) (Empty ((ret 2))) (_ ((ret 3))))
+  (given s (_ ((ret 4))) (Empty ((ret 5))))
+  (given s ((Circle r) ((ret 6))) ((Circle r) ((ret 7))) (_ ((ret 8))))
+  (given n (1 ((ret 9))) (1 ((ret 10))) (_ ((ret 11))))
+  (ret 0)))";
+    assert_eq!(
+        warnings(source),
+        [
+            "Unreachable case: every variant of `Shape` is matched above",
+            "Unreachable case: an earlier `_` case matches everything",
+            "Unreachable case: `Circle` is matched above",
+            "Unreachable case: the same value is matched above",
+        ]
+    );
+}
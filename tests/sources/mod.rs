@@ -0,0 +1,30 @@
+use crisp::{parsing::parse_source, sources::SourceMap};
+
+#[test]
+fn lines_and_columns_are_looked_up_by_offset() {
+    let mut sources = SourceMap::default();
+    let file = sources.add("lines.crisp", "(let a 1)\n\n(let é 2)\n");
+    let file = &sources[file];
+    assert_eq!(file.line_col(0), (1, 1));
+    assert_eq!(file.line_col(5), (1, 6));
+    assert_eq!(file.line_col(10), (2, 1));
+    assert_eq!(file.line_col(11), (3, 1));
+    // columns count characters, not bytes
+    assert_eq!(file.line_col(19), (3, 8));
+    assert_eq!(file.line_text(3), "(let é 2)");
+    assert_eq!(file.line_text(2), "");
+}
+
+#[test]
+fn locations_refer_to_their_file() {
+    let mut sources = SourceMap::default();
+    let first = sources.add("first.crisp", "(let a 1)");
+    let second = sources.add("second.crisp", "\n  (let b 2)");
+    assert_eq!(sources.find("second.crisp"), Some(second));
+    let a = parse_source(&sources[first]).unwrap();
+    let b = parse_source(&sources[second]).unwrap();
+    let (a, b) = (&a.children()[0].loc, &b.children()[0].loc);
+    assert_eq!((a.file, a.line, a.col), (first, 1, 1));
+    assert_eq!((b.file, b.line, b.col), (second, 2, 3));
+    assert_ne!(a, b);
+}
@@ -1,6 +1,7 @@
 use crate::structure::helpers::*;
 use crisp::parsing::ast::validation::validate_let;
 use crisp::parsing::{CrispParser, Rule};
+use crisp::sources::SourceMap;
 use pest::Parser;
 use proptest::prelude::ProptestConfig;
 use proptest::{prop_assert, proptest};
@@ -10,15 +11,21 @@ proptest! {
     #[test]
     fn valid(f in gen_let()) {
         let source = format!("{}", f);
-        let mut pairs = CrispParser::parse(Rule::list, &source).unwrap();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
         let pair = pairs.next().unwrap();
-        prop_assert!(validate_let(&pair, ""));
+        prop_assert!(validate_let(&pair, file));
     }
     #[test]
     fn invalid(f in gen_bad_let()) {
         let source = format!("{}", f);
-        let mut pairs = CrispParser::parse(Rule::list, &source).unwrap();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
         let pair = pairs.next().unwrap();
-        prop_assert!(!validate_let(&pair, ""));
+        prop_assert!(!validate_let(&pair, file));
     }
 }
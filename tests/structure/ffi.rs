@@ -0,0 +1,53 @@
+use crate::structure::helpers::*;
+use crisp::parsing::{
+    CrispParser, Rule,
+    ast::validation::{validate_extern, validate_include},
+};
+use crisp::sources::SourceMap;
+use pest::Parser;
+use proptest::prelude::ProptestConfig;
+use proptest::{prop_assert, proptest};
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10000))]
+    #[test]
+    fn valid_extern(f in gen_extern()) {
+        let source = f.to_string();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
+        let pair = pairs.next().unwrap();
+        prop_assert!(validate_extern(&pair, file));
+    }
+    #[test]
+    fn invalid_extern(f in gen_bad_extern()) {
+        let source = f.to_string();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
+        let pair = pairs.next().unwrap();
+        prop_assert!(!validate_extern(&pair, file));
+    }
+    #[test]
+    fn valid_include(f in gen_include()) {
+        let source = f.to_string();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
+        let pair = pairs.next().unwrap();
+        prop_assert!(validate_include(&pair, file));
+    }
+    #[test]
+    fn invalid_include(f in gen_bad_include()) {
+        let source = f.to_string();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
+        let pair = pairs.next().unwrap();
+        prop_assert!(!validate_include(&pair, file));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::structure::helpers::*;
 use crisp::parsing::{CrispParser, Rule, ast::validation::validate_for};
+use crisp::sources::SourceMap;
 use pest::Parser;
 use proptest::prelude::ProptestConfig;
 use proptest::{prop_assert, proptest};
@@ -9,15 +10,21 @@ proptest! {
     #[test]
     fn valid(f in gen_for()) {
         let source = format!("{}", f);
-        let mut pairs = CrispParser::parse(Rule::list, &source).unwrap();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
         let pair = pairs.next().unwrap();
-        prop_assert!(validate_for(&pair, ""));
+        prop_assert!(validate_for(&pair, file));
     }
     #[test]
     fn invalid(f in gen_bad_for()) {
         let source = format!("{}", f);
-        let mut pairs = CrispParser::parse(Rule::list, &source).unwrap();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
         let pair = pairs.next().unwrap();
-        prop_assert!(!validate_for(&pair, ""));
+        prop_assert!(!validate_for(&pair, file));
     }
 }
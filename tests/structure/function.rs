@@ -1,5 +1,6 @@
 use crate::structure::helpers::*;
 use crisp::parsing::{CrispParser, Rule, ast::validation::validate_fn};
+use crisp::sources::SourceMap;
 use pest::Parser;
 use proptest::prop_assert;
 use proptest::{prelude::ProptestConfig, proptest};
@@ -9,16 +10,22 @@ proptest! {
     #[test]
     fn valid(f in gen_fn()) {
         let source = format!("{}", f);
-        let mut pairs = CrispParser::parse(Rule::list, &source).unwrap();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
         let pair = pairs.next().unwrap();
-        prop_assert!(validate_fn(&pair, "test"));
+        prop_assert!(validate_fn(&pair, file));
     }
 
     #[test]
     fn invalid(f in gen_bad_fn()) {
         let source = format!("{}", f);
-        let mut pairs = CrispParser::parse(Rule::list, &source).unwrap();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
         let pair = pairs.next().unwrap();
-        prop_assert!(!validate_fn(&pair, "test"));
+        prop_assert!(!validate_fn(&pair, file));
     }
 }
@@ -1,4 +1,4 @@
-use proptest::collection::vec;
+use proptest::collection::{btree_set, vec};
 use proptest::prelude::*;
 use proptest::string::string_regex;
 
@@ -62,7 +62,15 @@ pub fn gen_param() -> impl Strategy<Value = String> {
 }
 
 pub fn gen_params() -> impl Strategy<Value = String> {
-    list(gen_param(), 0..3)
+    // parameter names have to be distinct
+    (btree_set(gen_ident(), 0..3), vec(gen_type(), 3)).prop_map(|(names, types)| {
+        let params: Vec<String> = names
+            .iter()
+            .zip(types)
+            .map(|(name, ty)| format!("{name}:{ty}"))
+            .collect();
+        format!("({})", params.join(" "))
+    })
 }
 
 pub fn gen_bad_params() -> impl Strategy<Value = String> {
@@ -113,10 +121,11 @@ pub fn gen_for() -> impl Strategy<Value = String> {
 
 pub fn gen_let() -> impl Strategy<Value = String> {
     (
-        gen_ident(),
+        prop_oneof![Just("".to_string()), Just("mut ".to_string())],
+        gen_ident().prop_filter("mut is reserved", |name| name != "mut"),
         prop_oneof![gen_body(), gen_number(), gen_bool(), gen_ident()],
     )
-        .prop_map(|(a, b)| format!("(let {a} {b})"))
+        .prop_map(|(m, a, b)| format!("(let {m}{a} {b})"))
 }
 
 pub fn gen_bad_let() -> impl Strategy<Value = String> {
@@ -131,17 +140,138 @@ pub fn gen_bad_let() -> impl Strategy<Value = String> {
     ]
 }
 
+pub fn gen_set() -> impl Strategy<Value = String> {
+    (
+        prop_oneof![gen_ident(), gen_index(), gen_field()],
+        prop_oneof![gen_body(), gen_number(), gen_bool(), gen_ident()],
+    )
+        .prop_map(|(a, b)| format!("(set! {a} {b})"))
+}
+
+pub fn gen_bad_set() -> impl Strategy<Value = String> {
+    prop_oneof![
+        gen_ident().prop_map(|a| format!("(set! {a})")),
+        (gen_ident(), gen_body(), gen_body()).prop_map(|(a, b, c)| format!("(set! {a} {b} {c})")),
+        (
+            prop_oneof![gen_body(), gen_bool(), gen_number()],
+            gen_body()
+        )
+            .prop_map(|(a, b)| format!("(set! {a} {b})")),
+        (gen_param(), gen_number()).prop_map(|(a, b)| format!("(set! {a} {b})")),
+    ]
+}
+
+pub fn gen_field() -> impl Strategy<Value = String> {
+    (prop_oneof![gen_ident(), gen_body()], gen_ident()).prop_map(|(a, f)| format!("(. {a} {f})"))
+}
+
+pub fn gen_array() -> impl Strategy<Value = String> {
+    vec(prop_oneof![gen_number(), gen_ident(), gen_body()], 0..6)
+        .prop_map(|xs| format!("[{}]", xs.join(" ")))
+}
+
+pub fn gen_index() -> impl Strategy<Value = String> {
+    (
+        prop_oneof![gen_ident(), gen_body(), gen_array()],
+        prop_oneof![gen_ident(), gen_number(), gen_body()],
+    )
+        .prop_map(|(a, i)| format!("(at {a} {i})"))
+}
+
+pub fn gen_bad_index() -> impl Strategy<Value = String> {
+    prop_oneof![
+        gen_ident().prop_map(|a| format!("(at {a})")),
+        (gen_ident(), gen_number(), gen_number()).prop_map(|(a, i, j)| format!("(at {a} {i} {j})")),
+        (gen_number(), gen_number()).prop_map(|(a, i)| format!("(at {a} {i})")),
+        (gen_ident(), gen_bool()).prop_map(|(a, i)| format!("(at {a} {i})")),
+    ]
+}
+
+pub fn gen_fields() -> impl Strategy<Value = String> {
+    (btree_set(gen_ident(), 1..4), vec(gen_type(), 4)).prop_map(|(names, types)| {
+        let fields: Vec<String> = names
+            .iter()
+            .zip(types)
+            .map(|(name, ty)| format!("{name}:{ty}"))
+            .collect();
+        format!("({})", fields.join(" "))
+    })
+}
+
+pub fn gen_struct() -> impl Strategy<Value = String> {
+    (gen_ident(), gen_fields()).prop_map(|(name, fields)| format!("(struct {name} {fields})"))
+}
+
+pub fn gen_bad_struct() -> impl Strategy<Value = String> {
+    prop_oneof![
+        gen_ident().prop_map(|n| format!("(struct {n})")),
+        gen_ident().prop_map(|n| format!("(struct {n} ())")),
+        (gen_ident(), gen_ident()).prop_map(|(n, f)| format!("(struct {n} ({f}))")),
+        (gen_param(), gen_fields()).prop_map(|(n, f)| format!("(struct {n} {f})")),
+        (gen_body(), gen_fields()).prop_map(|(n, f)| format!("(struct {n} {f})")),
+        (gen_ident(), gen_fields(), gen_body())
+            .prop_map(|(n, f, b)| format!("(struct {n} {f} {b})")),
+        (gen_ident(), gen_param()).prop_map(|(n, f)| format!("(struct {n} ({f} {f}))")),
+    ]
+}
+
+pub fn gen_variant() -> impl Strategy<Value = String> {
+    prop_oneof![
+        gen_ident(),
+        (gen_ident(), gen_fields())
+            .prop_map(|(name, fields)| format!("({name} {})", &fields[1..fields.len() - 1])),
+    ]
+}
+
+pub fn gen_enum() -> impl Strategy<Value = String> {
+    (gen_ident(), vec(gen_variant(), 1..4)).prop_filter_map(
+        "duplicate variants",
+        |(name, variants)| {
+            let names: std::collections::BTreeSet<&str> = variants
+                .iter()
+                .map(|v| v.trim_start_matches('(').split(' ').next().unwrap())
+                .collect();
+            (names.len() == variants.len()).then(|| format!("(enum {name} {})", variants.join(" ")))
+        },
+    )
+}
+
+pub fn gen_bad_enum() -> impl Strategy<Value = String> {
+    prop_oneof![
+        gen_ident().prop_map(|n| format!("(enum {n})")),
+        (gen_param(), gen_ident()).prop_map(|(n, v)| format!("(enum {n} {v})")),
+        (gen_ident(), gen_ident()).prop_map(|(n, v)| format!("(enum {n} {v} {v})")),
+        (gen_ident(), gen_param()).prop_map(|(n, v)| format!("(enum {n} {v})")),
+        (gen_ident(), gen_ident(), gen_ident())
+            .prop_map(|(n, v, f)| format!("(enum {n} ({v} {f}))")),
+        (gen_ident(), gen_number()).prop_map(|(n, v)| format!("(enum {n} {v})")),
+        gen_ident().prop_map(|n| format!("(enum {n} ())")),
+    ]
+}
+
 pub fn gen_fn() -> impl Strategy<Value = String> {
     prop_oneof![
         (gen_return_type(), gen_ident(), gen_params(), gen_body())
             .prop_map(|(ret, name, params, body)| { format!("(fn:{ret} {name} {params} {body})") }),
         (gen_return_type(), gen_params(), gen_body())
             .prop_map(|(ret, params, body)| { format!("(fn:{ret} {params} {body})") }),
+        (gen_return_type(), gen_c_ident(), gen_params(), gen_body()).prop_map(
+            |(ret, name, params, body)| { format!("(fn:{ret} {name} (export) {params} {body})") }
+        ),
     ]
 }
 
 pub fn gen_bad_fn() -> impl Strategy<Value = String> {
     prop_oneof![
+        (gen_return_type(), gen_ident(), gen_params(), gen_body())
+            .prop_map(|(r, n, p, b)| format!("(fn:{r} {n} (frobnicate) {p} {b})")),
+        (gen_return_type(), gen_c_ident(), gen_params(), gen_body())
+            .prop_map(|(r, n, p, b)| format!("(fn:{r} {n} (export) (export) {p} {b})")),
+        (gen_return_type(), gen_ident(), gen_params(), gen_body())
+            .prop_filter("name must not be a C identifier", |(_, n, _, _)| {
+                !n.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+            .prop_map(|(r, n, p, b)| format!("(fn:{r} {n} (export) {p} {b})")),
         (gen_return_type(), gen_ident(), gen_params())
             .prop_map(|(r, n, p)| format!("(fn:{r} {n} {p})")),
         (gen_return_type(), gen_ident(), gen_body())
@@ -164,3 +294,74 @@ pub fn gen_bad_fn() -> impl Strategy<Value = String> {
             .prop_map(|(r, bp, b)| format!("(fn:{r} {bp} {b})")),
     ]
 }
+
+pub fn gen_import() -> impl Strategy<Value = String> {
+    prop_oneof![
+        gen_ident(),
+        string_regex(r#""[a-z/.]{1,20}\.crisp""#).unwrap(),
+    ]
+    .prop_map(|module| format!("(import {module})"))
+}
+
+pub fn gen_bad_import() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("(import)".to_string()),
+        Just("(import \"\")".to_string()),
+        (gen_ident(), gen_ident()).prop_map(|(a, b)| format!("(import {a} {b})")),
+        gen_param().prop_map(|p| format!("(import {p})")),
+        gen_number().prop_map(|n| format!("(import {n})")),
+        gen_body().prop_map(|b| format!("(import {b})")),
+    ]
+}
+
+pub fn gen_c_ident() -> impl Strategy<Value = String> {
+    string_regex(r"[a-zA-Z_][a-zA-Z0-9_]{0,31}").unwrap()
+}
+
+pub fn gen_extern_fn() -> impl Strategy<Value = String> {
+    (gen_return_type(), gen_c_ident(), gen_params())
+        .prop_map(|(r, n, p)| format!("(fn:{r} {n} {p})"))
+}
+
+pub fn gen_extern() -> impl Strategy<Value = String> {
+    vec(gen_extern_fn(), 1..4).prop_map(|fns| format!("(extern \"C\" {})", fns.join(" ")))
+}
+
+pub fn gen_bad_extern() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("(extern \"C\")".to_string()),
+        gen_extern_fn().prop_map(|f| format!("(extern {f})")),
+        gen_extern_fn().prop_map(|f| format!("(extern \"Rust\" {f})")),
+        (gen_extern_fn(), gen_body())
+            .prop_map(|(f, b)| format!("(extern \"C\" {} {b}))", &f[..f.len() - 1])),
+        (gen_return_type(), gen_c_ident(), gen_bad_params())
+            .prop_map(|(r, n, p)| format!("(extern \"C\" (fn:{r} {n} {p}))")),
+        (gen_return_type(), gen_ident(), gen_params())
+            .prop_filter("name must not be a C identifier", |(_, n, _)| {
+                !n.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+            .prop_map(|(r, n, p)| format!("(extern \"C\" (fn:{r} {n} {p}))")),
+        (gen_c_ident(), gen_params()).prop_map(|(n, p)| format!("(extern \"C\" (fn {n} {p}))")),
+        gen_atom().prop_map(|a| format!("(extern \"C\" {a})")),
+    ]
+}
+
+pub fn gen_include() -> impl Strategy<Value = String> {
+    prop_oneof![
+        string_regex(r"<[a-z/_]{1,12}\.h>").unwrap(),
+        string_regex(r"[a-z/_.]{1,12}\.h").unwrap(),
+    ]
+    .prop_map(|header| format!("(include \"{header}\")"))
+}
+
+pub fn gen_bad_include() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("(include)".to_string()),
+        Just("(include \"\")".to_string()),
+        Just("(include \"<>\")".to_string()),
+        Just("(include \"<stdio.h\")".to_string()),
+        Just("(include \"stdio.h>\")".to_string()),
+        gen_ident().prop_map(|i| format!("(include {i})")),
+        gen_include().prop_map(|i| format!("{} \"x.h\")", &i[..i.len() - 1])),
+    ]
+}
@@ -1,5 +1,6 @@
 use crate::structure::helpers::*;
 use crisp::parsing::{CrispParser, Rule, ast::validation::validate_if};
+use crisp::sources::SourceMap;
 use pest::Parser;
 use proptest::prelude::ProptestConfig;
 use proptest::{prop_assert, proptest};
@@ -9,15 +10,21 @@ proptest! {
     #[test]
     fn valid(predicate in gen_body(), then_block in gen_body(), else_block in gen_body()) {
         let source = format!("(if {} {} {})", predicate, then_block, else_block);
-        let mut pairs = CrispParser::parse(Rule::list, &source).unwrap();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
         let pair = pairs.next().unwrap();
-        prop_assert!(validate_if(&pair, ""));
+        prop_assert!(validate_if(&pair, file));
     }
     #[test]
     fn invalid(if_statement in gen_bad_if()) {
         let source = format!("{}", if_statement);
-        let mut pairs = CrispParser::parse(Rule::file, &source).unwrap();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::file, file.source()).unwrap();
         let pair = pairs.next().unwrap();
-        prop_assert!(!validate_if(&pair, ""));
+        prop_assert!(!validate_if(&pair, file));
     }
 }
@@ -0,0 +1,118 @@
+use crisp::{
+    parsing::{
+        incremental::{ParsedFile, TextEdit},
+        parse_collecting,
+    },
+    sources::SourceMap,
+};
+use proptest::prelude::*;
+
+const SOURCE: &str = "(let a:i32 1)
+(fn:i32 f (x:i32) ((ret (+ x a))))
+
+; the entry point
+(fn:i32 main () ((ret (f 2))))";
+
+/// Whether a file that was edited parsed to what parsing the text it has
+/// now as a whole does. The whole file reports what validation finds after
+/// what parsing does, rather than form by form, so the order of the
+/// diagnostics is left out.
+fn assert_reparsed(parsed: &ParsedFile) {
+    let mut sources = SourceMap::default();
+    let file = sources.add("test", parsed.file().source());
+    let (ast, diagnostics) = parse_collecting(&sources[file]);
+    let ast = ast.expect("a broken file should still give an AST");
+    assert_eq!(format!("{:?}", parsed.ast()), format!("{:?}", ast));
+    let sorted = |diagnostics: Vec<String>| {
+        let mut diagnostics = diagnostics;
+        diagnostics.sort();
+        diagnostics
+    };
+    assert_eq!(
+        sorted(parsed.diagnostics().map(|d| format!("{:?}", d)).collect()),
+        sorted(diagnostics.iter().map(|d| format!("{:?}", d)).collect())
+    );
+}
+
+fn edit(parsed: &mut ParsedFile, text: &str, replacement: &str) -> usize {
+    let start = parsed.file().source().find(text).unwrap();
+    parsed.edit(TextEdit {
+        range: start..start + text.len(),
+        text: replacement.to_string(),
+    })
+}
+
+#[test]
+fn edits_parse_again_only_the_forms_they_touch() {
+    let mut parsed = ParsedFile::new("test", SOURCE.to_string());
+    assert_eq!(parsed.ast().children().len(), 3);
+
+    assert_eq!(edit(&mut parsed, "(+ x a)", "(- x a)"), 1);
+    assert_reparsed(&parsed);
+    // between forms, nothing is parsed again, but the later ones move
+    assert_eq!(edit(&mut parsed, "\n; the", "\n\n\n; the"), 0);
+    assert_reparsed(&parsed);
+    assert_eq!(parsed.ast().children()[2].loc.line, 7);
+    // at the end of a form, it and what follows it are
+    assert_eq!(edit(&mut parsed, "1)", "1) (let b 2)"), 2);
+    assert_reparsed(&parsed);
+    assert_eq!(parsed.ast().children().len(), 4);
+}
+
+#[test]
+fn forms_broken_by_an_edit_are_recovered() {
+    let mut parsed = ParsedFile::new("test", SOURCE.to_string());
+    edit(&mut parsed, "a))))", "a)))");
+    assert_reparsed(&parsed);
+    let errors: Vec<_> = parsed
+        .diagnostics()
+        .map(|diagnostic| (diagnostic.message.as_str(), diagnostic.loc.line))
+        .collect();
+    assert_eq!(errors, [("Missing `)` to close this `(`", 2)]);
+    assert_eq!(parsed.ast().children().len(), 3);
+
+    edit(&mut parsed, "a)))", "a))))");
+    assert_reparsed(&parsed);
+    assert_eq!(parsed.diagnostics().count(), 0);
+}
+
+#[test]
+fn strings_left_open_close_at_a_later_quote() {
+    let mut parsed = ParsedFile::new("test", "(let s \"abc)\n(let t 2)\n".to_string());
+    assert_eq!(parsed.diagnostics().count(), 2);
+    edit(&mut parsed, "2)\n", "2)\n\"");
+    assert_reparsed(&parsed);
+    assert_eq!(parsed.ast().children().len(), 1);
+}
+
+fn gen_edit() -> impl Strategy<Value = (usize, usize, &'static str)> {
+    let texts = prop::sample::select(vec![
+        "",
+        "(",
+        ")",
+        "[",
+        "]",
+        "\"",
+        ";",
+        "\n",
+        " x",
+        "(let b 2)",
+        "\n(fn:i32 g () ((ret 1)))",
+    ]);
+    (0..=SOURCE.len() + 40, 0..8usize, texts)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(500))]
+    #[test]
+    fn edits_parse_like_the_text_they_leave(edits in prop::collection::vec(gen_edit(), 1..6)) {
+        let mut parsed = ParsedFile::new("test", SOURCE.to_string());
+        for (start, length, text) in edits {
+            let len = parsed.file().source().len();
+            let start = start.min(len);
+            let end = (start + length).min(len);
+            parsed.edit(TextEdit { range: start..end, text: text.to_string() });
+            assert_reparsed(&parsed);
+        }
+    }
+}
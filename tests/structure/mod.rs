@@ -1,5 +1,13 @@
+pub mod arrays;
 pub mod assignment;
+pub mod enums;
+pub mod ffi;
 pub mod for_loop;
 pub mod function;
 pub mod helpers;
 pub mod if_statement;
+pub mod imports;
+pub mod incremental;
+pub mod mutation;
+pub mod recovery;
+pub mod structs;
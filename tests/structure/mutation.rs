@@ -0,0 +1,30 @@
+use crate::structure::helpers::*;
+use crisp::parsing::{CrispParser, Rule, ast::validation::validate_set};
+use crisp::sources::SourceMap;
+use pest::Parser;
+use proptest::prelude::ProptestConfig;
+use proptest::{prop_assert, proptest};
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10000))]
+    #[test]
+    fn valid(f in gen_set()) {
+        let source = f.to_string();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
+        let pair = pairs.next().unwrap();
+        prop_assert!(validate_set(&pair, file));
+    }
+    #[test]
+    fn invalid(f in gen_bad_set()) {
+        let source = f.to_string();
+        let mut sources = SourceMap::default();
+        let file = sources.add("test", source);
+        let file = &sources[file];
+        let mut pairs = CrispParser::parse(Rule::list, file.source()).unwrap();
+        let pair = pairs.next().unwrap();
+        prop_assert!(!validate_set(&pair, file));
+    }
+}
@@ -0,0 +1,157 @@
+use crisp::{
+    parsing::{
+        ast::nodes::{Node, NodeKind},
+        parse_collecting,
+    },
+    sources::SourceMap,
+};
+
+/// A top-level form as `fn name`, `let name` or `invalid`, with the line it
+/// starts on.
+type Form = (String, usize);
+
+/// An error as its message, line and column.
+type Error = (String, usize, usize);
+
+/// The top-level forms a source recovers to, and the errors found.
+fn recover(source: &str) -> (Vec<Form>, Vec<Error>) {
+    let mut sources = SourceMap::default();
+    let file = sources.add("test", source);
+    let (ast, diagnostics) = parse_collecting(&sources[file]);
+    let ast = ast.expect("a broken file should still give an AST");
+    let forms = ast
+        .children()
+        .into_iter()
+        .map(|node: &Node| {
+            let form = match &node.kind {
+                NodeKind::Fn {
+                    name: Some(name), ..
+                } => format!("fn {}", name.name()),
+                NodeKind::Let { symbol, .. } => format!("let {}", symbol.name()),
+                NodeKind::Invalid => "invalid".to_string(),
+                other => panic!("unexpected form {:?}", other),
+            };
+            (form, node.loc.line)
+        })
+        .collect();
+    let errors = diagnostics
+        .into_iter()
+        .map(|diagnostic| (diagnostic.message, diagnostic.loc.line, diagnostic.loc.col))
+        .collect();
+    (forms, errors)
+}
+
+fn forms(forms: &[(&str, usize)]) -> Vec<Form> {
+    forms
+        .iter()
+        .map(|(form, line)| (form.to_string(), *line))
+        .collect()
+}
+
+#[test]
+fn files_that_parse_need_no_recovery() {
+    let (forms_found, errors) = recover("(let a 1)\n(fn:i32 f () ((ret a)))");
+    assert_eq!(forms_found, forms(&[("let a", 1), ("fn f", 2)]));
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn missing_parentheses_are_closed_before_the_next_definition() {
+    let (forms_found, errors) = recover(
+        "(fn:i32 f () (
+  (ret 1))
+(fn:i32 g () ((ret 2)))",
+    );
+    assert_eq!(forms_found, forms(&[("fn f", 1), ("fn g", 3)]));
+    assert_eq!(
+        errors,
+        [("Missing `)` to close this `(`".to_string(), 1, 1)]
+    );
+}
+
+#[test]
+fn parentheses_missing_at_the_end_are_closed_there() {
+    let (forms_found, errors) = recover("(let a 1)\n(fn:i32 f () ((ret (+ a 1");
+    assert_eq!(forms_found, forms(&[("let a", 1), ("fn f", 2)]));
+    let missing: Vec<usize> = errors.iter().map(|(_, _, col)| *col).collect();
+    // innermost first
+    assert_eq!(missing, [20, 15, 14, 1]);
+}
+
+#[test]
+fn stray_closing_brackets_are_skipped() {
+    let (forms_found, errors) = recover("(let a 1))\n(let b [1 2)])");
+    assert_eq!(forms_found, forms(&[("let a", 1), ("let b", 2)]));
+    assert_eq!(
+        errors,
+        [
+            ("Unexpected `)` with nothing to close".to_string(), 1, 10),
+            (
+                "Unexpected `)`; the innermost open bracket needs a `]`".to_string(),
+                2,
+                12
+            ),
+        ]
+    );
+}
+
+#[test]
+fn unclosed_strings_end_with_their_line() {
+    let (forms_found, errors) = recover("(let s \"abc)\n(let t 2)");
+    assert_eq!(forms_found, forms(&[("let s", 1), ("let t", 2)]));
+    assert_eq!(
+        errors,
+        [
+            (
+                "This string is never closed, so it is taken to end with its line".to_string(),
+                1,
+                8
+            ),
+            ("Missing `)` to close this `(`".to_string(), 1, 1),
+        ]
+    );
+}
+
+#[test]
+fn forms_that_cannot_be_repaired_become_invalid() {
+    let (forms_found, errors) = recover("(let a 1)\n(let b #)\n(let c (+ a 1)\n(let d 4)");
+    assert_eq!(
+        forms_found,
+        forms(&[("let a", 1), ("invalid", 2), ("let c", 3), ("let d", 4)])
+    );
+    assert_eq!(
+        errors,
+        [
+            ("Missing `)` to close this `(`".to_string(), 3, 1),
+            ("Parsing error".to_string(), 2, 8),
+        ]
+    );
+}
+
+#[test]
+fn integers_too_large_for_64_bits_are_reported() {
+    let (forms_found, errors) =
+        recover("(let a 99999999999999999999)\n(let b -9223372036854775808)");
+    assert_eq!(forms_found, forms(&[("let a", 1), ("let b", 2)]));
+    assert_eq!(
+        errors,
+        [(
+            "Integer literal `99999999999999999999` does not fit in 64 bits".to_string(),
+            1,
+            8
+        )]
+    );
+}
+
+#[test]
+fn mut_cannot_name_a_variable() {
+    let (forms_found, errors) = recover("(let mut 5)\n(let mut:i32 5)\n(let mut x 1)");
+    assert_eq!(
+        forms_found,
+        forms(&[("invalid", 1), ("invalid", 2), ("let x", 3)])
+    );
+    let reserved = "`mut` is reserved and cannot name a variable, use (let mut name value)";
+    let reserved = |line| (reserved.to_string(), line, 6);
+    let invalid = |line| ("Invalid assignment".to_string(), line, 1);
+    assert_eq!(errors, [reserved(1), invalid(1), reserved(2), invalid(2)]);
+}
@@ -1 +1,12 @@
+pub mod build;
+pub mod cli;
+pub mod codegen;
+pub mod diagnostics;
+pub mod doc;
+pub mod ir;
+pub mod lsp;
+pub mod modules;
+pub mod project;
+pub mod semantics;
+pub mod sources;
 pub mod structure;